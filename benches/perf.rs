@@ -7,7 +7,8 @@ use std::hash::{Hash, Hasher};
 use std::hint::black_box;
 use std::sync::Arc;
 
-use livequery_server::core::event::{EventBatch, SubscribeEvent};
+use livequery_server::core::event::{EventBatch, SubscribeEvent, SystemClock};
+use livequery_server::core::hash::ContentBuildHasher;
 use livequery_server::core::query::{EvalResult, analyze};
 use livequery_server::core::row::{RowData, RowValue};
 use livequery_server::core::subscription::Snapshot;
@@ -326,7 +327,7 @@ fn bench_snapshot_init(c: &mut Criterion) {
     group.bench_function("init_rows_events", |b| {
         b.iter_batched(
             Snapshot::new,
-            |mut snap| snap.init_rows(black_box(rows.clone()), &cols),
+            |mut snap| snap.init_rows(black_box(rows.clone()), &cols, &SystemClock),
             BatchSize::SmallInput,
         )
     });
@@ -346,7 +347,7 @@ fn bench_snapshot_get_all(c: &mut Criterion) {
     let rows = typed_rows(500);
     let cols = None;
     let mut snap = Snapshot::new();
-    snap.init_rows(rows, &cols);
+    snap.init_rows(rows, &cols, &SystemClock);
 
     group.bench_function("get_all_rows", |b| b.iter(|| snap.get_all_rows()));
     group.finish();
@@ -482,7 +483,8 @@ fn bench_rowdata(c: &mut Criterion) {
     let row = RowData::from_value(&json!({"id": 1, "name": "Alice", "active": true, "age": 30}));
     let row_value = json!({"id": 1, "name": "Alice", "active": true, "age": 30});
 
-    group.bench_function("hash_content", |b| b.iter(|| row.hash_content()));
+    let build = ContentBuildHasher::default();
+    group.bench_function("hash_content", |b| b.iter(|| row.hash_content(&build)));
     group.bench_function("to_value", |b| b.iter(|| row.to_value()));
     group.bench_function("from_value", |b| {
         b.iter(|| RowData::from_value(black_box(&row_value)))
@@ -548,11 +550,12 @@ fn bench_snapshot_diff_typed(c: &mut Criterion) {
         b.iter_batched(
             || {
                 let mut snap = Snapshot::new();
-                snap.init_rows(rows.clone(), &cols);
+                snap.init_rows(rows.clone(), &cols, &SystemClock);
                 snap
             },
             |mut snap| {
-                let ev: Vec<SubscribeEvent> = snap.diff_rows(black_box(changed.clone()), &cols);
+                let ev: Vec<SubscribeEvent> =
+                    snap.diff_rows(black_box(changed.clone()), &cols, &SystemClock);
                 black_box(ev)
             },
             BatchSize::SmallInput,
@@ -673,6 +676,57 @@ fn bench_pgoutput_decode(c: &mut Criterion) {
         )
     });
 
+    // Benchmark decoding across several interleaved relation OIDs, as a
+    // multi-table replication stream would - exercises RelTable's dense
+    // array across more than one slot instead of a single cached relation.
+    group.bench_function("decode_interleaved_relations", |b| {
+        let table_cols = [
+            ("users", vec![("id", 23u32), ("name", 25), ("email", 25)]),
+            ("orders", vec![("id", 23u32), ("user_id", 23), ("total", 701)]),
+            ("events", vec![("id", 23u32), ("kind", 25), ("ts", 1114)]),
+        ];
+        let rel_msgs: Vec<_> = table_cols
+            .iter()
+            .enumerate()
+            .map(|(i, (name, cols))| build_relation_msg(16384 + i as u32, name, cols))
+            .collect();
+
+        b.iter_batched(
+            || {
+                let mut decoder = PgOutputDecoder::new();
+                for msg in &rel_msgs {
+                    decoder.decode(msg);
+                }
+                let msgs: Vec<_> = (0..100)
+                    .map(|i| {
+                        let rel = 16384 + (i % table_cols.len()) as u32;
+                        match i % table_cols.len() {
+                            0 => build_insert_msg(
+                                rel,
+                                &[&i.to_string(), &format!("User {}", i), "u@example.com"],
+                            ),
+                            1 => build_insert_msg(
+                                rel,
+                                &[&i.to_string(), &(i % 10).to_string(), "19.99"],
+                            ),
+                            _ => build_insert_msg(
+                                rel,
+                                &[&i.to_string(), "click", "2024-01-01 00:00:00"],
+                            ),
+                        }
+                    })
+                    .collect();
+                (decoder, msgs)
+            },
+            |(mut decoder, msgs)| {
+                for msg in &msgs {
+                    black_box(decoder.decode(msg));
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
     group.finish();
 }
 
@@ -766,6 +820,66 @@ fn bench_json_serialization(c: &mut Criterion) {
     group.finish();
 }
 
+// === CBOR serialization benchmarks (WireFormat::Cbor) ===
+//
+// Mirrors `bench_json_serialization` on the same batches, so the two can be
+// compared head-to-head on the same hot push-path shapes.
+
+fn bench_cbor_serialization(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cbor_serialization");
+
+    let small_events: Vec<SubscribeEvent> = (0..5)
+        .map(|i| SubscribeEvent::insert(i as i64, json!({"id": i, "name": format!("User {}", i)})))
+        .collect();
+    let large_events: Vec<SubscribeEvent> = (0..100).map(|i| SubscribeEvent::insert(
+        i as i64, json!({"id": i, "name": format!("User {}", i), "email": format!("user{}@test.com", i), "data": {"nested": true}})
+    )).collect();
+
+    let small_batch = EventBatch {
+        seq: 1,
+        ts: 1234567890,
+        events: small_events,
+    };
+    let large_batch = EventBatch {
+        seq: 1,
+        ts: 1234567890,
+        events: large_events,
+    };
+
+    group.bench_function("serialize_5_events", |b| {
+        b.iter(|| serde_cbor::to_vec(black_box(&small_batch)))
+    });
+
+    group.bench_function("serialize_100_events", |b| {
+        b.iter(|| serde_cbor::to_vec(black_box(&large_batch)))
+    });
+
+    // Round trip, to weigh the decode side too - the server itself never
+    // decodes its own published batches (clients own that), but this is the
+    // shape a client pays on every message.
+    let large_cbor = serde_cbor::to_vec(&large_batch).unwrap();
+    group.bench_function("round_trip_100_events", |b| {
+        b.iter(|| {
+            let bytes = serde_cbor::to_vec(black_box(&large_batch)).unwrap();
+            let _: EventBatch = serde_cbor::from_slice(black_box(&bytes)).unwrap();
+        })
+    });
+    group.bench_function("deserialize_100_events", |b| {
+        b.iter(|| serde_cbor::from_slice::<EventBatch>(black_box(&large_cbor)))
+    });
+
+    let rows: Vec<Value> = (0..100)
+        .map(|i| json!({"id": i, "name": format!("User {}", i)}))
+        .collect();
+    let snapshot_payload = json!({ "seq": 1, "ts": 1234567890u64, "rows": rows });
+
+    group.bench_function("serialize_snapshot_100_rows", |b| {
+        b.iter(|| serde_cbor::to_vec(black_box(&snapshot_payload)))
+    });
+
+    group.finish();
+}
+
 // === RowData creation patterns ===
 
 fn bench_rowdata_creation(c: &mut Criterion) {
@@ -985,6 +1099,7 @@ criterion_group!(
     bench_pgoutput_decode,
     bench_query_cache_patterns,
     bench_json_serialization,
+    bench_cbor_serialization,
     bench_rowdata_creation,
     bench_filter_comparison,
     bench_memory_patterns
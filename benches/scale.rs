@@ -0,0 +1,264 @@
+//! Scalable dataset benchmark harness - run with:
+//!   BENCH_TIER=medium BENCH_ITERS=5 BENCH_PARALLEL=1 cargo bench --bench scale
+//!
+//! `perf.rs`'s benchmarks hardcode small fixed-size inputs (100-500 rows),
+//! which is the right shape for micro-benchmarking a single hot function but
+//! doesn't say anything about how ingestion/diff/filter-scan behave at the
+//! tens-of-thousands-to-millions-of-rows scale a real deployment runs at.
+//! This harness generates synthetic users/orders datasets sized by an
+//! environment-variable tier, loads them into the snapshot store (optionally
+//! sharded across `ShardedSnapshot`, see `BENCH_PARALLEL`), and prints one
+//! JSON line of rows/sec, bytes, and p50/p99 latency per benchmark, so a run
+//! from one version can be diffed against a run from another at realistic
+//! scale instead of at the toy sizes criterion's micro-benchmarks cover.
+//!
+//! It's a plain binary rather than a criterion target: criterion's
+//! statistical model assumes many sub-millisecond iterations, not a handful
+//! of iterations that each touch millions of rows. Registered in Cargo.toml
+//! as `[[bench]] name = "scale", harness = false` so `cargo bench --bench
+//! scale` still works alongside the criterion-driven `perf` target.
+//!
+//! Env vars:
+//!   BENCH_TIER     small (10k rows, default) | medium (200k) | large (2M)
+//!   BENCH_ITERS    iterations per benchmark (default 3)
+//!   BENCH_PARALLEL 1/true to load/diff/scan via `ShardedSnapshot` instead of
+//!                  the plain sequential `Snapshot`
+
+use livequery_server::core::event::SystemClock;
+use livequery_server::core::query::{EvalResult, analyze};
+use livequery_server::core::row::RowData;
+use livequery_server::core::subscription::{ShardedSnapshot, Snapshot};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy)]
+enum Tier {
+    Small,
+    Medium,
+    Large,
+}
+
+impl Tier {
+    fn from_env() -> Self {
+        match std::env::var("BENCH_TIER").as_deref() {
+            Ok("medium") => Tier::Medium,
+            Ok("large") => Tier::Large,
+            _ => Tier::Small,
+        }
+    }
+
+    fn row_count(self) -> usize {
+        match self {
+            Tier::Small => 10_000,
+            Tier::Medium => 200_000,
+            Tier::Large => 2_000_000,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Tier::Small => "small",
+            Tier::Medium => "medium",
+            Tier::Large => "large",
+        }
+    }
+}
+
+fn iterations() -> usize {
+    std::env::var("BENCH_ITERS").ok().and_then(|s| s.parse().ok()).unwrap_or(3)
+}
+
+fn parallel() -> bool {
+    std::env::var("BENCH_PARALLEL")
+        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+#[derive(Serialize)]
+struct BenchResult {
+    tier: &'static str,
+    name: &'static str,
+    rows: usize,
+    iterations: usize,
+    bytes: usize,
+    rows_per_sec: f64,
+    p50_ms: f64,
+    p99_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    selectivity: Option<f64>,
+}
+
+fn percentile(sorted: &[Duration], pct: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let idx = ((sorted.len() as f64 - 1.0) * pct).round() as usize;
+    sorted[idx].as_secs_f64() * 1000.0
+}
+
+fn report(name: &'static str, tier: Tier, rows: usize, bytes: usize, mut samples: Vec<Duration>, selectivity: Option<f64>) {
+    samples.sort();
+    let total_secs: f64 = samples.iter().map(Duration::as_secs_f64).sum();
+    let result = BenchResult {
+        tier: tier.name(),
+        name,
+        rows,
+        iterations: samples.len(),
+        bytes,
+        rows_per_sec: if total_secs > 0.0 {
+            (rows as f64 * samples.len() as f64) / total_secs
+        } else {
+            0.0
+        },
+        p50_ms: percentile(&samples, 0.50),
+        p99_ms: percentile(&samples, 0.99),
+        selectivity,
+    };
+    println!("{}", serde_json::to_string(&result).unwrap());
+}
+
+// === Synthetic dataset generation ===
+
+fn generate_users(n: usize) -> Vec<RowData> {
+    (0..n)
+        .map(|i| {
+            RowData::from_value(&serde_json::json!({
+                "id": i as i64,
+                "name": format!("User {i}"),
+                "email": format!("user{i}@example.com"),
+                "age": 18 + (i % 60) as i64,
+                "active": i % 3 != 0,
+            }))
+        })
+        .collect()
+}
+
+fn generate_orders(n: usize, user_count: usize) -> Vec<RowData> {
+    const REGIONS: [&str; 4] = ["east", "west", "north", "south"];
+    (0..n)
+        .map(|i| {
+            RowData::from_value(&serde_json::json!({
+                "id": i as i64,
+                "user_id": (i % user_count.max(1)) as i64,
+                "region": REGIONS[i % REGIONS.len()],
+                "amount": 1 + (i % 500) as i64,
+                "status": if i % 7 == 0 { "cancelled" } else { "paid" },
+            }))
+        })
+        .collect()
+}
+
+fn row_bytes(rows: &[RowData]) -> usize {
+    rows.iter().map(|r| r.to_value().to_string().len()).sum()
+}
+
+/// Rebuild the first `change_count` orders with a changed amount/status - the
+/// "10% of rows changed" shape `bench_snapshot_diff_typed` in `perf.rs`
+/// exercises at 500 rows, scaled up to a realistic dataset size.
+fn mutate_orders(rows: &[RowData], change_count: usize) -> Vec<RowData> {
+    let mut changed = rows.to_vec();
+    for (i, row) in changed.iter_mut().take(change_count).enumerate() {
+        let user_id = row.get("user_id").unwrap().to_value();
+        let region = row.get("region").unwrap().to_value();
+        *row = RowData::from_value(&serde_json::json!({
+            "id": i as i64,
+            "user_id": user_id,
+            "region": region,
+            "amount": 9999,
+            "status": "refunded",
+        }));
+    }
+    changed
+}
+
+// === Benchmarks ===
+
+fn bench_ingest(tier: Tier, iters: usize, parallel: bool) {
+    let users = generate_users(tier.row_count() / 10);
+    let orders = generate_orders(tier.row_count(), users.len());
+    let bytes = row_bytes(&orders);
+    let mut samples = Vec::with_capacity(iters);
+    for _ in 0..iters {
+        let data = orders.clone();
+        let start = Instant::now();
+        if parallel {
+            let snap = ShardedSnapshot::new(16);
+            snap.par_init_rows(data, &None);
+        } else {
+            let mut snap = Snapshot::new();
+            snap.init_rows(data, &None, &SystemClock);
+        }
+        samples.push(start.elapsed());
+    }
+    let name = if parallel { "ingest_parallel" } else { "ingest_sequential" };
+    report(name, tier, orders.len(), bytes, samples, None);
+}
+
+fn bench_diff(tier: Tier, iters: usize, parallel: bool) {
+    let users = generate_users(tier.row_count() / 10);
+    let orders = generate_orders(tier.row_count(), users.len());
+    let changed = mutate_orders(&orders, orders.len() / 10);
+    let bytes = row_bytes(&changed);
+    let mut samples = Vec::with_capacity(iters);
+    for _ in 0..iters {
+        let new_rows = changed.clone();
+        let elapsed = if parallel {
+            let snap = ShardedSnapshot::new(16);
+            snap.par_init_rows(orders.clone(), &None);
+            let start = Instant::now();
+            snap.par_diff_rows(new_rows, &None);
+            start.elapsed()
+        } else {
+            let mut snap = Snapshot::new();
+            snap.init_rows_snapshot(orders.clone(), &None);
+            let start = Instant::now();
+            snap.diff_rows(new_rows, &None, &SystemClock);
+            start.elapsed()
+        };
+        samples.push(elapsed);
+    }
+    let name = if parallel { "diff_parallel" } else { "diff_sequential" };
+    report(name, tier, orders.len(), bytes, samples, None);
+}
+
+fn bench_filter_scan(tier: Tier, iters: usize, parallel: bool) {
+    let users = generate_users(tier.row_count() / 10);
+    let orders = generate_orders(tier.row_count(), users.len());
+    let bytes = row_bytes(&orders);
+    let analysis = analyze("SELECT * FROM orders WHERE amount > 250");
+    let mut samples = Vec::with_capacity(iters);
+    let mut matched = 0usize;
+
+    if parallel {
+        let snap = ShardedSnapshot::new(16);
+        snap.par_init_rows(orders.clone(), &None);
+        for _ in 0..iters {
+            let start = Instant::now();
+            matched = snap.par_filter_scan(&analysis.filter).len();
+            samples.push(start.elapsed());
+        }
+    } else {
+        for _ in 0..iters {
+            let start = Instant::now();
+            matched = orders
+                .iter()
+                .filter(|r| analysis.filter.eval_row(r) == EvalResult::Match)
+                .count();
+            samples.push(start.elapsed());
+        }
+    }
+
+    let selectivity = matched as f64 / orders.len() as f64;
+    let name = if parallel { "filter_scan_parallel" } else { "filter_scan_sequential" };
+    report(name, tier, orders.len(), bytes, samples, Some(selectivity));
+}
+
+fn main() {
+    let tier = Tier::from_env();
+    let iters = iterations();
+    let parallel = parallel();
+
+    bench_ingest(tier, iters, parallel);
+    bench_diff(tier, iters, parallel);
+    bench_filter_scan(tier, iters, parallel);
+}
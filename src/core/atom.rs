@@ -0,0 +1,69 @@
+//! Process-wide atom table mapping interned column names to small integer
+//! handles (row values are deduped separately by `RowValue::intern_str`), so `RowData::get` can compare handles instead of strings (see
+//! `RowData`'s `handles` field). Append-mostly and sharded via `DashMap`:
+//! once a handle is assigned it's never reused, so a handle resolved once
+//! stays valid, and correct, for the rest of the process's life.
+
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use rustc_hash::FxBuildHasher;
+use std::sync::{Arc, LazyLock};
+
+static LOOKUP: LazyLock<DashMap<Box<str>, u32, FxBuildHasher>> =
+    LazyLock::new(|| DashMap::with_hasher(FxBuildHasher));
+static REVERSE: LazyLock<RwLock<Vec<Arc<str>>>> = LazyLock::new(|| RwLock::new(Vec::new()));
+
+/// Resolve `name` to its handle, interning it (and pushing it onto the
+/// reverse table) on first sight.
+pub fn intern(name: &str) -> u32 {
+    if let Some(h) = LOOKUP.get(name) {
+        return *h;
+    }
+    // `or_insert_with`'s closure only runs while this shard's entry lock is
+    // held, so the handle assignment and the `REVERSE` push below happen
+    // exactly once per distinct name even under concurrent interning.
+    *LOOKUP.entry(Box::from(name)).or_insert_with(|| {
+        let mut rev = REVERSE.write();
+        let handle = rev.len() as u32;
+        rev.push(Arc::from(name));
+        handle
+    })
+}
+
+/// Resolve `name` to its handle only if it's already interned, without
+/// interning an arbitrary caller-supplied name on a miss - used to probe a
+/// row's already-interned column set (`RowData::get`), where a name that
+/// was never a column anywhere can't possibly match one.
+pub fn lookup(name: &str) -> Option<u32> {
+    LOOKUP.get(name).map(|h| *h)
+}
+
+/// Resolve a handle back to its string, for `RowData::to_value`/`Serialize`.
+pub fn resolve(handle: u32) -> Option<Arc<str>> {
+    REVERSE.read().get(handle as usize).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_name_gets_same_handle() {
+        let a = intern("chunk13_5_test_col_a");
+        let b = intern("chunk13_5_test_col_a");
+        assert_eq!(a, b);
+        assert_eq!(resolve(a).as_deref(), Some("chunk13_5_test_col_a"));
+    }
+
+    #[test]
+    fn distinct_names_get_distinct_handles() {
+        let a = intern("chunk13_5_test_col_b");
+        let b = intern("chunk13_5_test_col_c");
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn unknown_name_is_not_interned_by_lookup() {
+        assert_eq!(lookup("chunk13_5_never_interned_xyz"), None);
+    }
+}
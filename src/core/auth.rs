@@ -0,0 +1,63 @@
+//! Pluggable authentication/authorization for `SubscriptionManager::subscribe`
+//! - see `AuthProvider`.
+
+use crate::core::query::WhereFilter;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Resolved identity for a subscribe/heartbeat request, returned by
+/// `AuthProvider::authenticate` and attached to the `Subscription` for the
+/// life of its query (see `Subscription::principal`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Principal(pub Arc<str>);
+
+/// Validates the credential a client sends with `subscribe` (the
+/// `SubscribeRequest::auth_token` field, or a NATS message header in
+/// `infra::nats`) and optionally restricts which tables the resolved
+/// principal may query - row/table-level access control on top of the
+/// existing `max_subscriptions`/`max_distinct_queries` admission caps.
+/// Injected rather than hardwired so an embedding application can swap in
+/// its own credential scheme (JWT, API keys, mTLS-derived identity, ...)
+/// without touching `SubscriptionManager`; see `SubscriptionManager::with_auth`.
+pub trait AuthProvider: Send + Sync {
+    /// Resolve `credential` to a `Principal`, or reject it with a message
+    /// surfaced via `SubError::Forbidden`.
+    fn authenticate(&self, credential: &str) -> Result<Principal, String>;
+
+    /// Tables `principal` may query, or `None` for unrestricted. Checked
+    /// against `QueryAnalysis::tables` before a query is admitted - see
+    /// `SubscriptionManager::subscribe`.
+    fn allowed_tables(&self, _principal: &Principal) -> Option<HashSet<String>> {
+        None
+    }
+
+    /// Mandatory row-level predicate for `principal`, or `None` to enforce
+    /// none. `SubscriptionManager::subscribe` AND-combines this into the
+    /// client's own `WHERE` clause (see `query::WhereFilter::And`), so a row
+    /// failing it evaluates to `NoMatch` regardless of what the client's
+    /// filter alone would allow - the client's SQL can't bypass it. Build it
+    /// from `principal`'s identity, e.g. `WhereFilter::Eq { col:
+    /// "tenant_id".into(), val: FilterValue::Str(principal.0.as_ref().into()) }`,
+    /// or `WhereFilter::And` several clauses together for more than one claim.
+    fn row_filter(&self, _principal: &Principal) -> Option<WhereFilter> {
+        None
+    }
+}
+
+/// Default provider when no `AuthProvider` is configured: every credential
+/// (including an absent one) resolves to an anonymous principal with no
+/// table restrictions, matching this server's behavior before auth existed.
+#[derive(Debug, Default)]
+pub struct AllowAllProvider;
+
+impl AuthProvider for AllowAllProvider {
+    #[inline]
+    fn authenticate(&self, credential: &str) -> Result<Principal, String> {
+        let id = if credential.is_empty() {
+            "anonymous"
+        } else {
+            credential
+        };
+        Ok(Principal(Arc::from(id)))
+    }
+}
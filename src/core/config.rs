@@ -1,11 +1,79 @@
 //! Production Configuration - All tunables in one place
 
+use crate::core::event::WireFormat;
 use anyhow::{bail, Context, Result};
+use serde::Deserialize;
 use std::env::var;
+use std::path::Path;
 use std::time::Duration;
 
+/// How `DbPool::new` should establish the connection to Postgres.
+/// `VerifyFull` additionally requires `db_tls_ca_cert` so the client can
+/// validate the server certificate instead of just encrypting the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DbTlsMode {
+    #[default]
+    Disable,
+    Require,
+    VerifyFull,
+}
+
+impl std::str::FromStr for DbTlsMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "disable" => Ok(DbTlsMode::Disable),
+            "require" => Ok(DbTlsMode::Require),
+            "verify-full" | "verifyfull" | "verify_full" => Ok(DbTlsMode::VerifyFull),
+            other => Err(format!("unknown db TLS mode: {other}")),
+        }
+    }
+}
+
+impl std::fmt::Display for DbTlsMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DbTlsMode::Disable => "disable",
+            DbTlsMode::Require => "require",
+            DbTlsMode::VerifyFull => "verify-full",
+        })
+    }
+}
+
+/// Backing store for the JetStream event stream (`Config::jetstream_*`) -
+/// mirrors `async_nats::jetstream::stream::StorageType`, which `infra::nats`
+/// converts this into when calling `get_or_create_stream`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum JetStreamStorage {
+    #[default]
+    File,
+    Memory,
+}
+
+impl std::str::FromStr for JetStreamStorage {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "file" => Ok(JetStreamStorage::File),
+            "memory" => Ok(JetStreamStorage::Memory),
+            other => Err(format!("unknown JetStream storage type: {other}")),
+        }
+    }
+}
+
+impl std::fmt::Display for JetStreamStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            JetStreamStorage::File => "file",
+            JetStreamStorage::Memory => "memory",
+        })
+    }
+}
+
 /// LiveQuery Server Configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     // === Server ===
     pub server_id: String,
@@ -16,19 +84,85 @@ pub struct Config {
     pub db_url: String,
     pub db_pool_size: u32,
     pub db_timeout_secs: u64,
+    /// Transport encryption mode for the Postgres pool. See `DbTlsMode`.
+    pub db_tls_mode: DbTlsMode,
+    /// PEM root CA bundle used to validate the server certificate when
+    /// `db_tls_mode` is `verify-full`. Ignored otherwise.
+    pub db_tls_ca_cert: Option<String>,
 
     // === NATS ===
     pub nats_url: String,
     pub nats_prefix: String,
+    /// Opt into JetStream-backed at-least-once delivery (see `infra::nats`'s
+    /// `publish_bytes`/`publish_batch`) instead of core NATS fire-and-forget
+    /// pub/sub. Off by default - core NATS is lower-latency and needs no
+    /// stream/consumer bookkeeping, the right tradeoff for deployments that
+    /// don't need to survive a subscriber being briefly disconnected.
+    pub jetstream_enabled: bool,
+    /// Stream name `infra::nats` idempotently creates (via
+    /// `get_or_create_stream`) bound to `{nats_prefix}.*.events`.
+    pub jetstream_stream_name: String,
+    pub jetstream_storage: JetStreamStorage,
+    /// Max age a message is retained for, in seconds; 0 means no age limit.
+    pub jetstream_max_age_secs: u64,
+    /// Max total bytes retained in the stream; -1 means unlimited, matching
+    /// NATS's own convention for this field.
+    pub jetstream_max_bytes: i64,
 
     // === Subscriptions ===
     pub client_timeout_secs: u64,
     pub cleanup_interval_secs: u64,
     pub max_subscriptions: usize,
+    /// Grace period a server-initiated `force_unsubscribe` waits for the
+    /// client's `ack_gone` before the subscription is reclaimed anyway.
+    pub gone_grace_secs: u64,
+    /// Batches retained per `SharedQuery` for `replay_since`, so a
+    /// reconnecting client can resume instead of re-requesting a full
+    /// snapshot (see `SubscriptionManager::with_replay_cap`). Baked into
+    /// `SubscriptionManager` at construction, so changing it requires a
+    /// restart - see `reload_from_source`.
+    pub replay_buffer_cap: usize,
+    /// How long a `SubscriptionMode::Coalesced` subscriber's pending row
+    /// changes accumulate before being flushed as net events - see
+    /// `SharedQuery::flush_coalesced`.
+    pub coalesce_window_ms: u64,
+    /// Pending-row count at which a `SubscriptionMode::Coalesced` query
+    /// flushes early instead of waiting out `coalesce_window_ms`.
+    pub coalesce_max_pending: usize,
+    /// Cap on distinct shared queries, independent of `max_subscriptions` -
+    /// see `SubscriptionManager::with_limits`. A fresh `subscribe` past this
+    /// cap is rejected with `SubError::ServiceOverloaded` instead of being
+    /// attempted and timing out.
+    pub max_distinct_queries: usize,
+    /// Cap on concurrent in-flight fresh-query snapshot executions - see
+    /// `SubscriptionManager::try_reserve_snapshot_slot`. Bounds how many
+    /// expensive initial full-table queries run at once regardless of
+    /// `max_subscriptions`/`max_distinct_queries`.
+    pub max_in_flight_snapshots: usize,
+
+    // === String interning ===
+    /// Cap on entries held in the global string interner (`core::row`); once
+    /// reached, a bounded eviction sweep reclaims entries no longer
+    /// referenced before falling back to a non-interned allocation.
+    pub max_interned_strings: usize,
 
     // === WAL ===
     pub wal_slot: String,
     pub wal_publication: String,
+
+    // === Gateway ===
+    /// Address the client-facing HTTP gateway (SSE `/subscribe` + WebSocket
+    /// `/ws`, see `infra::gateway::Gateway`) binds to.
+    pub gateway_bind: String,
+    /// Cadence of `mz_progressed` heartbeats sent to gateway clients so they
+    /// can detect a silently dead connection, mirroring the NATS
+    /// control-plane `heartbeat` subject's purpose for HTTP transports.
+    pub heartbeat_interval_ms: u64,
+
+    // === Wire format ===
+    /// Default serialization format for published batches/snapshots; a
+    /// subscription can override this via `SubscribeRequest::format`.
+    pub wire_format: WireFormat,
 }
 
 impl Config {
@@ -48,24 +182,172 @@ impl Config {
                 .context("DATABASE_URL required")?,
             db_pool_size: env("DB_POOL_SIZE", 16),
             db_timeout_secs: env("DB_TIMEOUT_SECS", 30),
+            db_tls_mode: env("DB_TLS_MODE", DbTlsMode::Disable),
+            db_tls_ca_cert: var("DB_TLS_CA_CERT").ok(),
 
             // NATS
             nats_url: var("NATS_URL").unwrap_or_else(|_| "nats://localhost:4222".into()),
             nats_prefix: var("NATS_PREFIX").unwrap_or_else(|_| "livequery".into()),
+            jetstream_enabled: env("JETSTREAM_ENABLED", false),
+            jetstream_stream_name: var("JETSTREAM_STREAM_NAME")
+                .unwrap_or_else(|_| "LIVEQUERY_EVENTS".into()),
+            jetstream_storage: env("JETSTREAM_STORAGE", JetStreamStorage::File),
+            jetstream_max_age_secs: env("JETSTREAM_MAX_AGE_SECS", 0),
+            jetstream_max_bytes: env("JETSTREAM_MAX_BYTES", -1),
 
             // Subscriptions
             client_timeout_secs: env("CLIENT_TIMEOUT_SECS", 30),
             cleanup_interval_secs: env("CLEANUP_INTERVAL_SECS", 10),
             max_subscriptions: env("MAX_SUBSCRIPTIONS", 10000),
+            gone_grace_secs: env("GONE_GRACE_SECS", 30),
+            replay_buffer_cap: env("REPLAY_BUFFER_CAP", 256),
+            coalesce_window_ms: env("COALESCE_WINDOW_MS", 500),
+            coalesce_max_pending: env("COALESCE_MAX_PENDING", 1000),
+            max_distinct_queries: env("MAX_DISTINCT_QUERIES", usize::MAX),
+            max_in_flight_snapshots: env("MAX_IN_FLIGHT_SNAPSHOTS", 64),
+
+            // String interning
+            max_interned_strings: env("MAX_INTERNED_STRINGS", 200_000),
 
             // WAL
             wal_slot: var("WAL_SLOT").unwrap_or_else(|_| "livequery_slot".into()),
             wal_publication: var("WAL_PUBLICATION").unwrap_or_else(|_| "livequery_pub".into()),
+
+            gateway_bind: var("GATEWAY_BIND").unwrap_or_else(|_| "0.0.0.0:8090".into()),
+            heartbeat_interval_ms: env("HEARTBEAT_INTERVAL_MS", 15_000),
+
+            wire_format: env("WIRE_FORMAT", WireFormat::Json),
         };
         cfg.validate()?;
         Ok(cfg)
     }
 
+    /// Layered load: built-in defaults < config file (`--config` flag or
+    /// `LIVEQUERY_CONFIG` env var) < environment variables < CLI flags,
+    /// `validate()` running once at the end over the fully merged result.
+    /// This lets operators keep a version-controlled TOML/YAML file for the
+    /// common case while still overriding individual values per-deploy
+    /// through env vars or a one-off `--flag value` on the command line.
+    pub fn load() -> Result<Self> {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+
+        let config_path = args
+            .iter()
+            .position(|a| a == "--config")
+            .and_then(|i| args.get(i + 1))
+            .cloned()
+            .or_else(|| var("LIVEQUERY_CONFIG").ok());
+
+        let file_layer = match config_path {
+            Some(path) => Self::from_file(Path::new(&path))?,
+            None => Partial::default(),
+        };
+
+        file_layer
+            .merge(Self::env_partial())
+            .merge(Self::cli_partial(&args))
+            .into_config()
+    }
+
+    /// Deserialize a `Partial` overlay from a TOML/YAML file, format chosen
+    /// by extension (`.yaml`/`.yml` vs everything else defaulting to TOML).
+    pub fn from_file(path: &Path) -> Result<Partial> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("reading config file {}", path.display()))?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml") | Some("yml") => serde_yaml::from_str(&contents)
+                .with_context(|| format!("parsing YAML config {}", path.display())),
+            _ => toml::from_str(&contents)
+                .with_context(|| format!("parsing TOML config {}", path.display())),
+        }
+    }
+
+    /// Environment-derived overlay, mirroring `from_env`'s variable names
+    /// but leaving an unset variable `None` instead of substituting a
+    /// default - defaults are only applied once, in `Partial::into_config`.
+    fn env_partial() -> Partial {
+        Partial {
+            server_id: var("SERVER_ID").ok(),
+            log_level: var("LOG_LEVEL").or_else(|_| var("RUST_LOG")).ok(),
+            shutdown_timeout_secs: env_opt("SHUTDOWN_TIMEOUT_SECS"),
+            db_url: var("DATABASE_URL").or_else(|_| var("POSTGRES_URL")).ok(),
+            db_pool_size: env_opt("DB_POOL_SIZE"),
+            db_timeout_secs: env_opt("DB_TIMEOUT_SECS"),
+            db_tls_mode: env_opt("DB_TLS_MODE"),
+            db_tls_ca_cert: var("DB_TLS_CA_CERT").ok(),
+            nats_url: var("NATS_URL").ok(),
+            nats_prefix: var("NATS_PREFIX").ok(),
+            jetstream_enabled: env_opt("JETSTREAM_ENABLED"),
+            jetstream_stream_name: var("JETSTREAM_STREAM_NAME").ok(),
+            jetstream_storage: env_opt("JETSTREAM_STORAGE"),
+            jetstream_max_age_secs: env_opt("JETSTREAM_MAX_AGE_SECS"),
+            jetstream_max_bytes: env_opt("JETSTREAM_MAX_BYTES"),
+            client_timeout_secs: env_opt("CLIENT_TIMEOUT_SECS"),
+            cleanup_interval_secs: env_opt("CLEANUP_INTERVAL_SECS"),
+            max_subscriptions: env_opt("MAX_SUBSCRIPTIONS"),
+            gone_grace_secs: env_opt("GONE_GRACE_SECS"),
+            replay_buffer_cap: env_opt("REPLAY_BUFFER_CAP"),
+            coalesce_window_ms: env_opt("COALESCE_WINDOW_MS"),
+            coalesce_max_pending: env_opt("COALESCE_MAX_PENDING"),
+            max_distinct_queries: env_opt("MAX_DISTINCT_QUERIES"),
+            max_in_flight_snapshots: env_opt("MAX_IN_FLIGHT_SNAPSHOTS"),
+            max_interned_strings: env_opt("MAX_INTERNED_STRINGS"),
+            wal_slot: var("WAL_SLOT").ok(),
+            wal_publication: var("WAL_PUBLICATION").ok(),
+            gateway_bind: var("GATEWAY_BIND").ok(),
+            heartbeat_interval_ms: env_opt("HEARTBEAT_INTERVAL_MS"),
+            wire_format: env_opt("WIRE_FORMAT"),
+        }
+    }
+
+    /// CLI overlay: `--<kebab-case-field> <value>` for every `Partial`
+    /// field (e.g. `--db-url postgres://...`, `--db-pool-size 32`);
+    /// `--config <path>` is handled separately by `load` before this runs.
+    fn cli_partial(args: &[String]) -> Partial {
+        let mut p = Partial::default();
+        let mut iter = args.iter();
+        while let Some(flag) = iter.next() {
+            let Some(key) = flag.strip_prefix("--") else {
+                continue;
+            };
+            let Some(value) = iter.next() else { break };
+            match key {
+                "server-id" => p.server_id = Some(value.clone()),
+                "log-level" => p.log_level = Some(value.clone()),
+                "shutdown-timeout-secs" => p.shutdown_timeout_secs = value.parse().ok(),
+                "db-url" => p.db_url = Some(value.clone()),
+                "db-pool-size" => p.db_pool_size = value.parse().ok(),
+                "db-timeout-secs" => p.db_timeout_secs = value.parse().ok(),
+                "db-tls-mode" => p.db_tls_mode = value.parse().ok(),
+                "db-tls-ca-cert" => p.db_tls_ca_cert = Some(value.clone()),
+                "nats-url" => p.nats_url = Some(value.clone()),
+                "nats-prefix" => p.nats_prefix = Some(value.clone()),
+                "jetstream-enabled" => p.jetstream_enabled = value.parse().ok(),
+                "jetstream-stream-name" => p.jetstream_stream_name = Some(value.clone()),
+                "jetstream-storage" => p.jetstream_storage = value.parse().ok(),
+                "jetstream-max-age-secs" => p.jetstream_max_age_secs = value.parse().ok(),
+                "jetstream-max-bytes" => p.jetstream_max_bytes = value.parse().ok(),
+                "client-timeout-secs" => p.client_timeout_secs = value.parse().ok(),
+                "cleanup-interval-secs" => p.cleanup_interval_secs = value.parse().ok(),
+                "max-subscriptions" => p.max_subscriptions = value.parse().ok(),
+                "gone-grace-secs" => p.gone_grace_secs = value.parse().ok(),
+                "replay-buffer-cap" => p.replay_buffer_cap = value.parse().ok(),
+                "coalesce-window-ms" => p.coalesce_window_ms = value.parse().ok(),
+                "coalesce-max-pending" => p.coalesce_max_pending = value.parse().ok(),
+                "max-distinct-queries" => p.max_distinct_queries = value.parse().ok(),
+                "max-in-flight-snapshots" => p.max_in_flight_snapshots = value.parse().ok(),
+                "max-interned-strings" => p.max_interned_strings = value.parse().ok(),
+                "wal-slot" => p.wal_slot = Some(value.clone()),
+                "wal-publication" => p.wal_publication = Some(value.clone()),
+                "gateway-bind" => p.gateway_bind = Some(value.clone()),
+                "heartbeat-interval-ms" => p.heartbeat_interval_ms = value.parse().ok(),
+                "wire-format" => p.wire_format = value.parse().ok(),
+                _ => {}
+            }
+        }
+        p
+    }
+
     /// Validate configuration values
     fn validate(&self) -> Result<()> {
         macro_rules! check {
@@ -79,30 +361,126 @@ impl Config {
         check!(self.db_pool_size > 100, "DB_POOL_SIZE must be <= 100");
         check!(self.db_timeout_secs == 0, "DB_TIMEOUT_SECS must be > 0");
         check!(
-            self.client_timeout_secs < 5,
-            "CLIENT_TIMEOUT_SECS must be >= 5"
+            self.db_tls_mode == DbTlsMode::VerifyFull && self.db_tls_ca_cert.is_none(),
+            "DB_TLS_CA_CERT is required when DB_TLS_MODE=verify-full"
+        );
+        Self::validate_client_timeout_secs(self.client_timeout_secs)?;
+        Self::validate_cleanup_interval_secs(self.cleanup_interval_secs)?;
+        Self::validate_max_subscriptions(self.max_subscriptions)?;
+        Self::validate_db_pool_size(self.db_pool_size)?;
+        check!(self.gone_grace_secs == 0, "GONE_GRACE_SECS must be > 0");
+        check!(
+            self.replay_buffer_cap == 0,
+            "REPLAY_BUFFER_CAP must be > 0"
+        );
+        check!(
+            self.coalesce_window_ms == 0,
+            "COALESCE_WINDOW_MS must be > 0"
+        );
+        check!(
+            self.coalesce_max_pending == 0,
+            "COALESCE_MAX_PENDING must be > 0"
         );
         check!(
-            self.cleanup_interval_secs == 0,
-            "CLEANUP_INTERVAL_SECS must be > 0"
+            self.max_distinct_queries == 0,
+            "MAX_DISTINCT_QUERIES must be > 0"
+        );
+        check!(
+            self.max_in_flight_snapshots == 0,
+            "MAX_IN_FLIGHT_SNAPSHOTS must be > 0"
         );
-        check!(self.max_subscriptions == 0, "MAX_SUBSCRIPTIONS must be > 0");
         check!(
             self.shutdown_timeout_secs == 0,
             "SHUTDOWN_TIMEOUT_SECS must be > 0"
         );
+        check!(
+            self.heartbeat_interval_ms == 0,
+            "HEARTBEAT_INTERVAL_MS must be > 0"
+        );
+        Ok(())
+    }
+
+    /// Per-field checks used by both `validate()` and `vars::set` (a `SET`
+    /// must re-run the same rule the field was validated against at load).
+    pub(crate) fn validate_client_timeout_secs(v: u64) -> Result<()> {
+        if v < 5 {
+            bail!("CLIENT_TIMEOUT_SECS must be >= 5");
+        }
+        Ok(())
+    }
+    pub(crate) fn validate_cleanup_interval_secs(v: u64) -> Result<()> {
+        if v == 0 {
+            bail!("CLEANUP_INTERVAL_SECS must be > 0");
+        }
         Ok(())
     }
+    pub(crate) fn validate_max_subscriptions(v: usize) -> Result<()> {
+        if v == 0 {
+            bail!("MAX_SUBSCRIPTIONS must be > 0");
+        }
+        Ok(())
+    }
+    pub(crate) fn validate_db_pool_size(v: u32) -> Result<()> {
+        if v == 0 {
+            bail!("DB_POOL_SIZE must be > 0");
+        }
+        if v > 100 {
+            bail!("DB_POOL_SIZE must be <= 100");
+        }
+        Ok(())
+    }
+
+    /// Mask the password portion of a Postgres connection string for safe
+    /// logging/`SHOW db_url` (shared with `db_url_safe`).
+    pub(crate) fn mask_url(url: &str) -> String {
+        url.find('@')
+            .and_then(|a| {
+                url[..a]
+                    .rfind(':')
+                    .map(|c| format!("{}****{}", &url[..c + 1], &url[a..]))
+            })
+            .unwrap_or_else(|| url.to_string())
+    }
+
+    // === Live variables (SET/SHOW) ===
+
+    /// Parse and apply `value` to the named runtime-tunable, re-running its
+    /// `validate` check and swapping it in atomically. Unknown or
+    /// non-mutable names (`db_url`, `wal_slot`, `nats_url`, ...) are rejected.
+    pub fn set(&self, name: &str, value: &str) -> Result<()> {
+        super::vars::set(name, value)
+    }
+
+    /// Current value, default and description for one variable.
+    pub fn show(&self, name: &str) -> Result<super::vars::VarValue> {
+        super::vars::show(name)
+    }
+
+    /// Current value, default and description for every known variable.
+    pub fn show_all(&self) -> Vec<super::vars::VarValue> {
+        super::vars::show_all()
+    }
 
     // === Duration helpers ===
 
+    /// Reads the live value (see `core::vars`), so a `SET client_timeout_secs`
+    /// takes effect on the reaper's very next sweep.
     #[inline]
     pub fn client_timeout(&self) -> Duration {
-        Duration::from_secs(self.client_timeout_secs)
+        Duration::from_secs(super::vars::client_timeout_secs())
     }
+    /// Reads the live value (see `core::vars`).
     #[inline]
     pub fn cleanup_interval(&self) -> Duration {
-        Duration::from_secs(self.cleanup_interval_secs)
+        Duration::from_secs(super::vars::cleanup_interval_secs())
+    }
+    #[inline]
+    pub fn gone_grace(&self) -> Duration {
+        Duration::from_secs(self.gone_grace_secs)
+    }
+    #[inline]
+    pub fn coalesce_window(&self) -> Duration {
+        Duration::from_millis(self.coalesce_window_ms)
     }
     #[inline]
     pub fn db_timeout(&self) -> Duration {
@@ -112,6 +490,10 @@ impl Config {
     pub fn shutdown_timeout(&self) -> Duration {
         Duration::from_secs(self.shutdown_timeout_secs)
     }
+    #[inline]
+    pub fn heartbeat_interval(&self) -> Duration {
+        Duration::from_millis(self.heartbeat_interval_ms)
+    }
 
     /// Subscription-specific events subject: livequery.{sub_id}.events
     #[inline]
@@ -121,14 +503,104 @@ impl Config {
 
     /// Safe URL for logging (hides password)
     pub fn db_url_safe(&self) -> String {
-        self.db_url
-            .find('@')
-            .and_then(|a| {
-                self.db_url[..a]
-                    .rfind(':')
-                    .map(|c| format!("{}****{}", &self.db_url[..c + 1], &self.db_url[a..]))
-            })
-            .unwrap_or_else(|| self.db_url.clone())
+        Self::mask_url(&self.db_url)
+    }
+
+    /// Re-read configuration from the same source(s) `load()` used (same
+    /// `--config`/`LIVEQUERY_CONFIG` file, env vars, CLI flags) and diff it
+    /// against `self`. Applies the live-tunable subset to the shared
+    /// `core::vars` registry and returns the full `ConfigDiff` so the caller
+    /// can log it; fields that require a restart are reported but never
+    /// touched. If the freshly loaded config fails `validate()`, no vars are
+    /// changed and the old config keeps running - a reload never partially
+    /// applies.
+    pub fn reload_from_source(&self) -> Result<ConfigDiff> {
+        let new = Self::load()?;
+        let mut diff = ConfigDiff::default();
+
+        macro_rules! field {
+            ($bucket:ident, $f:ident, $name:literal) => {
+                if self.$f != new.$f {
+                    diff.$bucket
+                        .push(($name, self.$f.to_string(), new.$f.to_string()));
+                }
+            };
+        }
+        field!(applied, client_timeout_secs, "client_timeout_secs");
+        field!(applied, cleanup_interval_secs, "cleanup_interval_secs");
+        field!(applied, max_subscriptions, "max_subscriptions");
+        field!(applied, log_level, "log_level");
+        field!(restart_required, db_url, "db_url");
+        field!(restart_required, nats_url, "nats_url");
+        field!(restart_required, jetstream_enabled, "jetstream_enabled");
+        field!(
+            restart_required,
+            jetstream_stream_name,
+            "jetstream_stream_name"
+        );
+        field!(restart_required, jetstream_storage, "jetstream_storage");
+        field!(
+            restart_required,
+            jetstream_max_age_secs,
+            "jetstream_max_age_secs"
+        );
+        field!(
+            restart_required,
+            jetstream_max_bytes,
+            "jetstream_max_bytes"
+        );
+        field!(restart_required, wal_slot, "wal_slot");
+        field!(restart_required, wal_publication, "wal_publication");
+        field!(restart_required, db_pool_size, "db_pool_size");
+        field!(restart_required, db_tls_mode, "db_tls_mode");
+        field!(restart_required, replay_buffer_cap, "replay_buffer_cap");
+        field!(restart_required, coalesce_window_ms, "coalesce_window_ms");
+        field!(
+            restart_required,
+            coalesce_max_pending,
+            "coalesce_max_pending"
+        );
+        field!(
+            restart_required,
+            max_distinct_queries,
+            "max_distinct_queries"
+        );
+        field!(
+            restart_required,
+            max_in_flight_snapshots,
+            "max_in_flight_snapshots"
+        );
+        field!(restart_required, gateway_bind, "gateway_bind");
+        field!(
+            restart_required,
+            heartbeat_interval_ms,
+            "heartbeat_interval_ms"
+        );
+        field!(restart_required, gone_grace_secs, "gone_grace_secs");
+        field!(restart_required, shutdown_timeout_secs, "shutdown_timeout_secs");
+        field!(restart_required, db_timeout_secs, "db_timeout_secs");
+        field!(restart_required, nats_prefix, "nats_prefix");
+        field!(restart_required, max_interned_strings, "max_interned_strings");
+        if self.wire_format != new.wire_format {
+            diff.restart_required.push((
+                "wire_format",
+                format!("{:?}", self.wire_format),
+                format!("{:?}", new.wire_format),
+            ));
+        }
+        if self.db_tls_ca_cert != new.db_tls_ca_cert {
+            diff.restart_required.push((
+                "db_tls_ca_cert",
+                self.db_tls_ca_cert.clone().unwrap_or_default(),
+                new.db_tls_ca_cert.clone().unwrap_or_default(),
+            ));
+        }
+
+        for (name, _, new_value) in &diff.applied {
+            super::vars::set(name, new_value)?;
+        }
+
+        Ok(diff)
     }
 
     /// Log all config (safe)
@@ -138,9 +610,11 @@ impl Config {
         info!("│ server_id: {:<27} │", &self.server_id);
         info!("│ db:        {:<27} │", self.db_url_safe());
         info!("│ db_pool:   {:<27} │", self.db_pool_size);
+        info!("│ db_tls:    {:<27} │", self.db_tls_mode.to_string());
         info!("│ nats:      {:<27} │", &self.nats_url);
         info!("│ prefix:    {:<27} │", &self.nats_prefix);
         info!("│ wal_slot:  {:<27} │", &self.wal_slot);
+        info!("│ gateway:   {:<27} │", &self.gateway_bind);
         info!(
             "│ timeout:   {}s cleanup={}s{:<13} │",
             self.client_timeout_secs, self.cleanup_interval_secs, ""
@@ -149,6 +623,36 @@ impl Config {
     }
 }
 
+/// Result of `Config::reload_from_source`: every field whose value changed
+/// (`server_id` is not compared), split by whether it was applied live (via `core::vars`) or merely noted
+/// as needing a process restart. Each entry is `(field, old, new)`.
+#[derive(Debug, Default)]
+pub struct ConfigDiff {
+    pub applied: Vec<(&'static str, String, String)>,
+    pub restart_required: Vec<(&'static str, String, String)>,
+}
+
+impl ConfigDiff {
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.restart_required.is_empty()
+    }
+
+    /// Log the diff in the same boxed style as `Config::log_summary`.
+    pub fn log_summary(&self) {
+        use tracing::{info, warn};
+        if self.is_empty() {
+            info!("Config reload: no changes");
+            return;
+        }
+        for (name, old, new) in &self.applied {
+            info!("Config reload: {name} {old} -> {new} (applied)");
+        }
+        for (name, old, new) in &self.restart_required {
+            warn!("Config reload: {name} {old} -> {new} (requires restart, not applied)");
+        }
+    }
+}
+
 #[inline]
 fn env<T: std::str::FromStr>(key: &str, default: T) -> T {
     var(key)
@@ -157,6 +661,11 @@ fn env<T: std::str::FromStr>(key: &str, default: T) -> T {
         .unwrap_or(default)
 }
 
+#[inline]
+fn env_opt<T: std::str::FromStr>(key: &str) -> Option<T> {
+    var(key).ok().and_then(|v| v.parse().ok())
+}
+
 #[inline]
 fn gen_id() -> String {
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -167,6 +676,133 @@ fn gen_id() -> String {
     format!("lq-{:x}", ts & 0xFFFFFF)
 }
 
+/// Every `Config` field as an `Option`, deserialized from a config file and
+/// overlaid with environment/CLI layers before `Config::load` fills in
+/// built-in defaults and validates once at the end. Mirrors `ConfigBuilder`'s
+/// field set field-for-field.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Partial {
+    pub server_id: Option<String>,
+    pub log_level: Option<String>,
+    pub shutdown_timeout_secs: Option<u64>,
+    pub db_url: Option<String>,
+    pub db_pool_size: Option<u32>,
+    pub db_timeout_secs: Option<u64>,
+    pub db_tls_mode: Option<DbTlsMode>,
+    pub db_tls_ca_cert: Option<String>,
+    pub nats_url: Option<String>,
+    pub nats_prefix: Option<String>,
+    pub jetstream_enabled: Option<bool>,
+    pub jetstream_stream_name: Option<String>,
+    pub jetstream_storage: Option<JetStreamStorage>,
+    pub jetstream_max_age_secs: Option<u64>,
+    pub jetstream_max_bytes: Option<i64>,
+    pub client_timeout_secs: Option<u64>,
+    pub cleanup_interval_secs: Option<u64>,
+    pub max_subscriptions: Option<usize>,
+    pub gone_grace_secs: Option<u64>,
+    pub replay_buffer_cap: Option<usize>,
+    pub coalesce_window_ms: Option<u64>,
+    pub coalesce_max_pending: Option<usize>,
+    pub max_distinct_queries: Option<usize>,
+    pub max_in_flight_snapshots: Option<usize>,
+    pub max_interned_strings: Option<usize>,
+    pub wal_slot: Option<String>,
+    pub wal_publication: Option<String>,
+    pub gateway_bind: Option<String>,
+    pub heartbeat_interval_ms: Option<u64>,
+    pub wire_format: Option<WireFormat>,
+}
+
+impl Partial {
+    /// Overlay `other`'s present fields on top of `self`; `other` wins wherever it's `Some`.
+    fn merge(mut self, other: Partial) -> Self {
+        macro_rules! take {
+            ($f:ident) => {
+                if other.$f.is_some() {
+                    self.$f = other.$f;
+                }
+            };
+        }
+        take!(server_id);
+        take!(log_level);
+        take!(shutdown_timeout_secs);
+        take!(db_url);
+        take!(db_pool_size);
+        take!(db_timeout_secs);
+        take!(db_tls_mode);
+        take!(db_tls_ca_cert);
+        take!(nats_url);
+        take!(nats_prefix);
+        take!(jetstream_enabled);
+        take!(jetstream_stream_name);
+        take!(jetstream_storage);
+        take!(jetstream_max_age_secs);
+        take!(jetstream_max_bytes);
+        take!(client_timeout_secs);
+        take!(cleanup_interval_secs);
+        take!(max_subscriptions);
+        take!(gone_grace_secs);
+        take!(replay_buffer_cap);
+        take!(coalesce_window_ms);
+        take!(coalesce_max_pending);
+        take!(max_distinct_queries);
+        take!(max_in_flight_snapshots);
+        take!(max_interned_strings);
+        take!(wal_slot);
+        take!(wal_publication);
+        take!(gateway_bind);
+        take!(heartbeat_interval_ms);
+        take!(wire_format);
+        self
+    }
+
+    /// Fill in built-in defaults for anything still unset, then validate.
+    fn into_config(self) -> Result<Config> {
+        let cfg = Config {
+            server_id: self.server_id.unwrap_or_else(gen_id),
+            log_level: self.log_level.unwrap_or_else(|| "info".into()),
+            shutdown_timeout_secs: self.shutdown_timeout_secs.unwrap_or(30),
+            db_url: self.db_url.context("DATABASE_URL required")?,
+            db_pool_size: self.db_pool_size.unwrap_or(16),
+            db_timeout_secs: self.db_timeout_secs.unwrap_or(30),
+            db_tls_mode: self.db_tls_mode.unwrap_or_default(),
+            db_tls_ca_cert: self.db_tls_ca_cert,
+            nats_url: self
+                .nats_url
+                .unwrap_or_else(|| "nats://localhost:4222".into()),
+            nats_prefix: self.nats_prefix.unwrap_or_else(|| "livequery".into()),
+            jetstream_enabled: self.jetstream_enabled.unwrap_or(false),
+            jetstream_stream_name: self
+                .jetstream_stream_name
+                .unwrap_or_else(|| "LIVEQUERY_EVENTS".into()),
+            jetstream_storage: self.jetstream_storage.unwrap_or_default(),
+            jetstream_max_age_secs: self.jetstream_max_age_secs.unwrap_or(0),
+            jetstream_max_bytes: self.jetstream_max_bytes.unwrap_or(-1),
+            client_timeout_secs: self.client_timeout_secs.unwrap_or(30),
+            cleanup_interval_secs: self.cleanup_interval_secs.unwrap_or(10),
+            max_subscriptions: self.max_subscriptions.unwrap_or(10000),
+            gone_grace_secs: self.gone_grace_secs.unwrap_or(30),
+            replay_buffer_cap: self.replay_buffer_cap.unwrap_or(256),
+            coalesce_window_ms: self.coalesce_window_ms.unwrap_or(500),
+            coalesce_max_pending: self.coalesce_max_pending.unwrap_or(1000),
+            max_distinct_queries: self.max_distinct_queries.unwrap_or(usize::MAX),
+            max_in_flight_snapshots: self.max_in_flight_snapshots.unwrap_or(64),
+            max_interned_strings: self.max_interned_strings.unwrap_or(200_000),
+            wal_slot: self.wal_slot.unwrap_or_else(|| "livequery_slot".into()),
+            wal_publication: self
+                .wal_publication
+                .unwrap_or_else(|| "livequery_pub".into()),
+            gateway_bind: self.gateway_bind.unwrap_or_else(|| "0.0.0.0:8090".into()),
+            heartbeat_interval_ms: self.heartbeat_interval_ms.unwrap_or(15_000),
+            wire_format: self.wire_format.unwrap_or_default(),
+        };
+        cfg.validate()?;
+        Ok(cfg)
+    }
+}
+
 /// Builder pattern for Config (useful for testing)
 #[derive(Default)]
 #[allow(dead_code)] // API for testing/embedding
@@ -177,13 +813,30 @@ pub struct ConfigBuilder {
     db_url: Option<String>,
     db_pool_size: Option<u32>,
     db_timeout_secs: Option<u64>,
+    db_tls_mode: Option<DbTlsMode>,
+    db_tls_ca_cert: Option<String>,
     nats_url: Option<String>,
     nats_prefix: Option<String>,
+    jetstream_enabled: Option<bool>,
+    jetstream_stream_name: Option<String>,
+    jetstream_storage: Option<JetStreamStorage>,
+    jetstream_max_age_secs: Option<u64>,
+    jetstream_max_bytes: Option<i64>,
     client_timeout_secs: Option<u64>,
     cleanup_interval_secs: Option<u64>,
     max_subscriptions: Option<usize>,
+    gone_grace_secs: Option<u64>,
+    replay_buffer_cap: Option<usize>,
+    coalesce_window_ms: Option<u64>,
+    coalesce_max_pending: Option<usize>,
+    max_distinct_queries: Option<usize>,
+    max_in_flight_snapshots: Option<usize>,
+    max_interned_strings: Option<usize>,
     wal_slot: Option<String>,
     wal_publication: Option<String>,
+    gateway_bind: Option<String>,
+    heartbeat_interval_ms: Option<u64>,
+    wire_format: Option<WireFormat>,
 }
 
 #[allow(dead_code)] // API for testing/embedding
@@ -216,6 +869,14 @@ impl ConfigBuilder {
         self.db_timeout_secs = Some(v);
         self
     }
+    pub fn db_tls_mode(mut self, v: DbTlsMode) -> Self {
+        self.db_tls_mode = Some(v);
+        self
+    }
+    pub fn db_tls_ca_cert(mut self, v: impl Into<String>) -> Self {
+        self.db_tls_ca_cert = Some(v.into());
+        self
+    }
     pub fn nats_url(mut self, v: impl Into<String>) -> Self {
         self.nats_url = Some(v.into());
         self
@@ -224,6 +885,26 @@ impl ConfigBuilder {
         self.nats_prefix = Some(v.into());
         self
     }
+    pub fn jetstream_enabled(mut self, v: bool) -> Self {
+        self.jetstream_enabled = Some(v);
+        self
+    }
+    pub fn jetstream_stream_name(mut self, v: impl Into<String>) -> Self {
+        self.jetstream_stream_name = Some(v.into());
+        self
+    }
+    pub fn jetstream_storage(mut self, v: JetStreamStorage) -> Self {
+        self.jetstream_storage = Some(v);
+        self
+    }
+    pub fn jetstream_max_age_secs(mut self, v: u64) -> Self {
+        self.jetstream_max_age_secs = Some(v);
+        self
+    }
+    pub fn jetstream_max_bytes(mut self, v: i64) -> Self {
+        self.jetstream_max_bytes = Some(v);
+        self
+    }
     pub fn client_timeout_secs(mut self, v: u64) -> Self {
         self.client_timeout_secs = Some(v);
         self
@@ -236,6 +917,34 @@ impl ConfigBuilder {
         self.max_subscriptions = Some(v);
         self
     }
+    pub fn gone_grace_secs(mut self, v: u64) -> Self {
+        self.gone_grace_secs = Some(v);
+        self
+    }
+    pub fn replay_buffer_cap(mut self, v: usize) -> Self {
+        self.replay_buffer_cap = Some(v);
+        self
+    }
+    pub fn coalesce_window_ms(mut self, v: u64) -> Self {
+        self.coalesce_window_ms = Some(v);
+        self
+    }
+    pub fn coalesce_max_pending(mut self, v: usize) -> Self {
+        self.coalesce_max_pending = Some(v);
+        self
+    }
+    pub fn max_distinct_queries(mut self, v: usize) -> Self {
+        self.max_distinct_queries = Some(v);
+        self
+    }
+    pub fn max_in_flight_snapshots(mut self, v: usize) -> Self {
+        self.max_in_flight_snapshots = Some(v);
+        self
+    }
+    pub fn max_interned_strings(mut self, v: usize) -> Self {
+        self.max_interned_strings = Some(v);
+        self
+    }
     pub fn wal_slot(mut self, v: impl Into<String>) -> Self {
         self.wal_slot = Some(v.into());
         self
@@ -244,6 +953,18 @@ impl ConfigBuilder {
         self.wal_publication = Some(v.into());
         self
     }
+    pub fn gateway_bind(mut self, v: impl Into<String>) -> Self {
+        self.gateway_bind = Some(v.into());
+        self
+    }
+    pub fn heartbeat_interval_ms(mut self, v: u64) -> Self {
+        self.heartbeat_interval_ms = Some(v);
+        self
+    }
+    pub fn wire_format(mut self, v: WireFormat) -> Self {
+        self.wire_format = Some(v);
+        self
+    }
 
     pub fn build(self) -> Result<Config> {
         let cfg = Config {
@@ -255,17 +976,36 @@ impl ConfigBuilder {
                 .ok_or_else(|| anyhow::anyhow!("db_url is required"))?,
             db_pool_size: self.db_pool_size.unwrap_or(16),
             db_timeout_secs: self.db_timeout_secs.unwrap_or(30),
+            db_tls_mode: self.db_tls_mode.unwrap_or_default(),
+            db_tls_ca_cert: self.db_tls_ca_cert,
             nats_url: self
                 .nats_url
                 .unwrap_or_else(|| "nats://localhost:4222".into()),
             nats_prefix: self.nats_prefix.unwrap_or_else(|| "livequery".into()),
+            jetstream_enabled: self.jetstream_enabled.unwrap_or(false),
+            jetstream_stream_name: self
+                .jetstream_stream_name
+                .unwrap_or_else(|| "LIVEQUERY_EVENTS".into()),
+            jetstream_storage: self.jetstream_storage.unwrap_or_default(),
+            jetstream_max_age_secs: self.jetstream_max_age_secs.unwrap_or(0),
+            jetstream_max_bytes: self.jetstream_max_bytes.unwrap_or(-1),
             client_timeout_secs: self.client_timeout_secs.unwrap_or(30),
             cleanup_interval_secs: self.cleanup_interval_secs.unwrap_or(10),
             max_subscriptions: self.max_subscriptions.unwrap_or(10000),
+            gone_grace_secs: self.gone_grace_secs.unwrap_or(30),
+            replay_buffer_cap: self.replay_buffer_cap.unwrap_or(256),
+            coalesce_window_ms: self.coalesce_window_ms.unwrap_or(500),
+            coalesce_max_pending: self.coalesce_max_pending.unwrap_or(1000),
+            max_distinct_queries: self.max_distinct_queries.unwrap_or(usize::MAX),
+            max_in_flight_snapshots: self.max_in_flight_snapshots.unwrap_or(64),
+            max_interned_strings: self.max_interned_strings.unwrap_or(200_000),
             wal_slot: self.wal_slot.unwrap_or_else(|| "livequery_slot".into()),
             wal_publication: self
                 .wal_publication
                 .unwrap_or_else(|| "livequery_pub".into()),
+            gateway_bind: self.gateway_bind.unwrap_or_else(|| "0.0.0.0:8090".into()),
+            heartbeat_interval_ms: self.heartbeat_interval_ms.unwrap_or(15_000),
+            wire_format: self.wire_format.unwrap_or_default(),
         };
         cfg.validate()?;
         Ok(cfg)
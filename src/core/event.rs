@@ -2,6 +2,7 @@
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
 use std::sync::Arc;
 
 /// Subscription delivery mode
@@ -13,6 +14,102 @@ pub enum SubscriptionMode {
     Events,
     /// Deliver full snapshot on every change
     Snapshot,
+    /// Coalesce a burst of changes to the same row within a window (or count
+    /// threshold) into one net event instead of delivering each - see
+    /// `SharedQuery::fold_coalesced`/`flush_coalesced`.
+    Coalesced,
+}
+
+/// Stable machine-readable error code for `SubscribeResponse::code`, so a
+/// client can branch on the failure kind (retry with backoff, fix the
+/// request, drop a stale id) instead of string-matching `error`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubError {
+    /// Malformed request JSON, or a query that failed to parse/validate.
+    BadRequest,
+    /// An admission-control cap in `SubscriptionManager` was hit (total
+    /// subscriptions, distinct queries, or in-flight snapshot executions);
+    /// safe to retry with backoff.
+    ServiceOverloaded,
+    /// The query parsed but failed to execute against the database.
+    QueryFailed,
+    /// Referenced a subscription/query id that doesn't exist.
+    NotFound,
+    /// Credential failed `AuthProvider::authenticate`, its principal is
+    /// banned, or its `AuthProvider::allowed_tables` doesn't cover the
+    /// query's table(s).
+    Forbidden,
+}
+
+/// Wire format used to encode published batches/snapshots. Negotiable per
+/// subscription alongside `SubscriptionMode`, falling back to
+/// `Config::wire_format` when a request doesn't specify one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WireFormat {
+    #[default]
+    Json,
+    #[serde(rename = "msgpack")]
+    MessagePack,
+    Cbor,
+    /// Compact length-prefixed binary framing for `EventBatch`, with row
+    /// values encoded columnarly to avoid repeating JSON keys per event; see
+    /// `EventBatch::encode_binary`. Generic payloads that aren't an
+    /// `EventBatch` (e.g. snapshot rows) don't have a known column list to
+    /// encode against, so `encode` falls back to MessagePack for those.
+    Binary,
+    /// Arrow IPC stream framing for `EventBatch`, one `RecordBatch` per
+    /// batch with a `mz_timestamp`/`mz_diff` column alongside the query's
+    /// SELECT columns; see `core::wire::encode_batch_arrow`. Same caveat as
+    /// `Binary`: only `EventBatch` publish paths that know the query's fixed
+    /// column list can build a `RecordBatch` schema for it, so `encode`
+    /// falls back to MessagePack for everything else.
+    Arrow,
+}
+
+impl WireFormat {
+    /// Content-type hint published alongside the encoded bytes.
+    #[inline]
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            WireFormat::Json => "application/json",
+            WireFormat::MessagePack => "application/msgpack",
+            WireFormat::Cbor => "application/cbor",
+            WireFormat::Binary => "application/octet-stream",
+            WireFormat::Arrow => "application/vnd.apache.arrow.stream",
+        }
+    }
+
+    /// Encode a serializable value in this format. `Binary` and `Arrow` only
+    /// have a dedicated columnar encoding for `EventBatch` (see
+    /// `EventBatch::encode_binary` and `core::wire::encode_batch_arrow`,
+    /// both used directly by publish paths that know the query's column
+    /// list); for any other `T` this falls back to MessagePack.
+    #[inline]
+    pub fn encode<T: Serialize>(&self, v: &T) -> Vec<u8> {
+        match self {
+            WireFormat::Json => serde_json::to_vec(v).unwrap_or_default(),
+            WireFormat::MessagePack | WireFormat::Binary | WireFormat::Arrow => {
+                rmp_serde::to_vec_named(v).unwrap_or_default()
+            }
+            WireFormat::Cbor => serde_cbor::to_vec(v).unwrap_or_default(),
+        }
+    }
+}
+
+impl std::str::FromStr for WireFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(WireFormat::Json),
+            "msgpack" | "messagepack" => Ok(WireFormat::MessagePack),
+            "cbor" => Ok(WireFormat::Cbor),
+            "binary" | "bin" => Ok(WireFormat::Binary),
+            "arrow" => Ok(WireFormat::Arrow),
+            other => Err(format!("unknown wire format: {other}")),
+        }
+    }
 }
 
 /// Single change event (insert=+1, delete=-1)
@@ -22,6 +119,11 @@ pub struct SubscribeEvent {
     pub mz_diff: i8,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<Arc<Value>>,
+    /// Set only on a terminal "subscription ended" event (see
+    /// `SubscriptionManager::force_unsubscribe`) - absent on ordinary
+    /// insert/delete events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gone: Option<Arc<str>>,
 }
 
 #[allow(dead_code)]
@@ -32,6 +134,7 @@ impl SubscribeEvent {
             mz_timestamp: t,
             mz_diff: 1,
             data: Some(Arc::new(d)),
+            gone: None,
         }
     }
     #[inline(always)]
@@ -40,6 +143,7 @@ impl SubscribeEvent {
             mz_timestamp: t,
             mz_diff: -1,
             data: Some(Arc::new(d)),
+            gone: None,
         }
     }
     #[inline(always)]
@@ -48,6 +152,7 @@ impl SubscribeEvent {
             mz_timestamp: t,
             mz_diff: 1,
             data: Some(d),
+            gone: None,
         }
     }
     #[inline(always)]
@@ -56,12 +161,24 @@ impl SubscribeEvent {
             mz_timestamp: t,
             mz_diff: -1,
             data: Some(d),
+            gone: None,
+        }
+    }
+    /// Terminal event marking a server-driven unsubscribe; carries no row
+    /// data. The client should stop reading after this and `ack_gone` it.
+    #[inline(always)]
+    pub fn gone(t: i64, reason: impl Into<Arc<str>>) -> Self {
+        Self {
+            mz_timestamp: t,
+            mz_diff: 0,
+            data: None,
+            gone: Some(reason.into()),
         }
     }
 }
 
 /// Batch of events with sequence number and server timestamp
-#[derive(Debug, Clone, Serialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct EventBatch {
     pub seq: u64,
     /// Server timestamp in milliseconds (for client latency calculation)
@@ -71,22 +188,118 @@ pub struct EventBatch {
 
 impl EventBatch {
     #[inline(always)]
-    pub fn new(seq: u64, events: Vec<SubscribeEvent>) -> Self {
+    pub fn new(seq: u64, events: Vec<SubscribeEvent>, clock: &dyn Clock) -> Self {
         Self {
             seq,
-            ts: ts_millis(),
+            ts: clock.now_millis(),
             events,
         }
     }
+
+    /// Encode this batch as `[seq u64][count u32]` followed by one record per
+    /// event - `[mz_timestamp i64][mz_diff i8][len u32][value bytes]`, all
+    /// integers little-endian. This is the wire representation for
+    /// `WireFormat::Binary`.
+    ///
+    /// `cols` is the query's fixed SELECT column list, if it has one (see
+    /// `query::QueryAnalysis::select_cols`); it's sent to the client once per
+    /// query rather than once per row. When an event's `data` is a JSON
+    /// object with exactly those keys, `value bytes` is a JSON array of just
+    /// the values in `cols` order. Events that don't fit that shape (`gone`
+    /// events, a row missing a column, queries without a fixed projection)
+    /// fall back to encoding the full value instead.
+    pub fn encode_binary(&self, cols: Option<&[Arc<str>]>) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(12 + self.events.len() * 64);
+        buf.extend_from_slice(&self.seq.to_le_bytes());
+        buf.extend_from_slice(&(self.events.len() as u32).to_le_bytes());
+        for event in &self.events {
+            buf.extend_from_slice(&event.mz_timestamp.to_le_bytes());
+            buf.push(event.mz_diff as u8);
+            let value = Self::encode_event_value(event, cols);
+            buf.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            buf.extend_from_slice(&value);
+        }
+        buf
+    }
+
+    /// Encode this batch as an Arrow IPC stream: a `mz_timestamp`/`mz_diff`
+    /// column per event plus one column per entry in `cols`, with types
+    /// inferred from the events' JSON values (see `core::wire`). Returns an
+    /// empty `Vec` if writing the IPC stream fails. A query without a fixed
+    /// column list goes through `WireFormat::encode` (MessagePack) instead,
+    /// as `WireFormat::Arrow`'s doc comment describes.
+    pub fn encode_arrow(&self, cols: &[Arc<str>]) -> Vec<u8> {
+        crate::core::wire::encode_batch_arrow(self, cols)
+    }
+
+    fn encode_event_value(event: &SubscribeEvent, cols: Option<&[Arc<str>]>) -> Vec<u8> {
+        if let (Some(cols), Some(data)) = (cols, &event.data) {
+            if let Value::Object(map) = data.as_ref() {
+                if map.len() == cols.len() && cols.iter().all(|c| map.contains_key(c.as_ref())) {
+                    let row: Vec<&Value> = cols.iter().map(|c| &map[c.as_ref()]).collect();
+                    return serde_json::to_vec(&row).unwrap_or_default();
+                }
+            }
+        }
+        match (&event.data, &event.gone) {
+            (Some(data), _) => serde_json::to_vec(data.as_ref()).unwrap_or_default(),
+            (None, Some(reason)) => serde_json::to_vec(reason.as_ref()).unwrap_or_default(),
+            (None, None) => Vec::new(),
+        }
+    }
+}
+
+/// Source of the millisecond timestamps stamped onto `EventBatch::ts` and
+/// `SubscribeEvent::mz_timestamp`. Injected rather than read from the
+/// system clock directly so ordering/latency behavior is reproducible in
+/// tests and so an embedding application can supply its own hybrid logical
+/// clock (e.g. to keep `mz_timestamp` strictly increasing across a system
+/// clock step backward).
+pub trait Clock: Send + Sync {
+    fn now_millis(&self) -> u64;
+}
+
+/// Default clock: wall-clock time since the Unix epoch.
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline(always)]
+    fn now_millis(&self) -> u64 {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64)
+    }
+}
+
+/// Deterministic clock for tests. Starts at `start` and advances by one
+/// millisecond on every call, so successive events still get strictly
+/// increasing timestamps without depending on wall-clock time.
+#[derive(Debug)]
+pub struct TestClock {
+    next: AtomicU64,
+}
+
+impl TestClock {
+    pub fn new(start: u64) -> Self {
+        Self {
+            next: AtomicU64::new(start),
+        }
+    }
+}
+
+impl Clock for TestClock {
+    #[inline(always)]
+    fn now_millis(&self) -> u64 {
+        self.next.fetch_add(1, Relaxed)
+    }
 }
 
 /// Current time in milliseconds since UNIX epoch
 #[inline(always)]
 pub fn ts_millis() -> u64 {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map_or(0, |d| d.as_millis() as u64)
+    SystemClock.now_millis()
 }
 
 // === Request Types ===
@@ -99,6 +312,21 @@ pub struct SubscribeRequest {
     pub identity_columns: Option<Vec<String>>,
     #[serde(default)]
     pub mode: SubscriptionMode,
+    /// Wire format for published batches/snapshots; defaults to the
+    /// server's configured format when omitted.
+    #[serde(default)]
+    pub format: Option<WireFormat>,
+    /// Last batch seq this subscription acked before reconnecting. When
+    /// set and the query already exists, the manager tries to replay
+    /// buffered batches after it instead of returning a full resnapshot;
+    /// see `SubscriptionManager::replay`. Ignored for brand-new queries.
+    #[serde(default)]
+    pub resume_from_seq: Option<u64>,
+    /// Bearer credential for `AuthProvider::authenticate`, or the NATS
+    /// message's `Auth-Token` header in `infra::nats` when unset here.
+    /// Ignored under the default `AllowAllProvider`.
+    #[serde(default)]
+    pub auth_token: Option<String>,
 }
 
 // === Response Types ===
@@ -112,6 +340,10 @@ pub struct SubscribeResponse {
     pub subject: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// Machine-readable classification of `error`, set whenever `success` is
+    /// `false` (see `SubError`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<SubError>,
     pub is_new: bool,
     pub seq: u64,
     pub mode: SubscriptionMode,
@@ -121,6 +353,19 @@ pub struct SubscribeResponse {
     /// Initial data for snapshot mode
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub rows: Vec<Arc<Value>>,
+    /// Set when a resume request's `resume_from_seq` has fallen behind the
+    /// replay buffer; the client must re-subscribe fresh rather than trust
+    /// this response's (empty) snapshot.
+    #[serde(default)]
+    pub resync_required: bool,
+    /// `true` when `snapshot`/`rows` is just the delta since the caller's
+    /// `resume_from_seq` (via `SharedQuery::replay_since`), `false` when it's
+    /// the full current row set (a brand-new query, or an existing one
+    /// resubscribed without a resume cursor). `is_new` alone can't signal
+    /// this, since an existing query resubscribed both with and without a
+    /// resume cursor reports `is_new: false` either way.
+    #[serde(default)]
+    pub is_delta: bool,
 }
 
 impl SubscribeResponse {
@@ -132,6 +377,7 @@ impl SubscribeResponse {
         is_new: bool,
         seq: u64,
         snapshot: Vec<SubscribeEvent>,
+        is_delta: bool,
     ) -> Self {
         Self {
             success: true,
@@ -143,6 +389,8 @@ impl SubscribeResponse {
             mode: SubscriptionMode::Events,
             snapshot,
             rows: vec![],
+            is_delta,
+            ..Default::default()
         }
     }
     /// Snapshot mode response with full rows
@@ -153,6 +401,7 @@ impl SubscribeResponse {
         is_new: bool,
         seq: u64,
         rows: Vec<Arc<Value>>,
+        is_delta: bool,
     ) -> Self {
         Self {
             success: true,
@@ -164,13 +413,29 @@ impl SubscribeResponse {
             mode: SubscriptionMode::Snapshot,
             snapshot: vec![],
             rows,
+            is_delta,
+            ..Default::default()
         }
     }
     #[inline]
-    pub fn err(msg: &str) -> Self {
+    pub fn err(code: SubError, msg: &str) -> Self {
         Self {
             success: false,
             error: Some(msg.into()),
+            code: Some(code),
+            ..Default::default()
+        }
+    }
+    /// A resume request whose seq has fallen behind the replay buffer; the
+    /// client must re-subscribe for a fresh snapshot instead of trusting a
+    /// gap.
+    #[inline]
+    pub fn resync_required(sub_id: String, subject: String) -> Self {
+        Self {
+            success: true,
+            subscription_id: Some(sub_id),
+            subject: Some(subject),
+            resync_required: true,
             ..Default::default()
         }
     }
@@ -0,0 +1,186 @@
+//! Seeded content hasher for row fingerprinting (`row::RowData::hash_content`,
+//! `row::RowValue::hash_into`, `subscription::Snapshot`'s `id_hash`/
+//! `content_hash`), used in place of the unseeded `FxHasher` wherever a
+//! client can shape the hashed content and bucket collisions would matter -
+//! `subscription::Snapshot::diff_rows` keys its rows by `id_hash` in an
+//! `FxHashMap`, so a predictable hash lets a client craft rows that all
+//! collide into one bucket.
+//!
+//! On x86_64 with the `aes` target feature (and aarch64 with `aes`), each
+//! round runs a hardware AES round (`_mm_aesenc_si128` / `vaeseq_u8`+
+//! `vaesmcq_u8`) over a state initialized from the per-process seed;
+//! everywhere else falls back to a multiply-xor-rotate mix over the same
+//! seeded state, so callers don't need to know which backend compiled in.
+
+use std::hash::{BuildHasher, Hasher};
+use std::sync::LazyLock;
+
+/// The seed generated once per process - see [`ContentBuildHasher::default`].
+static PROCESS_SEED: LazyLock<ContentBuildHasher> = LazyLock::new(ContentBuildHasher::random);
+
+/// Four 64-bit seeds, cheap to `Copy` onto every `Snapshot`/`OrderedSnapshot`
+/// that needs a [`ContentHasher`] - every row fingerprinted with the same
+/// `ContentBuildHasher` is consistent with every other, but two processes
+/// (each with their own [`PROCESS_SEED`]) never agree on one.
+#[derive(Debug, Clone, Copy)]
+pub struct ContentBuildHasher {
+    seeds: [u64; 4],
+}
+
+impl ContentBuildHasher {
+    /// Draw four random seeds from the OS CSPRNG via
+    /// `std::collections::hash_map::RandomState`, already pulled in by
+    /// every `HashMap` in this crate, instead of adding a `rand` dependency
+    /// just for this.
+    fn random() -> Self {
+        let seed_of = |tag: u64| {
+            let mut h = std::collections::hash_map::RandomState::new().build_hasher();
+            h.write_u64(tag);
+            h.finish()
+        };
+        Self {
+            seeds: [seed_of(0), seed_of(1), seed_of(2), seed_of(3)],
+        }
+    }
+}
+
+/// The shared, process-wide seed (generated lazily on first use) - every
+/// `Snapshot`/`OrderedSnapshot` gets this same value, so `#[derive(Default)]`
+/// gives them a consistent-within-process, random-across-processes hasher
+/// for free.
+impl Default for ContentBuildHasher {
+    #[inline]
+    fn default() -> Self {
+        *PROCESS_SEED
+    }
+}
+
+impl BuildHasher for ContentBuildHasher {
+    type Hasher = ContentHasher;
+
+    #[inline]
+    fn build_hasher(&self) -> ContentHasher {
+        ContentHasher::new(self.seeds)
+    }
+}
+
+/// `Hasher` built from a [`ContentBuildHasher`]. Buffers input eight bytes
+/// at a time and folds a pair of words into the 128-bit state per
+/// [`backend::round`] call - once on every second `write`-sized word, and
+/// once more (with any odd trailing word) on `finish`.
+pub struct ContentHasher {
+    state: [u64; 2],
+    pending: u64,
+    has_pending: bool,
+}
+
+impl ContentHasher {
+    #[inline]
+    fn new(seeds: [u64; 4]) -> Self {
+        Self {
+            state: [seeds[0] ^ seeds[2], seeds[1] ^ seeds[3]],
+            pending: 0,
+            has_pending: false,
+        }
+    }
+
+    #[inline]
+    fn push_word(&mut self, word: u64) {
+        if self.has_pending {
+            self.state = backend::round(self.state, [self.pending, word]);
+            self.has_pending = false;
+        } else {
+            self.pending = word;
+            self.has_pending = true;
+        }
+    }
+}
+
+impl Hasher for ContentHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for c in &mut chunks {
+            self.push_word(u64::from_ne_bytes(c.try_into().unwrap()));
+        }
+        let rem = chunks.remainder();
+        if !rem.is_empty() {
+            // Tag the trailing word with its length so e.g. a single 0x01
+            // byte can't collide with a zero-padded two-byte [0x01, 0x00].
+            let mut buf = [0u8; 8];
+            buf[..rem.len()].copy_from_slice(rem);
+            buf[7] = rem.len() as u8;
+            self.push_word(u64::from_ne_bytes(buf));
+        }
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        let state = if self.has_pending {
+            backend::round(self.state, [self.pending, self.pending])
+        } else {
+            self.state
+        };
+        let [lo, hi] = backend::round(state, state);
+        lo ^ hi
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", target_feature = "aes"))]
+mod backend {
+    use std::arch::x86_64::{_mm_aesenc_si128, _mm_set_epi64x, _mm_storeu_si128, _mm_xor_si128, __m128i};
+
+    /// One AES round, input-keyed: `state` is the accumulator going in,
+    /// `input` is folded in as the round key before the AES round scrambles
+    /// it (SubBytes/ShiftRows/MixColumns/AddRoundKey), matching the shape of
+    /// the portable fallback below.
+    #[inline]
+    pub fn round(state: [u64; 2], input: [u64; 2]) -> [u64; 2] {
+        unsafe {
+            let s: __m128i = _mm_set_epi64x(state[1] as i64, state[0] as i64);
+            let key: __m128i = _mm_set_epi64x(input[1] as i64, input[0] as i64);
+            let mixed = _mm_aesenc_si128(_mm_xor_si128(s, key), key);
+            let mut out = [0u64; 2];
+            _mm_storeu_si128(out.as_mut_ptr().cast(), mixed);
+            out
+        }
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", target_feature = "aes"))]
+mod backend {
+    use std::arch::aarch64::{vaeseq_u8, vaesmcq_u8, vdupq_n_u8, veorq_u8};
+
+    /// ARMv8 crypto extensions have no single "AES round keyed by input"
+    /// instruction like `aesenc`, so this does it in two steps: `vaeseq_u8`
+    /// (SubBytes+ShiftRows+AddRoundKey(0)) then `vaesmcq_u8` (MixColumns),
+    /// then XOR `input` in by hand to play the same role as `aesenc`'s
+    /// implicit round-key add.
+    #[inline]
+    pub fn round(state: [u64; 2], input: [u64; 2]) -> [u64; 2] {
+        unsafe {
+            let s = std::mem::transmute::<[u64; 2], std::arch::aarch64::uint8x16_t>(state);
+            let mixed = vaesmcq_u8(vaeseq_u8(s, vdupq_n_u8(0)));
+            let keyed = veorq_u8(mixed, std::mem::transmute::<[u64; 2], std::arch::aarch64::uint8x16_t>(input));
+            std::mem::transmute::<std::arch::aarch64::uint8x16_t, [u64; 2]>(keyed)
+        }
+    }
+}
+
+#[cfg(not(any(
+    all(target_arch = "x86_64", target_feature = "aes"),
+    all(target_arch = "aarch64", target_feature = "aes"),
+)))]
+mod backend {
+    /// Multiply-xor-rotate mix, the same shape `FxHasher` uses but folding
+    /// `input` (the next pair of content words) into an accumulator that
+    /// starts from the per-process seed instead of zero - two processes with
+    /// different seeds never agree on which rows collide.
+    const MULTIPLIER: u64 = 0x517c_c1b7_2722_0a95;
+
+    #[inline]
+    pub fn round(state: [u64; 2], input: [u64; 2]) -> [u64; 2] {
+        let lo = (state[0] ^ input[0]).wrapping_mul(MULTIPLIER).rotate_left(31);
+        let hi = (state[1] ^ input[1]).wrapping_mul(MULTIPLIER).rotate_left(29);
+        [lo ^ hi.rotate_left(13), hi ^ lo.rotate_left(17)]
+    }
+}
@@ -1,8 +1,16 @@
+pub mod atom;
+pub mod auth;
+pub mod codec;
 pub mod config;
 pub mod event;
+pub mod hash;
 pub mod query;
 pub mod row;
 pub mod subscription;
+pub mod vars;
+pub mod wire;
 
-pub use config::Config;
+pub use auth::{AllowAllProvider, AuthProvider, Principal};
+pub use config::{Config, ConfigDiff, Partial as ConfigPartial};
 pub use subscription::SubscriptionManager;
+pub use vars::VarValue;
@@ -77,6 +77,69 @@ pub struct QueryAnalysis {
     pub tables: Vec<String>,
     pub filter: WhereFilter,
     pub is_simple: bool, // Single table, no JOINs/subqueries
+    /// Columns named in the SELECT list, or None for `SELECT *`/computed
+    /// expressions where any column change must be assumed relevant.
+    pub select_cols: Option<Vec<Box<str>>>,
+    /// `is_simple` and free of GROUP BY/DISTINCT/aggregates/ORDER BY/LIMIT,
+    /// so its snapshot can be maintained row-by-row from WAL changes instead
+    /// of requerying the database.
+    pub can_incremental: bool,
+    /// True if the query has an `ORDER BY` and/or `LIMIT`, i.e. row position
+    /// is meaningful. `SubscriptionManager` does not read this flag; it picks
+    /// a windowed `Snapshot` from `order_key` and `limit` instead.
+    pub is_ordered: bool,
+    /// Parsed `LIMIT` value, if present and a simple integer literal.
+    pub limit: Option<usize>,
+    /// `ORDER BY` sort key, if present and recognized (see `extract_order_key`).
+    /// Paired with `limit` by `SubscriptionManager` to seed a windowed
+    /// `subscription::Snapshot` for top-N "leaderboard" queries.
+    pub order_key: Option<OrderKey>,
+    /// `GROUP BY` columns, if present and every one is a plain column
+    /// reference. Paired with `aggregates` to seed a
+    /// `subscription::GroupSnapshot` for continuous-aggregate queries.
+    pub group_cols: Option<Vec<Box<str>>>,
+    /// SELECT-list aggregate functions, recognized only when every
+    /// projection item is either one of these or a bare `group_cols` column
+    /// (see `extract_aggregates`). `None` if there's no `GROUP BY`, or the
+    /// SELECT list has anything `subscription::GroupSnapshot` can't maintain
+    /// incrementally.
+    pub aggregates: Option<Vec<AggSpec>>,
+    /// `is_simple`, has a recognized `group_cols`/`aggregates`, and is free
+    /// of DISTINCT/HAVING/ORDER BY/LIMIT - i.e. a `GroupSnapshot` can
+    /// maintain it from WAL changes instead of requerying the database.
+    pub can_aggregate: bool,
+}
+
+/// One SELECT-list aggregate function recognized for incremental
+/// maintenance - see `QueryAnalysis::aggregates`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggSpec {
+    pub func: AggFunc,
+    /// Source column, `None` for `count(*)`.
+    pub col: Option<Box<str>>,
+    /// Output column name - the explicit alias, or a Postgres-style default
+    /// (`count`, `sum_x`, `avg_x`, ...) when none was given.
+    pub alias: Box<str>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggFunc {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+/// A single-column `ORDER BY <col> [ASC|DESC]` sort key recognized for top-N
+/// window maintenance. `None` in `QueryAnalysis::order_key` covers anything
+/// else - no `ORDER BY`, more than one sort expression, or a sort key that
+/// isn't a plain column reference - since a windowed `Snapshot` can't rank
+/// rows from those alone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderKey {
+    pub col: Box<str>,
+    pub desc: bool,
 }
 
 /// Analyze SQL query - cached for performance
@@ -86,8 +149,10 @@ pub fn analyze(q: &str) -> QueryAnalysis {
 
     // Fast path: cache hit - clone from Arc (QueryAnalysis is small)
     if let Some(cached) = CACHE.get(&h) {
+        crate::telemetry::record_query_analyze_cache(true);
         return QueryAnalysis::clone(&cached);
     }
+    crate::telemetry::record_query_analyze_cache(false);
 
     // Slow path: parse and cache
     let result = analyze_inner(q);
@@ -128,6 +193,13 @@ fn analyze_inner(q: &str) -> QueryAnalysis {
     let filter = extract_where(ast);
     let is_simple =
         tables.len() == 1 && !has_join && !has_subq && !matches!(filter, WhereFilter::Complex);
+    let select_cols = extract_select_cols(ast);
+    let can_incremental = is_simple && is_incremental_eligible(ast);
+    let limit = extract_limit(ast);
+    let order_key = extract_order_key(ast);
+    let is_ordered = ast.order_by.is_some() || limit.is_some();
+
+    let (group_cols, aggregates, can_aggregate) = extract_group_aggregate(ast, is_simple);
 
     QueryAnalysis {
         is_valid: true,
@@ -135,9 +207,211 @@ fn analyze_inner(q: &str) -> QueryAnalysis {
         tables,
         filter,
         is_simple,
+        select_cols,
+        can_incremental,
+        is_ordered,
+        limit,
+        order_key,
+        group_cols,
+        aggregates,
+        can_aggregate,
     }
 }
 
+/// Recognize a `GROUP BY`/aggregate query eligible for incremental
+/// maintenance by `subscription::GroupSnapshot`: `is_simple`, a single
+/// non-CTE SELECT, no DISTINCT/HAVING/ORDER BY/LIMIT, a `GROUP BY` of plain
+/// columns, and a SELECT list of nothing but those columns and recognized
+/// aggregate functions (see `extract_group_cols`/`extract_aggregates`).
+fn extract_group_aggregate(
+    q: &Query,
+    is_simple: bool,
+) -> (Option<Vec<Box<str>>>, Option<Vec<AggSpec>>, bool) {
+    if !is_simple || q.order_by.is_some() || q.limit.is_some() {
+        return (None, None, false);
+    }
+    let SetExpr::Select(s) = q.body.as_ref() else {
+        return (None, None, false);
+    };
+    if s.distinct.is_some() || s.having.is_some() {
+        return (None, None, false);
+    }
+    let Some(group_cols) = extract_group_cols(s) else {
+        return (None, None, false);
+    };
+    let aggregates = extract_aggregates(s, &group_cols);
+    let can_aggregate = aggregates.is_some();
+    (Some(group_cols), aggregates, can_aggregate)
+}
+
+/// Parse a `GROUP BY col1, col2` clause's columns, if non-empty and every
+/// expression is a plain column reference - the only form
+/// `subscription::GroupSnapshot` can key a group by.
+fn extract_group_cols(s: &Select) -> Option<Vec<Box<str>>> {
+    let exprs = match &s.group_by {
+        GroupByExpr::Expressions(e, _) if !e.is_empty() => e,
+        _ => return None,
+    };
+    exprs
+        .iter()
+        .map(|e| match e {
+            Expr::Identifier(id) => Some(id.value.to_lowercase().into_boxed_str()),
+            Expr::CompoundIdentifier(ids) => {
+                Some(ids.last()?.value.to_lowercase().into_boxed_str())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parse the SELECT list's aggregate functions - and the bare `group_cols`
+/// columns interspersed with them, e.g. `SELECT region, count(*) ...` -
+/// returning `None` if any item isn't one of those two things, or an
+/// aggregate this module doesn't know how to maintain incrementally.
+fn extract_aggregates(s: &Select, group_cols: &[Box<str>]) -> Option<Vec<AggSpec>> {
+    let mut specs = Vec::new();
+    for item in &s.projection {
+        let (expr, alias) = match item {
+            SelectItem::UnnamedExpr(e) => (e, None),
+            SelectItem::ExprWithAlias { expr, alias } => (expr, Some(alias.value.as_str())),
+            _ => return None,
+        };
+        match expr {
+            Expr::Function(f) => specs.push(parse_agg_spec(f, alias)?),
+            Expr::Identifier(id) => {
+                let col = id.value.to_lowercase();
+                if !group_cols.iter().any(|g| g.as_ref() == col) {
+                    return None;
+                }
+            }
+            Expr::CompoundIdentifier(ids) => {
+                let col = ids.last()?.value.to_lowercase();
+                if !group_cols.iter().any(|g| g.as_ref() == col) {
+                    return None;
+                }
+            }
+            _ => return None,
+        }
+    }
+    if specs.is_empty() {
+        None
+    } else {
+        Some(specs)
+    }
+}
+
+/// Parse one `count(*)`/`sum(col)`/`avg(col)`/`min(col)`/`max(col)` call
+/// into an `AggSpec`, or `None` for anything else (an unrecognized function,
+/// multiple/complex arguments, or a non-`*` argument to a function that
+/// needs exactly one plain column).
+fn parse_agg_spec(f: &Function, alias: Option<&str>) -> Option<AggSpec> {
+    let func = match f.name.to_string().to_uppercase().as_str() {
+        "COUNT" => AggFunc::Count,
+        "SUM" => AggFunc::Sum,
+        "AVG" => AggFunc::Avg,
+        "MIN" => AggFunc::Min,
+        "MAX" => AggFunc::Max,
+        _ => return None,
+    };
+    let FunctionArguments::List(args) = &f.args else {
+        return None;
+    };
+    let col = match args.args.as_slice() {
+        [] if func == AggFunc::Count => None,
+        [FunctionArg::Unnamed(FunctionArgExpr::Wildcard)] if func == AggFunc::Count => None,
+        [FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::Identifier(id)))] => {
+            Some(id.value.to_lowercase().into_boxed_str())
+        }
+        [FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::CompoundIdentifier(ids)))] => {
+            Some(ids.last()?.value.to_lowercase().into_boxed_str())
+        }
+        _ => return None,
+    };
+    if col.is_none() && func != AggFunc::Count {
+        return None; // sum/avg/min/max need a column, count(*) doesn't
+    }
+    let alias = match alias {
+        Some(a) => a.to_lowercase().into_boxed_str(),
+        None => default_agg_alias(func, col.as_deref()),
+    };
+    Some(AggSpec { func, col, alias })
+}
+
+/// Default output column name for an unaliased aggregate call: `count` for
+/// `count(...)` (as Postgres names it), `<func>_<col>` for the others, e.g.
+/// `sum(amount)` becomes `sum_amount`.
+fn default_agg_alias(func: AggFunc, col: Option<&str>) -> Box<str> {
+    match (func, col) {
+        (AggFunc::Count, _) => "count".into(),
+        (AggFunc::Sum, Some(c)) => format!("sum_{c}").into_boxed_str(),
+        (AggFunc::Avg, Some(c)) => format!("avg_{c}").into_boxed_str(),
+        (AggFunc::Min, Some(c)) => format!("min_{c}").into_boxed_str(),
+        (AggFunc::Max, Some(c)) => format!("max_{c}").into_boxed_str(),
+        (_, None) => unreachable!("sum/avg/min/max always carry a column"),
+    }
+}
+
+/// True if the query has no ORDER BY/LIMIT and its (single, non-CTE) SELECT
+/// has no GROUP BY/DISTINCT/aggregates - i.e. nothing that depends on seeing
+/// the whole result set rather than one row at a time.
+fn is_incremental_eligible(q: &Query) -> bool {
+    if q.order_by.is_some() || q.limit.is_some() {
+        return false;
+    }
+    let SetExpr::Select(s) = q.body.as_ref() else {
+        return false;
+    };
+    if s.distinct.is_some() || s.having.is_some() {
+        return false;
+    }
+    let group_by_empty = match &s.group_by {
+        GroupByExpr::All(_) => false,
+        GroupByExpr::Expressions(e, _) => e.is_empty(),
+    };
+    if !group_by_empty {
+        return false;
+    }
+    !s.projection.iter().any(|item| {
+        let e = match item {
+            SelectItem::UnnamedExpr(e) | SelectItem::ExprWithAlias { expr: e, .. } => e,
+            _ => return false,
+        };
+        matches!(e, Expr::Function(f) if AGG_FUNCS.iter().any(|a| f.name.to_string().to_uppercase().starts_with(a)))
+    })
+}
+
+const AGG_FUNCS: &[&str] = &["COUNT", "SUM", "AVG", "MIN", "MAX", "ARRAY_AGG", "STRING_AGG"];
+
+/// Parse a `LIMIT` clause's row count, if it's a plain integer literal (the
+/// only form `OrderedSnapshot`/a windowed `Snapshot` can bound a window by).
+fn extract_limit(q: &Query) -> Option<usize> {
+    let Expr::Value(v) = q.limit.as_ref()? else {
+        return None;
+    };
+    match &v.value {
+        sqlparser::ast::Value::Number(n, _) => n.parse::<usize>().ok(),
+        _ => None,
+    }
+}
+
+/// Parse an `ORDER BY` clause's sort key, if it's a single plain column
+/// reference (the only form a windowed `Snapshot` can rank rows by).
+fn extract_order_key(q: &Query) -> Option<OrderKey> {
+    let order_by = q.order_by.as_ref()?;
+    let [only] = order_by.exprs.as_slice() else {
+        return None;
+    };
+    let col = match &only.expr {
+        Expr::Identifier(id) => id.value.to_lowercase().into_boxed_str(),
+        Expr::CompoundIdentifier(ids) => ids.last()?.value.to_lowercase().into_boxed_str(),
+        _ => return None,
+    };
+    Some(OrderKey {
+        col,
+        desc: only.asc == Some(false),
+    })
+}
+
 #[inline]
 fn err(s: &str) -> QueryAnalysis {
     QueryAnalysis {
@@ -146,7 +420,40 @@ fn err(s: &str) -> QueryAnalysis {
         tables: vec![],
         filter: WhereFilter::None,
         is_simple: false,
+        select_cols: None,
+        can_incremental: false,
+        is_ordered: false,
+        limit: None,
+        order_key: None,
+        group_cols: None,
+        aggregates: None,
+        can_aggregate: false,
+    }
+}
+
+/// Named columns in the SELECT list, or None if any item is a wildcard or an
+/// expression we can't attribute to a single column (forces "assume changed").
+fn extract_select_cols(q: &Query) -> Option<Vec<Box<str>>> {
+    let SetExpr::Select(s) = q.body.as_ref() else {
+        return None;
+    };
+    let mut cols = Vec::with_capacity(s.projection.len());
+    for item in &s.projection {
+        match item {
+            SelectItem::UnnamedExpr(Expr::Identifier(id)) => {
+                cols.push(id.value.to_lowercase().into_boxed_str())
+            }
+            SelectItem::UnnamedExpr(Expr::CompoundIdentifier(ids)) => {
+                let ident = ids.last()?;
+                cols.push(ident.value.to_lowercase().into_boxed_str())
+            }
+            SelectItem::ExprWithAlias { alias, .. } => {
+                cols.push(alias.value.to_lowercase().into_boxed_str())
+            }
+            _ => return None,
+        }
     }
+    Some(cols)
 }
 
 fn extract(q: &Query, t: &mut Vec<String>, has_join: &mut bool, has_subq: &mut bool) {
@@ -370,6 +677,27 @@ fn parse_value(e: &Expr) -> Option<FilterValue> {
 // === WHERE Filter Evaluation ===
 
 impl WhereFilter {
+    /// Collect the column names this filter reads, for change-detection skip logic.
+    pub fn columns(&self, out: &mut Vec<Box<str>>) {
+        match self {
+            WhereFilter::Eq { col, .. }
+            | WhereFilter::Ne { col, .. }
+            | WhereFilter::Gt { col, .. }
+            | WhereFilter::Gte { col, .. }
+            | WhereFilter::Lt { col, .. }
+            | WhereFilter::Lte { col, .. }
+            | WhereFilter::In { col, .. }
+            | WhereFilter::IsNull { col }
+            | WhereFilter::IsNotNull { col } => out.push(col.clone()),
+            WhereFilter::And(fs) | WhereFilter::Or(fs) => {
+                for f in fs.iter() {
+                    f.columns(out);
+                }
+            }
+            WhereFilter::Complex | WhereFilter::None => {}
+        }
+    }
+
     /// Evaluate filter against a JSON row
     #[allow(dead_code)]
     pub fn eval(&self, row: &Value) -> EvalResult {
@@ -808,6 +1136,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_order_by_limit_marks_ordered_and_parses_limit() {
+        let a = analyze("SELECT * FROM scores ORDER BY score DESC LIMIT 100");
+        assert!(a.is_ordered);
+        assert_eq!(a.limit, Some(100));
+        assert!(!a.can_incremental); // ORDER BY/LIMIT disqualifies incremental
+
+        let b = analyze("SELECT * FROM users");
+        assert!(!b.is_ordered);
+        assert_eq!(b.limit, None);
+    }
+
+    #[test]
+    fn test_order_key_extraction() {
+        let a = analyze("SELECT * FROM scores ORDER BY score DESC LIMIT 100");
+        assert_eq!(
+            a.order_key,
+            Some(OrderKey {
+                col: "score".into(),
+                desc: true,
+            })
+        );
+
+        let asc = analyze("SELECT * FROM scores ORDER BY score LIMIT 100");
+        assert_eq!(asc.order_key.unwrap().desc, false);
+
+        // Multi-column ORDER BY isn't a recognized single sort key.
+        let multi = analyze("SELECT * FROM scores ORDER BY score DESC, name ASC LIMIT 100");
+        assert_eq!(multi.order_key, None);
+
+        // No ORDER BY at all.
+        let none = analyze("SELECT * FROM scores LIMIT 100");
+        assert_eq!(none.order_key, None);
+    }
+
     #[test]
     fn test_where_complex() {
         // Subquery = complex
@@ -816,4 +1179,44 @@ mod tests {
         assert!(matches!(a.filter, WhereFilter::Complex));
         assert_eq!(a.filter.eval(&json!({"id": 1})), EvalResult::Unknown);
     }
+
+    #[test]
+    fn test_group_by_aggregates_recognized() {
+        let a = analyze("SELECT region, count(*), sum(amount), avg(amount) AS avg_amt FROM orders WHERE amount > 0 GROUP BY region");
+        assert!(a.can_aggregate);
+        assert_eq!(a.group_cols, Some(vec!["region".into()]));
+        assert_eq!(
+            a.aggregates,
+            Some(vec![
+                AggSpec {
+                    func: AggFunc::Count,
+                    col: None,
+                    alias: "count".into(),
+                },
+                AggSpec {
+                    func: AggFunc::Sum,
+                    col: Some("amount".into()),
+                    alias: "sum_amount".into(),
+                },
+                AggSpec {
+                    func: AggFunc::Avg,
+                    col: Some("amount".into()),
+                    alias: "avg_amt".into(),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_group_by_disqualified_by_order_by_limit_or_non_aggregate_projection() {
+        let ordered = analyze("SELECT region, count(*) FROM orders GROUP BY region ORDER BY region LIMIT 10");
+        assert!(!ordered.can_aggregate);
+
+        let stray_col = analyze("SELECT region, status, count(*) FROM orders GROUP BY region");
+        assert!(!stray_col.can_aggregate);
+
+        let no_group = analyze("SELECT count(*) FROM orders");
+        assert_eq!(no_group.group_cols, None);
+        assert!(!no_group.can_aggregate);
+    }
 }
@@ -1,12 +1,73 @@
+use crate::core::atom;
+use crate::core::hash::ContentBuildHasher;
 use dashmap::DashMap;
-use rustc_hash::{FxBuildHasher, FxHashMap, FxHasher};
+use rustc_hash::{FxBuildHasher, FxHashMap};
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
 use serde_json::{Map, Number, Value};
 use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering::Relaxed};
 use std::sync::{Arc, LazyLock, OnceLock};
 
 const INTERN_MAX_LEN: usize = 32;
+/// Default cap on interner entries; overridden at startup via
+/// [`init_interner`] from `Config::max_interned_strings`.
+const DEFAULT_INTERN_CAP: usize = 200_000;
+/// Entries to scan per eviction sweep - bounded so a sweep never blocks
+/// interning behind a full-map scan.
+const EVICT_SCAN: usize = 256;
+
 static STR_INTERN: LazyLock<DashMap<String, Arc<str>, FxBuildHasher>> =
     LazyLock::new(|| DashMap::with_hasher(FxBuildHasher));
+static INTERN_CAP: AtomicUsize = AtomicUsize::new(DEFAULT_INTERN_CAP);
+static INTERN_HITS: AtomicU64 = AtomicU64::new(0);
+static INTERN_MISSES: AtomicU64 = AtomicU64::new(0);
+static INTERN_EVICTIONS: AtomicU64 = AtomicU64::new(0);
+
+/// Set the interner's entry cap (call once at startup from
+/// `Config::max_interned_strings`). Uninitialized, it defaults to
+/// [`DEFAULT_INTERN_CAP`].
+pub fn init_interner(cap: usize) {
+    INTERN_CAP.store(cap.max(1), Relaxed);
+}
+
+/// Point-in-time snapshot of interner occupancy and effectiveness, suitable
+/// for logging alongside other subsystem stats (cf. `WalStats`).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InternStats {
+    pub size: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+/// Current interner stats.
+pub fn intern_stats() -> InternStats {
+    InternStats {
+        size: STR_INTERN.len(),
+        hits: INTERN_HITS.load(Relaxed),
+        misses: INTERN_MISSES.load(Relaxed),
+        evictions: INTERN_EVICTIONS.load(Relaxed),
+    }
+}
+
+/// Drop interned entries that are no longer referenced elsewhere (`Arc`
+/// strong count of 1 - just the interner's own copy), scanning at most
+/// `EVICT_SCAN` entries so the sweep stays cheap under lock contention.
+fn evict_unreferenced() {
+    let mut scanned = 0usize;
+    STR_INTERN.retain(|_, v| {
+        if scanned >= EVICT_SCAN {
+            return true;
+        }
+        scanned += 1;
+        let keep = Arc::strong_count(v) > 1;
+        if !keep {
+            INTERN_EVICTIONS.fetch_add(1, Relaxed);
+        }
+        keep
+    });
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum RowValue {
@@ -18,20 +79,39 @@ pub enum RowValue {
     Bytes(Vec<u8>),
     Json(Value),
     Array(Vec<RowValue>),
+    /// Microseconds since the Unix epoch (UTC). Used for timestamp,
+    /// timestamptz, date (midnight UTC), and time (micros since midnight).
+    Timestamp(i64),
+    /// Decimal text kept verbatim to avoid float precision loss.
+    Numeric(Arc<str>),
+    Uuid([u8; 16]),
+    /// Column pgoutput omitted as unchanged TOAST (`u` marker) with no
+    /// cached prior row to backfill from. Distinct from `Null` so the event
+    /// layer can tell "genuinely unknown" apart from "actually null" and
+    /// decide whether to requery.
+    Unchanged,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct RowData {
     cols: Arc<[Arc<str>]>,
+    /// `cols`, resolved once to atom-table handles (see `core::atom`) -
+    /// `get` compares these (integer equality) instead of comparing `cols`'
+    /// strings directly. Two rows built from the same column names always
+    /// get the same handle values here, even though this particular `Vec`
+    /// allocation isn't itself shared between them.
+    handles: Arc<[u32]>,
     values: Vec<RowValue>,
     // Cached column index for O(1) lookup (built lazily on first get)
-    col_idx: OnceLock<Arc<FxHashMap<Arc<str>, usize>>>,
+    col_idx: OnceLock<Arc<FxHashMap<u32, usize>>>,
 }
 
 impl RowData {
     pub fn new(cols: Arc<[Arc<str>]>, values: Vec<RowValue>) -> Self {
+        let handles = intern_cols(&cols);
         Self {
             cols,
+            handles,
             values,
             col_idx: OnceLock::new(),
         }
@@ -39,11 +119,13 @@ impl RowData {
 
     /// Create with pre-built index for faster lookups
     pub fn new_indexed(cols: Arc<[Arc<str>]>, values: Vec<RowValue>) -> Self {
-        let idx = build_col_index(&cols);
+        let handles = intern_cols(&cols);
+        let idx = build_col_index(&handles);
         let col_idx = OnceLock::new();
         let _ = col_idx.set(Arc::new(idx));
         Self {
             cols,
+            handles,
             values,
             col_idx,
         }
@@ -77,31 +159,58 @@ impl RowData {
         Self::new_indexed(Arc::from(cols.into_boxed_slice()), values)
     }
 
-    /// O(1) lookup with cached index, O(n) fallback for small rows
+    /// O(1) lookup with cached index, O(n) fallback for small rows. Probes
+    /// by atom handle rather than by string - see `handles` - so the
+    /// per-column comparison is an integer equality check either way.
+    /// `name` that was never interned anywhere (so it can't be one of
+    /// `cols`) short-circuits to `None` without touching `values` at all.
     #[inline]
     pub fn get(&self, name: &str) -> Option<&RowValue> {
+        let Some(handle) = atom::lookup(name) else {
+            return None;
+        };
         // Fast path: use cached index if available
         if let Some(idx) = self.col_idx.get() {
-            return idx.get(name).and_then(|&i| self.values.get(i));
+            return idx.get(&handle).and_then(|&i| self.values.get(i));
         }
         // For small rows (<=8 cols), linear search is faster than building/using hash index
-        if self.cols.len() <= 8 {
+        if self.handles.len() <= 8 {
             return self
-                .cols
+                .handles
                 .iter()
-                .position(|c| c.as_ref() == name)
+                .position(|&h| h == handle)
                 .and_then(|i| self.values.get(i));
         }
         // Build index once for wider rows
         let idx = self
             .col_idx
-            .get_or_init(|| Arc::new(build_col_index(&self.cols)));
-        idx.get(name).and_then(|&i| self.values.get(i))
+            .get_or_init(|| Arc::new(build_col_index(&self.handles)));
+        idx.get(&handle).and_then(|&i| self.values.get(i))
     }
 
+    /// All column values, in column order. Used to build a row's identity
+    /// key when no identity columns are configured - see
+    /// `subscription::row_identity`.
     #[inline]
-    pub fn hash_content(&self) -> u64 {
-        let mut h = FxHasher::default();
+    pub fn values(&self) -> &[RowValue] {
+        &self.values
+    }
+
+    /// Column names, in the same order as [`RowData::values`]. Used by
+    /// `core::wire`'s Arrow export to infer a schema without re-deriving
+    /// column order from `get` lookups.
+    #[inline]
+    pub fn cols(&self) -> &[Arc<str>] {
+        &self.cols
+    }
+
+    /// Content fingerprint used for diffing (see `subscription::row_hashes`).
+    /// `build` is a [`ContentBuildHasher`] rather than the unseeded
+    /// `FxHasher` this used to hash with directly, so a client shaping row
+    /// content can't predict which rows collide.
+    #[inline]
+    pub fn hash_content(&self, build: &ContentBuildHasher) -> u64 {
+        let mut h = build.build_hasher();
         // Hash column count as discriminator (columns are same for query results)
         self.cols.len().hash(&mut h);
         for v in self.values.iter() {
@@ -120,6 +229,19 @@ impl RowData {
     }
 }
 
+/// Serializes directly from columns/values, skipping the `to_value()`
+/// `serde_json::Value` allocation - works with any serde format (JSON,
+/// MessagePack, CBOR), not just `serde_json`.
+impl Serialize for RowData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.cols.len()))?;
+        for (k, v) in self.cols.iter().zip(self.values.iter()) {
+            map.serialize_entry(k.as_ref(), v)?;
+        }
+        map.end()
+    }
+}
+
 impl RowValue {
     /// Create RowValue from JSON Value
     pub fn from_value(v: &Value) -> RowValue {
@@ -143,19 +265,33 @@ impl RowValue {
 
     #[inline(always)]
     pub fn intern_str(s: &str) -> RowValue {
-        if s.len() <= INTERN_MAX_LEN {
-            let arc = STR_INTERN
-                .entry(s.to_string())
-                .or_insert_with(|| Arc::from(s))
-                .clone();
-            RowValue::Str(arc)
-        } else {
-            RowValue::Str(Arc::from(s))
+        if s.len() > INTERN_MAX_LEN {
+            return RowValue::Str(Arc::from(s));
+        }
+        if let Some(existing) = STR_INTERN.get(s) {
+            INTERN_HITS.fetch_add(1, Relaxed);
+            return RowValue::Str(existing.clone());
         }
+        // Miss - at capacity, try reclaiming dead entries before growing;
+        // if the map is still full after a sweep, skip interning this one
+        // rather than block on a full-map eviction pass.
+        if STR_INTERN.len() >= INTERN_CAP.load(Relaxed) {
+            evict_unreferenced();
+            if STR_INTERN.len() >= INTERN_CAP.load(Relaxed) {
+                INTERN_MISSES.fetch_add(1, Relaxed);
+                return RowValue::Str(Arc::from(s));
+            }
+        }
+        INTERN_MISSES.fetch_add(1, Relaxed);
+        let arc = STR_INTERN
+            .entry(s.to_string())
+            .or_insert_with(|| Arc::from(s))
+            .clone();
+        RowValue::Str(arc)
     }
 
     #[inline(always)]
-    pub fn hash_into(&self, h: &mut FxHasher) {
+    pub fn hash_into<H: Hasher>(&self, h: &mut H) {
         match self {
             RowValue::Null => 0u8.hash(h),
             RowValue::Bool(b) => {
@@ -180,9 +316,7 @@ impl RowValue {
             }
             RowValue::Json(v) => {
                 6u8.hash(h);
-                let mut hv = FxHasher::default();
-                hash_value(v, &mut hv);
-                hv.finish().hash(h);
+                hash_value(v, h);
             }
             RowValue::Array(a) => {
                 7u8.hash(h);
@@ -190,6 +324,19 @@ impl RowValue {
                     v.hash_into(h);
                 }
             }
+            RowValue::Timestamp(ts) => {
+                8u8.hash(h);
+                ts.hash(h);
+            }
+            RowValue::Numeric(s) => {
+                9u8.hash(h);
+                s.hash(h);
+            }
+            RowValue::Uuid(u) => {
+                10u8.hash(h);
+                u.hash(h);
+            }
+            RowValue::Unchanged => 11u8.hash(h),
         }
     }
 
@@ -212,6 +359,29 @@ impl RowValue {
                 }
                 Value::Array(out)
             }
+            RowValue::Timestamp(ts) => Value::Number(Number::from(*ts)),
+            RowValue::Numeric(s) => Value::String(s.to_string()),
+            RowValue::Uuid(u) => Value::String(uuid::Uuid::from_bytes(*u).to_string()),
+            RowValue::Unchanged => Value::Null,
+        }
+    }
+}
+
+impl Serialize for RowValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            RowValue::Null => serializer.serialize_none(),
+            RowValue::Bool(b) => serializer.serialize_bool(*b),
+            RowValue::Int(i) => serializer.serialize_i64(*i),
+            RowValue::Float(f) => serializer.serialize_f64(*f),
+            RowValue::Str(s) => serializer.serialize_str(s),
+            RowValue::Bytes(b) => serializer.serialize_str(&hex_bytes(b)),
+            RowValue::Json(v) => v.serialize(serializer),
+            RowValue::Array(a) => a.serialize(serializer),
+            RowValue::Timestamp(ts) => serializer.serialize_i64(*ts),
+            RowValue::Numeric(s) => serializer.serialize_str(s),
+            RowValue::Uuid(u) => serializer.serialize_str(&uuid::Uuid::from_bytes(*u).to_string()),
+            RowValue::Unchanged => serializer.serialize_none(),
         }
     }
 }
@@ -230,16 +400,22 @@ fn hex_bytes(b: &[u8]) -> String {
 
 /// Build column name -> index map for O(1) lookup
 #[inline]
-fn build_col_index(cols: &[Arc<str>]) -> FxHashMap<Arc<str>, usize> {
-    let mut map = FxHashMap::with_capacity_and_hasher(cols.len(), Default::default());
-    for (i, c) in cols.iter().enumerate() {
-        map.insert(c.clone(), i);
+fn build_col_index(handles: &[u32]) -> FxHashMap<u32, usize> {
+    let mut map = FxHashMap::with_capacity_and_hasher(handles.len(), Default::default());
+    for (i, &h) in handles.iter().enumerate() {
+        map.insert(h, i);
     }
     map
 }
 
+/// Resolve every column name in `cols` to its atom handle (interning any
+/// not already seen) - see `RowData::handles`.
+fn intern_cols(cols: &[Arc<str>]) -> Arc<[u32]> {
+    cols.iter().map(|c| atom::intern(c)).collect()
+}
+
 #[inline]
-fn hash_value(v: &Value, h: &mut FxHasher) {
+fn hash_value<H: Hasher>(v: &Value, h: &mut H) {
     match v {
         Value::Null => 0u8.hash(h),
         Value::Bool(b) => {
@@ -275,3 +451,49 @@ fn hash_value(v: &Value, h: &mut FxHasher) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cols(names: &[&str]) -> Arc<[Arc<str>]> {
+        Arc::from(names.iter().map(|n| Arc::<str>::from(*n)).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn rows_with_same_column_set_share_handles() {
+        let row_a = RowData::new(
+            cols(&["chunk13_5_id", "chunk13_5_name"]),
+            vec![RowValue::Int(1), RowValue::intern_str("alice")],
+        );
+        let row_b = RowData::new(
+            cols(&["chunk13_5_id", "chunk13_5_name"]),
+            vec![RowValue::Int(2), RowValue::intern_str("bob")],
+        );
+        assert_eq!(row_a.handles, row_b.handles);
+        assert_eq!(row_a.get("chunk13_5_name"), Some(&RowValue::intern_str("alice")));
+        assert_eq!(row_a.get("chunk13_5_missing"), None);
+    }
+
+    #[test]
+    fn to_value_reconstructs_original_column_names() {
+        let row = RowData::new(
+            cols(&["chunk13_5_a", "chunk13_5_b"]),
+            vec![RowValue::Int(1), RowValue::Bool(true)],
+        );
+        let v = row.to_value();
+        assert_eq!(v["chunk13_5_a"], serde_json::json!(1));
+        assert_eq!(v["chunk13_5_b"], serde_json::json!(true));
+    }
+
+    #[test]
+    fn get_works_for_wide_rows_past_the_linear_scan_cutoff() {
+        let names: Vec<&str> = vec![
+            "c0", "c1", "c2", "c3", "c4", "c5", "c6", "c7", "c8", "c9", "c10",
+        ];
+        let values: Vec<RowValue> = (0..names.len() as i64).map(RowValue::Int).collect();
+        let row = RowData::new(cols(&names), values);
+        assert_eq!(row.get("c10"), Some(&RowValue::Int(10)));
+        assert_eq!(row.get("c0"), Some(&RowValue::Int(0)));
+    }
+}
@@ -3,30 +3,96 @@
 use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
 use parking_lot::RwLock;
+use rayon::prelude::*;
 use rustc_hash::{FxBuildHasher, FxHashMap, FxHashSet, FxHasher};
+use serde::Serialize;
 use smallvec::SmallVec;
+use std::collections::{BTreeSet, HashSet, VecDeque};
 use std::hash::{Hash, Hasher};
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering::Relaxed};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tracing::{info, warn};
 
-use crate::core::event::{ts_millis, EventBatch, SubscribeEvent, SubscriptionMode};
-use crate::core::query::{self, WhereFilter};
-use crate::core::row::RowData;
+use crate::core::auth::{AllowAllProvider, AuthProvider, Principal};
+use crate::core::event::{
+    Clock, EventBatch, SubError, SubscribeEvent, SubscriptionMode, SystemClock, WireFormat,
+};
+use crate::core::hash::ContentBuildHasher;
+use crate::core::query::{self, AggFunc, AggSpec, OrderKey, WhereFilter};
+use crate::core::row::{RowData, RowValue};
 
 type Map<K, V> = DashMap<K, V, FxBuildHasher>;
 
+/// Default number of past `EventBatch`es a `SharedQuery` retains for replay,
+/// so a briefly-disconnected client can resume with
+/// `SubscriptionManager::replay` instead of re-requesting a full snapshot.
+/// Override per manager with `SubscriptionManager::with_replay_cap`.
+const DEFAULT_REPLAY_CAP: usize = 256;
+
 /// Manages subscriptions and shared queries
 pub struct SubscriptionManager {
     /// subscription_id → Subscription
     subs: Map<Arc<str>, Subscription>,
-    /// query_hash → SharedQuery  
+    /// query_hash → SharedQuery
     queries: Map<Arc<str>, SharedQuery>,
     /// table → [query_hash]
     table_idx: Map<Arc<str>, FxHashSet<Arc<str>>>,
     max_subs: usize,
     subs_count: AtomicUsize,
+    /// Clock handed to each new `SharedQuery`, used to stamp `EventBatch`/
+    /// `SubscribeEvent` timestamps. Defaults to [`SystemClock`]; override
+    /// with [`SubscriptionManager::with_clock`] (e.g. a `TestClock` in tests).
+    clock: Arc<dyn Clock>,
+    /// Replay ring buffer capacity handed to each new `SharedQuery` (see
+    /// `SharedQuery::replay`). Defaults to `DEFAULT_REPLAY_CAP`; override
+    /// with `SubscriptionManager::with_replay_cap` (e.g. from
+    /// `Config::replay_buffer_cap`).
+    replay_cap: usize,
+    /// Failed NATS deliveries, recorded by the WAL publish path via
+    /// `record_publish_failure` - see `MetricsSnapshot::publish_failures`.
+    publish_failures: AtomicU64,
+    /// Cap on distinct shared queries (`queries.len()`), independent of
+    /// `max_subs` - many subscriptions can share one query. Defaults to
+    /// `usize::MAX` (unlimited); set via `with_limits`.
+    max_queries: usize,
+    /// Cap on concurrent in-flight fresh-query snapshot executions (the
+    /// `db.query_rows_typed` call `infra::subscribe::execute_subscribe` runs
+    /// for a brand-new query), independent of `max_subs`/`max_queries` -
+    /// bounds how many expensive initial full-table queries run at once.
+    /// Defaults to `usize::MAX` (unlimited); set via `with_limits`.
+    max_in_flight_snapshots: usize,
+    in_flight_snapshots: AtomicUsize,
+    /// Rejections by `SubError` code, for `MetricsSnapshot`/`{prefix}.health`
+    /// - see `record_rejection`.
+    rejected_bad_request: AtomicU64,
+    rejected_overloaded: AtomicU64,
+    rejected_query_failed: AtomicU64,
+    rejected_not_found: AtomicU64,
+    /// Resolves `subscribe`'s credential to a `Principal` and optionally
+    /// restricts it to a table allow-list - see `with_auth`. Defaults to
+    /// `AllowAllProvider` (every credential accepted, no restrictions).
+    auth: Arc<dyn AuthProvider>,
+    /// Principal ids rejected by every `subscribe`/`heartbeat` - see `ban`/
+    /// `unban`. Checked by `Principal.0`, not by subscription.
+    banned: RwLock<HashSet<Arc<str>>>,
+    /// Banned-on-subscribe/heartbeat + force-unsubscribed-on-ban counts, for
+    /// `MetricsSnapshot`/`{prefix}.health`.
+    rejected_banned: AtomicU64,
+    /// Cumulative count of subscriptions `cleanup` has force-unsubscribed
+    /// for going silent past the heartbeat timeout - see
+    /// `MetricsSnapshot::expired_timeout`.
+    expired_timeout: AtomicU64,
+}
+
+/// Lifecycle state of a [`Subscription`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubState {
+    /// Normal, actively receiving events.
+    Active,
+    /// A terminal `Gone` event has been published; the slot is kept alive
+    /// until `ack_gone` or the grace period in `expire_gone` elapses.
+    PendingGone,
 }
 
 /// Individual subscription (client-provided ID)
@@ -34,7 +100,21 @@ pub struct Subscription {
     pub id: Arc<str>,
     pub query_id: Arc<str>,
     pub mode: SubscriptionMode,
+    /// Negotiated wire format for this subscription's published payloads.
+    pub format: WireFormat,
     pub last_activity: RwLock<Instant>,
+    pub state: RwLock<SubState>,
+    /// Seq of the `Gone` batch this subscription is waiting to be acked
+    /// for, once `state` is `PendingGone` (0 = none yet).
+    pub gone_seq: AtomicU64,
+    /// Highest batch seq this subscription has acknowledged receiving (see
+    /// `SubscriptionManager::ack`). Used as the resume cursor for
+    /// `SubscriptionManager::replay` after a reconnect.
+    pub acked_seq: AtomicU64,
+    /// Principal this subscription authenticated as (see `AuthProvider`),
+    /// `"anonymous"` under the default `AllowAllProvider`. Used by `ban` to
+    /// find every subscription belonging to a principal being banned.
+    pub principal: Arc<str>,
 }
 
 /// Shared query state (optimized - one per unique query)
@@ -44,6 +124,23 @@ pub struct SharedQuery {
     pub tables: Arc<[String]>,
     pub filter: WhereFilter,
     pub is_simple: bool,
+    /// Columns that, if unchanged, mean this query can skip a requery.
+    /// None means "assume every column change is relevant" (e.g. `SELECT *`).
+    pub ref_cols: Option<Arc<[Box<str>]>>,
+    /// Fixed SELECT column list (`query::QueryAnalysis::select_cols`), or
+    /// None for `SELECT *`/computed expressions. Sent to `Binary`-format
+    /// subscribers once per query so per-event payloads can carry just a
+    /// value array; see `EventBatch::encode_binary`.
+    pub select_cols: Option<Arc<[Arc<str>]>>,
+    /// Whether `snap` can be maintained directly from WAL inserts/updates
+    /// instead of requerying the database (see `query::QueryAnalysis::can_incremental`).
+    pub can_incremental: bool,
+    /// `LIMIT`/`ORDER BY` key this query was recognized with, if any (see
+    /// `query::QueryAnalysis::limit`/`order_key`). When both are set, `snap`
+    /// is a windowed `Snapshot` (`Snapshot::new_windowed`) that tracks
+    /// top-N membership instead of a plain row set.
+    pub limit: Option<usize>,
+    pub order_key: Option<OrderKey>,
     pub snap: RwLock<Snapshot>,
     /// Sequence counter for events
     pub seq: AtomicU64,
@@ -51,27 +148,325 @@ pub struct SharedQuery {
     pub refcount: AtomicUsize,
     /// subscription_ids using this query (for iteration only)
     pub subscribers: RwLock<FxHashSet<Arc<str>>>,
+    /// Clock used to stamp events/batches for this query (see
+    /// `SubscriptionManager::clock`).
+    pub clock: Arc<dyn Clock>,
+    /// Ring buffer of the last `replay_cap` batches published on this query,
+    /// oldest first, for `replay_since`.
+    replay: RwLock<VecDeque<EventBatch>>,
+    /// Capacity of `replay`, copied from `SubscriptionManager::replay_cap`
+    /// at construction (see `SubscriptionManager::with_replay_cap`).
+    replay_cap: usize,
+    /// Total diff events (inserts+deletes) ever broadcast for this query,
+    /// i.e. before multiplying by `subscribers.len()` to get the actual
+    /// NATS message fan-out. See `MetricsSnapshot`.
+    events_broadcast: AtomicU64,
+    /// Sum of `diff_rows`/`diff_rows_ordered` wall-clock time in
+    /// microseconds, and the number of samples, for a rolling average
+    /// exposed as `QueryMetrics::avg_diff_micros`.
+    diff_micros_total: AtomicU64,
+    diff_samples: AtomicU64,
+    /// Net pending op per row (by identity hash) awaiting the next
+    /// `SubscriptionMode::Coalesced` flush - see `fold_coalesced`/
+    /// `flush_coalesced`.
+    pending: RwLock<FxHashMap<u64, PendingOp>>,
+}
+
+/// Outcome of resuming a subscription from a previously acked seq.
+pub enum ReplayResult {
+    /// Buffered batches with `seq` greater than the requested cursor,
+    /// oldest first. May be empty if the cursor is already current.
+    Batches(Vec<EventBatch>),
+    /// The cursor is older than the oldest buffered batch (the ring buffer
+    /// overflowed since); the client must re-request a fresh snapshot
+    /// instead of replaying over the gap.
+    ResyncRequired,
 }
 
 #[derive(Default)]
 pub struct Snapshot {
-    rows: FxHashMap<u64, RowEntry>,
+    /// Rows keyed by identity hash, chained on a genuine `id_hash` collision
+    /// between two distinct identities - see `upsert_chained`/`row_identity`.
+    /// Almost always a single entry; `SmallVec` keeps that common case
+    /// allocation-free.
+    rows: FxHashMap<u64, SmallVec<[RowEntry; 1]>>,
+    /// Top-N window state for `ORDER BY ... LIMIT` queries, set via
+    /// `Snapshot::new_windowed`. When present, `rows` is unused and every
+    /// method instead delegates to `window`.
+    window: Option<Window>,
+    /// Seeded hasher for `row_hashes` - see `ContentBuildHasher`. Defaults
+    /// to the process-wide seed, so every `Snapshot` in this process agrees
+    /// on a row's `id_hash`/content hash without needing to share anything
+    /// beyond the `Default` impl.
+    content_hasher: ContentBuildHasher,
 }
 
 #[derive(Clone)]
 struct RowEntry {
+    /// Raw identity-column values (or, with no identity columns configured,
+    /// the whole row) - compared against an incoming row's identity on an
+    /// `id_hash` bucket hit, so two distinct rows that happen to hash the
+    /// same never silently overwrite one another.
+    id_key: Box<[RowValue]>,
     hash: u64,
     data: Arc<serde_json::Value>,
 }
 
-/// Returns (identity_hash, content_hash). When cols is None, both are the same value (computed once).
+/// Top-N window state backing a windowed `Snapshot` (see
+/// `Snapshot::new_windowed`). Tracks every row currently matching the
+/// query's filter, not just the visible top-`limit`, so evicting the
+/// window's current boundary row can immediately promote whichever row is
+/// next in order without a requery.
+struct Window {
+    key_col: Box<str>,
+    desc: bool,
+    limit: usize,
+    /// Every row matching the query, keyed by identity hash - the window's
+    /// full candidate set.
+    all: FxHashMap<u64, RowEntry>,
+    /// Current sort key per identity hash, so `sorted` can be updated
+    /// without a linear scan on upsert/remove.
+    keys: FxHashMap<u64, SortKey>,
+    /// `(sort key, id_hash)` ascending - "best" rows first regardless of
+    /// `desc`, since `SortKey` already folds the sort direction in.
+    sorted: BTreeSet<(SortKey, u64)>,
+    /// Ids currently in the visible top-`limit` window.
+    visible: FxHashSet<u64>,
+}
+
+impl Window {
+    fn new(key_col: Box<str>, desc: bool, limit: usize) -> Self {
+        Self {
+            key_col,
+            desc,
+            limit,
+            all: FxHashMap::default(),
+            keys: FxHashMap::default(),
+            sorted: BTreeSet::new(),
+            visible: FxHashSet::default(),
+        }
+    }
+
+    #[inline]
+    fn sort_key(&self, row: &RowData) -> SortKey {
+        let val = order_val(row.get(&self.key_col));
+        if self.desc {
+            SortKey::Desc(val)
+        } else {
+            SortKey::Asc(val)
+        }
+    }
+
+    /// Insert or update a candidate row, without recomputing window
+    /// membership - returns its previous entry, if any. Call `rebalance`
+    /// once the whole batch of upserts/removals is applied.
+    fn upsert_no_rebalance(&mut self, id_hash: u64, key: SortKey, entry: RowEntry) -> Option<RowEntry> {
+        if let Some(old_key) = self.keys.insert(id_hash, key.clone()) {
+            self.sorted.remove(&(old_key, id_hash));
+        }
+        self.sorted.insert((key, id_hash));
+        self.all.insert(id_hash, entry)
+    }
+
+    /// Drop a row from the candidate set entirely (it no longer matches the
+    /// query), without recomputing window membership - see
+    /// `upsert_no_rebalance`.
+    fn remove_no_rebalance(&mut self, id_hash: u64) -> Option<RowEntry> {
+        if let Some(old_key) = self.keys.remove(&id_hash) {
+            self.sorted.remove(&(old_key, id_hash));
+        }
+        self.all.remove(&id_hash)
+    }
+
+    /// Recompute the visible top-`limit` set from `sorted`, returning the
+    /// ids that newly entered and newly left it relative to the previous
+    /// `visible`.
+    fn rebalance(&mut self) -> (Vec<u64>, Vec<u64>) {
+        let new_visible: FxHashSet<u64> =
+            self.sorted.iter().take(self.limit).map(|(_, id)| *id).collect();
+        let entered = new_visible.difference(&self.visible).copied().collect();
+        let left = self.visible.difference(&new_visible).copied().collect();
+        self.visible = new_visible;
+        (entered, left)
+    }
+}
+
+/// Total-ordered sort key for a `Window`'s index. Wraps `OrderVal` with the
+/// query's sort direction folded in, so ascending iteration of
+/// `Window::sorted` always yields "best" rows first regardless of
+/// ASC/DESC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SortKey {
+    Asc(OrderVal),
+    Desc(OrderVal),
+}
+
+impl PartialOrd for SortKey {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SortKey {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        match (self, other) {
+            (SortKey::Asc(a), SortKey::Asc(b)) => a.cmp(b),
+            (SortKey::Desc(a), SortKey::Desc(b)) => b.cmp(a),
+            // A Window's keys are always built with one fixed direction -
+            // this arm is unreachable in practice.
+            (SortKey::Asc(_), SortKey::Desc(_)) => std::cmp::Ordering::Less,
+            (SortKey::Desc(_), SortKey::Asc(_)) => std::cmp::Ordering::Greater,
+        }
+    }
+}
+
+/// A `RowValue` reduced to a totally-ordered sort key for `Window`'s index.
+/// Non-scalar values (JSON, arrays, UUIDs, bytes) sort as `Null` - the
+/// ordering column is expected to be a plain scalar, like a leaderboard
+/// score or timestamp.
+#[derive(Debug, Clone, PartialEq)]
+enum OrderVal {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(OrdFloat),
+    Str(Arc<str>),
+}
+
+impl Eq for OrderVal {}
+
+impl OrderVal {
+    /// Cross-variant tie-break so mismatched comparisons still total-order
+    /// (e.g. the column's type changed between rows) instead of panicking.
+    fn rank(&self) -> u8 {
+        match self {
+            OrderVal::Null => 0,
+            OrderVal::Bool(_) => 1,
+            OrderVal::Int(_) => 2,
+            OrderVal::Float(_) => 3,
+            OrderVal::Str(_) => 4,
+        }
+    }
+}
+
+impl PartialOrd for OrderVal {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrderVal {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        use OrderVal::*;
+        match (self, other) {
+            (Null, Null) => std::cmp::Ordering::Equal,
+            (Bool(a), Bool(b)) => a.cmp(b),
+            (Int(a), Int(b)) => a.cmp(b),
+            (Float(a), Float(b)) => a.cmp(b),
+            (Str(a), Str(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+/// `f64` wrapper with a total order (via `total_cmp`) so it can key a
+/// `BTreeSet`, unlike plain `f64` which only implements `PartialOrd`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct OrdFloat(f64);
+
+impl Eq for OrdFloat {}
+
+impl PartialOrd for OrdFloat {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrdFloat {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// Reduce a row's sort-key column to an `OrderVal` - `None`/non-scalar
+/// values are treated as `Null` (see `OrderVal`).
+fn order_val(v: Option<&RowValue>) -> OrderVal {
+    match v {
+        None | Some(RowValue::Null) | Some(RowValue::Unchanged) => OrderVal::Null,
+        Some(RowValue::Bool(b)) => OrderVal::Bool(*b),
+        Some(RowValue::Int(i)) => OrderVal::Int(*i),
+        Some(RowValue::Float(f)) => OrderVal::Float(OrdFloat(*f)),
+        Some(RowValue::Timestamp(t)) => OrderVal::Int(*t),
+        Some(RowValue::Str(s)) => OrderVal::Str(s.clone()),
+        Some(RowValue::Numeric(s)) => s
+            .parse::<f64>()
+            .map(|f| OrderVal::Float(OrdFloat(f)))
+            .unwrap_or_else(|_| OrderVal::Str(s.clone())),
+        Some(RowValue::Bytes(_))
+        | Some(RowValue::Json(_))
+        | Some(RowValue::Array(_))
+        | Some(RowValue::Uuid(_)) => OrderVal::Null,
+    }
+}
+
+/// Net change to one row (keyed by identity hash), either freshly diffed or
+/// already folded with an earlier pending op awaiting a
+/// `SubscriptionMode::Coalesced` flush - see `SharedQuery::fold_coalesced`.
+#[derive(Debug, Clone)]
+pub enum PendingOp {
+    Insert(Arc<serde_json::Value>),
+    Update(Arc<serde_json::Value>, Arc<serde_json::Value>),
+    Delete(Arc<serde_json::Value>),
+}
+
+/// Fold `fresh` into `existing` (the row's currently pending op, if any)
+/// using the standard CDC merge rules, returning `None` when the two cancel
+/// out entirely (e.g. a row inserted and deleted within the same coalescing
+/// window never needs to reach the client at all).
+fn merge_pending_op(existing: Option<PendingOp>, fresh: PendingOp) -> Option<PendingOp> {
+    use PendingOp::*;
+    match (existing, fresh) {
+        (None, op) => Some(op),
+        (Some(Insert(_)), Delete(_)) => None,
+        (Some(Insert(_)), Update(_, new)) => Some(Insert(new)),
+        (Some(Update(orig_old, _)), Update(_, new)) => Some(Update(orig_old, new)),
+        (Some(Update(orig_old, _)), Delete(_)) => Some(Delete(orig_old)),
+        (Some(Delete(orig_old)), Insert(new)) => Some(Update(orig_old, new)),
+        // Combinations that can't arise from a well-formed change stream
+        // (e.g. two inserts for the same identity) - keep the latest op
+        // rather than panic, so a bug elsewhere degrades instead of wedging
+        // the coalescing buffer.
+        (Some(_), fresh) => Some(fresh),
+    }
+}
+
+/// Expand one net `op` into the delete/insert event(s) that represent it,
+/// appending to `out`. Shared by `Snapshot::diff_rows` (flattens immediately)
+/// and `SharedQuery::flush_coalesced` (flattens once per coalescing window).
+#[inline]
+pub(crate) fn push_pending_op_events(t: i64, op: PendingOp, out: &mut Vec<SubscribeEvent>) {
+    match op {
+        PendingOp::Insert(new) => out.push(SubscribeEvent::insert_arc(t, new)),
+        PendingOp::Update(old, new) => {
+            out.push(SubscribeEvent::delete_arc(t, old));
+            out.push(SubscribeEvent::insert_arc(t, new));
+        }
+        PendingOp::Delete(old) => out.push(SubscribeEvent::delete_arc(t, old)),
+    }
+}
+
+/// Returns (identity_hash, content_hash). When cols is None, both are the
+/// same value (computed once). `build` is the caller's
+/// `ContentBuildHasher` (see `Snapshot::content_hasher`) - `id_hash` is the
+/// `FxHashMap` bucket key in `Snapshot::rows`, so a predictable hash here is
+/// exactly the HashDoS angle `ContentBuildHasher` closes off.
 #[inline(always)]
-fn row_hashes(r: &RowData, cols: &Option<Arc<[Arc<str>]>>) -> (u64, u64) {
-    let content = r.hash_content();
+fn row_hashes(r: &RowData, cols: &Option<Arc<[Arc<str>]>>, build: &ContentBuildHasher) -> (u64, u64) {
+    let content = r.hash_content(build);
     match cols {
         None => (content, content), // Same hash, computed once
         Some(c) => {
-            let mut h = FxHasher::default();
+            let mut h = build.build_hasher();
             if c.len() == 1 {
                 if let Some(v) = r.get(&c[0]) {
                     v.hash_into(&mut h);
@@ -88,6 +483,53 @@ fn row_hashes(r: &RowData, cols: &Option<Arc<[Arc<str>]>>) -> (u64, u64) {
     }
 }
 
+/// The raw identity-column values `row_hashes`'s `id_hash` was computed
+/// from, or the whole row when no identity columns are configured - used to
+/// tell apart two distinct rows that collide on `id_hash` (see
+/// `Snapshot::rows`).
+fn row_identity(r: &RowData, cols: &Option<Arc<[Arc<str>]>>) -> Box<[RowValue]> {
+    match cols {
+        None => r.values().into(),
+        Some(c) => c
+            .iter()
+            .map(|col| r.get(col).cloned().unwrap_or(RowValue::Null))
+            .collect(),
+    }
+}
+
+/// Insert or update the entry for `entry`'s identity within `id_hash`'s
+/// chain, returning its previous value if this exact identity was already
+/// present. A different identity that happens to share `id_hash` is chained
+/// alongside rather than overwriting it.
+fn upsert_chained(
+    rows: &mut FxHashMap<u64, SmallVec<[RowEntry; 1]>>,
+    id_hash: u64,
+    entry: RowEntry,
+) -> Option<RowEntry> {
+    let bucket = rows.entry(id_hash).or_default();
+    if let Some(slot) = bucket.iter_mut().find(|e| e.id_key == entry.id_key) {
+        return Some(std::mem::replace(slot, entry));
+    }
+    bucket.push(entry);
+    None
+}
+
+/// Remove the entry matching `id_key` from `id_hash`'s chain, dropping the
+/// bucket entirely once it's emptied.
+fn remove_chained(
+    rows: &mut FxHashMap<u64, SmallVec<[RowEntry; 1]>>,
+    id_hash: u64,
+    id_key: &[RowValue],
+) -> Option<RowEntry> {
+    let bucket = rows.get_mut(&id_hash)?;
+    let pos = bucket.iter().position(|e| &*e.id_key == id_key)?;
+    let removed = bucket.remove(pos);
+    if bucket.is_empty() {
+        rows.remove(&id_hash);
+    }
+    Some(removed)
+}
+
 pub struct SubscribeResult {
     pub subscription_id: Arc<str>,
     pub query_id: Arc<str>,
@@ -97,42 +539,235 @@ pub struct SubscribeResult {
 
 impl SubscriptionManager {
     pub fn new(max_subs: usize) -> Self {
+        Self::with_clock(max_subs, Arc::new(SystemClock))
+    }
+
+    /// Create a manager that stamps events/batches using `clock` instead of
+    /// the system clock, e.g. a `TestClock` for deterministic tests.
+    pub fn with_clock(max_subs: usize, clock: Arc<dyn Clock>) -> Self {
         Self {
             subs: Map::with_hasher(FxBuildHasher),
             queries: Map::with_hasher(FxBuildHasher),
             table_idx: Map::with_hasher(FxBuildHasher),
             max_subs,
             subs_count: AtomicUsize::new(0),
+            clock,
+            replay_cap: DEFAULT_REPLAY_CAP,
+            publish_failures: AtomicU64::new(0),
+            max_queries: usize::MAX,
+            max_in_flight_snapshots: usize::MAX,
+            in_flight_snapshots: AtomicUsize::new(0),
+            rejected_bad_request: AtomicU64::new(0),
+            rejected_overloaded: AtomicU64::new(0),
+            rejected_query_failed: AtomicU64::new(0),
+            rejected_not_found: AtomicU64::new(0),
+            auth: Arc::new(AllowAllProvider),
+            banned: RwLock::new(HashSet::new()),
+            rejected_banned: AtomicU64::new(0),
+            expired_timeout: AtomicU64::new(0),
+        }
+    }
+
+    /// Create a manager whose `SharedQuery`s retain `replay_cap` batches each
+    /// for `replay_since`, instead of `DEFAULT_REPLAY_CAP` - see
+    /// `Config::replay_buffer_cap`.
+    pub fn with_replay_cap(max_subs: usize, replay_cap: usize) -> Self {
+        Self {
+            replay_cap,
+            ..Self::with_clock(max_subs, Arc::new(SystemClock))
+        }
+    }
+
+    /// Create a manager that additionally caps distinct queries and
+    /// concurrent in-flight snapshot executions - see `max_queries`/
+    /// `max_in_flight_snapshots`, sourced from `Config::max_distinct_queries`/
+    /// `Config::max_in_flight_snapshots`.
+    pub fn with_limits(
+        max_subs: usize,
+        replay_cap: usize,
+        max_queries: usize,
+        max_in_flight_snapshots: usize,
+    ) -> Self {
+        Self {
+            max_queries,
+            max_in_flight_snapshots,
+            ..Self::with_replay_cap(max_subs, replay_cap)
+        }
+    }
+
+    /// Create a manager that resolves `subscribe`'s credential through
+    /// `auth` instead of accepting everything - see `AuthProvider`.
+    pub fn with_auth(
+        max_subs: usize,
+        replay_cap: usize,
+        max_queries: usize,
+        max_in_flight_snapshots: usize,
+        auth: Arc<dyn AuthProvider>,
+    ) -> Self {
+        Self {
+            auth,
+            ..Self::with_limits(max_subs, replay_cap, max_queries, max_in_flight_snapshots)
+        }
+    }
+
+    /// Ban `principal_id`, rejecting its future `subscribe`/`heartbeat`
+    /// calls with `SubError::Forbidden` and force-unsubscribing every
+    /// subscription it currently holds. Returns the number of subscriptions
+    /// force-unsubscribed.
+    pub fn ban(&self, principal_id: &str) -> usize {
+        self.banned.write().insert(Arc::from(principal_id));
+        let victims: Vec<Arc<str>> = self
+            .subs
+            .iter()
+            .filter(|e| &*e.value().principal == principal_id)
+            .map(|e| e.key().clone())
+            .collect();
+        for sub_id in &victims {
+            self.unsubscribe(sub_id);
+        }
+        victims.len()
+    }
+
+    /// Lift a ban placed by `ban`. Doesn't restore any subscription that was
+    /// force-unsubscribed; the client must resubscribe.
+    pub fn unban(&self, principal_id: &str) -> bool {
+        self.banned.write().remove(principal_id)
+    }
+
+    /// Whether `principal_id` is currently banned.
+    #[inline]
+    pub fn is_banned(&self, principal_id: &str) -> bool {
+        self.banned.read().contains(principal_id)
+    }
+
+    /// Record a failed NATS delivery (see `MetricsSnapshot::publish_failures`).
+    #[inline]
+    pub fn record_publish_failure(&self) {
+        self.publish_failures.fetch_add(1, Relaxed);
+    }
+
+    /// Record an admission-control/request rejection by code, for
+    /// `MetricsSnapshot`/`{prefix}.health`. Public so callers outside
+    /// `subscribe`/`try_reserve_snapshot_slot` (e.g. `QueryFailed`/`NotFound`
+    /// in `infra::subscribe::execute_subscribe`) can report their own
+    /// rejections through the same counters.
+    pub fn record_rejection(&self, code: SubError) {
+        let counter = match code {
+            SubError::BadRequest => &self.rejected_bad_request,
+            SubError::ServiceOverloaded => &self.rejected_overloaded,
+            SubError::QueryFailed => &self.rejected_query_failed,
+            SubError::NotFound => &self.rejected_not_found,
+            SubError::Forbidden => &self.rejected_banned,
+        };
+        counter.fetch_add(1, Relaxed);
+    }
+
+    /// Reserve a slot for a fresh query's initial snapshot execution (see
+    /// `infra::subscribe::execute_subscribe`), enforcing
+    /// `max_in_flight_snapshots` independently of `max_subs`/`max_queries`.
+    /// Callers must call `release_snapshot_slot` exactly once after the
+    /// query completes, whether it succeeded or failed.
+    pub fn try_reserve_snapshot_slot(&self) -> Result<(), SubError> {
+        loop {
+            let cur = self.in_flight_snapshots.load(Relaxed);
+            if cur >= self.max_in_flight_snapshots {
+                self.record_rejection(SubError::ServiceOverloaded);
+                return Err(SubError::ServiceOverloaded);
+            }
+            if self
+                .in_flight_snapshots
+                .compare_exchange(cur, cur + 1, Relaxed, Relaxed)
+                .is_ok()
+            {
+                return Ok(());
+            }
         }
     }
 
-    /// Subscribe with client-provided subscription_id (atomic)
+    /// Release a slot reserved by `try_reserve_snapshot_slot`.
+    #[inline]
+    pub fn release_snapshot_slot(&self) {
+        self.in_flight_snapshots.fetch_sub(1, Relaxed);
+    }
+
+    /// Subscribe with client-provided subscription_id (atomic). `credential`
+    /// is resolved through the configured `AuthProvider` (see `with_auth`);
+    /// under the default `AllowAllProvider` any non-empty string (or none)
+    /// is accepted as its own principal.
     pub fn subscribe(
         &self,
         sub_id: &str,
         q: &str,
         cols: Option<Vec<String>>,
         mode: SubscriptionMode,
-    ) -> Result<SubscribeResult, String> {
+        format: WireFormat,
+        credential: &str,
+    ) -> Result<SubscribeResult, (SubError, String)> {
+        let principal = match self.auth.authenticate(credential) {
+            Ok(p) => p,
+            Err(msg) => {
+                self.record_rejection(SubError::Forbidden);
+                return Err((SubError::Forbidden, msg));
+            }
+        };
+        if self.is_banned(&principal.0) {
+            self.record_rejection(SubError::Forbidden);
+            return Err((SubError::Forbidden, "Principal is banned".into()));
+        }
+
         // Atomic check-and-insert for subscription
         let sub_id: Arc<str> = sub_id.into();
         match self.subs.entry(sub_id.clone()) {
-            Entry::Occupied(_) => Err(format!("Subscription '{}' already exists", sub_id)),
+            Entry::Occupied(_) => {
+                self.record_rejection(SubError::BadRequest);
+                Err((
+                    SubError::BadRequest,
+                    format!("Subscription '{}' already exists", sub_id),
+                ))
+            }
             Entry::Vacant(entry) => {
                 if self.subs_count.load(Relaxed) >= self.max_subs {
-                    return Err("Max subscriptions reached".into());
+                    self.record_rejection(SubError::ServiceOverloaded);
+                    return Err((SubError::ServiceOverloaded, "Max subscriptions reached".into()));
                 }
 
                 // Analyze query
-                let a = query::analyze(q);
+                let mut a = query::analyze(q);
                 if !a.is_valid {
-                    return Err(a.error.unwrap_or_else(|| "Invalid query".into()));
+                    self.record_rejection(SubError::BadRequest);
+                    return Err((
+                        SubError::BadRequest,
+                        a.error.unwrap_or_else(|| "Invalid query".into()),
+                    ));
                 }
                 if a.tables.is_empty() {
-                    return Err("No table in query".into());
+                    self.record_rejection(SubError::BadRequest);
+                    return Err((SubError::BadRequest, "No table in query".into()));
+                }
+                if let Some(allowed) = self.auth.allowed_tables(&principal) {
+                    if let Some(denied) = a.tables.iter().find(|t| !allowed.contains(*t)) {
+                        self.record_rejection(SubError::Forbidden);
+                        return Err((
+                            SubError::Forbidden,
+                            format!("Principal not allowed to query table '{denied}'"),
+                        ));
+                    }
+                }
+
+                // Row-level security: AND the principal's mandatory predicate
+                // (if any) into the client's own filter, so a row failing it
+                // is NoMatch regardless of what the client's WHERE allows -
+                // see `AuthProvider::row_filter`. This changes the effective
+                // filter per principal, so a secured query can't share a
+                // `SharedQuery`/snapshot with another principal's identical
+                // query text - fold the principal into the dedup key too.
+                let row_filter = self.auth.row_filter(&principal);
+                if let Some(predicate) = &row_filter {
+                    a.filter = WhereFilter::And(Box::from([a.filter, predicate.clone()]));
+                    a.is_simple = a.is_simple && !matches!(a.filter, WhereFilter::Complex);
                 }
 
-                let query_id: Arc<str> = qhash(q).into();
+                let query_id: Arc<str> = qhash(q, row_filter.is_some().then_some(principal.0.as_ref())).into();
 
                 // Atomic get-or-create SharedQuery
                 let is_new_query = match self.queries.entry(query_id.clone()) {
@@ -144,6 +779,14 @@ impl SubscriptionManager {
                         false
                     }
                     Entry::Vacant(qe) => {
+                        if self.queries.len() >= self.max_queries {
+                            self.record_rejection(SubError::ServiceOverloaded);
+                            return Err((
+                                SubError::ServiceOverloaded,
+                                "Max distinct queries reached".into(),
+                            ));
+                        }
+
                         // New query
                         let cols_arc = cols.map(|c| {
                             Arc::from(
@@ -165,16 +808,55 @@ impl SubscriptionManager {
                         let mut subscribers = FxHashSet::default();
                         subscribers.insert(sub_id.clone());
 
+                        let ref_cols = a.select_cols.clone().map(|mut cols| {
+                            let mut where_cols = Vec::new();
+                            a.filter.columns(&mut where_cols);
+                            for c in where_cols {
+                                if !cols.contains(&c) {
+                                    cols.push(c);
+                                }
+                            }
+                            Arc::from(cols.into_boxed_slice())
+                        });
+
+                        let select_cols = a.select_cols.clone().map(|cols| {
+                            Arc::from(
+                                cols.into_iter()
+                                    .map(|c| Arc::<str>::from(c.as_ref()))
+                                    .collect::<Vec<_>>()
+                                    .into_boxed_slice(),
+                            )
+                        });
+
+                        let snap = match (&a.order_key, a.limit) {
+                            (Some(key), Some(limit)) => {
+                                Snapshot::new_windowed(key.col.clone(), key.desc, limit)
+                            }
+                            _ => Snapshot::new(),
+                        };
+
                         qe.insert(SharedQuery {
                             query: q.into(),
                             cols: cols_arc,
                             tables: Arc::from(a.tables.into_boxed_slice()),
                             filter: a.filter,
                             is_simple: a.is_simple,
-                            snap: RwLock::new(Snapshot::new()),
+                            ref_cols,
+                            select_cols,
+                            can_incremental: a.can_incremental,
+                            limit: a.limit,
+                            order_key: a.order_key.clone(),
+                            snap: RwLock::new(snap),
                             seq: AtomicU64::new(0),
                             refcount: AtomicUsize::new(1),
                             subscribers: RwLock::new(subscribers),
+                            clock: self.clock.clone(),
+                            replay: RwLock::new(VecDeque::with_capacity(self.replay_cap)),
+                            replay_cap: self.replay_cap,
+                            events_broadcast: AtomicU64::new(0),
+                            diff_micros_total: AtomicU64::new(0),
+                            diff_samples: AtomicU64::new(0),
+                            pending: RwLock::new(FxHashMap::default()),
                         });
                         info!("New query Q{} + sub [{}]", &query_id[..8], sub_id);
                         true
@@ -186,9 +868,15 @@ impl SubscriptionManager {
                     id: sub_id.clone(),
                     query_id: query_id.clone(),
                     mode,
+                    format,
                     last_activity: RwLock::new(Instant::now()),
+                    state: RwLock::new(SubState::Active),
+                    gone_seq: AtomicU64::new(0),
+                    acked_seq: AtomicU64::new(0),
+                    principal: principal.0,
                 });
                 self.subs_count.fetch_add(1, Relaxed);
+                crate::telemetry::subscription_opened();
 
                 // Avoid double lookup: seq is 0 for new queries, fetch from existing
                 let seq = if is_new_query {
@@ -216,6 +904,7 @@ impl SubscriptionManager {
             return false;
         };
         self.subs_count.fetch_sub(1, Relaxed);
+        crate::telemetry::subscription_closed();
         info!("-Sub [{}]", sub_id);
 
         // Decrement refcount and remove query if zero
@@ -230,6 +919,78 @@ impl SubscriptionManager {
         true
     }
 
+    /// Server-driven unsubscribe: publishes a terminal `Gone` event on the
+    /// subscription's query instead of freeing the slot immediately, and
+    /// flips it to `PendingGone`. The caller is responsible for publishing
+    /// the returned batch to the subscription's subject; actual removal
+    /// happens on `ack_gone` (or `expire_gone` after the grace period).
+    pub fn force_unsubscribe(&self, sub_id: &str, reason: &str) -> Option<EventBatch> {
+        let sub = self.subs.get(sub_id)?;
+        let sq = self.queries.get(&sub.query_id)?;
+        let t = sq.clock.now_millis() as i64;
+        let batch = sq.make_batch(vec![SubscribeEvent::gone(t, reason)])?;
+        *sub.state.write() = SubState::PendingGone;
+        sub.gone_seq.store(batch.seq, Relaxed);
+        *sub.last_activity.write() = Instant::now();
+        info!("Gone [{}]: {}", sub_id, reason);
+        Some(batch)
+    }
+
+    /// Client ack of a `Gone` event at `seq`. Only removes the subscription
+    /// if it's actually `PendingGone` and `seq` covers the batch it was
+    /// told about; a stale or unrelated ack is a no-op.
+    pub fn ack_gone(&self, sub_id: &str, seq: u64) -> bool {
+        let Some(sub) = self.subs.get(sub_id) else {
+            return false;
+        };
+        if *sub.state.read() != SubState::PendingGone || seq < sub.gone_seq.load(Relaxed) {
+            return false;
+        }
+        drop(sub);
+        self.unsubscribe(sub_id)
+    }
+
+    /// Reclaim subscriptions left `PendingGone` past `grace` - the client
+    /// never acked, so free the slot anyway.
+    pub fn expire_gone(&self, grace: Duration) -> Vec<Arc<str>> {
+        let now = Instant::now();
+        let mut expired: SmallVec<[Arc<str>; 16]> = SmallVec::new();
+
+        for sub in self.subs.iter() {
+            if *sub.state.read() == SubState::PendingGone
+                && now.duration_since(*sub.last_activity.read()) >= grace
+            {
+                expired.push(sub.id.clone());
+            }
+        }
+
+        for sub_id in &expired {
+            warn!("Gone-grace expired [{}]", sub_id);
+            self.unsubscribe(sub_id);
+        }
+
+        expired.into_vec()
+    }
+
+    /// Record the highest batch seq `sub_id` has acknowledged receiving, as
+    /// a resume cursor for `replay`. Out-of-order/duplicate acks never move
+    /// the cursor backward.
+    pub fn ack(&self, sub_id: &str, seq: u64) -> bool {
+        let Some(sub) = self.subs.get(sub_id) else {
+            return false;
+        };
+        sub.acked_seq.fetch_max(seq, Relaxed);
+        true
+    }
+
+    /// Replay `sub_id`'s query batches after `last_acked` (typically the
+    /// client's own last-seen seq from before a disconnect).
+    pub fn replay(&self, sub_id: &str, last_acked: u64) -> Option<ReplayResult> {
+        let sub = self.subs.get(sub_id)?;
+        let sq = self.queries.get(&sub.query_id)?;
+        Some(sq.replay_since(last_acked))
+    }
+
     fn remove_query(&self, query_id: &str) {
         // Double-check refcount is still zero before removal (handles race)
         if let Entry::Occupied(e) = self.queries.entry(query_id.into()) {
@@ -245,31 +1006,53 @@ impl SubscriptionManager {
         }
     }
 
-    #[inline]
+    /// Bumps `last_activity`, or force-unsubscribes and returns `false` if
+    /// this subscription's principal has been `ban`ned since it subscribed.
     pub fn heartbeat(&self, sub_id: &str) -> bool {
-        self.subs
-            .get(sub_id)
-            .map(|s| *s.last_activity.write() = Instant::now())
-            .is_some()
+        let Some(sub) = self.subs.get(sub_id) else {
+            return false;
+        };
+        if self.is_banned(&sub.principal) {
+            drop(sub);
+            self.record_rejection(SubError::Forbidden);
+            self.unsubscribe(sub_id);
+            return false;
+        }
+        *sub.last_activity.write() = Instant::now();
+        true
     }
 
     /// Cleanup stale subscriptions
-    pub fn cleanup(&self, timeout: Duration) -> Vec<Arc<str>> {
+    /// Reap subscriptions whose `last_activity` (bumped by `subscribe`/
+    /// `heartbeat`) has gone stale past `timeout`: `force_unsubscribe`s each
+    /// one instead of a plain `unsubscribe`, so a still-listening client
+    /// learns the stream was terminated server-side (see `expired_timeout`/
+    /// `MetricsSnapshot::expired_timeout`). Already-`PendingGone` subs are
+    /// left alone here - `expire_gone` reclaims those once their grace
+    /// period elapses. The caller is responsible for publishing each
+    /// returned batch, same contract as `force_unsubscribe`.
+    pub fn cleanup(&self, timeout: Duration) -> Vec<(Arc<str>, EventBatch)> {
         let now = Instant::now();
         let mut stale: SmallVec<[Arc<str>; 16]> = SmallVec::new();
 
         for sub in self.subs.iter() {
-            if now.duration_since(*sub.last_activity.read()) >= timeout {
+            if *sub.state.read() == SubState::Active
+                && now.duration_since(*sub.last_activity.read()) >= timeout
+            {
                 stale.push(sub.id.clone());
             }
         }
 
+        let mut reaped = Vec::with_capacity(stale.len());
         for sub_id in &stale {
             warn!("Timeout [{}]", sub_id);
-            self.unsubscribe(sub_id);
+            if let Some(batch) = self.force_unsubscribe(sub_id, "heartbeat timeout") {
+                self.expired_timeout.fetch_add(1, Relaxed);
+                reaped.push((sub_id.clone(), batch));
+            }
         }
 
-        stale.into_vec()
+        reaped
     }
 
     // === Getters ===
@@ -309,22 +1092,345 @@ impl SubscriptionManager {
         }
     }
 
+    /// Query ids with a non-empty coalescing buffer, for the periodic flush
+    /// task to drain via `SharedQuery::flush_coalesced` - see
+    /// `Config::coalesce_window_ms`.
+    pub fn coalesced_query_ids(&self) -> Vec<Arc<str>> {
+        self.queries
+            .iter()
+            .filter(|e| e.value().coalesced_len() > 0)
+            .map(|e| e.key().clone())
+            .collect()
+    }
+
     /// Stats: (subscriptions, queries)
     #[inline]
     pub fn stats(&self) -> (usize, usize) {
         (self.subs_count.load(Relaxed), self.queries.len())
     }
+
+    /// Render the live table → query → subscriber topology as a Graphviz
+    /// `digraph`, so operators can see which queries and NATS subscribers a
+    /// WAL change on a given table will fan out to.
+    pub fn topology_dot(&self) -> String {
+        let mut out = String::from("digraph livequery {\n  rankdir=LR;\n");
+
+        for entry in self.table_idx.iter() {
+            let table = entry.key();
+            out.push_str(&format!(
+                "  \"t:{table}\" [shape=box,style=filled,fillcolor=lightblue,label=\"{}\"];\n",
+                dot_escape(table)
+            ));
+            for qid in entry.value().iter() {
+                out.push_str(&format!("  \"t:{table}\" -> \"q:{qid}\";\n"));
+            }
+        }
+
+        for entry in self.queries.iter() {
+            let qid = entry.key();
+            let q = entry.value();
+            out.push_str(&format!(
+                "  \"q:{qid}\" [shape=ellipse,label=\"{}\\n{}\"];\n",
+                &qid[..8.min(qid.len())],
+                dot_escape(&format!("{:.40}", q.query))
+            ));
+            for sid in q.subscribers.read().iter() {
+                out.push_str(&format!("  \"q:{qid}\" -> \"s:{sid}\";\n"));
+            }
+        }
+
+        for entry in self.subs.iter() {
+            let sid = entry.key();
+            out.push_str(&format!(
+                "  \"s:{sid}\" [shape=circle,style=filled,fillcolor=lightyellow,label=\"{}\"];\n",
+                dot_escape(sid)
+            ));
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Snapshot of runtime metrics for operators - see `QueryMetrics` for the
+    /// per-query breakdown and [`render_prometheus`] to scrape it.
+    pub fn metrics_snapshot(&self) -> MetricsSnapshot {
+        let (subscriptions, queries) = self.stats();
+        let per_query = self
+            .queries
+            .iter()
+            .map(|entry| {
+                let qid = entry.key().clone();
+                let q = entry.value();
+
+                let mut max_ack_lag = 0u64;
+                let cur_seq = q.seq.load(Relaxed);
+                for sid in q.subscribers.read().iter() {
+                    if let Some(sub) = self.subs.get(sid) {
+                        let lag = cur_seq.saturating_sub(sub.acked_seq.load(Relaxed));
+                        max_ack_lag = max_ack_lag.max(lag);
+                    }
+                }
+
+                let samples = q.diff_samples.load(Relaxed);
+                let avg_diff_micros = if samples == 0 {
+                    0.0
+                } else {
+                    q.diff_micros_total.load(Relaxed) as f64 / samples as f64
+                };
+
+                QueryMetrics {
+                    query_id: qid,
+                    subscribers: q.subscribers.read().len(),
+                    seq: cur_seq,
+                    max_ack_lag,
+                    replay_len: q.replay.read().len(),
+                    replay_cap: q.replay_cap,
+                    events_broadcast: q.events_broadcast.load(Relaxed),
+                    avg_diff_micros,
+                }
+            })
+            .collect();
+
+        MetricsSnapshot {
+            subscriptions,
+            queries,
+            publish_failures: self.publish_failures.load(Relaxed),
+            rejected_bad_request: self.rejected_bad_request.load(Relaxed),
+            rejected_overloaded: self.rejected_overloaded.load(Relaxed),
+            rejected_query_failed: self.rejected_query_failed.load(Relaxed),
+            rejected_not_found: self.rejected_not_found.load(Relaxed),
+            rejected_banned: self.rejected_banned.load(Relaxed),
+            expired_timeout: self.expired_timeout.load(Relaxed),
+            per_query,
+        }
+    }
+}
+
+/// Escape a label for embedding in a double-quoted Graphviz string.
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', " ")
+}
+
+/// Per-query runtime metrics, as returned in `MetricsSnapshot::per_query`.
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryMetrics {
+    pub query_id: Arc<str>,
+    /// Number of subscriptions currently sharing this query.
+    pub subscribers: usize,
+    /// Current `EventBatch.seq` (last batch published for this query).
+    pub seq: u64,
+    /// Largest `seq - acked_seq` across this query's subscribers, i.e. how
+    /// far behind its most backed-up subscriber is.
+    pub max_ack_lag: u64,
+    /// Current occupancy of the replay ring buffer, out of `replay_cap`.
+    pub replay_len: usize,
+    pub replay_cap: usize,
+    /// Total diff events broadcast for this query; multiply by `subscribers`
+    /// for the actual NATS message fan-out.
+    pub events_broadcast: u64,
+    /// Rolling average of `diff_rows`/`diff_rows_ordered` wall-clock time.
+    pub avg_diff_micros: f64,
+}
+
+/// Global + per-query runtime metrics, returned by
+/// [`SubscriptionManager::metrics_snapshot`]. Render with
+/// [`render_prometheus`] for a scrape endpoint.
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSnapshot {
+    pub subscriptions: usize,
+    pub queries: usize,
+    /// Failed NATS deliveries (see `SubscriptionManager::record_publish_failure`).
+    pub publish_failures: u64,
+    /// Rejected `subscribe` requests by `SubError` code - see
+    /// `SubscriptionManager::record_rejection`.
+    pub rejected_bad_request: u64,
+    pub rejected_overloaded: u64,
+    pub rejected_query_failed: u64,
+    pub rejected_not_found: u64,
+    /// Rejected for a banned principal, or a query outside
+    /// `AuthProvider::allowed_tables` - see `SubscriptionManager::ban`.
+    pub rejected_banned: u64,
+    /// Force-unsubscribed by `SubscriptionManager::cleanup` for going silent
+    /// past the heartbeat timeout.
+    pub expired_timeout: u64,
+    pub per_query: Vec<QueryMetrics>,
+}
+
+/// Render a [`MetricsSnapshot`] as Prometheus text-exposition format.
+pub fn render_prometheus(snapshot: &MetricsSnapshot) -> String {
+    let mut out = String::new();
+
+    out.push_str("# HELP livequery_subscriptions Active subscriptions.\n");
+    out.push_str("# TYPE livequery_subscriptions gauge\n");
+    out.push_str(&format!("livequery_subscriptions {}\n", snapshot.subscriptions));
+
+    out.push_str("# HELP livequery_queries Active shared queries.\n");
+    out.push_str("# TYPE livequery_queries gauge\n");
+    out.push_str(&format!("livequery_queries {}\n", snapshot.queries));
+
+    out.push_str("# HELP livequery_publish_failures_total Failed NATS deliveries.\n");
+    out.push_str("# TYPE livequery_publish_failures_total counter\n");
+    out.push_str(&format!(
+        "livequery_publish_failures_total {}\n",
+        snapshot.publish_failures
+    ));
+
+    out.push_str("# HELP livequery_rejected_total Rejected subscribe requests by SubError code.\n");
+    out.push_str("# TYPE livequery_rejected_total counter\n");
+    out.push_str(&format!(
+        "livequery_rejected_total{{code=\"bad_request\"}} {}\n",
+        snapshot.rejected_bad_request
+    ));
+    out.push_str(&format!(
+        "livequery_rejected_total{{code=\"service_overloaded\"}} {}\n",
+        snapshot.rejected_overloaded
+    ));
+    out.push_str(&format!(
+        "livequery_rejected_total{{code=\"query_failed\"}} {}\n",
+        snapshot.rejected_query_failed
+    ));
+    out.push_str(&format!(
+        "livequery_rejected_total{{code=\"not_found\"}} {}\n",
+        snapshot.rejected_not_found
+    ));
+    out.push_str(&format!(
+        "livequery_rejected_total{{code=\"forbidden\"}} {}\n",
+        snapshot.rejected_banned
+    ));
+
+    out.push_str("# HELP livequery_expired_timeout_total Subscriptions force-unsubscribed for going silent past the heartbeat timeout.\n");
+    out.push_str("# TYPE livequery_expired_timeout_total counter\n");
+    out.push_str(&format!(
+        "livequery_expired_timeout_total {}\n",
+        snapshot.expired_timeout
+    ));
+
+    out.push_str("# HELP livequery_query_subscribers Subscribers sharing a query.\n");
+    out.push_str("# TYPE livequery_query_subscribers gauge\n");
+    out.push_str("# HELP livequery_query_seq Current EventBatch seq for a query.\n");
+    out.push_str("# TYPE livequery_query_seq counter\n");
+    out.push_str("# HELP livequery_query_max_ack_lag_batches Worst-case subscriber ack lag, in batches.\n");
+    out.push_str("# TYPE livequery_query_max_ack_lag_batches gauge\n");
+    out.push_str("# HELP livequery_query_replay_occupancy_ratio Replay ring buffer occupancy (0-1).\n");
+    out.push_str("# TYPE livequery_query_replay_occupancy_ratio gauge\n");
+    out.push_str("# HELP livequery_query_events_broadcast_total Diff events broadcast for a query.\n");
+    out.push_str("# TYPE livequery_query_events_broadcast_total counter\n");
+    out.push_str("# HELP livequery_query_avg_diff_micros Rolling average diff_rows wall-clock time, in microseconds.\n");
+    out.push_str("# TYPE livequery_query_avg_diff_micros gauge\n");
+
+    for q in &snapshot.per_query {
+        let label = format!("query_id=\"{}\"", &q.query_id[..8.min(q.query_id.len())]);
+        out.push_str(&format!("livequery_query_subscribers{{{label}}} {}\n", q.subscribers));
+        out.push_str(&format!("livequery_query_seq{{{label}}} {}\n", q.seq));
+        out.push_str(&format!(
+            "livequery_query_max_ack_lag_batches{{{label}}} {}\n",
+            q.max_ack_lag
+        ));
+        let occupancy = if q.replay_cap == 0 {
+            0.0
+        } else {
+            q.replay_len as f64 / q.replay_cap as f64
+        };
+        out.push_str(&format!(
+            "livequery_query_replay_occupancy_ratio{{{label}}} {:.4}\n",
+            occupancy
+        ));
+        out.push_str(&format!(
+            "livequery_query_events_broadcast_total{{{label}}} {}\n",
+            q.events_broadcast
+        ));
+        out.push_str(&format!(
+            "livequery_query_avg_diff_micros{{{label}}} {:.2}\n",
+            q.avg_diff_micros
+        ));
+    }
+
+    out
 }
 
 impl SharedQuery {
-    /// Create batch from events, incrementing sequence
+    /// Create batch from events, incrementing sequence, and retain it in
+    /// the replay ring buffer for `replay_since`.
     #[inline]
     pub fn make_batch(&self, ev: Vec<SubscribeEvent>) -> Option<EventBatch> {
         if ev.is_empty() {
             return None;
         }
+        self.events_broadcast.fetch_add(ev.len() as u64, Relaxed);
         let seq = self.seq.fetch_add(1, Relaxed) + 1;
-        Some(EventBatch::new(seq, ev))
+        let batch = EventBatch::new(seq, ev, self.clock.as_ref());
+
+        let mut buf = self.replay.write();
+        if buf.len() >= self.replay_cap {
+            buf.pop_front();
+        }
+        buf.push_back(batch.clone());
+        drop(buf);
+
+        Some(batch)
+    }
+
+    /// Replay buffered batches after `last_acked`, or report that the
+    /// client has fallen further behind than the buffer retains.
+    pub fn replay_since(&self, last_acked: u64) -> ReplayResult {
+        let buf = self.replay.read();
+        match buf.front() {
+            Some(oldest) if last_acked + 1 < oldest.seq => ReplayResult::ResyncRequired,
+            _ => ReplayResult::Batches(
+                buf.iter()
+                    .filter(|b| b.seq > last_acked)
+                    .cloned()
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Record a `diff_rows`/`diff_rows_ordered` wall-clock sample, for the
+    /// rolling average exposed as `QueryMetrics::avg_diff_micros`.
+    #[inline]
+    pub fn record_diff_time(&self, elapsed: Duration) {
+        self.diff_micros_total
+            .fetch_add(elapsed.as_micros() as u64, Relaxed);
+        self.diff_samples.fetch_add(1, Relaxed);
+    }
+
+    /// Fold a fresh per-row diff into this query's coalescing buffer for
+    /// `SubscriptionMode::Coalesced` subscribers (see `merge_pending_op`),
+    /// returning the buffer's size afterward so the caller can flush early
+    /// once it crosses `Config::coalesce_max_pending` instead of waiting for
+    /// the next timed flush.
+    pub fn fold_coalesced(&self, id_hash: u64, op: PendingOp) -> usize {
+        let mut pending = self.pending.write();
+        let existing = pending.remove(&id_hash);
+        if let Some(merged) = merge_pending_op(existing, op) {
+            pending.insert(id_hash, merged);
+        }
+        pending.len()
+    }
+
+    /// Number of rows with a coalesced change awaiting flush.
+    #[inline]
+    pub fn coalesced_len(&self) -> usize {
+        self.pending.read().len()
+    }
+
+    /// Drain the coalescing buffer, turning each row's net pending op into
+    /// its delete/insert event(s) - e.g. a row folded to `Update` becomes a
+    /// delete of the original row plus an insert of its latest value, same
+    /// as an uncoalesced change. Empty if nothing was pending.
+    pub fn flush_coalesced(&self) -> Vec<SubscribeEvent> {
+        let pending = std::mem::take(&mut *self.pending.write());
+        if pending.is_empty() {
+            return Vec::new();
+        }
+        let t = ts(self.clock.as_ref());
+        let mut ev = Vec::with_capacity(pending.len() + pending.len() / 4);
+        for (_, op) in pending {
+            push_pending_op_events(t, op, &mut ev);
+        }
+        ev
     }
 }
 
@@ -334,24 +1440,71 @@ impl Snapshot {
         Self::default()
     }
 
+    /// A bounded variant for `ORDER BY <key_col> [DESC] LIMIT <limit>`
+    /// "leaderboard" queries (see `query::QueryAnalysis::order_key`/`limit`).
+    /// Tracks every row matching the query, not just the visible top-N, so
+    /// a row falling out of the window can be immediately replaced by
+    /// whichever candidate is next in order - see `Window`.
+    #[inline]
+    pub fn new_windowed(key_col: Box<str>, desc: bool, limit: usize) -> Self {
+        Self {
+            rows: FxHashMap::default(),
+            window: Some(Window::new(key_col, desc, limit)),
+            content_hasher: ContentBuildHasher::default(),
+        }
+    }
+
+    /// Reset a windowed snapshot's candidate set from a fresh full result,
+    /// without producing events - shared by `init_rows`/`init_rows_snapshot`.
+    fn load_window(&mut self, rows: Vec<RowData>, cols: &Option<Arc<[Arc<str>]>>) {
+        let w = self.window.as_mut().expect("load_window without a window");
+        w.all.clear();
+        w.keys.clear();
+        w.sorted.clear();
+        w.visible.clear();
+        for row in &rows {
+            let (id_hash, content_hash) = row_hashes(row, cols, &self.content_hasher);
+            let key = w.sort_key(row);
+            let id_key = row_identity(row, cols);
+            let val = Arc::new(row.to_value());
+            w.upsert_no_rebalance(id_hash, key, RowEntry { id_key, hash: content_hash, data: val });
+        }
+        w.rebalance();
+    }
+
     /// Initialize with typed rows - returns events for Events mode
     pub fn init_rows(
         &mut self,
         rows: Vec<RowData>,
         cols: &Option<Arc<[Arc<str>]>>,
+        clock: &dyn Clock,
     ) -> Vec<SubscribeEvent> {
-        let t = ts();
+        let t = ts(clock);
+        if self.window.is_some() {
+            let ids: Vec<u64> = rows.iter().map(|r| row_hashes(r, cols, &self.content_hasher).0).collect();
+            self.load_window(rows, cols);
+            let w = self.window.as_ref().unwrap();
+            return ids
+                .into_iter()
+                .filter(|id| w.visible.contains(id))
+                .filter_map(|id| w.all.get(&id).map(|e| SubscribeEvent::insert_arc(t, e.data.clone())))
+                .collect();
+        }
+
         let len = rows.len();
         let mut ev = Vec::with_capacity(len);
         self.rows.clear();
         self.rows.reserve(len);
         for row in rows {
-            let (id_hash, content_hash) = row_hashes(&row, cols);
+            let (id_hash, content_hash) = row_hashes(&row, cols, &self.content_hasher);
+            let id_key = row_identity(&row, cols);
             let val = Arc::new(row.to_value());
             ev.push(SubscribeEvent::insert_arc(t, val.clone()));
-            self.rows.insert(
+            upsert_chained(
+                &mut self.rows,
                 id_hash,
                 RowEntry {
+                    id_key,
                     hash: content_hash,
                     data: val,
                 },
@@ -366,17 +1519,25 @@ impl Snapshot {
         rows: Vec<RowData>,
         cols: &Option<Arc<[Arc<str>]>>,
     ) -> Vec<Arc<serde_json::Value>> {
+        if self.window.is_some() {
+            self.load_window(rows, cols);
+            return self.get_all_rows();
+        }
+
         let len = rows.len();
         self.rows.clear();
         self.rows.reserve(len);
         let mut out = Vec::with_capacity(len);
         for row in rows {
-            let (id_hash, content_hash) = row_hashes(&row, cols);
+            let (id_hash, content_hash) = row_hashes(&row, cols, &self.content_hasher);
+            let id_key = row_identity(&row, cols);
             let val = Arc::new(row.to_value());
             out.push(val.clone());
-            self.rows.insert(
+            upsert_chained(
+                &mut self.rows,
                 id_hash,
                 RowEntry {
+                    id_key,
                     hash: content_hash,
                     data: val,
                 },
@@ -388,7 +1549,20 @@ impl Snapshot {
     /// Get all current rows as Arc<Value> for Snapshot mode publish
     #[inline]
     pub fn get_all_rows(&self) -> Vec<Arc<serde_json::Value>> {
-        self.rows.values().map(|e| e.data.clone()).collect()
+        match &self.window {
+            Some(w) => w
+                .visible
+                .iter()
+                .filter_map(|id| w.all.get(id))
+                .map(|e| e.data.clone())
+                .collect(),
+            None => self
+                .rows
+                .values()
+                .flatten()
+                .map(|e| e.data.clone())
+                .collect(),
+        }
     }
 
     #[inline]
@@ -396,72 +1570,1011 @@ impl Snapshot {
         &mut self,
         rows: Vec<RowData>,
         cols: &Option<Arc<[Arc<str>]>>,
+        clock: &dyn Clock,
     ) -> Vec<SubscribeEvent> {
-        let t = ts();
-        let mut old = std::mem::take(&mut self.rows);
-        let mut new: FxHashMap<u64, RowEntry> =
-            FxHashMap::with_capacity_and_hasher(rows.len(), Default::default());
-
-        // Pre-size for typical 5% change rate
+        let started = Instant::now();
+        let t = ts(clock);
+        let ops = self.diff_rows_classified(rows, cols);
+        let mut ev = Vec::with_capacity(ops.len() + ops.len() / 4);
+        for (_, op) in ops {
+            push_pending_op_events(t, op, &mut ev);
+        }
+        crate::telemetry::record_diff_rows(started.elapsed(), ev.len());
+        ev
+    }
+
+    /// Same diff as `diff_rows`, but classified per row instead of flattened
+    /// into insert/delete events - the input a `SubscriptionMode::Coalesced`
+    /// subscriber's pending buffer folds against (see
+    /// `SharedQuery::fold_coalesced`). `diff_rows` is built on top of this.
+    pub fn diff_rows_classified(
+        &mut self,
+        rows: Vec<RowData>,
+        cols: &Option<Arc<[Arc<str>]>>,
+    ) -> Vec<(u64, PendingOp)> {
+        if self.window.is_some() {
+            return self.diff_rows_windowed(rows, cols);
+        }
+
+        let mut old = std::mem::take(&mut self.rows);
+        let mut new: FxHashMap<u64, SmallVec<[RowEntry; 1]>> =
+            FxHashMap::with_capacity_and_hasher(rows.len(), Default::default());
+
+        // Pre-size for typical 5% change rate
         let est = (old.len().max(rows.len()) / 20).max(4);
-        let mut ev = Vec::with_capacity(est);
+        let mut ops = Vec::with_capacity(est);
 
         for row in rows {
-            let (id_hash, content_hash) = row_hashes(&row, cols);
-            if let Some(prev) = old.remove(&id_hash) {
+            let (id_hash, content_hash) = row_hashes(&row, cols, &self.content_hasher);
+            let id_key = row_identity(&row, cols);
+            let prev = old.get_mut(&id_hash).and_then(|bucket| {
+                let pos = bucket.iter().position(|e| e.id_key == id_key)?;
+                Some(bucket.swap_remove(pos))
+            });
+            match prev {
                 // Fast path: content hash match means unchanged
-                if prev.hash == content_hash {
-                    new.insert(
+                Some(prev) if prev.hash == content_hash => {
+                    upsert_chained(
+                        &mut new,
                         id_hash,
                         RowEntry {
+                            id_key,
                             hash: content_hash,
                             data: prev.data,
                         },
                     );
-                } else {
-                    ev.push(SubscribeEvent::delete_arc(t, prev.data));
+                }
+                Some(prev) => {
                     let val = Arc::new(row.to_value());
-                    ev.push(SubscribeEvent::insert_arc(t, val.clone()));
-                    new.insert(
+                    ops.push((id_hash, PendingOp::Update(prev.data, val.clone())));
+                    upsert_chained(
+                        &mut new,
                         id_hash,
                         RowEntry {
+                            id_key,
+                            hash: content_hash,
+                            data: val,
+                        },
+                    );
+                }
+                None => {
+                    let val = Arc::new(row.to_value());
+                    ops.push((id_hash, PendingOp::Insert(val.clone())));
+                    upsert_chained(
+                        &mut new,
+                        id_hash,
+                        RowEntry {
+                            id_key,
                             hash: content_hash,
                             data: val,
                         },
                     );
                 }
-            } else {
-                let val = Arc::new(row.to_value());
-                ev.push(SubscribeEvent::insert_arc(t, val.clone()));
-                new.insert(
-                    id_hash,
-                    RowEntry {
-                        hash: content_hash,
-                        data: val,
-                    },
-                );
             }
         }
 
         // Remaining are deletes
-        ev.reserve(old.len());
-        for (_, old_row) in old {
-            ev.push(SubscribeEvent::delete_arc(t, old_row.data));
+        ops.reserve(old.values().map(SmallVec::len).sum());
+        for (id_hash, bucket) in old {
+            for old_row in bucket {
+                ops.push((id_hash, PendingOp::Delete(old_row.data)));
+            }
         }
         self.rows = new;
-        ev
+        ops
+    }
+
+    /// `diff_rows_classified` for a windowed snapshot: every row in `rows`
+    /// upserts the candidate set, anything previously tracked but absent
+    /// from `rows` is dropped from it entirely, then membership is
+    /// rebalanced once for the whole batch - a row crossing the window
+    /// boundary becomes an `Insert`/`Delete` rather than an `Update`, even
+    /// if its content didn't otherwise change.
+    fn diff_rows_windowed(
+        &mut self,
+        rows: Vec<RowData>,
+        cols: &Option<Arc<[Arc<str>]>>,
+    ) -> Vec<(u64, PendingOp)> {
+        let w = self.window.as_mut().expect("diff_rows_windowed without a window");
+        let old_visible = w.visible.clone();
+        let old_ids: FxHashSet<u64> = w.all.keys().copied().collect();
+
+        let mut seen = FxHashSet::default();
+        let mut touched: Vec<(u64, Option<RowEntry>, RowEntry)> = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let (id_hash, content_hash) = row_hashes(row, cols, &self.content_hasher);
+            seen.insert(id_hash);
+            let key = w.sort_key(row);
+            let new_entry = RowEntry {
+                id_key: row_identity(row, cols),
+                hash: content_hash,
+                data: Arc::new(row.to_value()),
+            };
+            let old_entry = w.upsert_no_rebalance(id_hash, key, new_entry.clone());
+            touched.push((id_hash, old_entry, new_entry));
+        }
+
+        let mut gone: Vec<(u64, RowEntry)> = Vec::new();
+        for id_hash in old_ids.difference(&seen) {
+            if let Some(entry) = w.remove_no_rebalance(*id_hash) {
+                gone.push((*id_hash, entry));
+            }
+        }
+
+        w.rebalance();
+        let new_visible = &w.visible;
+
+        let mut ops = Vec::new();
+        for (id_hash, old_entry, new_entry) in touched {
+            let was_visible = old_visible.contains(&id_hash);
+            let now_visible = new_visible.contains(&id_hash);
+            match (was_visible, now_visible, old_entry) {
+                (false, false, _) => {}
+                (false, true, _) => ops.push((id_hash, PendingOp::Insert(new_entry.data))),
+                (true, false, Some(old)) => ops.push((id_hash, PendingOp::Delete(old.data))),
+                (true, true, Some(old)) if old.hash != new_entry.hash => {
+                    ops.push((id_hash, PendingOp::Update(old.data, new_entry.data)))
+                }
+                (true, true, _) => {} // unchanged, still visible
+                (true, false, None) => {} // was never tracked - nothing to report
+            }
+        }
+        for (id_hash, entry) in gone {
+            if old_visible.contains(&id_hash) {
+                ops.push((id_hash, PendingOp::Delete(entry.data)));
+            }
+        }
+        ops
+    }
+
+    /// Upsert a single row maintained incrementally from a WAL insert/update,
+    /// returning the diff events (empty if the row's content didn't change).
+    pub fn upsert_row(
+        &mut self,
+        row: RowData,
+        cols: &Option<Arc<[Arc<str>]>>,
+        clock: &dyn Clock,
+    ) -> Vec<SubscribeEvent> {
+        let t = ts(clock);
+        if let Some(w) = &mut self.window {
+            let (id_hash, content_hash) = row_hashes(&row, cols, &self.content_hasher);
+            let key = w.sort_key(&row);
+            let new_entry = RowEntry {
+                id_key: row_identity(&row, cols),
+                hash: content_hash,
+                data: Arc::new(row.to_value()),
+            };
+            let old_entry = w.upsert_no_rebalance(id_hash, key, new_entry.clone());
+            let was_visible = w.visible.contains(&id_hash);
+            let (entered, left) = w.rebalance();
+            let now_visible = w.visible.contains(&id_hash);
+
+            let mut events = Vec::new();
+            match (was_visible, now_visible, old_entry) {
+                (false, false, _) => {}
+                (false, true, _) => events.push(SubscribeEvent::insert_arc(t, new_entry.data.clone())),
+                (true, false, Some(old)) => events.push(SubscribeEvent::delete_arc(t, old.data)),
+                (true, true, Some(old)) if old.hash != new_entry.hash => {
+                    events.push(SubscribeEvent::delete_arc(t, old.data));
+                    events.push(SubscribeEvent::insert_arc(t, new_entry.data.clone()));
+                }
+                _ => {}
+            }
+            // Other rows whose membership shifted purely from this row's
+            // rank, e.g. a new leaderboard entry evicting the previous last
+            // place.
+            for id in entered.into_iter().filter(|id| *id != id_hash) {
+                if let Some(e) = w.all.get(&id) {
+                    events.push(SubscribeEvent::insert_arc(t, e.data.clone()));
+                }
+            }
+            for id in left.into_iter().filter(|id| *id != id_hash) {
+                if let Some(e) = w.all.get(&id) {
+                    events.push(SubscribeEvent::delete_arc(t, e.data.clone()));
+                }
+            }
+            return events;
+        }
+
+        let (id_hash, content_hash) = row_hashes(&row, cols, &self.content_hasher);
+        let id_key = row_identity(&row, cols);
+        let existing = self
+            .rows
+            .get(&id_hash)
+            .and_then(|bucket| bucket.iter().find(|e| e.id_key == id_key))
+            .map(|e| (e.hash, e.data.clone()));
+
+        if let Some((old_hash, old_data)) = existing {
+            if old_hash == content_hash {
+                return Vec::new();
+            }
+            let val = Arc::new(row.to_value());
+            upsert_chained(
+                &mut self.rows,
+                id_hash,
+                RowEntry {
+                    id_key,
+                    hash: content_hash,
+                    data: val.clone(),
+                },
+            );
+            return vec![
+                SubscribeEvent::delete_arc(t, old_data),
+                SubscribeEvent::insert_arc(t, val),
+            ];
+        }
+        let val = Arc::new(row.to_value());
+        upsert_chained(
+            &mut self.rows,
+            id_hash,
+            RowEntry {
+                id_key,
+                hash: content_hash,
+                data: val.clone(),
+            },
+        );
+        vec![SubscribeEvent::insert_arc(t, val)]
+    }
+
+    /// Remove a row (by identity) if it's currently part of the snapshot,
+    /// e.g. because a WAL update took it out of a query's WHERE filter.
+    pub fn remove_row(
+        &mut self,
+        row: &RowData,
+        cols: &Option<Arc<[Arc<str>]>>,
+        clock: &dyn Clock,
+    ) -> Vec<SubscribeEvent> {
+        let (id_hash, _) = row_hashes(row, cols, &self.content_hasher);
+        let t = ts(clock);
+        if let Some(w) = &mut self.window {
+            let was_visible = w.visible.contains(&id_hash);
+            let old_entry = w.remove_no_rebalance(id_hash);
+            let (entered, left) = w.rebalance();
+
+            let mut events = Vec::new();
+            if was_visible {
+                if let Some(old) = old_entry {
+                    events.push(SubscribeEvent::delete_arc(t, old.data));
+                }
+            }
+            for id in entered.into_iter().filter(|id| *id != id_hash) {
+                if let Some(e) = w.all.get(&id) {
+                    events.push(SubscribeEvent::insert_arc(t, e.data.clone()));
+                }
+            }
+            for id in left.into_iter().filter(|id| *id != id_hash) {
+                if let Some(e) = w.all.get(&id) {
+                    events.push(SubscribeEvent::delete_arc(t, e.data.clone()));
+                }
+            }
+            return events;
+        }
+        let id_key = row_identity(row, cols);
+        match remove_chained(&mut self.rows, id_hash, &id_key) {
+            Some(old) => vec![SubscribeEvent::delete_arc(t, old.data)],
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Edit-script op produced by `OrderedSnapshot::init_rows`/`diff_rows` for
+/// `ORDER BY ... LIMIT` queries. See `OrderedSnapshot` for index semantics
+/// and the order a client should apply a script in.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum OrderedDiffOp {
+    /// A brand-new row belongs at `index` in the new sequence.
+    Insert {
+        index: usize,
+        data: Arc<serde_json::Value>,
+    },
+    /// The row at `index` in the previous sequence is no longer present.
+    Delete { index: usize },
+    /// A row with unchanged identity *and* content moved from `from_index`
+    /// (previous sequence) to `to_index` (new sequence); its data isn't
+    /// re-sent since the client already has it.
+    Move { from_index: usize, to_index: usize },
+}
+
+/// Ordered counterpart to `Snapshot`: tracks a positional row sequence for
+/// `ORDER BY`/`LIMIT` queries and emits an index-aware edit script
+/// (`OrderedDiffOp`) instead of an unordered insert/delete event set, so a
+/// client can maintain a windowed list by splicing rather than re-rendering
+/// it on every change.
+///
+/// To apply a script against a client-held copy of the previous sequence:
+/// remove every `Delete.index` first (all indices refer to the *previous*
+/// sequence - remove highest-to-lowest so earlier removals don't perturb
+/// later ones); then build the new sequence of length `new_len` by placing
+/// each `Move`'s referenced previous element (looked up by `from_index`
+/// against the previous sequence, before any deletions) at its `to_index`,
+/// placing each `Insert.data` at its `index`, and filling every remaining
+/// new-sequence position, in order, with the surviving (non-deleted,
+/// non-moved) previous elements in their original relative order.
+#[derive(Default)]
+pub struct OrderedSnapshot {
+    rows: Vec<(u64, u64, Arc<serde_json::Value>)>,
+    /// Seeded hasher for `row_hashes` - see `Snapshot::content_hasher`.
+    content_hasher: ContentBuildHasher,
+}
+
+impl OrderedSnapshot {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Seed the sequence, returning an `Insert` for every row in order.
+    pub fn init_rows(
+        &mut self,
+        rows: Vec<RowData>,
+        cols: &Option<Arc<[Arc<str>]>>,
+    ) -> Vec<OrderedDiffOp> {
+        self.rows = Self::keyed(rows, cols, &self.content_hasher);
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(index, (_, _, data))| OrderedDiffOp::Insert {
+                index,
+                data: data.clone(),
+            })
+            .collect()
+    }
+
+    /// Diff a new ordered result set against the current sequence.
+    pub fn diff_rows(
+        &mut self,
+        rows: Vec<RowData>,
+        cols: &Option<Arc<[Arc<str>]>>,
+    ) -> Vec<OrderedDiffOp> {
+        let new = Self::keyed(rows, cols, &self.content_hasher);
+        let ops = diff_ordered(&self.rows, &new);
+        self.rows = new;
+        ops
+    }
+
+    #[inline]
+    fn keyed(
+        rows: Vec<RowData>,
+        cols: &Option<Arc<[Arc<str>]>>,
+        build: &ContentBuildHasher,
+    ) -> Vec<(u64, u64, Arc<serde_json::Value>)> {
+        rows.into_iter()
+            .map(|r| {
+                let (id_hash, content_hash) = row_hashes(&r, cols, build);
+                (id_hash, content_hash, Arc::new(r.to_value()))
+            })
+            .collect()
+    }
+}
+
+/// Compute the minimal ordered edit script between `old` and `new` row
+/// sequences (see `OrderedSnapshot`). First finds the longest common
+/// subsequence of entries that match on *both* identity and content hash -
+/// those stay untouched - then pairs up remaining same-identity-and-content
+/// entries as `Move`s (a pure reorder); anything left over is a plain
+/// `Delete`/`Insert`. That also covers a row whose sort-key (or any other
+/// column) changed: its content hash differs from its old self, so it's
+/// excluded from the LCS and reported as delete+insert rather than a move,
+/// per occurrence order for duplicate identity keys (the DP naturally pairs
+/// the k-th occurrence of a repeated key with the k-th occurrence on the
+/// other side, since it operates positionally on the sequences).
+///
+/// O(n*m) in the sequence lengths via a standard LCS DP table, which is
+/// fine for the small, `LIMIT`-bounded windows this targets.
+fn diff_ordered(
+    old: &[(u64, u64, Arc<serde_json::Value>)],
+    new: &[(u64, u64, Arc<serde_json::Value>)],
+) -> Vec<OrderedDiffOp> {
+    let (n, m) = (old.len(), new.len());
+    let eq = |i: usize, j: usize| old[i].0 == new[j].0 && old[i].1 == new[j].1;
+
+    let mut dp = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            dp[i][j] = if eq(i, j) {
+                dp[i + 1][j + 1] + 1
+            } else {
+                dp[i + 1][j].max(dp[i][j + 1])
+            };
+        }
+    }
+
+    let mut kept_old = vec![false; n];
+    let mut kept_new = vec![false; m];
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if eq(i, j) {
+            kept_old[i] = true;
+            kept_new[j] = true;
+            i += 1;
+            j += 1;
+        } else if dp[i + 1][j] >= dp[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    let rem_old: Vec<usize> = (0..n).filter(|&i| !kept_old[i]).collect();
+    let rem_new: Vec<usize> = (0..m).filter(|&j| !kept_new[j]).collect();
+
+    // Pair up remaining same-identity-and-content entries (reorders) as
+    // Moves before anything else is declared a Delete/Insert.
+    let mut new_taken = vec![false; rem_new.len()];
+    let mut old_taken = vec![false; rem_old.len()];
+    let mut ops = Vec::new();
+    for (oi_idx, &oi) in rem_old.iter().enumerate() {
+        if let Some(k) = rem_new
+            .iter()
+            .enumerate()
+            .find(|&(k, &nj)| !new_taken[k] && eq(oi, nj))
+            .map(|(k, _)| k)
+        {
+            new_taken[k] = true;
+            old_taken[oi_idx] = true;
+            ops.push(OrderedDiffOp::Move {
+                from_index: oi,
+                to_index: rem_new[k],
+            });
+        }
+    }
+
+    for (oi_idx, &oi) in rem_old.iter().enumerate() {
+        if !old_taken[oi_idx] {
+            ops.push(OrderedDiffOp::Delete { index: oi });
+        }
+    }
+    for (k, &nj) in rem_new.iter().enumerate() {
+        if !new_taken[k] {
+            ops.push(OrderedDiffOp::Insert {
+                index: nj,
+                data: new[nj].2.clone(),
+            });
+        }
+    }
+    ops
+}
+
+/// One identity's entry in a `ShardedSnapshot` shard - `Snapshot`'s
+/// `RowEntry` holds the row pre-flattened to `Arc<serde_json::Value>` since
+/// that's what every subscriber ultimately gets sent; this keeps the typed
+/// `RowData` around instead, since `ShardedSnapshot::par_filter_scan` needs
+/// `WhereFilter::eval_row` to run against it directly.
+#[derive(Clone)]
+struct ShardedRowEntry {
+    id_key: Box<[RowValue]>,
+    hash: u64,
+    row: RowData,
+    /// `row.to_value()`, cached the same way `Snapshot::RowEntry::data` is -
+    /// computed once when the row is inserted/changed and reused on every
+    /// subsequent diff where it turns out unchanged.
+    data: Arc<serde_json::Value>,
+}
+
+/// Sharded, lock-striped counterpart to `Snapshot` for large result sets.
+/// `Snapshot` stores every row in one `FxHashMap` behind `&mut self`, which
+/// is the right tradeoff for the common small-to-medium query (no locking
+/// overhead at all) but means a single `diff_rows` call does the whole
+/// comparison on one thread. `ShardedSnapshot` instead partitions rows
+/// across `N` `DashMap` shards keyed by `id_hash % N`, so:
+/// - concurrent reads (`par_filter_scan`) never block each other, even
+///   against different shards being written during ingestion;
+/// - `par_diff_rows` computes each shard's added/removed/changed set
+///   independently via rayon and merges them, instead of one thread walking
+///   every row.
+///
+/// `par_diff_rows`'s merged result is the same *set* of `(id_hash,
+/// PendingOp)` pairs `Snapshot::diff_rows_classified` would produce for the
+/// same input (order isn't preserved - shards finish in whatever order
+/// rayon schedules them), because every row's shard is a pure function of
+/// its `id_hash`, and `id_hash` is computed identically to `Snapshot`'s -
+/// so a given identity always lands in the same shard across calls, and
+/// diffing a shard in isolation sees exactly the rows `Snapshot` would have
+/// compared it against.
+pub struct ShardedSnapshot {
+    shards: Vec<Map<u64, SmallVec<[ShardedRowEntry; 1]>>>,
+    content_hasher: ContentBuildHasher,
+}
+
+impl ShardedSnapshot {
+    /// `shards` is clamped to at least 1 - see `Default` for the
+    /// available-parallelism default.
+    pub fn new(shards: usize) -> Self {
+        let shards = shards.max(1);
+        Self {
+            shards: (0..shards).map(|_| DashMap::with_hasher(FxBuildHasher)).collect(),
+            content_hasher: ContentBuildHasher::default(),
+        }
+    }
+
+    #[inline]
+    fn shard_of(&self, id_hash: u64) -> usize {
+        (id_hash as usize) % self.shards.len()
+    }
+
+    /// Replace every shard's contents with `rows`, without producing diff
+    /// events - the sharded counterpart to `Snapshot::init_rows_snapshot`.
+    pub fn par_init_rows(&self, rows: Vec<RowData>, cols: &Option<Arc<[Arc<str>]>>) {
+        for shard in &self.shards {
+            shard.clear();
+        }
+        let hasher = &self.content_hasher;
+        let grouped = self.group_by_shard(rows, cols, hasher);
+        grouped.into_par_iter().zip(self.shards.par_iter()).for_each(|(incoming, shard)| {
+            for (id_hash, id_key, hash, row) in incoming {
+                let data = Arc::new(row.to_value());
+                let bucket = shard.entry(id_hash).or_default();
+                upsert_sharded(bucket, ShardedRowEntry { id_key, hash, row, data });
+            }
+        });
+    }
+
+    /// Partition `rows` by the shard their `id_hash` falls into, computing
+    /// each row's hashes up front (in parallel) - shared by
+    /// `par_init_rows`/`par_diff_rows`.
+    fn group_by_shard(
+        &self,
+        rows: Vec<RowData>,
+        cols: &Option<Arc<[Arc<str>]>>,
+        hasher: &ContentBuildHasher,
+    ) -> Vec<Vec<(u64, Box<[RowValue]>, u64, RowData)>> {
+        let hashed: Vec<(u64, Box<[RowValue]>, u64, RowData)> = rows
+            .into_par_iter()
+            .map(|row| {
+                let (id_hash, content_hash) = row_hashes(&row, cols, hasher);
+                let id_key = row_identity(&row, cols);
+                (id_hash, id_key, content_hash, row)
+            })
+            .collect();
+
+        let mut grouped: Vec<Vec<(u64, Box<[RowValue]>, u64, RowData)>> =
+            (0..self.shards.len()).map(|_| Vec::new()).collect();
+        for entry in hashed {
+            grouped[self.shard_of(entry.0)].push(entry);
+        }
+        grouped
+    }
+
+    /// Diff `rows` against the current shard contents, computing each
+    /// shard's added/removed/changed set independently via rayon and
+    /// merging them - see the struct doc comment for the correctness
+    /// argument. Mirrors `Snapshot::diff_rows_classified`'s per-row logic
+    /// exactly, just run once per shard instead of once overall.
+    pub fn par_diff_rows(
+        &self,
+        rows: Vec<RowData>,
+        cols: &Option<Arc<[Arc<str>]>>,
+    ) -> Vec<(u64, PendingOp)> {
+        let grouped = self.group_by_shard(rows, cols, &self.content_hasher);
+        grouped
+            .into_par_iter()
+            .zip(self.shards.par_iter())
+            .flat_map(|(incoming, shard)| diff_shard(shard, incoming))
+            .collect()
+    }
+
+    /// Evaluate `filter` against every row across all shards concurrently,
+    /// returning the rows it matches. Rows `filter.eval_row` can't resolve
+    /// (`EvalResult::Unknown`) are excluded, the same conservative default a
+    /// caller falling back to a requery would apply.
+    pub fn par_filter_scan(&self, filter: &WhereFilter) -> Vec<RowData> {
+        self.shards
+            .par_iter()
+            .flat_map(|shard| {
+                shard
+                    .iter()
+                    .flat_map(|bucket| {
+                        bucket
+                            .value()
+                            .iter()
+                            .filter(|e| matches!(filter.eval_row(&e.row), query::EvalResult::Match))
+                            .map(|e| e.row.clone())
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+impl Default for ShardedSnapshot {
+    /// Defaults to `std::thread::available_parallelism()` shards (falling
+    /// back to 1 if the platform can't report it), so the concurrency this
+    /// buys scales with the machine it runs on without configuration.
+    fn default() -> Self {
+        let shards = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        Self::new(shards)
+    }
+}
+
+/// Insert or update `entry`'s identity within a `ShardedRowEntry` bucket,
+/// chaining on an `id_hash` collision between two distinct identities -
+/// same role as `upsert_chained`, just against a `DashMap`-owned bucket
+/// rather than `Snapshot::rows`'s.
+fn upsert_sharded(mut bucket: impl std::ops::DerefMut<Target = SmallVec<[ShardedRowEntry; 1]>>, entry: ShardedRowEntry) {
+    if let Some(slot) = bucket.iter_mut().find(|e| e.id_key == entry.id_key) {
+        *slot = entry;
+    } else {
+        bucket.push(entry);
+    }
+}
+
+/// Diff one shard's `incoming` rows against its current contents, returning
+/// that shard's slice of `(id_hash, PendingOp)` ops - see
+/// `ShardedSnapshot::par_diff_rows`.
+fn diff_shard(
+    shard: &Map<u64, SmallVec<[ShardedRowEntry; 1]>>,
+    incoming: Vec<(u64, Box<[RowValue]>, u64, RowData)>,
+) -> Vec<(u64, PendingOp)> {
+    let mut old: FxHashMap<u64, SmallVec<[ShardedRowEntry; 1]>> =
+        shard.iter().map(|e| (*e.key(), e.value().clone())).collect();
+    shard.clear();
+
+    let mut new: FxHashMap<u64, SmallVec<[ShardedRowEntry; 1]>> =
+        FxHashMap::with_capacity_and_hasher(incoming.len(), Default::default());
+    let mut ops = Vec::with_capacity(incoming.len() / 20 + 4);
+
+    for (id_hash, id_key, content_hash, row) in incoming {
+        let prev = old.get_mut(&id_hash).and_then(|bucket| {
+            let pos = bucket.iter().position(|e| e.id_key == id_key)?;
+            Some(bucket.swap_remove(pos))
+        });
+        match prev {
+            Some(prev) if prev.hash == content_hash => {
+                new.entry(id_hash).or_default().push(ShardedRowEntry {
+                    id_key,
+                    hash: content_hash,
+                    row: prev.row,
+                    data: prev.data,
+                });
+            }
+            Some(prev) => {
+                let data = Arc::new(row.to_value());
+                ops.push((id_hash, PendingOp::Update(prev.data, data.clone())));
+                new.entry(id_hash).or_default().push(ShardedRowEntry {
+                    id_key,
+                    hash: content_hash,
+                    row,
+                    data,
+                });
+            }
+            None => {
+                let data = Arc::new(row.to_value());
+                ops.push((id_hash, PendingOp::Insert(data.clone())));
+                new.entry(id_hash).or_default().push(ShardedRowEntry {
+                    id_key,
+                    hash: content_hash,
+                    row,
+                    data,
+                });
+            }
+        }
+    }
+
+    for (id_hash, bucket) in old {
+        for old_entry in bucket {
+            ops.push((id_hash, PendingOp::Delete(old_entry.data)));
+        }
+    }
+
+    for (id_hash, bucket) in new {
+        shard.insert(id_hash, bucket);
+    }
+    ops
+}
+
+/// Result of folding one row change into a `GroupSnapshot` - either a
+/// group's aggregate row changed, or its membership count reached zero and
+/// it no longer exists.
+#[derive(Debug, Clone)]
+pub enum GroupChange {
+    /// The group's new aggregate row.
+    Updated(Arc<serde_json::Value>),
+    /// The group's last aggregate row, just before it was removed.
+    Removed(Arc<serde_json::Value>),
+}
+
+/// One GROUP BY group's incrementally-maintained aggregate state.
+/// `count` alone answers COUNT(*); SUM/AVG keep a running total plus a
+/// separate non-null observation count (AVG's divisor, since a column can
+/// be NULL on rows that still count toward the group); MIN/MAX keep a
+/// value -> occurrence multiset rather than a single cached extreme, so a
+/// deletion can recover the next-best value without rescanning the group.
+#[derive(Default, Clone)]
+struct GroupAccumulator {
+    count: u64,
+    sums: FxHashMap<Box<str>, f64>,
+    non_null: FxHashMap<Box<str>, u64>,
+    min_max: FxHashMap<Box<str>, std::collections::BTreeMap<OrderVal, u32>>,
+}
+
+/// Incrementally-maintained `GROUP BY`/aggregate view - the continuous-
+/// aggregation counterpart to `Snapshot`'s plain row set. Maintains a
+/// `GroupAccumulator` per distinct `group_cols` value and folds each
+/// inserted/deleted/updated row into just its group's accumulator (add on
+/// insert, subtract on delete, subtract-then-add on update), so a change
+/// only ever touches the one or two groups it actually affects instead of
+/// recomputing every group from scratch. See `query::QueryAnalysis::can_aggregate`
+/// for when a query qualifies.
+pub struct GroupSnapshot {
+    group_cols: Arc<[Box<str>]>,
+    aggregates: Arc<[AggSpec]>,
+    groups: FxHashMap<u64, SmallVec<[(Box<[RowValue]>, GroupAccumulator); 1]>>,
+    content_hasher: ContentBuildHasher,
+}
+
+impl GroupSnapshot {
+    pub fn new(group_cols: Arc<[Box<str>]>, aggregates: Arc<[AggSpec]>) -> Self {
+        Self {
+            group_cols,
+            aggregates,
+            groups: FxHashMap::default(),
+            content_hasher: ContentBuildHasher::default(),
+        }
+    }
+
+    /// `row`'s group-by values, hashed the same way `row_identity`/
+    /// `row_hashes` hash an identity, plus the key itself to resolve a hash
+    /// collision between two distinct groups.
+    fn group_key(&self, row: &RowData) -> (u64, Box<[RowValue]>) {
+        let key: Box<[RowValue]> = self
+            .group_cols
+            .iter()
+            .map(|c| row.get(c).cloned().unwrap_or(RowValue::Null))
+            .collect();
+        let mut h = self.content_hasher.build_hasher();
+        for v in key.iter() {
+            v.hash_into(&mut h);
+        }
+        (h.finish(), key)
+    }
+
+    fn accumulator_mut(&mut self, hash: u64, key: &[RowValue]) -> &mut GroupAccumulator {
+        let bucket = self.groups.entry(hash).or_default();
+        if let Some(pos) = bucket.iter().position(|(k, _)| k.as_ref() == key) {
+            &mut bucket[pos].1
+        } else {
+            bucket.push((key.into(), GroupAccumulator::default()));
+            &mut bucket.last_mut().unwrap().1
+        }
+    }
+
+    /// Replace every group's state with the aggregates of `rows`, without
+    /// producing change events - the aggregate counterpart to
+    /// `Snapshot::init_rows_snapshot`, for seeding a client's first snapshot.
+    pub fn init_rows(&mut self, rows: Vec<RowData>) -> Vec<Arc<serde_json::Value>> {
+        self.groups.clear();
+        for row in &rows {
+            let (hash, key) = self.group_key(row);
+            let aggregates = self.aggregates.clone();
+            add_to_group(self.accumulator_mut(hash, &key), &aggregates, row);
+        }
+        self.groups
+            .iter()
+            .flat_map(|(_, bucket)| bucket.iter().map(|(key, acc)| self.group_row(key, acc)))
+            .collect()
+    }
+
+    /// Fold a newly-inserted row into its group, creating the group if this
+    /// is its first member.
+    pub fn apply_insert(&mut self, row: &RowData) -> GroupChange {
+        let (hash, key) = self.group_key(row);
+        let aggregates = self.aggregates.clone();
+        let acc = self.accumulator_mut(hash, &key);
+        add_to_group(acc, &aggregates, row);
+        let snapshot = acc.clone();
+        GroupChange::Updated(self.group_row(&key, &snapshot))
+    }
+
+    /// Fold a deleted row out of its group, removing the group entirely
+    /// once its count reaches zero. A no-op (returns `None`) if the row's
+    /// group was never tracked, e.g. a delete for a row this view never
+    /// saw an insert for.
+    pub fn apply_delete(&mut self, row: &RowData) -> Option<GroupChange> {
+        let (hash, key) = self.group_key(row);
+        let aggregates = self.aggregates.clone();
+        if !self.group_exists(hash, &key) {
+            return None;
+        }
+        let acc = self.accumulator_mut(hash, &key);
+        subtract_from_group(acc, &aggregates, row);
+        let emptied = acc.count == 0;
+        let snapshot = acc.clone();
+        if emptied {
+            self.remove_group(hash, &key);
+            Some(GroupChange::Removed(self.group_row(&key, &snapshot)))
+        } else {
+            Some(GroupChange::Updated(self.group_row(&key, &snapshot)))
+        }
+    }
+
+    /// Fold an UPDATE's old/new tuple into this view. When `old` and `new`
+    /// fall in the same group this is a pure in-group delta (subtract old,
+    /// add new); when the group-by value itself changed, `old`'s group
+    /// loses a member (possibly removed) and `new`'s group gains one - both
+    /// reported.
+    pub fn apply_update(&mut self, old: &RowData, new: &RowData) -> Vec<GroupChange> {
+        let (old_hash, old_key) = self.group_key(old);
+        let (new_hash, new_key) = self.group_key(new);
+        if old_hash == new_hash && old_key == new_key {
+            let aggregates = self.aggregates.clone();
+            let acc = self.accumulator_mut(old_hash, &old_key);
+            subtract_from_group(acc, &aggregates, old);
+            add_to_group(acc, &aggregates, new);
+            let snapshot = acc.clone();
+            return vec![GroupChange::Updated(self.group_row(&old_key, &snapshot))];
+        }
+        let mut changes = Vec::with_capacity(2);
+        changes.extend(self.apply_delete(old));
+        changes.push(self.apply_insert(new));
+        changes
+    }
+
+    fn group_exists(&self, hash: u64, key: &[RowValue]) -> bool {
+        self.groups
+            .get(&hash)
+            .is_some_and(|bucket| bucket.iter().any(|(k, _)| k.as_ref() == key))
+    }
+
+    fn remove_group(&mut self, hash: u64, key: &[RowValue]) {
+        if let Some(bucket) = self.groups.get_mut(&hash) {
+            if let Some(pos) = bucket.iter().position(|(k, _)| k.as_ref() == key) {
+                bucket.swap_remove(pos);
+            }
+            if bucket.is_empty() {
+                self.groups.remove(&hash);
+            }
+        }
+    }
+
+    /// Build `key`/`acc`'s current aggregate row: the group-by columns
+    /// followed by each `AggSpec`'s current value under its alias.
+    fn group_row(&self, key: &[RowValue], acc: &GroupAccumulator) -> Arc<serde_json::Value> {
+        let mut map = serde_json::Map::with_capacity(self.group_cols.len() + self.aggregates.len());
+        for (col, v) in self.group_cols.iter().zip(key.iter()) {
+            map.insert(col.to_string(), v.to_value());
+        }
+        for spec in self.aggregates.iter() {
+            let alias = spec.alias.as_ref();
+            let val = match spec.func {
+                AggFunc::Count => serde_json::Value::from(acc.count),
+                AggFunc::Sum => acc
+                    .sums
+                    .get(alias)
+                    .copied()
+                    .map(number_value)
+                    .unwrap_or(serde_json::Value::Null),
+                AggFunc::Avg => match acc.non_null.get(alias).copied().unwrap_or(0) {
+                    0 => serde_json::Value::Null,
+                    n => number_value(acc.sums.get(alias).copied().unwrap_or(0.0) / n as f64),
+                },
+                AggFunc::Min => acc
+                    .min_max
+                    .get(alias)
+                    .and_then(|ms| ms.keys().next())
+                    .map(order_val_value)
+                    .unwrap_or(serde_json::Value::Null),
+                AggFunc::Max => acc
+                    .min_max
+                    .get(alias)
+                    .and_then(|ms| ms.keys().next_back())
+                    .map(order_val_value)
+                    .unwrap_or(serde_json::Value::Null),
+            };
+            map.insert(alias.to_string(), val);
+        }
+        Arc::new(serde_json::Value::Object(map))
+    }
+}
+
+/// Fold `row` into `acc` - COUNT always (via `acc.count`), SUM/AVG's source
+/// column added to the running total and non-null count, MIN/MAX's source
+/// column added to the occurrence multiset. Non-numeric values for SUM/AVG
+/// and NULL values for MIN/MAX are skipped, same as Postgres ignoring NULLs
+/// in an aggregate.
+fn add_to_group(acc: &mut GroupAccumulator, aggregates: &[AggSpec], row: &RowData) {
+    acc.count += 1;
+    for spec in aggregates {
+        let Some(col) = &spec.col else { continue }; // COUNT(*) needs nothing further
+        match spec.func {
+            AggFunc::Count => {}
+            AggFunc::Sum | AggFunc::Avg => {
+                if let Some(n) = numeric_value(row.get(col)) {
+                    *acc.sums.entry(spec.alias.clone()).or_insert(0.0) += n;
+                    *acc.non_null.entry(spec.alias.clone()).or_insert(0) += 1;
+                }
+            }
+            AggFunc::Min | AggFunc::Max => {
+                let v = order_val(row.get(col));
+                if v != OrderVal::Null {
+                    *acc.min_max.entry(spec.alias.clone()).or_default().entry(v).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Reverse of `add_to_group` - the exact counterpart subtracted when a row
+/// leaves a group (delete, or the old side of an update).
+fn subtract_from_group(acc: &mut GroupAccumulator, aggregates: &[AggSpec], row: &RowData) {
+    acc.count = acc.count.saturating_sub(1);
+    for spec in aggregates {
+        let Some(col) = &spec.col else { continue };
+        match spec.func {
+            AggFunc::Count => {}
+            AggFunc::Sum | AggFunc::Avg => {
+                if let Some(n) = numeric_value(row.get(col)) {
+                    if let Some(s) = acc.sums.get_mut(spec.alias.as_ref()) {
+                        *s -= n;
+                    }
+                    if let Some(c) = acc.non_null.get_mut(spec.alias.as_ref()) {
+                        *c = c.saturating_sub(1);
+                    }
+                }
+            }
+            AggFunc::Min | AggFunc::Max => {
+                let v = order_val(row.get(col));
+                if v != OrderVal::Null {
+                    if let Some(ms) = acc.min_max.get_mut(spec.alias.as_ref()) {
+                        if let Some(n) = ms.get_mut(&v) {
+                            *n -= 1;
+                            if *n == 0 {
+                                ms.remove(&v);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `RowValue` reduced to `f64` for SUM/AVG - `Int`, `Float` and
+/// `Numeric` (parsed from its text) count; anything else, NULL included, is
+/// excluded from the aggregate, as Postgres's SUM/AVG skip NULLs.
+fn numeric_value(v: Option<&RowValue>) -> Option<f64> {
+    match v {
+        Some(RowValue::Int(i)) => Some(*i as f64),
+        Some(RowValue::Float(f)) => Some(*f),
+        Some(RowValue::Numeric(s)) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// `f64` to `serde_json::Value`, `Null` for non-finite results - the same
+/// convention `RowData::to_value`'s `RowValue::Float` arm uses.
+fn number_value(f: f64) -> serde_json::Value {
+    serde_json::Number::from_f64(f)
+        .map(serde_json::Value::Number)
+        .unwrap_or(serde_json::Value::Null)
+}
+
+/// `OrderVal` back to a `serde_json::Value` for a MIN/MAX aggregate's
+/// output - the reverse of `order_val`.
+fn order_val_value(v: &OrderVal) -> serde_json::Value {
+    match v {
+        OrderVal::Null => serde_json::Value::Null,
+        OrderVal::Bool(b) => serde_json::Value::Bool(*b),
+        OrderVal::Int(i) => serde_json::Value::Number(serde_json::Number::from(*i)),
+        OrderVal::Float(f) => number_value(f.0),
+        OrderVal::Str(s) => serde_json::Value::String(s.to_string()),
     }
 }
 
 #[inline(always)]
-fn ts() -> i64 {
-    ts_millis() as i64
+fn ts(clock: &dyn Clock) -> i64 {
+    clock.now_millis() as i64
 }
 
 const HEX: &[u8; 16] = b"0123456789abcdef";
 
+/// `security_key` folds the principal into the hash whenever a row-level
+/// security predicate applies (see `SubscriptionManager::subscribe`), so two
+/// principals with the same query text but different mandatory predicates
+/// never collide onto the same `SharedQuery`/snapshot.
 #[inline]
-fn qhash(q: &str) -> String {
+fn qhash(q: &str, security_key: Option<&str>) -> String {
     let mut h = FxHasher::default();
     let mut sp = true;
     for c in q.bytes() {
@@ -475,6 +2588,10 @@ fn qhash(q: &str) -> String {
             sp = false;
         }
     }
+    if let Some(k) = security_key {
+        0u8.hash(&mut h);
+        k.hash(&mut h);
+    }
     // Avoid format! allocation - use lookup table
     let n = h.finish();
     let mut s = String::with_capacity(16);
@@ -484,34 +2601,90 @@ fn qhash(q: &str) -> String {
     s
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_query() {
+        let m = SubscriptionManager::new(1000);
+        let r1 = m
+            .subscribe(
+                "sub-1",
+                "SELECT * FROM users",
+                None,
+                SubscriptionMode::Events,
+                WireFormat::Json,
+                "",
+            )
+            .unwrap();
+        assert!(r1.is_new_query);
+        let r2 = m
+            .subscribe(
+                "sub-2",
+                "SELECT * FROM users",
+                None,
+                SubscriptionMode::Events,
+                WireFormat::Json,
+                "",
+            )
+            .unwrap();
+        assert!(!r2.is_new_query); // Same query, not new
+        assert_eq!(r1.query_id, r2.query_id); // Same query_id
+        assert_ne!(r1.subscription_id, r2.subscription_id); // Different sub_ids
+        assert_eq!(m.stats(), (2, 1)); // 2 subs, 1 query
+    }
+
+    /// Row-level security `AuthProvider` for tests: every credential is its
+    /// own tenant, and the mandatory predicate restricts rows to `tenant_id
+    /// = <credential>`.
+    struct TenantAuthProvider;
+
+    impl AuthProvider for TenantAuthProvider {
+        fn authenticate(&self, credential: &str) -> Result<Principal, String> {
+            Ok(Principal(Arc::from(credential)))
+        }
+
+        fn row_filter(&self, principal: &Principal) -> Option<WhereFilter> {
+            Some(WhereFilter::Eq {
+                col: "tenant_id".into(),
+                val: query::FilterValue::Str(principal.0.as_ref().into()),
+            })
+        }
+    }
 
     #[test]
-    fn test_shared_query() {
-        let m = SubscriptionManager::new(1000);
+    fn test_row_filter_isolates_queries_by_principal() {
+        let m = SubscriptionManager::with_auth(1000, DEFAULT_REPLAY_CAP, usize::MAX, usize::MAX, Arc::new(TenantAuthProvider));
+
         let r1 = m
             .subscribe(
                 "sub-1",
-                "SELECT * FROM users",
+                "SELECT * FROM orders",
                 None,
                 SubscriptionMode::Events,
+                WireFormat::Json,
+                "tenant-a",
             )
             .unwrap();
-        assert!(r1.is_new_query);
         let r2 = m
             .subscribe(
                 "sub-2",
-                "SELECT * FROM users",
+                "SELECT * FROM orders",
                 None,
                 SubscriptionMode::Events,
+                WireFormat::Json,
+                "tenant-b",
             )
             .unwrap();
-        assert!(!r2.is_new_query); // Same query, not new
-        assert_eq!(r1.query_id, r2.query_id); // Same query_id
-        assert_ne!(r1.subscription_id, r2.subscription_id); // Different sub_ids
-        assert_eq!(m.stats(), (2, 1)); // 2 subs, 1 query
+
+        // Same query text, different principal - each gets its own
+        // `SharedQuery` rather than sharing one secured by only the first
+        // principal's predicate.
+        assert_ne!(r1.query_id, r2.query_id);
+        assert!(r1.is_new_query);
+        assert!(r2.is_new_query);
+        assert_eq!(m.stats(), (2, 2));
     }
 
     #[test]
@@ -522,6 +2695,8 @@ mod tests {
             "SELECT * FROM users",
             None,
             SubscriptionMode::Events,
+            WireFormat::Json,
+            "",
         )
         .unwrap();
         m.subscribe(
@@ -529,6 +2704,8 @@ mod tests {
             "SELECT * FROM users",
             None,
             SubscriptionMode::Events,
+            WireFormat::Json,
+            "",
         )
         .unwrap();
         assert_eq!(m.stats(), (2, 1));
@@ -542,10 +2719,705 @@ mod tests {
 
     #[test]
     fn test_qhash() {
-        assert_eq!(qhash("SELECT * FROM users"), qhash("select * from users"));
+        assert_eq!(qhash("SELECT * FROM users", None), qhash("select * from users", None));
         assert_eq!(
-            qhash("SELECT  *  FROM  users"),
-            qhash("SELECT * FROM users")
+            qhash("SELECT  *  FROM  users", None),
+            qhash("SELECT * FROM users", None)
+        );
+    }
+
+    #[test]
+    fn test_qhash_security_key_separates_otherwise_identical_queries() {
+        // Same query text, different principal-scoped predicate - must not
+        // collide onto the same `SharedQuery`.
+        assert_ne!(
+            qhash("SELECT * FROM orders", Some("tenant-a")),
+            qhash("SELECT * FROM orders", Some("tenant-b"))
+        );
+        assert_ne!(
+            qhash("SELECT * FROM orders", None),
+            qhash("SELECT * FROM orders", Some("tenant-a"))
+        );
+    }
+
+    #[test]
+    fn test_force_unsubscribe_then_ack_gone() {
+        let m = SubscriptionManager::new(1000);
+        m.subscribe(
+            "sub-1",
+            "SELECT * FROM users",
+            None,
+            SubscriptionMode::Events,
+            WireFormat::Json,
+            "",
+        )
+        .unwrap();
+        assert_eq!(m.stats(), (1, 1));
+
+        let batch = m.force_unsubscribe("sub-1", "query evicted").unwrap();
+        assert_eq!(batch.events.len(), 1);
+        assert_eq!(batch.events[0].gone.as_deref(), Some("query evicted"));
+        // Slot stays alive until the ack.
+        assert_eq!(m.stats(), (1, 1));
+
+        // A stale ack for an older seq is a no-op.
+        assert!(!m.ack_gone("sub-1", batch.seq - 1));
+        assert_eq!(m.stats(), (1, 1));
+
+        assert!(m.ack_gone("sub-1", batch.seq));
+        assert_eq!(m.stats(), (0, 0));
+    }
+
+    #[test]
+    fn test_expire_gone_reclaims_after_grace() {
+        let m = SubscriptionManager::new(1000);
+        m.subscribe(
+            "sub-1",
+            "SELECT * FROM users",
+            None,
+            SubscriptionMode::Events,
+            WireFormat::Json,
+            "",
+        )
+        .unwrap();
+        m.force_unsubscribe("sub-1", "query evicted").unwrap();
+
+        // Grace hasn't elapsed yet.
+        assert!(m.expire_gone(Duration::from_secs(60)).is_empty());
+        assert_eq!(m.stats(), (1, 1));
+
+        // A zero grace period is always elapsed.
+        let expired = m.expire_gone(Duration::from_secs(0));
+        assert_eq!(expired.len(), 1);
+        assert_eq!(m.stats(), (0, 0));
+    }
+
+    #[test]
+    fn test_replay_since_returns_batches_after_cursor() {
+        let m = SubscriptionManager::new(1000);
+        let r = m
+            .subscribe(
+                "sub-1",
+                "SELECT * FROM users",
+                None,
+                SubscriptionMode::Events,
+                WireFormat::Json,
+                "",
+            )
+            .unwrap();
+        let q = m.get_query(&r.query_id).unwrap();
+
+        let b1 = q
+            .make_batch(vec![SubscribeEvent::insert(1, serde_json::json!({"id": 1}))])
+            .unwrap();
+        let b2 = q
+            .make_batch(vec![SubscribeEvent::insert(2, serde_json::json!({"id": 2}))])
+            .unwrap();
+
+        match q.replay_since(b1.seq) {
+            ReplayResult::Batches(batches) => {
+                assert_eq!(batches.len(), 1);
+                assert_eq!(batches[0].seq, b2.seq);
+            }
+            ReplayResult::ResyncRequired => panic!("expected batches, got resync"),
+        }
+
+        match q.replay_since(b2.seq) {
+            ReplayResult::Batches(batches) => assert!(batches.is_empty()),
+            ReplayResult::ResyncRequired => panic!("expected empty batches, got resync"),
+        }
+    }
+
+    #[test]
+    fn test_replay_since_overflow_requires_resync() {
+        let m = SubscriptionManager::new(1000);
+        let r = m
+            .subscribe(
+                "sub-1",
+                "SELECT * FROM users",
+                None,
+                SubscriptionMode::Events,
+                WireFormat::Json,
+                "",
+            )
+            .unwrap();
+        let q = m.get_query(&r.query_id).unwrap();
+
+        for i in 0..(DEFAULT_REPLAY_CAP as i64 + 5) {
+            q.make_batch(vec![SubscribeEvent::insert(i, serde_json::json!({"id": i}))])
+                .unwrap();
+        }
+
+        // Cursor 0 is older than anything still buffered.
+        assert!(matches!(q.replay_since(0), ReplayResult::ResyncRequired));
+    }
+
+    #[test]
+    fn test_with_replay_cap_overrides_default_per_manager() {
+        let m = SubscriptionManager::with_replay_cap(1000, 2);
+        let r = m
+            .subscribe(
+                "sub-1",
+                "SELECT * FROM users",
+                None,
+                SubscriptionMode::Events,
+                WireFormat::Json,
+                "",
+            )
+            .unwrap();
+        let q = m.get_query(&r.query_id).unwrap();
+
+        for i in 0..3 {
+            q.make_batch(vec![SubscribeEvent::insert(i, serde_json::json!({"id": i}))])
+                .unwrap();
+        }
+
+        // Capacity 2: the first batch has already been evicted.
+        assert!(matches!(q.replay_since(0), ReplayResult::ResyncRequired));
+        match q.replay_since(1) {
+            ReplayResult::Batches(batches) => assert_eq!(batches.len(), 2),
+            ReplayResult::ResyncRequired => panic!("expected batches, got resync"),
+        }
+    }
+
+    #[test]
+    fn test_ack_tracks_highest_seq() {
+        let m = SubscriptionManager::new(1000);
+        m.subscribe(
+            "sub-1",
+            "SELECT * FROM users",
+            None,
+            SubscriptionMode::Events,
+            WireFormat::Json,
+            "",
+        )
+        .unwrap();
+
+        assert!(m.ack("sub-1", 5));
+        assert_eq!(m.get_sub("sub-1").unwrap().acked_seq.load(Relaxed), 5);
+
+        // Stale acks never move the cursor backward.
+        assert!(m.ack("sub-1", 2));
+        assert_eq!(m.get_sub("sub-1").unwrap().acked_seq.load(Relaxed), 5);
+
+        assert!(!m.ack("no-such-sub", 1));
+    }
+
+    #[test]
+    fn test_merge_pending_op_cdc_rules() {
+        use PendingOp::*;
+        let a = Arc::new(serde_json::json!({"v": "a"}));
+        let b = Arc::new(serde_json::json!({"v": "b"}));
+        let c = Arc::new(serde_json::json!({"v": "c"}));
+
+        // Insert then Delete cancels to nothing.
+        assert!(merge_pending_op(Some(Insert(a.clone())), Delete(a.clone())).is_none());
+
+        // Insert then Update becomes Insert(new).
+        match merge_pending_op(Some(Insert(a.clone())), Update(a.clone(), b.clone())) {
+            Some(Insert(new)) => assert_eq!(new, b),
+            other => panic!("expected Insert, got {other:?}"),
+        }
+
+        // Update then Update keeps the original old and the latest new.
+        match merge_pending_op(Some(Update(a.clone(), b.clone())), Update(b.clone(), c.clone())) {
+            Some(Update(old, new)) => {
+                assert_eq!(old, a);
+                assert_eq!(new, c);
+            }
+            other => panic!("expected Update, got {other:?}"),
+        }
+
+        // Update then Delete becomes Delete(orig_old).
+        match merge_pending_op(Some(Update(a.clone(), b.clone())), Delete(b.clone())) {
+            Some(Delete(old)) => assert_eq!(old, a),
+            other => panic!("expected Delete, got {other:?}"),
+        }
+
+        // Delete then Insert becomes Update(orig_old, new).
+        match merge_pending_op(Some(Delete(a.clone())), Insert(c.clone())) {
+            Some(Update(old, new)) => {
+                assert_eq!(old, a);
+                assert_eq!(new, c);
+            }
+            other => panic!("expected Update, got {other:?}"),
+        }
+
+        // Nothing pending yet - the fresh op passes through untouched.
+        match merge_pending_op(None, Insert(a.clone())) {
+            Some(Insert(new)) => assert_eq!(new, a),
+            other => panic!("expected Insert, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_fold_and_flush_coalesced_collapses_burst_to_net_event() {
+        let m = SubscriptionManager::new(1000);
+        let r = m
+            .subscribe(
+                "sub-1",
+                "SELECT * FROM users",
+                None,
+                SubscriptionMode::Coalesced,
+                WireFormat::Json,
+                "",
+            )
+            .unwrap();
+        let q = m.get_query(&r.query_id).unwrap();
+
+        // Same row inserted then updated twice within one coalescing window.
+        let v1 = Arc::new(serde_json::json!({"id": 1, "name": "a"}));
+        let v2 = Arc::new(serde_json::json!({"id": 1, "name": "b"}));
+        let v3 = Arc::new(serde_json::json!({"id": 1, "name": "c"}));
+        assert_eq!(q.fold_coalesced(1, PendingOp::Insert(v1)), 1);
+        assert_eq!(q.fold_coalesced(1, PendingOp::Update(v2.clone(), v2.clone())), 1);
+        assert_eq!(q.fold_coalesced(1, PendingOp::Update(v2, v3.clone())), 1);
+        assert_eq!(q.coalesced_len(), 1);
+
+        // Folds to a single net Insert(v3) - an insert followed only by
+        // updates is still just an insert of the latest value.
+        let events = q.flush_coalesced();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data.as_deref(), Some(v3.as_ref()));
+        assert_eq!(q.coalesced_len(), 0);
+
+        // Draining an empty buffer is a no-op.
+        assert!(q.flush_coalesced().is_empty());
+    }
+
+    #[test]
+    fn test_diff_rows_classified_matches_diff_rows_flattened() {
+        let mut classified = Snapshot::new();
+        let mut flat = Snapshot::new();
+        let clock = SystemClock;
+        classified.init_rows(vec![row(1), row(2)], &None);
+        flat.init_rows(vec![row(1), row(2)], &None);
+
+        let ops = classified.diff_rows_classified(vec![row(2), row(3)], &None);
+        let mut expanded = Vec::new();
+        for (_, op) in ops {
+            push_pending_op_events(0, op, &mut expanded);
+        }
+
+        let events = flat.diff_rows(vec![row(2), row(3)], &None, &clock);
+        assert_eq!(expanded.len(), events.len());
+    }
+
+    fn row(id: i64) -> RowData {
+        RowData::from_value(&serde_json::json!({"id": id}))
+    }
+
+    fn scored(id: i64, score: i64) -> RowData {
+        RowData::from_value(&serde_json::json!({"id": id, "score": score}))
+    }
+
+    #[test]
+    fn test_windowed_snapshot_init_keeps_only_top_n() {
+        let mut snap = Snapshot::new_windowed("score".into(), true, 2);
+        let rows = vec![scored(1, 10), scored(2, 30), scored(3, 20)];
+        let out = snap.init_rows_snapshot(rows, &None);
+
+        // Only the top 2 by score (30, 20) are visible - 10 never surfaces.
+        let scores: Vec<i64> = out
+            .iter()
+            .map(|v| v["score"].as_i64().unwrap())
+            .collect();
+        assert_eq!(scores.len(), 2);
+        assert!(scores.contains(&30));
+        assert!(scores.contains(&20));
+        assert!(!scores.contains(&10));
+    }
+
+    #[test]
+    fn test_windowed_snapshot_diff_promotes_on_eviction() {
+        let mut snap = Snapshot::new_windowed("score".into(), true, 2);
+        snap.init_rows_snapshot(vec![scored(1, 10), scored(2, 30), scored(3, 20)], &None);
+
+        // A new row (id=4, score=25) beats id=3 (score=20) out of the
+        // window; id=1 (score=10) was never visible and stays irrelevant.
+        let ops = snap.diff_rows_classified(
+            vec![scored(1, 10), scored(2, 30), scored(3, 20), scored(4, 25)],
+            &None,
+        );
+
+        let inserted: Vec<_> = ops
+            .iter()
+            .filter_map(|(_, op)| match op {
+                PendingOp::Insert(v) => Some(v["id"].as_i64().unwrap()),
+                _ => None,
+            })
+            .collect();
+        let deleted: Vec<_> = ops
+            .iter()
+            .filter_map(|(_, op)| match op {
+                PendingOp::Delete(v) => Some(v["id"].as_i64().unwrap()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(inserted, vec![4]);
+        assert_eq!(deleted, vec![3]);
+    }
+
+    #[test]
+    fn test_windowed_snapshot_upsert_and_remove_row_rebalance() {
+        let clock = SystemClock;
+        let mut snap = Snapshot::new_windowed("score".into(), true, 2);
+        snap.init_rows_snapshot(vec![scored(1, 10), scored(2, 30), scored(3, 20)], &None);
+
+        // A WAL upsert that pushes id=3 (score=20) out in favor of a new
+        // higher scorer surfaces both the entering and leaving row.
+        let events = snap.upsert_row(scored(4, 25), &None, &clock);
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().any(|e| e.data.as_deref() == Some(&serde_json::json!({"id": 4, "score": 25}))));
+        assert!(events.iter().any(|e| e.data.as_deref() == Some(&serde_json::json!({"id": 3, "score": 20}))));
+
+        // Removing the current leader (id=2) both drops id=2 itself and
+        // re-admits id=3 (score 20), which the window had pushed out earlier.
+        let events = snap.remove_row(&scored(2, 30), &None, &clock);
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .any(|e| e.data.as_deref() == Some(&serde_json::json!({"id": 2, "score": 30}))));
+        assert!(events
+            .iter()
+            .any(|e| e.data.as_deref() == Some(&serde_json::json!({"id": 3, "score": 20}))));
+    }
+
+    #[test]
+    fn test_upsert_chained_handles_id_hash_collision() {
+        let mut rows: FxHashMap<u64, SmallVec<[RowEntry; 1]>> = FxHashMap::default();
+        let a = RowEntry {
+            id_key: Box::from([RowValue::Int(1)]),
+            hash: 100,
+            data: Arc::new(serde_json::json!({"id": 1})),
+        };
+        let b = RowEntry {
+            id_key: Box::from([RowValue::Int(2)]),
+            hash: 200,
+            data: Arc::new(serde_json::json!({"id": 2})),
+        };
+
+        // Same id_hash despite distinct identities - a genuine collision
+        // that must chain rather than overwrite.
+        assert!(upsert_chained(&mut rows, 42, a.clone()).is_none());
+        assert!(upsert_chained(&mut rows, 42, b.clone()).is_none());
+        assert_eq!(rows.get(&42).unwrap().len(), 2);
+
+        // Updating one identity's entry doesn't disturb the other.
+        let updated = RowEntry {
+            id_key: a.id_key.clone(),
+            hash: 101,
+            data: Arc::new(serde_json::json!({"id": 1, "v": 2})),
+        };
+        let prev = upsert_chained(&mut rows, 42, updated);
+        assert_eq!(prev.unwrap().hash, 100);
+        assert_eq!(rows.get(&42).unwrap().len(), 2);
+
+        // Removing one identity leaves the other intact; removing the last
+        // entry in a bucket drops the bucket entirely.
+        assert!(remove_chained(&mut rows, 42, &a.id_key).is_some());
+        assert_eq!(rows.get(&42).unwrap().len(), 1);
+        assert!(remove_chained(&mut rows, 42, &b.id_key).is_some());
+        assert!(!rows.contains_key(&42));
+    }
+
+    fn id_cols() -> Option<Arc<[Arc<str>]>> {
+        Some(Arc::from(vec![Arc::<str>::from("id")].into_boxed_slice()))
+    }
+
+    #[test]
+    fn test_ordered_diff_pure_move() {
+        let mut snap = OrderedSnapshot::new();
+        snap.init_rows(vec![row(1), row(2), row(3)], &None);
+
+        // Row 3 moved to the front; 1 and 2 keep their relative order.
+        let ops = snap.diff_rows(vec![row(3), row(1), row(2)], &None);
+        assert_eq!(ops.len(), 1);
+        assert!(matches!(
+            ops[0],
+            OrderedDiffOp::Move {
+                from_index: 2,
+                to_index: 0
+            }
+        ));
+    }
+
+    #[test]
+    fn test_ordered_diff_content_change_is_delete_insert_not_move() {
+        let cols = id_cols();
+        let mut snap = OrderedSnapshot::new();
+        snap.init_rows(vec![row(1), row(2)], &cols);
+
+        // Same identity (id=1), different content (new "score" column) -
+        // must surface as delete+insert, never a move.
+        let changed = RowData::from_value(&serde_json::json!({"id": 1, "score": 9}));
+        let ops = snap.diff_rows(vec![changed, row(2)], &cols);
+        assert_eq!(ops.len(), 2);
+        assert!(ops
+            .iter()
+            .any(|op| matches!(op, OrderedDiffOp::Delete { index: 0 })));
+        assert!(ops
+            .iter()
+            .any(|op| matches!(op, OrderedDiffOp::Insert { index: 0, .. })));
+    }
+
+    #[test]
+    fn test_ordered_diff_insert_and_delete() {
+        let mut snap = OrderedSnapshot::new();
+        snap.init_rows(vec![row(1), row(2)], &None);
+
+        let ops = snap.diff_rows(vec![row(1), row(3)], &None);
+        assert_eq!(ops.len(), 2);
+        assert!(ops
+            .iter()
+            .any(|op| matches!(op, OrderedDiffOp::Delete { index: 1 })));
+        assert!(ops
+            .iter()
+            .any(|op| matches!(op, OrderedDiffOp::Insert { index: 1, .. })));
+    }
+
+    #[test]
+    fn test_ordered_diff_unchanged_sequence_is_empty() {
+        let mut snap = OrderedSnapshot::new();
+        snap.init_rows(vec![row(1), row(2), row(3)], &None);
+        assert!(snap.diff_rows(vec![row(1), row(2), row(3)], &None).is_empty());
+    }
+
+    /// `ShardedSnapshot::par_diff_rows` must classify every row exactly the
+    /// way `Snapshot::diff_rows_classified` would, regardless of how many
+    /// shards the rows land in - this is the invariant the struct doc
+    /// comment promises. Compare both as sets since shard scheduling order
+    /// isn't guaranteed to match.
+    #[test]
+    fn test_sharded_diff_matches_snapshot_diff() {
+        let mut sequential = Snapshot::new();
+        sequential.init_rows(vec![scored(1, 10), scored(2, 20), scored(3, 30)], &None);
+        let expected = sequential.diff_rows_classified(
+            vec![scored(2, 99), scored(3, 30), scored(4, 40)],
+            &None,
+        );
+
+        let sharded = ShardedSnapshot::new(4);
+        sharded.par_init_rows(vec![scored(1, 10), scored(2, 20), scored(3, 30)], &None);
+        let actual = sharded.par_diff_rows(
+            vec![scored(2, 99), scored(3, 30), scored(4, 40)],
+            &None,
+        );
+
+        let mut expected_sorted = expected;
+        let mut actual_sorted = actual;
+        expected_sorted.sort_by_key(|(hash, _)| *hash);
+        actual_sorted.sort_by_key(|(hash, _)| *hash);
+        assert_eq!(expected_sorted.len(), actual_sorted.len());
+        for ((eh, eop), (ah, aop)) in expected_sorted.iter().zip(actual_sorted.iter()) {
+            assert_eq!(eh, ah);
+            match (eop, aop) {
+                (PendingOp::Insert(e), PendingOp::Insert(a)) => assert_eq!(e, a),
+                (PendingOp::Update(ep, en), PendingOp::Update(ap, an)) => {
+                    assert_eq!(ep, ap);
+                    assert_eq!(en, an);
+                }
+                (PendingOp::Delete(e), PendingOp::Delete(a)) => assert_eq!(e, a),
+                _ => panic!("op kind mismatch: {eop:?} vs {aop:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_sharded_diff_is_stable_on_unchanged_rows() {
+        let sharded = ShardedSnapshot::new(3);
+        sharded.par_init_rows(vec![scored(1, 10), scored(2, 20)], &None);
+        let ops = sharded.par_diff_rows(vec![scored(1, 10), scored(2, 20)], &None);
+        assert!(ops.is_empty());
+    }
+
+    #[test]
+    fn test_sharded_filter_scan_matches_predicate() {
+        let sharded = ShardedSnapshot::new(4);
+        sharded.par_init_rows(
+            vec![scored(1, 10), scored(2, 20), scored(3, 30)],
+            &None,
         );
+
+        let filter = WhereFilter::Gt {
+            col: "score".into(),
+            val: query::FilterValue::Int(15),
+        };
+        let mut matched: Vec<i64> = sharded
+            .par_filter_scan(&filter)
+            .iter()
+            .map(|r| r.get("id").unwrap().to_value().as_i64().unwrap())
+            .collect();
+        matched.sort();
+        assert_eq!(matched, vec![2, 3]);
+    }
+
+    fn grouped(region: &str, amount: i64) -> RowData {
+        RowData::from_value(&serde_json::json!({"region": region, "amount": amount}))
+    }
+
+    fn grouped_null(region: &str) -> RowData {
+        RowData::from_value(&serde_json::json!({"region": region, "amount": null}))
+    }
+
+    fn min_max_spec() -> Arc<[AggSpec]> {
+        Arc::from(vec![
+            AggSpec {
+                func: AggFunc::Min,
+                col: Some("amount".into()),
+                alias: "min_amount".into(),
+            },
+            AggSpec {
+                func: AggFunc::Max,
+                col: Some("amount".into()),
+                alias: "max_amount".into(),
+            },
+        ])
+    }
+
+    fn count_sum_avg_spec() -> Arc<[AggSpec]> {
+        Arc::from(vec![
+            AggSpec {
+                func: AggFunc::Count,
+                col: None,
+                alias: "count".into(),
+            },
+            AggSpec {
+                func: AggFunc::Sum,
+                col: Some("amount".into()),
+                alias: "sum_amount".into(),
+            },
+            AggSpec {
+                func: AggFunc::Avg,
+                col: Some("amount".into()),
+                alias: "avg_amount".into(),
+            },
+            AggSpec {
+                func: AggFunc::Max,
+                col: Some("amount".into()),
+                alias: "max_amount".into(),
+            },
+        ])
+    }
+
+    fn group_row(rows: &[Arc<serde_json::Value>], region: &str) -> serde_json::Value {
+        rows.iter()
+            .map(|r| r.as_ref().clone())
+            .find(|r| r["region"] == region)
+            .unwrap_or_else(|| panic!("no group row for region {region}"))
+    }
+
+    #[test]
+    fn test_group_snapshot_init_aggregates_by_group() {
+        let mut groups = GroupSnapshot::new(Arc::from(vec!["region".into()]), count_sum_avg_spec());
+        let rows = groups.init_rows(vec![
+            grouped("east", 10),
+            grouped("east", 30),
+            grouped("west", 100),
+        ]);
+
+        let east = group_row(&rows, "east");
+        assert_eq!(east["count"], 2);
+        assert_eq!(east["sum_amount"], 40.0);
+        assert_eq!(east["avg_amount"], 20.0);
+        assert_eq!(east["max_amount"], 30.0);
+
+        let west = group_row(&rows, "west");
+        assert_eq!(west["count"], 1);
+        assert_eq!(west["sum_amount"], 100.0);
+    }
+
+    #[test]
+    fn test_group_snapshot_insert_and_delete_update_incrementally() {
+        let mut groups = GroupSnapshot::new(Arc::from(vec!["region".into()]), count_sum_avg_spec());
+        groups.init_rows(vec![grouped("east", 10)]);
+
+        let change = groups.apply_insert(&grouped("east", 30));
+        match change {
+            GroupChange::Updated(row) => {
+                assert_eq!(row["count"], 2);
+                assert_eq!(row["sum_amount"], 40.0);
+                assert_eq!(row["max_amount"], 30.0);
+            }
+            GroupChange::Removed(_) => panic!("group should still exist"),
+        }
+
+        let change = groups.apply_delete(&grouped("east", 30));
+        match change {
+            Some(GroupChange::Updated(row)) => {
+                assert_eq!(row["count"], 1);
+                assert_eq!(row["sum_amount"], 10.0);
+                assert_eq!(row["max_amount"], 10.0);
+            }
+            other => panic!("expected an updated group, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_group_snapshot_removes_group_when_count_reaches_zero() {
+        let mut groups = GroupSnapshot::new(Arc::from(vec!["region".into()]), count_sum_avg_spec());
+        groups.init_rows(vec![grouped("east", 10)]);
+
+        let change = groups.apply_delete(&grouped("east", 10));
+        match change {
+            Some(GroupChange::Removed(row)) => assert_eq!(row["count"], 0),
+            other => panic!("expected the group to be removed, got {other:?}"),
+        }
+
+        // A second delete for an untracked group is a no-op, not a panic.
+        assert!(groups.apply_delete(&grouped("east", 10)).is_none());
+    }
+
+    #[test]
+    fn test_group_snapshot_update_moves_row_between_groups() {
+        let mut groups = GroupSnapshot::new(Arc::from(vec!["region".into()]), count_sum_avg_spec());
+        groups.init_rows(vec![grouped("east", 10), grouped("west", 5)]);
+
+        let changes = groups.apply_update(&grouped("east", 10), &grouped("west", 10));
+        assert_eq!(changes.len(), 2);
+
+        // `east` loses its only row and is removed.
+        assert!(changes
+            .iter()
+            .any(|c| matches!(c, GroupChange::Removed(row) if row["region"] == "east")));
+        // `west` gains it and its sum reflects both rows.
+        assert!(changes.iter().any(|c| match c {
+            GroupChange::Updated(row) => row["region"] == "west" && row["sum_amount"] == 15.0,
+            GroupChange::Removed(_) => false,
+        }));
+    }
+
+    #[test]
+    fn test_group_snapshot_min_max_ignore_nulls() {
+        // A NULL in the aggregated column must not be treated as the minimum
+        // (Postgres' MIN()/MAX() ignore NULLs, only falling back to NULL when
+        // every row in the group is NULL).
+        let mut groups = GroupSnapshot::new(Arc::from(vec!["region".into()]), min_max_spec());
+        let rows = groups.init_rows(vec![grouped_null("east"), grouped("east", 30), grouped("east", 10)]);
+
+        let east = group_row(&rows, "east");
+        assert_eq!(east["min_amount"], 10.0);
+        assert_eq!(east["max_amount"], 30.0);
+
+        // Deleting the non-NULL minimum falls back to the next real value, not
+        // back to the NULL row still sitting in the group.
+        let change = groups.apply_delete(&grouped("east", 10));
+        match change {
+            Some(GroupChange::Updated(row)) => {
+                assert_eq!(row["min_amount"], 30.0);
+                assert_eq!(row["max_amount"], 30.0);
+            }
+            other => panic!("expected an updated group, got {other:?}"),
+        }
+
+        // Once every row in the group is NULL, MIN/MAX correctly report NULL.
+        let change = groups.apply_delete(&grouped("east", 30));
+        match change {
+            Some(GroupChange::Updated(row)) => {
+                assert!(row["min_amount"].is_null());
+                assert!(row["max_amount"].is_null());
+            }
+            other => panic!("expected an updated group, got {other:?}"),
+        }
     }
 }
@@ -0,0 +1,205 @@
+//! Live-tunable session variables - a Materialize-style SET/SHOW surface
+//! over a subset of `Config` fields, so an operator can change them without
+//! a restart. Each `Var` is backed by process-global atomic/`ArcSwap` state,
+//! seeded once at startup by [`init`] from the loaded `Config`; reads (e.g.
+//! `Config::client_timeout()`/`cleanup_interval()`, consulted on every
+//! cleanup tick) always see the latest value. `max_subscriptions` and
+//! `db_pool_size` only take effect for resources created after the change -
+//! `SubscriptionManager`/`DbPool` are sized once at construction today.
+//! `log_level` is stored and reported by `show`, but the tracing filter is
+//! built once by `telemetry::init` and is not rebuilt when it changes.
+
+use super::Config;
+use anyhow::{anyhow, bail, Result};
+use arc_swap::ArcSwap;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering::Relaxed};
+use std::sync::{Arc, LazyLock};
+
+static CLIENT_TIMEOUT_SECS: AtomicU64 = AtomicU64::new(30);
+static CLEANUP_INTERVAL_SECS: AtomicU64 = AtomicU64::new(10);
+static MAX_SUBSCRIPTIONS: AtomicUsize = AtomicUsize::new(10_000);
+static DB_POOL_SIZE: AtomicU32 = AtomicU32::new(16);
+static LOG_LEVEL: LazyLock<ArcSwap<String>> = LazyLock::new(|| ArcSwap::new(Arc::new("info".into())));
+
+// Read-only vars are seeded the same way as mutable ones so `show`/`show_all`
+// can report them uniformly - they just have no `set` path.
+static DB_URL: LazyLock<ArcSwap<String>> = LazyLock::new(|| ArcSwap::new(Arc::new(String::new())));
+static NATS_URL: LazyLock<ArcSwap<String>> =
+    LazyLock::new(|| ArcSwap::new(Arc::new("nats://localhost:4222".into())));
+static WAL_SLOT: LazyLock<ArcSwap<String>> = LazyLock::new(|| ArcSwap::new(Arc::new("livequery_slot".into())));
+
+/// Seed the live registry from a freshly loaded `Config`. Call once at
+/// startup, mirroring `core::row::init_interner`.
+pub fn init(cfg: &Config) {
+    CLIENT_TIMEOUT_SECS.store(cfg.client_timeout_secs, Relaxed);
+    CLEANUP_INTERVAL_SECS.store(cfg.cleanup_interval_secs, Relaxed);
+    MAX_SUBSCRIPTIONS.store(cfg.max_subscriptions, Relaxed);
+    DB_POOL_SIZE.store(cfg.db_pool_size, Relaxed);
+    LOG_LEVEL.store(Arc::new(cfg.log_level.clone()));
+    DB_URL.store(Arc::new(cfg.db_url.clone()));
+    NATS_URL.store(Arc::new(cfg.nats_url.clone()));
+    WAL_SLOT.store(Arc::new(cfg.wal_slot.clone()));
+}
+
+#[inline]
+pub fn client_timeout_secs() -> u64 {
+    CLIENT_TIMEOUT_SECS.load(Relaxed)
+}
+
+#[inline]
+pub fn cleanup_interval_secs() -> u64 {
+    CLEANUP_INTERVAL_SECS.load(Relaxed)
+}
+
+/// A single `SHOW`-able variable: its current value alongside the
+/// descriptor's static metadata, per `Config::show`/`Config::show_all`.
+#[derive(Debug, Clone)]
+pub struct VarValue {
+    pub name: &'static str,
+    pub current: String,
+    pub default: &'static str,
+    pub description: &'static str,
+}
+
+struct Var {
+    name: &'static str,
+    description: &'static str,
+    default: &'static str,
+    mutable: bool,
+    get: fn() -> String,
+    set: fn(&str) -> Result<()>,
+}
+
+fn reject_immutable(name: &str) -> Result<()> {
+    bail!("'{name}' is not a mutable variable")
+}
+
+static VARS: &[Var] = &[
+    Var {
+        name: "client_timeout_secs",
+        description: "Seconds of inactivity before a client subscription is reaped",
+        default: "30",
+        mutable: true,
+        get: || CLIENT_TIMEOUT_SECS.load(Relaxed).to_string(),
+        set: |v| {
+            let parsed: u64 = v.parse().map_err(|_| anyhow!("expected an integer"))?;
+            Config::validate_client_timeout_secs(parsed)?;
+            CLIENT_TIMEOUT_SECS.store(parsed, Relaxed);
+            Ok(())
+        },
+    },
+    Var {
+        name: "cleanup_interval_secs",
+        description: "How often the reaper sweeps for timed-out subscriptions",
+        default: "10",
+        mutable: true,
+        get: || CLEANUP_INTERVAL_SECS.load(Relaxed).to_string(),
+        set: |v| {
+            let parsed: u64 = v.parse().map_err(|_| anyhow!("expected an integer"))?;
+            Config::validate_cleanup_interval_secs(parsed)?;
+            CLEANUP_INTERVAL_SECS.store(parsed, Relaxed);
+            Ok(())
+        },
+    },
+    Var {
+        name: "max_subscriptions",
+        description: "Cap on concurrently live subscriptions",
+        default: "10000",
+        mutable: true,
+        get: || MAX_SUBSCRIPTIONS.load(Relaxed).to_string(),
+        set: |v| {
+            let parsed: usize = v.parse().map_err(|_| anyhow!("expected an integer"))?;
+            Config::validate_max_subscriptions(parsed)?;
+            MAX_SUBSCRIPTIONS.store(parsed, Relaxed);
+            Ok(())
+        },
+    },
+    Var {
+        name: "log_level",
+        description: "tracing/log filter directive",
+        default: "info",
+        mutable: true,
+        get: || LOG_LEVEL.load().as_str().to_string(),
+        set: |v| {
+            LOG_LEVEL.store(Arc::new(v.to_string()));
+            Ok(())
+        },
+    },
+    Var {
+        name: "db_pool_size",
+        description: "Database connection pool size",
+        default: "16",
+        mutable: true,
+        get: || DB_POOL_SIZE.load(Relaxed).to_string(),
+        set: |v| {
+            let parsed: u32 = v.parse().map_err(|_| anyhow!("expected an integer"))?;
+            Config::validate_db_pool_size(parsed)?;
+            DB_POOL_SIZE.store(parsed, Relaxed);
+            Ok(())
+        },
+    },
+    Var {
+        name: "db_url",
+        description: "Database connection string (fixed at startup)",
+        default: "",
+        mutable: false,
+        get: || Config::mask_url(DB_URL.load().as_str()),
+        set: |_| reject_immutable("db_url"),
+    },
+    Var {
+        name: "nats_url",
+        description: "NATS server URL (fixed at startup)",
+        default: "nats://localhost:4222",
+        mutable: false,
+        get: || NATS_URL.load().as_str().to_string(),
+        set: |_| reject_immutable("nats_url"),
+    },
+    Var {
+        name: "wal_slot",
+        description: "Logical replication slot name (fixed at startup)",
+        default: "livequery_slot",
+        mutable: false,
+        get: || WAL_SLOT.load().as_str().to_string(),
+        set: |_| reject_immutable("wal_slot"),
+    },
+];
+
+fn find(name: &str) -> Result<&'static Var> {
+    VARS.iter()
+        .find(|v| v.name == name)
+        .ok_or_else(|| anyhow!("unknown variable '{name}'"))
+}
+
+/// Parse and apply `value` to the named variable, re-running the relevant
+/// `Config::validate` check before swapping it in. Rejects unknown and
+/// non-mutable variables with a clear error.
+pub fn set(name: &str, value: &str) -> Result<()> {
+    let var = find(name)?;
+    if !var.mutable {
+        return reject_immutable(name);
+    }
+    (var.set)(value)
+}
+
+/// Current value, default and description for one variable.
+pub fn show(name: &str) -> Result<VarValue> {
+    let var = find(name)?;
+    Ok(VarValue {
+        name: var.name,
+        current: (var.get)(),
+        default: var.default,
+        description: var.description,
+    })
+}
+
+/// Current value, default and description for every known variable.
+pub fn show_all() -> Vec<VarValue> {
+    VARS.iter()
+        .map(|var| VarValue {
+            name: var.name,
+            current: (var.get)(),
+            default: var.default,
+            description: var.description,
+        })
+        .collect()
+}
@@ -0,0 +1,437 @@
+//! Arrow IPC columnar encoding for `WireFormat::Arrow` - an alternative to
+//! `EventBatch::encode_binary` for clients that want to decode batches with
+//! an off-the-shelf Arrow reader instead of the bespoke length-prefixed
+//! framing. Like `encode_binary`, this works off each event's
+//! already-materialized JSON `data` (the protocol never carries `RowData`
+//! past `core::row` into the event stream), so column types are inferred
+//! from `serde_json::Value` rather than `RowValue`.
+//!
+//! Every row maps onto one `RecordBatch` with a `mz_timestamp: Int64` and
+//! `mz_diff: Int8` column (mirroring `SubscribeEvent`'s own fields) followed
+//! by one column per entry in the query's fixed SELECT list, dictionary-
+//! encoding string columns to avoid repeating interned values per row.
+
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, BooleanBuilder, Float64Builder, Int8Array, Int64Array, StringDictionaryBuilder,
+    TimestampMicrosecondArray,
+};
+use arrow::datatypes::{DataType, Field, Int32Type, Schema, TimeUnit};
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatch;
+use serde_json::Value;
+
+use crate::core::event::{EventBatch, SubscribeEvent};
+use crate::core::row::{RowData, RowValue};
+
+/// Arrow type a column is encoded as, inferred from the first non-null value
+/// seen for it across the batch. A column with no non-null value anywhere in
+/// the batch defaults to `Utf8` (an all-null dictionary column).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColKind {
+    Int64,
+    Float64,
+    Boolean,
+    Utf8,
+}
+
+impl ColKind {
+    fn of(v: &Value) -> Self {
+        match v {
+            Value::Bool(_) => ColKind::Boolean,
+            Value::Number(n) if n.is_i64() || n.is_u64() => ColKind::Int64,
+            Value::Number(_) => ColKind::Float64,
+            _ => ColKind::Utf8,
+        }
+    }
+
+    fn data_type(self) -> DataType {
+        match self {
+            ColKind::Int64 => DataType::Int64,
+            ColKind::Float64 => DataType::Float64,
+            ColKind::Boolean => DataType::Boolean,
+            ColKind::Utf8 => DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+        }
+    }
+}
+
+/// Pull column `col`'s value out of an event's `data`, if it's a JSON object
+/// carrying that key - same shape check `EventBatch::encode_event_value`
+/// uses for its per-event fallback. `gone` events and rows that don't fit
+/// the expected object shape contribute a null to every column rather than
+/// being dropped, so every column in the batch stays the same length.
+fn cell<'a>(event: &'a SubscribeEvent, col: &str) -> Option<&'a Value> {
+    match event.data.as_deref() {
+        Some(Value::Object(map)) => map.get(col).filter(|v| !v.is_null()),
+        _ => None,
+    }
+}
+
+fn infer_kind(events: &[SubscribeEvent], col: &str) -> ColKind {
+    events
+        .iter()
+        .find_map(|e| cell(e, col).map(ColKind::of))
+        .unwrap_or(ColKind::Utf8)
+}
+
+fn build_column(events: &[SubscribeEvent], col: &str, kind: ColKind) -> ArrayRef {
+    match kind {
+        ColKind::Int64 => {
+            let values: Vec<Option<i64>> = events.iter().map(|e| cell(e, col).and_then(Value::as_i64)).collect();
+            Arc::new(Int64Array::from(values))
+        }
+        ColKind::Float64 => {
+            let values: Vec<Option<f64>> = events.iter().map(|e| cell(e, col).and_then(Value::as_f64)).collect();
+            Arc::new(arrow::array::Float64Array::from(values))
+        }
+        ColKind::Boolean => {
+            let mut b = BooleanBuilder::with_capacity(events.len());
+            for e in events {
+                b.append_option(cell(e, col).and_then(Value::as_bool));
+            }
+            Arc::new(b.finish())
+        }
+        ColKind::Utf8 => {
+            let mut b = StringDictionaryBuilder::<Int32Type>::new();
+            for e in events {
+                match cell(e, col) {
+                    Some(Value::String(s)) => b.append_value(s),
+                    Some(other) => b.append_value(other.to_string()),
+                    None => b.append_null(),
+                }
+            }
+            Arc::new(b.finish())
+        }
+    }
+}
+
+/// Build the `RecordBatch` for `batch`'s events against the fixed column
+/// list `cols`. Never fails - an event whose data doesn't fit a column
+/// becomes a null in that column, same tolerance `encode_event_value` has
+/// for the binary framing.
+fn to_record_batch(batch: &EventBatch, cols: &[Arc<str>]) -> RecordBatch {
+    let mz_timestamp = Arc::new(Int64Array::from(
+        batch.events.iter().map(|e| e.mz_timestamp).collect::<Vec<_>>(),
+    )) as ArrayRef;
+    let mz_diff = Arc::new(Int8Array::from(
+        batch.events.iter().map(|e| e.mz_diff).collect::<Vec<_>>(),
+    )) as ArrayRef;
+
+    let mut fields = vec![
+        Field::new("mz_timestamp", DataType::Int64, false),
+        Field::new("mz_diff", DataType::Int8, false),
+    ];
+    let mut arrays = vec![mz_timestamp, mz_diff];
+    for col in cols {
+        let kind = infer_kind(&batch.events, col);
+        fields.push(Field::new(col.as_ref(), kind.data_type(), true));
+        arrays.push(build_column(&batch.events, col, kind));
+    }
+
+    let schema = Arc::new(Schema::new(fields));
+    RecordBatch::try_new(schema, arrays).expect("column lengths match events.len() by construction")
+}
+
+/// Serialize `record_batch` as a single-`RecordBatch` Arrow IPC stream.
+/// Shared by `encode_batch_arrow` (JSON-inferred columns) and
+/// `encode_rows_arrow` (`RowValue`-inferred columns) - both end up with a
+/// `RecordBatch` and differ only in how they built it.
+fn write_ipc_stream(record_batch: &RecordBatch) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let result = (|| -> arrow::error::Result<()> {
+        let mut writer = StreamWriter::try_new(&mut buf, &record_batch.schema())?;
+        writer.write(record_batch)?;
+        writer.finish()
+    })();
+    match result {
+        Ok(()) => buf,
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Encode `batch` as a single-`RecordBatch` Arrow IPC stream over `cols`.
+/// See `EventBatch::encode_arrow` / `WireFormat::Arrow`.
+pub fn encode_batch_arrow(batch: &EventBatch, cols: &[Arc<str>]) -> Vec<u8> {
+    write_ipc_stream(&to_record_batch(batch, cols))
+}
+
+/// Decode an Arrow IPC stream produced by `encode_batch_arrow` back into its
+/// `RecordBatch`es, for round-trip verification. Not used on any publish
+/// path - clients read the stream with their own Arrow bindings.
+pub fn decode_arrow(bytes: &[u8]) -> Vec<RecordBatch> {
+    let Ok(reader) = StreamReader::try_new(std::io::Cursor::new(bytes), None) else {
+        return Vec::new();
+    };
+    reader.filter_map(Result::ok).collect()
+}
+
+// === RowData export ===
+//
+// `encode_batch_arrow` above infers column types from `SubscribeEvent`'s
+// already-JSON-flattened `data`, because that's all the event stream ever
+// carries. Callers sitting closer to `core::row` - e.g. `wal_stream`'s
+// incremental apply, which has `RowData` on hand before it's flattened to
+// events - can skip that round-trip and encode `RowValue`'s own variants
+// directly, so an `Int` stays `Int64` instead of going through
+// `serde_json::Number` first.
+
+/// Arrow type for one `RowValue`-typed column, inferred from the first
+/// non-null, non-`Unchanged` value seen for it across the rows - mirrors
+/// `ColKind` but dispatches on `RowValue`'s variants. Anything other than
+/// `Int`/`Float`/`Bool`/`Timestamp` (`Str`, `Numeric`, `Uuid`, `Json`,
+/// `Bytes`, `Array`) falls back to dictionary-encoded `Utf8`, same tolerance
+/// `ColKind::Utf8` has for JSON values it can't otherwise type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RowColKind {
+    Int64,
+    Float64,
+    Boolean,
+    Timestamp,
+    Utf8,
+}
+
+impl RowColKind {
+    fn of(v: &RowValue) -> Self {
+        match v {
+            RowValue::Int(_) => RowColKind::Int64,
+            RowValue::Float(_) => RowColKind::Float64,
+            RowValue::Bool(_) => RowColKind::Boolean,
+            RowValue::Timestamp(_) => RowColKind::Timestamp,
+            _ => RowColKind::Utf8,
+        }
+    }
+
+    fn data_type(self) -> DataType {
+        match self {
+            RowColKind::Int64 => DataType::Int64,
+            RowColKind::Float64 => DataType::Float64,
+            RowColKind::Boolean => DataType::Boolean,
+            RowColKind::Timestamp => DataType::Timestamp(TimeUnit::Microsecond, None),
+            RowColKind::Utf8 => {
+                DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8))
+            }
+        }
+    }
+}
+
+/// `row.get(col)`, treating both `Null` and `Unchanged` as absent - a column
+/// pgoutput left as unchanged TOAST has no value worth encoding any more
+/// than a genuine SQL null does.
+fn row_cell<'a>(row: &'a RowData, col: &str) -> Option<&'a RowValue> {
+    match row.get(col) {
+        Some(RowValue::Null) | Some(RowValue::Unchanged) | None => None,
+        some => some,
+    }
+}
+
+fn infer_row_kind(rows: &[RowData], col: &str) -> RowColKind {
+    rows.iter()
+        .find_map(|r| row_cell(r, col).map(RowColKind::of))
+        .unwrap_or(RowColKind::Utf8)
+}
+
+fn build_row_column(rows: &[RowData], col: &str, kind: RowColKind) -> ArrayRef {
+    match kind {
+        RowColKind::Int64 => {
+            let values: Vec<Option<i64>> = rows
+                .iter()
+                .map(|r| match row_cell(r, col) {
+                    Some(RowValue::Int(i)) => Some(*i),
+                    _ => None,
+                })
+                .collect();
+            Arc::new(Int64Array::from(values))
+        }
+        RowColKind::Float64 => {
+            let values: Vec<Option<f64>> = rows
+                .iter()
+                .map(|r| match row_cell(r, col) {
+                    Some(RowValue::Float(f)) => Some(*f),
+                    _ => None,
+                })
+                .collect();
+            Arc::new(arrow::array::Float64Array::from(values))
+        }
+        RowColKind::Boolean => {
+            let mut b = BooleanBuilder::with_capacity(rows.len());
+            for r in rows {
+                b.append_option(match row_cell(r, col) {
+                    Some(RowValue::Bool(v)) => Some(*v),
+                    _ => None,
+                });
+            }
+            Arc::new(b.finish())
+        }
+        RowColKind::Timestamp => {
+            let values: Vec<Option<i64>> = rows
+                .iter()
+                .map(|r| match row_cell(r, col) {
+                    Some(RowValue::Timestamp(ts)) => Some(*ts),
+                    _ => None,
+                })
+                .collect();
+            Arc::new(TimestampMicrosecondArray::from(values))
+        }
+        RowColKind::Utf8 => {
+            // `Str` values reuse whatever `Arc<str>` the interner handed
+            // back (see `RowValue::intern_str`), so rows sharing an interned
+            // value dedupe onto the same dictionary key for free.
+            let mut b = StringDictionaryBuilder::<Int32Type>::new();
+            for r in rows {
+                match row_cell(r, col) {
+                    Some(RowValue::Str(s)) => b.append_value(s.as_ref()),
+                    Some(other) => b.append_value(other.to_value().to_string()),
+                    None => b.append_null(),
+                }
+            }
+            Arc::new(b.finish())
+        }
+    }
+}
+
+/// Build the `RecordBatch` for `rows` against the fixed column list `cols`.
+/// Never fails - a row missing a column, or holding `Null`/`Unchanged` for
+/// it, becomes a null in that column, same tolerance `to_record_batch` has.
+fn rows_to_record_batch(rows: &[RowData], cols: &[Arc<str>]) -> RecordBatch {
+    let mut fields = Vec::with_capacity(cols.len());
+    let mut arrays = Vec::with_capacity(cols.len());
+    for col in cols {
+        let kind = infer_row_kind(rows, col);
+        fields.push(Field::new(col.as_ref(), kind.data_type(), true));
+        arrays.push(build_row_column(rows, col, kind));
+    }
+    let schema = Arc::new(Schema::new(fields));
+    RecordBatch::try_new(schema, arrays).expect("column lengths match rows.len() by construction")
+}
+
+/// Encode `rows` as a single-`RecordBatch` Arrow IPC stream over `cols`,
+/// typed straight from `RowValue` rather than through JSON - see the
+/// `RowData export` section above.
+pub fn encode_rows_arrow(rows: &[RowData], cols: &[Arc<str>]) -> Vec<u8> {
+    write_ipc_stream(&rows_to_record_batch(rows, cols))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::event::TestClock;
+
+    fn event(clock: &TestClock, diff: i8, data: Value) -> SubscribeEvent {
+        let t = clock.now_millis() as i64;
+        SubscribeEvent {
+            mz_timestamp: t,
+            mz_diff: diff,
+            data: Some(Arc::new(data)),
+            gone: None,
+        }
+    }
+
+    #[test]
+    fn round_trips_mixed_column_types() {
+        let clock = TestClock::new(1000);
+        let cols: Vec<Arc<str>> = vec![Arc::from("id"), Arc::from("name"), Arc::from("active")];
+        let events = vec![
+            event(
+                &clock,
+                1,
+                serde_json::json!({"id": 1, "name": "alice", "active": true}),
+            ),
+            event(
+                &clock,
+                1,
+                serde_json::json!({"id": 2, "name": "bob", "active": false}),
+            ),
+            event(&clock, -1, serde_json::json!({"id": 1, "name": null, "active": true})),
+        ];
+        let batch = EventBatch::new(7, events, &clock);
+
+        let bytes = encode_batch_arrow(&batch, &cols);
+        assert!(!bytes.is_empty());
+
+        let batches = decode_arrow(&bytes);
+        assert_eq!(batches.len(), 1);
+        let rb = &batches[0];
+        assert_eq!(rb.num_rows(), 3);
+        assert_eq!(rb.num_columns(), 5); // mz_timestamp, mz_diff, id, name, active
+
+        let ids = rb
+            .column_by_name("id")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(ids.value(0), 1);
+        assert_eq!(ids.value(1), 2);
+        assert_eq!(ids.value(2), 1);
+
+        let names = rb
+            .column_by_name("name")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<arrow::array::DictionaryArray<Int32Type>>()
+            .unwrap();
+        assert!(names.is_null(2));
+    }
+
+    #[test]
+    fn empty_batch_still_encodes() {
+        let clock = TestClock::new(0);
+        let cols: Vec<Arc<str>> = vec![Arc::from("id")];
+        let batch = EventBatch::new(1, Vec::new(), &clock);
+        let bytes = encode_batch_arrow(&batch, &cols);
+        let batches = decode_arrow(&bytes);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].num_rows(), 0);
+    }
+
+    fn row(id: i64, name: &str, active: bool) -> RowData {
+        let cols: Arc<[Arc<str>]> = Arc::from(vec![
+            Arc::from("id"),
+            Arc::from("name"),
+            Arc::from("active"),
+        ]);
+        RowData::new(
+            cols,
+            vec![
+                RowValue::Int(id),
+                RowValue::intern_str(name),
+                RowValue::Bool(active),
+            ],
+        )
+    }
+
+    #[test]
+    fn encode_rows_arrow_types_straight_from_row_value() {
+        let cols: Vec<Arc<str>> = vec![Arc::from("id"), Arc::from("name"), Arc::from("active")];
+        let rows = vec![row(1, "alice", true), row(2, "bob", false)];
+
+        let bytes = encode_rows_arrow(&rows, &cols);
+        assert!(!bytes.is_empty());
+
+        let batches = decode_arrow(&bytes);
+        assert_eq!(batches.len(), 1);
+        let rb = &batches[0];
+        assert_eq!(rb.num_rows(), 2);
+        assert_eq!(rb.num_columns(), 3);
+
+        let ids = rb
+            .column_by_name("id")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(ids.value(0), 1);
+        assert_eq!(ids.value(1), 2);
+
+        let names = rb
+            .column_by_name("name")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<arrow::array::DictionaryArray<Int32Type>>()
+            .unwrap();
+        assert!(!names.is_null(0));
+    }
+
+}
@@ -2,11 +2,15 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::json_patch::PatchOp;
+use crate::sqlstate::SqlState;
+
 /// A single event in the subscription stream
-/// 
+///
 /// Following Materialize semantics:
 /// - `mz_timestamp`: Logical timestamp
-/// - `mz_diff`: +1 for insert, -1 for delete
+/// - `mz_diff`: +1 for insert, -1 for delete, `2` for a JSON Patch `update`
+///   (opt-in - see `SubscribeEvent::update`)
 /// - `mz_progressed`: Heartbeat flag
 /// - `data`: Row data as JSON
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,6 +18,19 @@ pub struct SubscribeEvent {
     pub mz_timestamp: i64,
     pub mz_diff: i32,
     pub mz_progressed: bool,
+    /// SQLSTATE of a failed requery, set only on `error` events (`mz_diff ==
+    /// 0`, `mz_progressed == false`) so subscribers can distinguish them
+    /// from heartbeats and decide whether to retry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mz_errcode: Option<String>,
+    /// Global, cross-backend monotonic counter stamped by `shmem::push_event`
+    /// (see `shmem::WRITE_VERSION_COUNTER`) - unlike `mz_timestamp`, which two
+    /// concurrent backends can emit the same value for, this is a strict
+    /// total order. Zero until an event has actually passed through shmem;
+    /// a client comparing consecutive non-zero values can detect a gap and
+    /// compute exactly how many events it missed.
+    #[serde(default)]
+    pub mz_write_version: u64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<serde_json::Value>,
 }
@@ -21,19 +38,69 @@ pub struct SubscribeEvent {
 impl SubscribeEvent {
     /// Create INSERT event (+1)
     pub fn insert(timestamp: i64, data: serde_json::Value) -> Self {
-        Self { mz_timestamp: timestamp, mz_diff: 1, mz_progressed: false, data: Some(data) }
+        Self { mz_timestamp: timestamp, mz_diff: 1, mz_progressed: false, mz_errcode: None, mz_write_version: 0, data: Some(data) }
     }
-    
+
     /// Create DELETE event (-1)
     pub fn delete(timestamp: i64, data: serde_json::Value) -> Self {
-        Self { mz_timestamp: timestamp, mz_diff: -1, mz_progressed: false, data: Some(data) }
+        Self { mz_timestamp: timestamp, mz_diff: -1, mz_progressed: false, mz_errcode: None, mz_write_version: 0, data: Some(data) }
     }
-    
+
     /// Create progress/heartbeat event
     pub fn progress(timestamp: i64) -> Self {
-        Self { mz_timestamp: timestamp, mz_diff: 0, mz_progressed: true, data: None }
+        Self { mz_timestamp: timestamp, mz_diff: 0, mz_progressed: true, mz_errcode: None, mz_write_version: 0, data: None }
     }
-    
+
+    /// Create an UPDATE event carrying an RFC 6902 JSON Patch from the old
+    /// row to the new one, instead of a delete+insert pair - opt-in via
+    /// `subscribe(..., patch_updates => true)`. `mz_diff` is set to the `2`
+    /// sentinel so existing `-1`/`+1` consumers that don't know about this
+    /// mode keep working; `data` carries `{"identity", "patch"}` so the
+    /// client can apply it to the row it already has cached under `identity`.
+    pub fn update(timestamp: i64, identity: impl Into<String>, patch: Vec<PatchOp>) -> Self {
+        Self {
+            mz_timestamp: timestamp,
+            mz_diff: 2,
+            mz_progressed: false,
+            mz_errcode: None,
+            mz_write_version: 0,
+            data: Some(serde_json::json!({ "identity": identity.into(), "patch": patch })),
+        }
+    }
+
+    /// Create an UPDATE event carrying the full old and new row as JSON,
+    /// instead of a delete+insert pair or an RFC 6902 patch - opt-in via
+    /// `subscribe(..., full_updates => true)`. Cheaper for the client to
+    /// apply than a patch when it already wants the whole new row anyway,
+    /// at the cost of a larger payload for wide rows that only changed one
+    /// field. `mz_diff` reuses the `2` update sentinel so existing
+    /// delete/insert consumers keep working; `data` carries `{"identity",
+    /// "old", "new"}`.
+    pub fn update_full(timestamp: i64, identity: impl Into<String>, old: serde_json::Value, new: serde_json::Value) -> Self {
+        Self {
+            mz_timestamp: timestamp,
+            mz_diff: 2,
+            mz_progressed: false,
+            mz_errcode: None,
+            mz_write_version: 0,
+            data: Some(serde_json::json!({ "identity": identity.into(), "old": old, "new": new })),
+        }
+    }
+
+    /// Create an error event for a requery that failed with `state`,
+    /// instead of aborting the whole subscription (see
+    /// `crate::unified_subscribe::Snapshot::execute_and_diff`).
+    pub fn error(timestamp: i64, state: SqlState, message: impl Into<String>) -> Self {
+        Self {
+            mz_timestamp: timestamp,
+            mz_diff: 0,
+            mz_progressed: false,
+            mz_errcode: Some(state.code().to_string()),
+            mz_write_version: 0,
+            data: Some(serde_json::json!({ "message": message.into() })),
+        }
+    }
+
     /// Get data as JsonB for PostgreSQL return
     pub fn data_as_jsonb(&self) -> pgrx::JsonB {
         pgrx::JsonB(self.data.clone().unwrap_or(serde_json::json!({})))
@@ -58,4 +125,38 @@ mod tests {
         assert!(progress.mz_progressed);
         assert!(progress.data.is_none());
     }
+
+    #[test]
+    fn test_error_event() {
+        let err = SubscribeEvent::error(100, crate::sqlstate::SqlState::SyntaxError, "bad query");
+        assert_eq!(err.mz_diff, 0);
+        assert!(!err.mz_progressed);
+        assert_eq!(err.mz_errcode.as_deref(), Some("42601"));
+        assert_eq!(err.data.unwrap()["message"], "bad query");
+    }
+
+    #[test]
+    fn test_update_event() {
+        let old = serde_json::json!({"id": 1, "status": "open"});
+        let new = serde_json::json!({"id": 1, "status": "closed"});
+        let patch = crate::json_patch::diff(old.as_object().unwrap(), new.as_object().unwrap());
+        let update = SubscribeEvent::update(100, "1", patch);
+        assert_eq!(update.mz_diff, 2);
+        assert!(!update.mz_progressed);
+        let data = update.data.unwrap();
+        assert_eq!(data["identity"], "1");
+        assert_eq!(data["patch"][0]["op"], "replace");
+    }
+
+    #[test]
+    fn test_update_full_event() {
+        let old = serde_json::json!({"id": 1, "status": "open"});
+        let new = serde_json::json!({"id": 1, "status": "closed"});
+        let update = SubscribeEvent::update_full(100, "1", old.clone(), new.clone());
+        assert_eq!(update.mz_diff, 2);
+        let data = update.data.unwrap();
+        assert_eq!(data["identity"], "1");
+        assert_eq!(data["old"], old);
+        assert_eq!(data["new"], new);
+    }
 }
@@ -0,0 +1,336 @@
+//! Structured filter spec compiled into a canonical `SELECT`, giving
+//! `subscribe_filter` clients a declarative alternative to hand-writing SQL
+//! while flowing through the exact same `query_analyzer`/`query_dedup`/
+//! `unified_subscribe::Snapshot` path as `subscribe` - see `compile_select`.
+
+use serde::Deserialize;
+use serde_json::Value;
+
+/// One AND'd group of column predicates: `{"status": ["open", "pending"],
+/// "owner_id": 7}` means `status IN ('open', 'pending') AND owner_id = 7`.
+/// An array of groups at the call site is OR'd together - see `compile_select`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct FilterGroup(std::collections::BTreeMap<String, Value>);
+
+/// `since`/`until` bounds on a timestamp column, plus a row `limit` -
+/// layered onto the OR'd filter groups as an additional AND'd clause.
+#[derive(Debug, Clone, Default)]
+pub struct FilterBounds {
+    pub since_column: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Double-quote a (possibly schema-qualified) identifier, escaping embedded
+/// quotes so a column/table name can't break out of the identifier.
+pub(crate) fn quote_ident(ident: &str) -> String {
+    ident
+        .split('.')
+        .map(|part| format!("\"{}\"", part.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Render a JSON scalar as a SQL literal. Arrays/objects aren't valid
+/// filter values (use a JSON array to OR multiple scalars - see
+/// `compile_column`), so they're rejected rather than silently stringified.
+pub(crate) fn quote_literal(value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(format!("'{}'", s.replace('\'', "''"))),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        Value::Null => Ok("NULL".to_string()),
+        Value::Array(_) | Value::Object(_) => Err(format!("Unsupported filter value: {value}")),
+    }
+}
+
+/// `col = value`, or `col IN (v1, v2, ...)` when `value` is a JSON array -
+/// the OR-within-a-column half of `FilterGroup`'s semantics.
+fn compile_column(column: &str, value: &Value) -> Result<String, String> {
+    match value {
+        Value::Array(values) => {
+            if values.is_empty() {
+                // No value can ever satisfy an empty allow-list.
+                return Ok("FALSE".to_string());
+            }
+            let literals: Result<Vec<String>, String> = values.iter().map(quote_literal).collect();
+            Ok(format!("{} IN ({})", quote_ident(column), literals?.join(", ")))
+        }
+        other => Ok(format!("{} = {}", quote_ident(column), quote_literal(other)?)),
+    }
+}
+
+impl FilterGroup {
+    fn compile(&self) -> Result<String, String> {
+        if self.0.is_empty() {
+            return Ok("TRUE".to_string());
+        }
+        let clauses: Result<Vec<String>, String> =
+            self.0.iter().map(|(col, val)| compile_column(col, val)).collect();
+        Ok(format!("({})", clauses?.join(" AND ")))
+    }
+}
+
+/// Parse `filters` into the OR'd `FilterGroup`s `compile_select` expects -
+/// either a single filter object or a JSON array of them.
+pub fn parse_groups(value: &Value) -> Result<Vec<FilterGroup>, String> {
+    match value {
+        Value::Array(_) => {
+            serde_json::from_value(value.clone()).map_err(|e| format!("Invalid filters: {e}"))
+        }
+        Value::Object(_) => {
+            let group: FilterGroup =
+                serde_json::from_value(value.clone()).map_err(|e| format!("Invalid filters: {e}"))?;
+            Ok(vec![group])
+        }
+        _ => Err("filters must be a JSON object or an array of objects".to_string()),
+    }
+}
+
+/// Compile `table` + OR'd `groups` + `bounds` into a canonical
+/// `SELECT * FROM table WHERE (...) OR (...) [AND since/until] [LIMIT n]`,
+/// so the result can be handed to `query_analyzer::analyze_query` exactly
+/// like a hand-written query.
+pub fn compile_select(table: &str, groups: &[FilterGroup], bounds: &FilterBounds) -> Result<String, String> {
+    let mut sql = format!("SELECT * FROM {}", quote_ident(table));
+    let mut where_clauses = Vec::new();
+
+    if !groups.is_empty() {
+        let compiled = groups.iter().map(FilterGroup::compile).collect::<Result<Vec<String>, String>>()?;
+        // A single group is already parenthesized by `FilterGroup::compile`.
+        if compiled.len() == 1 {
+            where_clauses.push(compiled.into_iter().next().unwrap_or_default());
+        } else {
+            where_clauses.push(format!("({})", compiled.join(" OR ")));
+        }
+    }
+
+    if let Some(col) = &bounds.since_column {
+        if let Some(since) = &bounds.since {
+            where_clauses.push(format!(
+                "{} >= {}",
+                quote_ident(col),
+                quote_literal(&Value::String(since.clone()))?
+            ));
+        }
+        if let Some(until) = &bounds.until {
+            where_clauses.push(format!(
+                "{} <= {}",
+                quote_ident(col),
+                quote_literal(&Value::String(until.clone()))?
+            ));
+        }
+    }
+
+    if !where_clauses.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&where_clauses.join(" AND "));
+    }
+
+    if let Some(limit) = bounds.limit {
+        if limit < 0 {
+            return Err("limit must be non-negative".to_string());
+        }
+        sql.push_str(&format!(" LIMIT {limit}"));
+    }
+
+    Ok(sql)
+}
+
+/// Wrap `query` in a keyset-paginated outer `SELECT`, ordered by
+/// `identity_columns`, resuming strictly after `after` (the previous page's
+/// last row's identity tuple, in the same column order) when given - see
+/// `crate::unified_subscribe::execute_snapshot_page`.
+pub fn compile_snapshot_page(
+    query: &str,
+    identity_columns: &[String],
+    after: Option<&[Value]>,
+    page_size: i64,
+) -> Result<String, String> {
+    if identity_columns.is_empty() {
+        return Err("identity_columns is required for cursor pagination".to_string());
+    }
+    if page_size <= 0 {
+        return Err("page_size must be positive".to_string());
+    }
+
+    let order_cols: Vec<String> = identity_columns.iter().map(|c| quote_ident(c)).collect();
+    let mut sql = format!("SELECT * FROM ({query}) AS pg_subscribe_cursor_page");
+
+    if let Some(after_vals) = after {
+        if after_vals.len() != identity_columns.len() {
+            return Err("after must have one value per identity column".to_string());
+        }
+        let literals: Result<Vec<String>, String> = after_vals.iter().map(quote_literal).collect();
+        sql.push_str(&format!(" WHERE ({}) > ({})", order_cols.join(", "), literals?.join(", ")));
+    }
+
+    sql.push_str(&format!(" ORDER BY {} LIMIT {page_size}", order_cols.join(", ")));
+    Ok(sql)
+}
+
+/// Build `SELECT (<predicate>) FROM (SELECT ... AS col, ...) AS _visibility_row`,
+/// evaluating `predicate` against `row`'s scalar columns as if they were a
+/// real table row - lets `unified_subscribe::row_visible` re-check whether a
+/// role's `visibility_predicate` still allows a row through per-event,
+/// without baking the predicate into the shared, dedup'd query text the way
+/// `compile_select`'s bounds are (different roles sharing one subscription
+/// slot may have different visibility). Non-scalar columns (nested
+/// JSON/arrays) are left out of the synthetic row; a predicate that
+/// references one fails to parse, which `row_visible` reports as invisible
+/// (fail closed).
+pub fn compile_visibility_check(predicate: &str, row: &Value) -> Result<String, String> {
+    let Value::Object(fields) = row else {
+        return Err("visibility_predicate requires an object row".to_string());
+    };
+
+    let columns: Result<Vec<String>, String> = fields
+        .iter()
+        .filter(|(_, v)| !matches!(v, Value::Array(_) | Value::Object(_)))
+        .map(|(col, val)| Ok(format!("{} AS {}", quote_literal(val)?, quote_ident(col))))
+        .collect();
+
+    Ok(format!("SELECT ({predicate}) FROM (SELECT {}) AS _visibility_row", columns?.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_group_ands_columns() {
+        let groups = parse_groups(&serde_json::json!({"status": "open", "owner_id": 7})).unwrap();
+        let sql = compile_select("orders", &groups, &FilterBounds::default()).unwrap();
+        assert_eq!(
+            sql,
+            "SELECT * FROM \"orders\" WHERE (\"owner_id\" = 7 AND \"status\" = 'open')"
+        );
+    }
+
+    #[test]
+    fn test_array_value_ors_within_column() {
+        let groups = parse_groups(&serde_json::json!({"status": ["open", "pending"]})).unwrap();
+        let sql = compile_select("orders", &groups, &FilterBounds::default()).unwrap();
+        assert_eq!(sql, "SELECT * FROM \"orders\" WHERE (\"status\" IN ('open', 'pending'))");
+    }
+
+    #[test]
+    fn test_multiple_groups_or_together() {
+        let groups = parse_groups(&serde_json::json!([
+            {"status": "open"},
+            {"status": "closed", "urgent": true}
+        ]))
+        .unwrap();
+        let sql = compile_select("orders", &groups, &FilterBounds::default()).unwrap();
+        assert_eq!(
+            sql,
+            "SELECT * FROM \"orders\" WHERE ((\"status\" = 'open') OR (\"status\" = 'closed' AND \"urgent\" = true))"
+        );
+    }
+
+    #[test]
+    fn test_since_until_and_limit() {
+        let bounds = FilterBounds {
+            since_column: Some("created_at".to_string()),
+            since: Some("2024-01-01".to_string()),
+            until: Some("2024-02-01".to_string()),
+            limit: Some(50),
+        };
+        let sql = compile_select("orders", &[], &bounds).unwrap();
+        assert_eq!(
+            sql,
+            "SELECT * FROM \"orders\" WHERE \"created_at\" >= '2024-01-01' AND \"created_at\" <= '2024-02-01' LIMIT 50"
+        );
+    }
+
+    #[test]
+    fn test_schema_qualified_table_quoted_per_part() {
+        let sql = compile_select("public.orders", &[], &FilterBounds::default()).unwrap();
+        assert_eq!(sql, "SELECT * FROM \"public\".\"orders\"");
+    }
+
+    #[test]
+    fn test_string_literal_escapes_quotes() {
+        let groups = parse_groups(&serde_json::json!({"name": "o'brien"})).unwrap();
+        let sql = compile_select("users", &groups, &FilterBounds::default()).unwrap();
+        assert!(sql.contains("'o''brien'"));
+    }
+
+    #[test]
+    fn test_empty_in_list_is_always_false() {
+        let groups = parse_groups(&serde_json::json!({"status": []})).unwrap();
+        let sql = compile_select("orders", &groups, &FilterBounds::default()).unwrap();
+        assert_eq!(sql, "SELECT * FROM \"orders\" WHERE (FALSE)");
+    }
+
+    #[test]
+    fn test_negative_limit_rejected() {
+        let bounds = FilterBounds { limit: Some(-1), ..Default::default() };
+        assert!(compile_select("orders", &[], &bounds).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_page_first_page_has_no_where() {
+        let sql = compile_snapshot_page("SELECT * FROM orders", &["id".to_string()], None, 100).unwrap();
+        assert_eq!(
+            sql,
+            "SELECT * FROM (SELECT * FROM orders) AS pg_subscribe_cursor_page ORDER BY \"id\" LIMIT 100"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_page_resumes_after_cursor() {
+        let after = [serde_json::json!(42)];
+        let sql = compile_snapshot_page("SELECT * FROM orders", &["id".to_string()], Some(&after), 100).unwrap();
+        assert_eq!(
+            sql,
+            "SELECT * FROM (SELECT * FROM orders) AS pg_subscribe_cursor_page WHERE (\"id\") > (42) ORDER BY \"id\" LIMIT 100"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_page_composite_identity() {
+        let after = [serde_json::json!("acme"), serde_json::json!(5)];
+        let cols = vec!["tenant".to_string(), "id".to_string()];
+        let sql = compile_snapshot_page("SELECT * FROM orders", &cols, Some(&after), 10).unwrap();
+        assert_eq!(
+            sql,
+            "SELECT * FROM (SELECT * FROM orders) AS pg_subscribe_cursor_page WHERE (\"tenant\", \"id\") > ('acme', 5) ORDER BY \"tenant\", \"id\" LIMIT 10"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_page_requires_identity_columns() {
+        assert!(compile_snapshot_page("SELECT * FROM orders", &[], None, 100).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_page_mismatched_after_length_rejected() {
+        let after = [serde_json::json!(1), serde_json::json!(2)];
+        assert!(compile_snapshot_page("SELECT * FROM orders", &["id".to_string()], Some(&after), 100).is_err());
+    }
+
+    #[test]
+    fn test_visibility_check_projects_scalar_columns() {
+        let row = serde_json::json!({"tenant_id": 1, "owner_id": 7});
+        let sql = compile_visibility_check("owner_id = 7", &row).unwrap();
+        assert_eq!(
+            sql,
+            "SELECT (owner_id = 7) FROM (SELECT 1 AS \"tenant_id\", 7 AS \"owner_id\") AS _visibility_row"
+        );
+    }
+
+    #[test]
+    fn test_visibility_check_skips_nested_columns() {
+        let row = serde_json::json!({"id": 1, "tags": ["a", "b"]});
+        let sql = compile_visibility_check("id = 1", &row).unwrap();
+        assert_eq!(sql, "SELECT (id = 1) FROM (SELECT 1 AS \"id\") AS _visibility_row");
+    }
+
+    #[test]
+    fn test_visibility_check_rejects_non_object_row() {
+        assert!(compile_visibility_check("true", &serde_json::json!([1, 2])).is_err());
+    }
+}
@@ -1,16 +1,22 @@
 //! Database Pool with Metrics
 
 use anyhow::{Context, Result};
-use deadpool_postgres::{Config, ManagerConfig, Pool, PoolConfig, RecyclingMethod, Runtime};
+use async_stream::try_stream;
+use deadpool_postgres::{Config, GenericClient, ManagerConfig, Pool, PoolConfig, RecyclingMethod, Runtime};
+use futures::{Stream, StreamExt, TryStreamExt};
 use serde_json::Value;
 use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio_postgres::{types::Type, NoTls, Row};
+use tokio_postgres::{
+    types::{Kind, ToSql, Type},
+    NoTls, Row,
+};
 use tracing::{debug, warn};
 
-use crate::core::config;
+use crate::core::config::{self, DbTlsMode};
 use crate::core::row::{RowData, RowValue};
+use crate::infra::typecat::TypeCatalog;
 
 // Column name interning for reuse across queries
 static COL_NAMES: std::sync::LazyLock<
@@ -28,12 +34,216 @@ pub fn intern_col_name(name: &str) -> Arc<str> {
     arc
 }
 
+/// Upper bound (inclusive, milliseconds) of each query-latency histogram
+/// bucket rendered by `render_metrics` - cumulative, Prometheus-style: the
+/// counter for bucket `i` counts every query that took `<= LATENCY_BUCKETS_MS[i]`.
+const LATENCY_BUCKETS_MS: [u64; 9] = [1, 5, 10, 25, 50, 100, 250, 500, 1000];
+
 /// Database pool with query metrics
 pub struct DbPool {
     pool: Pool,
     queries: AtomicU64,
-    errors: AtomicU64,
+    errors_transient: AtomicU64,
+    errors_fatal: AtomicU64,
     total_ms: AtomicU64,
+    /// Queries that took longer than 100ms - the same threshold the slow
+    /// query `debug!` log already uses.
+    slow_queries: AtomicU64,
+    /// One cumulative counter per bound in `LATENCY_BUCKETS_MS`.
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS_MS.len()],
+    /// Sum of time-to-first-row, in ms, across every `query_rows_stream`
+    /// scan that yielded at least one row - paired with `first_row_samples`
+    /// for an average kept separate from `total_ms`'s end-to-end timing.
+    first_row_ms: AtomicU64,
+    first_row_samples: AtomicU64,
+    /// OID -> `Type` cache shared by every connection this pool hands out,
+    /// so an enum/composite/domain only needs resolving once per pool
+    /// rather than once per connection. See `infra::typecat`.
+    type_catalog: TypeCatalog,
+}
+
+/// Coarse classification of a query failure, mirroring the grouping the
+/// `postgres` crate's generated `SqlState` table implies: class `08`
+/// (connection exception), `40001`/`40P01` (serialization failure/deadlock)
+/// and `57P01` (admin shutdown) are transient - the same query is likely to
+/// succeed on retry - everything else (syntax errors, missing tables, ...)
+/// is fatal. A query error with no SQLSTATE at all (e.g. the connection
+/// itself dropped mid-request) is treated as transient for the same reason.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbErrorKind {
+    Transient,
+    Fatal,
+}
+
+impl DbErrorKind {
+    fn classify(e: &tokio_postgres::Error) -> Self {
+        match e.code() {
+            None => DbErrorKind::Transient,
+            Some(code) => match code.code() {
+                "40001" | "40P01" | "57P01" => DbErrorKind::Transient,
+                c if c.starts_with("08") => DbErrorKind::Transient,
+                _ => DbErrorKind::Fatal,
+            },
+        }
+    }
+}
+
+/// A failed query, classified as `Transient` (worth retrying / not fatal to
+/// the subscription) or `Fatal` (the subscription should be torn down).
+/// `query_rows_typed_params` already retries transient failures internally
+/// with backoff, so one reaching the caller means the retry budget was
+/// exhausted.
+#[derive(Debug)]
+pub struct DbError {
+    pub kind: DbErrorKind,
+    source: anyhow::Error,
+}
+
+impl std::fmt::Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl std::error::Error for DbError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.source()
+    }
+}
+
+/// Maximum number of attempts (including the first) for a transient failure
+/// before giving up and surfacing it to the caller.
+const MAX_RETRY_ATTEMPTS: u32 = 3;
+/// Backoff before the first retry; doubled after each further attempt.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(50);
+
+/// Hands `row_to_typed` the raw wire bytes for a column whose `Type` came
+/// from `TypeCatalog` (enum/composite/domain/array) instead of one of the
+/// builtin match arms in `row_to_typed` -`tokio_postgres`'s `FromSql` impls gate on
+/// `accepts()` matching a specific OID, so nothing built in will decode
+/// these; `RawBytes::accepts` says yes to everything and `decode_bytes`
+/// does the rest by hand, keyed off `Type::kind()`.
+struct RawBytes(Vec<u8>);
+
+impl<'a> tokio_postgres::types::FromSql<'a> for RawBytes {
+    fn from_sql(
+        _ty: &Type,
+        raw: &'a [u8],
+    ) -> std::result::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(RawBytes(raw.to_vec()))
+    }
+
+    fn accepts(_ty: &Type) -> bool {
+        true
+    }
+}
+
+/// Decode the binary wire representation of `ty` into a `RowValue`,
+/// recursing through `Kind::Domain`/`Kind::Array`/`Kind::Composite` for the
+/// types `TypeCatalog::resolve` built itself rather than ones
+/// `tokio_postgres` already ships a `FromSql` impl for.
+fn decode_bytes(ty: &Type, bytes: &[u8]) -> RowValue {
+    match ty.kind() {
+        Kind::Enum(_) => String::from_utf8(bytes.to_vec())
+            .map(|s| RowValue::intern_str(&s))
+            .unwrap_or(RowValue::Null),
+        Kind::Domain(base) => decode_bytes(base, bytes),
+        Kind::Composite(fields) => RowValue::Json(decode_composite(fields, bytes)),
+        Kind::Array(elem) => decode_array(elem, bytes),
+        _ => match *ty {
+            Type::INT2 => read_i16(bytes)
+                .map(|n| RowValue::Int(n as i64))
+                .unwrap_or(RowValue::Null),
+            Type::INT4 => read_i32(bytes)
+                .map(|n| RowValue::Int(n as i64))
+                .unwrap_or(RowValue::Null),
+            Type::INT8 => read_i64(bytes).map(RowValue::Int).unwrap_or(RowValue::Null),
+            Type::FLOAT4 => read_i32(bytes)
+                .map(|n| RowValue::Float(f32::from_bits(n as u32) as f64))
+                .unwrap_or(RowValue::Null),
+            Type::FLOAT8 => read_i64(bytes)
+                .map(|n| RowValue::Float(f64::from_bits(n as u64)))
+                .unwrap_or(RowValue::Null),
+            Type::BOOL => bytes
+                .first()
+                .map(|b| RowValue::Bool(*b != 0))
+                .unwrap_or(RowValue::Null),
+            _ => String::from_utf8(bytes.to_vec())
+                .map(|s| RowValue::intern_str(&s))
+                .unwrap_or(RowValue::Null),
+        },
+    }
+}
+
+fn read_i16(b: &[u8]) -> Option<i16> {
+    b.try_into().ok().map(i16::from_be_bytes)
+}
+
+fn read_i32(b: &[u8]) -> Option<i32> {
+    b.try_into().ok().map(i32::from_be_bytes)
+}
+
+fn read_i64(b: &[u8]) -> Option<i64> {
+    b.try_into().ok().map(i64::from_be_bytes)
+}
+
+/// Decode a composite's wire format - a field count followed by
+/// `(type oid: i32, length: i32, payload)` per field, in attribute order -
+/// into a JSON object keyed by field name, recursing into `decode_bytes`
+/// per field and reusing `RowValue::to_value` to get a `serde_json::Value`.
+fn decode_composite(fields: &[tokio_postgres::types::Field], bytes: &[u8]) -> Value {
+    let mut map = serde_json::Map::with_capacity(fields.len());
+    let Some(count) = bytes.get(0..4).and_then(read_i32) else {
+        return Value::Object(map);
+    };
+    let mut pos = 4;
+    for field in fields.iter().take(count.max(0) as usize) {
+        // 4 bytes of field type oid we don't need - each field already
+        // carries its own resolved `Type` from `TypeCatalog`.
+        let Some(len) = bytes.get(pos + 4..pos + 8).and_then(read_i32) else {
+            break;
+        };
+        pos += 8;
+        let value = if len < 0 {
+            Value::Null
+        } else {
+            let Some(field_bytes) = bytes.get(pos..pos + len as usize) else {
+                break;
+            };
+            pos += len as usize;
+            decode_bytes(field.type_(), field_bytes).to_value()
+        };
+        map.insert(field.name().to_string(), value);
+    }
+    Value::Object(map)
+}
+
+/// Decode an array's wire format - `ndim: i32`, `has_null: i32`,
+/// `element_oid: i32`, then `(size, lower_bound)` per dimension, then
+/// `(length: i32, payload)` per element in row-major order - into a flat
+/// `RowValue::Array` of the resolved element type, regardless of OID.
+fn decode_array(elem: &Type, bytes: &[u8]) -> RowValue {
+    let Some(ndim) = bytes.get(0..4).and_then(read_i32) else {
+        return RowValue::Array(Vec::new());
+    };
+    if ndim <= 0 {
+        return RowValue::Array(Vec::new());
+    }
+    let mut pos = 12 + ndim as usize * 8; // skip ndim/has_null/element_oid + per-dim headers
+    let mut out = Vec::new();
+    while let Some(len) = bytes.get(pos..pos + 4).and_then(read_i32) {
+        pos += 4;
+        if len < 0 {
+            out.push(RowValue::Null);
+            continue;
+        }
+        let Some(elem_bytes) = bytes.get(pos..pos + len as usize) else {
+            break;
+        };
+        pos += len as usize;
+        out.push(decode_bytes(elem, elem_bytes));
+    }
+    RowValue::Array(out)
 }
 
 #[inline(always)]
@@ -164,11 +374,21 @@ fn row_to_typed(row: &Row, cols: Arc<[Arc<str>]>, col_types: &[Type], use_index:
                     RowValue::Array(out)
                 }),
 
-            _ => row
-                .try_get::<_, Option<String>>(i)
-                .ok()
-                .flatten()
-                .map(|s| RowValue::intern_str(&s)),
+            // Not one of the builtins above - dispatch on `Kind` instead of
+            // falling back to a bare string for everything `TypeCatalog`
+            // resolved as an enum/composite/domain/array.
+            _ => match col_type.kind() {
+                Kind::Simple => row
+                    .try_get::<_, Option<String>>(i)
+                    .ok()
+                    .flatten()
+                    .map(|s| RowValue::intern_str(&s)),
+                _ => row
+                    .try_get::<_, Option<RawBytes>>(i)
+                    .ok()
+                    .flatten()
+                    .map(|b| decode_bytes(col_type, &b.0)),
+            },
         };
         values.push(v.unwrap_or(RowValue::Null));
     }
@@ -180,6 +400,92 @@ fn row_to_typed(row: &Row, cols: Arc<[Arc<str>]>, col_types: &[Type], use_index:
     }
 }
 
+/// Certificate verifier for `db_tls_mode = require`: encrypts the wire
+/// without validating the server's certificate, matching libpq's
+/// `sslmode=require` (as opposed to `verify-full`, which additionally
+/// authenticates the server against `db_tls_ca_cert`).
+#[derive(Debug)]
+struct NoCertVerification;
+
+impl tokio_postgres_rustls::rustls::client::danger::ServerCertVerifier for NoCertVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &tokio_postgres_rustls::rustls::pki_types::CertificateDer<'_>,
+        _intermediates: &[tokio_postgres_rustls::rustls::pki_types::CertificateDer<'_>],
+        _server_name: &tokio_postgres_rustls::rustls::pki_types::ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: tokio_postgres_rustls::rustls::pki_types::UnixTime,
+    ) -> Result<
+        tokio_postgres_rustls::rustls::client::danger::ServerCertVerified,
+        tokio_postgres_rustls::rustls::Error,
+    > {
+        Ok(tokio_postgres_rustls::rustls::client::danger::ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &tokio_postgres_rustls::rustls::pki_types::CertificateDer<'_>,
+        _dss: &tokio_postgres_rustls::rustls::DigitallySignedStruct,
+    ) -> Result<
+        tokio_postgres_rustls::rustls::client::danger::HandshakeSignatureValid,
+        tokio_postgres_rustls::rustls::Error,
+    > {
+        Ok(tokio_postgres_rustls::rustls::client::danger::HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _message: &[u8],
+        _cert: &tokio_postgres_rustls::rustls::pki_types::CertificateDer<'_>,
+        _dss: &tokio_postgres_rustls::rustls::DigitallySignedStruct,
+    ) -> Result<
+        tokio_postgres_rustls::rustls::client::danger::HandshakeSignatureValid,
+        tokio_postgres_rustls::rustls::Error,
+    > {
+        Ok(tokio_postgres_rustls::rustls::client::danger::HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<tokio_postgres_rustls::rustls::SignatureScheme> {
+        tokio_postgres_rustls::rustls::crypto::ring::default_provider()
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// Build the rustls-backed TLS connector for `db_tls_mode = require` or
+/// `verify-full`. `verify-full` loads `db_tls_ca_cert` (required by
+/// `Config::validate`) into the root store and verifies the server's
+/// certificate and hostname against it; `require` only encrypts the wire.
+fn make_rustls_connect(cfg: &config::Config) -> Result<tokio_postgres_rustls::MakeRustlsConnect> {
+    use tokio_postgres_rustls::rustls;
+
+    let tls_config = if cfg.db_tls_mode == DbTlsMode::VerifyFull {
+        let ca_path = cfg
+            .db_tls_ca_cert
+            .as_ref()
+            .context("db_tls_ca_cert is required for db_tls_mode = verify-full")?;
+        let pem = std::fs::read(ca_path)
+            .with_context(|| format!("reading db_tls_ca_cert {ca_path}"))?;
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in rustls_pemfile::certs(&mut pem.as_slice()) {
+            roots
+                .add(cert.context("parsing db_tls_ca_cert PEM")?)
+                .context("adding CA cert to root store")?;
+        }
+        rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth()
+    } else {
+        rustls::ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(NoCertVerification))
+            .with_no_client_auth()
+    };
+
+    Ok(tokio_postgres_rustls::MakeRustlsConnect::new(tls_config))
+}
+
 impl DbPool {
     pub fn new(cfg: &config::Config) -> Result<Self> {
         let mut c = Config::new();
@@ -196,33 +502,209 @@ impl DbPool {
             },
             ..Default::default()
         });
+        let pool = match cfg.db_tls_mode {
+            DbTlsMode::Disable => c.create_pool(Some(Runtime::Tokio1), NoTls)?,
+            DbTlsMode::Require | DbTlsMode::VerifyFull => {
+                c.create_pool(Some(Runtime::Tokio1), make_rustls_connect(cfg)?)?
+            }
+        };
+
         Ok(Self {
-            pool: c.create_pool(Some(Runtime::Tokio1), NoTls)?,
+            pool,
             queries: AtomicU64::new(0),
-            errors: AtomicU64::new(0),
+            errors_transient: AtomicU64::new(0),
+            errors_fatal: AtomicU64::new(0),
             total_ms: AtomicU64::new(0),
+            slow_queries: AtomicU64::new(0),
+            latency_buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            first_row_ms: AtomicU64::new(0),
+            first_row_samples: AtomicU64::new(0),
+            type_catalog: TypeCatalog::new(),
         })
     }
 
-    /// Query and return typed rows (no serde_json conversion until needed)
-    pub async fn query_rows_typed(&self, q: &str) -> Result<Vec<RowData>> {
+    /// Query and return typed rows (no serde_json conversion until needed).
+    /// A thin convenience wrapper over `query_rows_stream` that collects it
+    /// into a `Vec` - callers that can consume rows incrementally (e.g. an
+    /// initial subscription snapshot that's about to be chunked out to NATS
+    /// anyway) should use `query_rows_stream` directly instead, so the full
+    /// result set never has to sit in memory at once.
+    pub async fn query_rows_typed(&self, q: &str) -> std::result::Result<Vec<RowData>, DbError> {
+        let mut delay = RETRY_BASE_DELAY;
+        for attempt in 1..=MAX_RETRY_ATTEMPTS {
+            let stream = self.query_rows_stream(q);
+            futures::pin_mut!(stream);
+            let mut out = Vec::new();
+            let mut failure = None;
+            while let Some(item) = stream.next().await {
+                match item {
+                    Ok(row) => out.push(row),
+                    Err(e) => {
+                        failure = Some(e);
+                        break;
+                    }
+                }
+            }
+            match failure {
+                None => return Ok(out),
+                Some(e) if e.kind == DbErrorKind::Transient && attempt < MAX_RETRY_ATTEMPTS => {
+                    warn!(
+                        "Transient query error (attempt {}/{}), retrying in {:?}: {}",
+                        attempt, MAX_RETRY_ATTEMPTS, delay, e
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                Some(e) => return Err(e),
+            }
+        }
+        unreachable!("loop always returns on its last attempt")
+    }
+
+    /// Stream typed rows one at a time instead of buffering the whole
+    /// result set, built on `tokio_postgres::Client::query_raw` (row-by-row,
+    /// rather than `query`'s all-at-once `Vec<Row>`) - the same column
+    /// interning and `row_to_typed` decoding as `query_rows_typed` applies
+    /// incrementally as rows arrive, modeled on how a dedicated bulk-read
+    /// path streams a large base table without materializing it first.
+    /// Holds onto its pooled connection for as long as the stream is
+    /// iterated, so the connection isn't recycled mid-scan.
+    ///
+    /// Unlike `query_rows_typed`/`query_rows_typed_params`, a failure here
+    /// surfaces immediately rather than retrying - a retry would have to
+    /// either re-yield rows the caller already consumed or discard them, and
+    /// neither is the right call to make on the caller's behalf mid-stream.
+    pub fn query_rows_stream<'a>(
+        &'a self,
+        q: &'a str,
+    ) -> impl Stream<Item = std::result::Result<RowData, DbError>> + 'a {
+        try_stream! {
+            let start = Instant::now();
+            let c = self
+                .pool
+                .get()
+                .await
+                .map_err(|e| self.record_error(DbErrorKind::Transient, anyhow::anyhow!(e)))?;
+            let stmt = c.prepare_cached(q).await.map_err(|e| {
+                let kind = DbErrorKind::classify(&e);
+                self.record_error(kind, e.into())
+            })?;
+
+            let cols_meta = stmt.columns();
+            let cols_arc: Arc<[Arc<str>]> = Arc::from(
+                cols_meta
+                    .iter()
+                    .map(|c| intern_col_name(c.name()))
+                    .collect::<Vec<_>>()
+                    .into_boxed_slice(),
+            );
+            let mut col_types = Vec::with_capacity(cols_meta.len());
+            for col in cols_meta {
+                let resolved = match self.type_catalog.resolve(&c, col.type_()).await {
+                    Ok(ty) => ty,
+                    Err(e) => {
+                        warn!("type resolution failed for column {}: {}", col.name(), e);
+                        col.type_().clone()
+                    }
+                };
+                col_types.push(resolved);
+            }
+            let use_index = cols_arc.len() > 5;
+
+            let params: &[i32] = &[];
+            let rows = c.query_raw(&stmt, params).await.map_err(|e| {
+                let kind = DbErrorKind::classify(&e);
+                self.record_error(kind, e.into())
+            })?;
+            futures::pin_mut!(rows);
+
+            self.queries.fetch_add(1, Relaxed);
+            let mut first_row_seen = false;
+            while let Some(row) = rows.try_next().await.map_err(|e| {
+                let kind = DbErrorKind::classify(&e);
+                self.record_error(kind, e.into())
+            })? {
+                if !first_row_seen {
+                    first_row_seen = true;
+                    let ms = start.elapsed().as_millis() as u64;
+                    self.first_row_ms.fetch_add(ms, Relaxed);
+                    self.first_row_samples.fetch_add(1, Relaxed);
+                }
+                yield row_to_typed(&row, cols_arc.clone(), &col_types, use_index);
+            }
+
+            let ms = start.elapsed().as_millis() as u64;
+            self.total_ms.fetch_add(ms, Relaxed);
+            self.record_latency(ms);
+        }
+    }
+
+    /// Query and return typed rows, binding `params` instead of
+    /// string-interpolating them into `q`. The query is prepared via
+    /// `prepare_cached` - deadpool keeps one `Statement` cache per pooled
+    /// `tokio_postgres::Client`, so a requery loop over the same SQL text
+    /// reuses the already-parsed/planned statement instead of re-planning it
+    /// (and instead of falling onto the simple-query fast path) every time.
+    /// Column schema (`col_types`) comes from `Statement::columns()` rather
+    /// than the first returned row, so an empty result set still reports it.
+    ///
+    /// A failure classified `DbErrorKind::Transient` (see `DbErrorKind::classify`)
+    /// is retried in place, up to `MAX_RETRY_ATTEMPTS`, with exponential
+    /// backoff starting at `RETRY_BASE_DELAY` - only once that budget is
+    /// exhausted does it surface to the caller. A fatal one surfaces
+    /// immediately, since retrying a syntax error or missing table can't help.
+    pub async fn query_rows_typed_params(
+        &self,
+        q: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> std::result::Result<Vec<RowData>, DbError> {
+        let mut delay = RETRY_BASE_DELAY;
+        for attempt in 1..=MAX_RETRY_ATTEMPTS {
+            match self.query_rows_typed_once(q, params).await {
+                Ok(rows) => return Ok(rows),
+                Err(e) if e.kind == DbErrorKind::Transient && attempt < MAX_RETRY_ATTEMPTS => {
+                    warn!(
+                        "Transient query error (attempt {}/{}), retrying in {:?}: {}",
+                        attempt, MAX_RETRY_ATTEMPTS, delay, e
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        unreachable!("loop always returns on its last attempt")
+    }
+
+    async fn query_rows_typed_once(
+        &self,
+        q: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> std::result::Result<Vec<RowData>, DbError> {
         let start = Instant::now();
-        let c = self.pool.get().await.context("pool exhausted")?;
-        let result = c.query(q, &[]).await;
+        let c = self
+            .pool
+            .get()
+            .await
+            .map_err(|e| self.record_error(DbErrorKind::Transient, anyhow::anyhow!(e)))?;
+        let result = async {
+            let stmt = c.prepare_cached(q).await?;
+            let rows = c.query(&stmt, params).await?;
+            Ok::<_, tokio_postgres::Error>((stmt, rows))
+        }
+        .await;
         let ms = start.elapsed().as_millis() as u64;
         self.total_ms.fetch_add(ms, Relaxed);
         self.queries.fetch_add(1, Relaxed);
+        self.record_latency(ms);
 
         match result {
-            Ok(rows) => {
+            Ok((stmt, rows)) => {
                 if ms > 100 {
                     debug!("Slow query {}ms: {:.60}", ms, q);
                 }
-                if rows.is_empty() {
-                    return Ok(Vec::new());
-                }
 
-                let cols_meta = rows[0].columns();
+                let cols_meta = stmt.columns();
                 let cols_arc: Arc<[Arc<str>]> = Arc::from(
                     cols_meta
                         .iter()
@@ -230,7 +712,17 @@ impl DbPool {
                         .collect::<Vec<_>>()
                         .into_boxed_slice(),
                 );
-                let col_types: Vec<Type> = cols_meta.iter().map(|c| c.type_().clone()).collect();
+                let mut col_types = Vec::with_capacity(cols_meta.len());
+                for col in cols_meta {
+                    let resolved = match self.type_catalog.resolve(&c, col.type_()).await {
+                        Ok(ty) => ty,
+                        Err(e) => {
+                            warn!("type resolution failed for column {}: {}", col.name(), e);
+                            col.type_().clone()
+                        }
+                    };
+                    col_types.push(resolved);
+                }
 
                 // Use indexed rows if >5 columns for faster WHERE eval
                 let use_index = cols_arc.len() > 5;
@@ -241,9 +733,31 @@ impl DbPool {
                 Ok(out)
             }
             Err(e) => {
-                self.errors.fetch_add(1, Relaxed);
-                warn!("Query error: {} - {:.60}", e, q);
-                Err(e.into())
+                let kind = DbErrorKind::classify(&e);
+                warn!("Query error ({:?}): {} - {:.60}", kind, e, q);
+                Err(self.record_error(kind, e.into()))
+            }
+        }
+    }
+
+    fn record_error(&self, kind: DbErrorKind, source: anyhow::Error) -> DbError {
+        match kind {
+            DbErrorKind::Transient => self.errors_transient.fetch_add(1, Relaxed),
+            DbErrorKind::Fatal => self.errors_fatal.fetch_add(1, Relaxed),
+        };
+        DbError { kind, source }
+    }
+
+    /// Bump `slow_queries` and every `latency_buckets` counter whose bound
+    /// is `>= ms` - cumulative, so `render_metrics` can emit them directly
+    /// as a Prometheus histogram without any reaggregation at scrape time.
+    fn record_latency(&self, ms: u64) {
+        if ms > 100 {
+            self.slow_queries.fetch_add(1, Relaxed);
+        }
+        for (bound, bucket) in LATENCY_BUCKETS_MS.iter().zip(&self.latency_buckets) {
+            if ms <= *bound {
+                bucket.fetch_add(1, Relaxed);
             }
         }
     }
@@ -261,16 +775,97 @@ impl DbPool {
         (s.size - s.available, s.available, s.max_size)
     }
 
-    /// Query stats: (total, errors, avg_ms)
+    /// Query stats: (total, errors_transient, errors_fatal, avg_ms). Each
+    /// retry attempt inside `query_rows_typed_params` counts toward `total`
+    /// and, if it fails, toward `errors_transient` in its own right.
     #[inline]
-    pub fn query_stats(&self) -> (u64, u64, u64) {
+    pub fn query_stats(&self) -> (u64, u64, u64, u64) {
         let q = self.queries.load(Relaxed);
-        let e = self.errors.load(Relaxed);
+        let transient = self.errors_transient.load(Relaxed);
+        let fatal = self.errors_fatal.load(Relaxed);
         let avg = if q > 0 {
             self.total_ms.load(Relaxed) / q
         } else {
             0
         };
-        (q, e, avg)
+        (q, transient, fatal, avg)
+    }
+
+    /// Render pool and query metrics as Prometheus text-exposition format,
+    /// in the same style as [`crate::core::subscription::render_prometheus`].
+    /// The `+Inf` bucket is the query total, same as `queries.load`, so the
+    /// caller isn't forced to also scrape a separate counter for it.
+    pub fn render_metrics(&self) -> String {
+        let (active, available, max) = self.pool_status();
+        let mut out = String::new();
+
+        out.push_str("# HELP livequery_db_pool_connections Pooled connections by state.\n");
+        out.push_str("# TYPE livequery_db_pool_connections gauge\n");
+        out.push_str(&format!(
+            "livequery_db_pool_connections{{state=\"in_use\"}} {active}\n"
+        ));
+        out.push_str(&format!(
+            "livequery_db_pool_connections{{state=\"available\"}} {available}\n"
+        ));
+        out.push_str("# HELP livequery_db_pool_max_size Configured maximum pool size.\n");
+        out.push_str("# TYPE livequery_db_pool_max_size gauge\n");
+        out.push_str(&format!("livequery_db_pool_max_size {max}\n"));
+
+        out.push_str("# HELP livequery_db_queries_total Queries issued, across all retry attempts.\n");
+        out.push_str("# TYPE livequery_db_queries_total counter\n");
+        out.push_str(&format!(
+            "livequery_db_queries_total {}\n",
+            self.queries.load(Relaxed)
+        ));
+
+        out.push_str("# HELP livequery_db_errors_total Failed query attempts by classification.\n");
+        out.push_str("# TYPE livequery_db_errors_total counter\n");
+        out.push_str(&format!(
+            "livequery_db_errors_total{{kind=\"transient\"}} {}\n",
+            self.errors_transient.load(Relaxed)
+        ));
+        out.push_str(&format!(
+            "livequery_db_errors_total{{kind=\"fatal\"}} {}\n",
+            self.errors_fatal.load(Relaxed)
+        ));
+
+        out.push_str("# HELP livequery_db_slow_queries_total Queries slower than 100ms.\n");
+        out.push_str("# TYPE livequery_db_slow_queries_total counter\n");
+        out.push_str(&format!(
+            "livequery_db_slow_queries_total {}\n",
+            self.slow_queries.load(Relaxed)
+        ));
+
+        out.push_str("# HELP livequery_db_query_duration_ms Query duration.\n");
+        out.push_str("# TYPE livequery_db_query_duration_ms histogram\n");
+        for (bound, bucket) in LATENCY_BUCKETS_MS.iter().zip(&self.latency_buckets) {
+            out.push_str(&format!(
+                "livequery_db_query_duration_ms_bucket{{le=\"{bound}\"}} {}\n",
+                bucket.load(Relaxed)
+            ));
+        }
+        let total = self.queries.load(Relaxed);
+        out.push_str(&format!(
+            "livequery_db_query_duration_ms_bucket{{le=\"+Inf\"}} {total}\n"
+        ));
+        out.push_str(&format!(
+            "livequery_db_query_duration_ms_sum {}\n",
+            self.total_ms.load(Relaxed)
+        ));
+        out.push_str(&format!("livequery_db_query_duration_ms_count {total}\n"));
+
+        let first_row_samples = self.first_row_samples.load(Relaxed);
+        let avg_first_row_ms = if first_row_samples > 0 {
+            self.first_row_ms.load(Relaxed) / first_row_samples
+        } else {
+            0
+        };
+        out.push_str(
+            "# HELP livequery_db_query_first_row_ms Average time-to-first-row for query_rows_stream scans.\n",
+        );
+        out.push_str("# TYPE livequery_db_query_first_row_ms gauge\n");
+        out.push_str(&format!("livequery_db_query_first_row_ms {avg_first_row_ms}\n"));
+
+        out
     }
 }
@@ -0,0 +1,549 @@
+//! Client-facing HTTP gateway - SSE and WebSocket transports sitting
+//! alongside the NATS control plane (`infra::nats`).
+//!
+//! A NATS-native client talks to this server via request/reply subjects and
+//! receives live updates on `Config::sub_events_subject`. A gateway client
+//! instead talks HTTP/WebSocket directly to this process, but updates still
+//! flow the same way once subscribed: `infra::wal_stream` publishes every
+//! query's diff to that same NATS subject regardless of transport, so a
+//! gateway connection opens its own NATS subscription to it and relays
+//! whatever arrives verbatim - same wire bytes, new transport. `subscribe`
+//! itself is shared with the NATS path via `infra::subscribe::execute_subscribe`.
+
+use crate::core::event::*;
+use crate::core::subscription::render_prometheus;
+use crate::core::Config;
+use crate::infra::subscribe::execute_subscribe;
+use crate::infra::transport::socketio;
+use crate::infra::DbPool;
+use crate::core::SubscriptionManager;
+use anyhow::{Context, Result};
+use async_stream::stream;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query as AxumQuery, State};
+use axum::http::StatusCode;
+use axum::response::sse::{Event, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use futures::StreamExt;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{info, warn};
+
+/// Unsubscribes `sub_id` when dropped, including when a client's stream is
+/// cancelled mid-flight (SSE/WS disconnect) rather than ending normally -
+/// this is what keeps a dropped connection from leaking a slot until the
+/// `cleanup` task's `client_timeout` catches up.
+struct UnsubscribeOnDrop {
+    subs: Arc<SubscriptionManager>,
+    sub_id: Arc<str>,
+}
+
+impl Drop for UnsubscribeOnDrop {
+    fn drop(&mut self) {
+        self.subs.unsubscribe(&self.sub_id);
+    }
+}
+
+#[derive(Clone)]
+pub struct Gateway {
+    cfg: Arc<Config>,
+    subs: Arc<SubscriptionManager>,
+    db: Arc<DbPool>,
+    nc: async_nats::Client,
+}
+
+impl Gateway {
+    pub fn new(
+        cfg: Arc<Config>,
+        subs: Arc<SubscriptionManager>,
+        db: Arc<DbPool>,
+        nc: async_nats::Client,
+    ) -> Self {
+        Self { cfg, subs, db, nc }
+    }
+
+    /// Bind `Config::gateway_bind` and serve `/subscribe` (SSE) and `/ws`
+    /// (WebSocket) until `shutdown` fires.
+    pub async fn run(self, mut shutdown: broadcast::Receiver<()>) -> Result<()> {
+        let addr = self.cfg.gateway_bind.clone();
+        let app = Router::new()
+            .route("/subscribe", get(sse_handler))
+            .route("/ws", get(ws_handler))
+            .route("/socket.io/", get(socketio_handler))
+            .route("/metrics", get(metrics_handler))
+            .with_state(self);
+
+        let listener = tokio::net::TcpListener::bind(&addr)
+            .await
+            .with_context(|| format!("binding gateway to {addr}"))?;
+        info!("Gateway listening on {addr} (GET /subscribe, /ws, /socket.io/, /metrics)");
+
+        axum::serve(listener, app)
+            .with_graceful_shutdown(async move {
+                let _ = shutdown.recv().await;
+            })
+            .await
+            .context("gateway server")?;
+        Ok(())
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct SubscribeParams {
+    subscription_id: Option<String>,
+    query: String,
+    #[serde(default)]
+    identity_columns: Option<String>,
+    #[serde(default)]
+    mode: SubscriptionMode,
+    #[serde(default)]
+    resume_from_seq: Option<u64>,
+    /// Bearer credential for `AuthProvider::authenticate`; ignored under the
+    /// default `AllowAllProvider`.
+    #[serde(default)]
+    auth_token: Option<String>,
+}
+
+fn gen_sub_id() -> String {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    format!("gw-{:x}", ts & 0xFFFF_FFFF)
+}
+
+fn split_cols(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+        .collect()
+}
+
+/// `GET /subscribe?query=...&subscription_id=...&identity_columns=a,b&mode=events`
+///
+/// Always negotiates `WireFormat::Json` - SSE's `text/event-stream` framing
+/// is inherently text, so there's no benefit to the binary/MessagePack/Cbor
+/// formats `SubscribeRequest::format` offers NATS-native clients.
+async fn sse_handler(
+    State(gw): State<Gateway>,
+    AxumQuery(params): AxumQuery<SubscribeParams>,
+) -> Response {
+    let sub_id = params.subscription_id.clone().unwrap_or_else(gen_sub_id);
+    let identity_columns = params.identity_columns.as_deref().map(split_cols);
+    let credential = params.auth_token.clone().unwrap_or_default();
+
+    let resp = execute_subscribe(
+        &gw.cfg,
+        &gw.subs,
+        &gw.db,
+        sub_id.clone(),
+        params.query,
+        identity_columns,
+        params.mode,
+        WireFormat::Json,
+        params.resume_from_seq,
+        &credential,
+    )
+    .await;
+
+    if !resp.success {
+        return (StatusCode::BAD_REQUEST, Json(resp)).into_response();
+    }
+
+    let subject = resp.subject.clone().unwrap_or_default();
+    let sub_id: Arc<str> = Arc::from(resp.subscription_id.clone().unwrap_or(sub_id).as_str());
+    let snapshot = serde_json::to_string(&resp).unwrap_or_default();
+    let subs = gw.subs.clone();
+    let nc = gw.nc.clone();
+    let heartbeat_interval = gw.cfg.heartbeat_interval();
+
+    let events = stream! {
+        let guard = UnsubscribeOnDrop { subs: subs.clone(), sub_id: sub_id.clone() };
+        yield Ok::<_, std::convert::Infallible>(Event::default().event("snapshot").data(snapshot));
+
+        let mut relay = match nc.subscribe(subject).await {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("Gateway [{}]: NATS relay subscribe failed: {e}", guard.sub_id);
+                return;
+            }
+        };
+        let mut tick = tokio::time::interval(heartbeat_interval);
+        tick.tick().await; // first tick is immediate; the snapshot above already covers it
+
+        loop {
+            tokio::select! {
+                msg = relay.next() => {
+                    let Some(msg) = msg else { break };
+                    if let Ok(text) = String::from_utf8(msg.payload.to_vec()) {
+                        yield Ok(Event::default().event("events").data(text));
+                    }
+                }
+                _ = tick.tick() => {
+                    subs.heartbeat(&guard.sub_id);
+                    let hb = serde_json::json!({"mz_progressed": true, "mz_timestamp": ts_millis() as i64});
+                    yield Ok(Event::default().event("heartbeat").data(hb.to_string()));
+                }
+            }
+        }
+    };
+
+    Sse::new(events).into_response()
+}
+
+/// `GET /ws` - a graphql-ws-style multiplexed protocol: a single socket can
+/// carry many concurrent live queries, each addressed by a client-chosen
+/// `id` that has no relation to the server's own `subscription_id` (that
+/// `id` is purely a stream-multiplexing key, same role `id` plays in
+/// graphql-ws `subscribe`/`next`/`complete` frames). Clients should open
+/// with `{"type":"connection_init"}` and wait for `{"type":"connection_ack"}`
+/// before sending `subscribe` frames, though (like the rest of this
+/// gateway's framing) that handshake isn't enforced. Each `subscribe {id,
+/// query, identity_columns, mode, resume_from_seq}` spawns its own relay of
+/// `infra::subscribe::execute_subscribe`'s response followed by every
+/// relayed event batch as `next {id, payload}`, until `complete {id}` from
+/// either side or the socket closes. `heartbeat`/`ack`/`ack_gone` frames are
+/// also `id`-scoped and map onto the matching `SubscriptionManager` method,
+/// mirroring the four control operations `infra::nats` exposes as subjects.
+/// A periodic `ping` frame (on `HEARTBEAT_INTERVAL_MS`) doubles as the
+/// liveness heartbeat for every subscription still open on the socket.
+async fn ws_handler(State(gw): State<Gateway>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| handle_socket(socket, gw))
+}
+
+#[derive(serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientFrame {
+    ConnectionInit,
+    Subscribe {
+        id: String,
+        query: String,
+        #[serde(default)]
+        identity_columns: Option<Vec<String>>,
+        #[serde(default)]
+        mode: SubscriptionMode,
+        #[serde(default)]
+        resume_from_seq: Option<u64>,
+        /// Bearer credential for `AuthProvider::authenticate`; ignored
+        /// under the default `AllowAllProvider`.
+        #[serde(default)]
+        auth_token: Option<String>,
+    },
+    Complete {
+        id: String,
+    },
+    Heartbeat {
+        id: String,
+    },
+    Ack {
+        id: String,
+        seq: u64,
+    },
+    AckGone {
+        id: String,
+        seq: u64,
+    },
+    Pong,
+}
+
+#[derive(serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerFrame {
+    ConnectionAck,
+    Next { id: String, payload: serde_json::Value },
+    Complete { id: String },
+    Error { id: String, payload: SubscribeResponse },
+    Ping,
+}
+
+fn frame(f: &ServerFrame) -> Message {
+    Message::Text(serde_json::to_string(f).unwrap_or_default().into())
+}
+
+/// One entry per `id` currently open on a socket, keyed by the client's
+/// frame `id` rather than the server's `subscription_id` - see `ws_handler`.
+struct WsStream {
+    task: tokio::task::JoinHandle<()>,
+    sub_id: Arc<str>,
+}
+
+/// Runs one `id`'s subscribe-then-relay lifecycle: registers with
+/// `SubscriptionManager` via `execute_subscribe`, reports the resolved
+/// `subscription_id` through `ready`, sends the initial response and every
+/// subsequent relayed event batch as `next {id, ...}`, and unsubscribes
+/// (via `UnsubscribeOnDrop`) whenever this task ends or is aborted.
+#[allow(clippy::too_many_arguments)]
+async fn run_stream(
+    gw: Gateway,
+    id: String,
+    query: String,
+    identity_columns: Option<Vec<String>>,
+    mode: SubscriptionMode,
+    resume_from_seq: Option<u64>,
+    auth_token: Option<String>,
+    out: mpsc::UnboundedSender<Message>,
+    ready: mpsc::UnboundedSender<(String, Arc<str>)>,
+) {
+    let credential = auth_token.unwrap_or_default();
+    let resp = execute_subscribe(
+        &gw.cfg,
+        &gw.subs,
+        &gw.db,
+        gen_sub_id(),
+        query,
+        identity_columns,
+        mode,
+        WireFormat::Json,
+        resume_from_seq,
+        &credential,
+    )
+    .await;
+
+    let Some(sub_id) = resp.subscription_id.clone() else {
+        let _ = out.send(frame(&ServerFrame::Error { id, payload: resp }));
+        return;
+    };
+    let sub_id: Arc<str> = Arc::from(sub_id.as_str());
+    let _ = ready.send((id.clone(), sub_id.clone()));
+    let _guard = UnsubscribeOnDrop {
+        subs: gw.subs.clone(),
+        sub_id: sub_id.clone(),
+    };
+
+    let subject = resp.subject.clone().unwrap_or_default();
+    let payload = serde_json::to_value(&resp).unwrap_or_default();
+    if out.send(frame(&ServerFrame::Next { id: id.clone(), payload })).is_err() {
+        return;
+    }
+
+    let mut relay = match gw.nc.subscribe(subject).await {
+        Ok(s) => s,
+        Err(e) => {
+            warn!("Gateway [{id}]: NATS relay subscribe failed: {e}");
+            return;
+        }
+    };
+    while let Some(msg) = relay.next().await {
+        let Ok(text) = String::from_utf8(msg.payload.to_vec()) else {
+            continue;
+        };
+        let payload = serde_json::from_str(&text).unwrap_or(serde_json::Value::Null);
+        if out.send(frame(&ServerFrame::Next { id: id.clone(), payload })).is_err() {
+            break;
+        }
+    }
+    let _ = out.send(frame(&ServerFrame::Complete { id }));
+}
+
+async fn handle_socket(mut socket: WebSocket, gw: Gateway) {
+    let (out_tx, mut out_rx) = mpsc::unbounded_channel::<Message>();
+    let (ready_tx, mut ready_rx) = mpsc::unbounded_channel::<(String, Arc<str>)>();
+    let mut streams: HashMap<String, WsStream> = HashMap::new();
+    let mut tick = tokio::time::interval(gw.cfg.heartbeat_interval());
+    tick.tick().await;
+
+    loop {
+        tokio::select! {
+            Some((id, sub_id)) = ready_rx.recv() => {
+                if let Some(s) = streams.get_mut(&id) {
+                    s.sub_id = sub_id;
+                }
+            }
+            Some(msg) = out_rx.recv() => {
+                if socket.send(msg).await.is_err() {
+                    break;
+                }
+            }
+            _ = tick.tick() => {
+                for s in streams.values() {
+                    gw.subs.heartbeat(&s.sub_id);
+                }
+                if socket.send(frame(&ServerFrame::Ping)).await.is_err() {
+                    break;
+                }
+            }
+            incoming = socket.recv() => {
+                match incoming {
+                    Some(Ok(Message::Text(raw))) => {
+                        let Ok(cf) = serde_json::from_str::<ClientFrame>(&raw) else { continue };
+                        match cf {
+                            ClientFrame::ConnectionInit => {
+                                let _ = socket.send(frame(&ServerFrame::ConnectionAck)).await;
+                            }
+                            ClientFrame::Subscribe { id, query, identity_columns, mode, resume_from_seq, auth_token } => {
+                                let task = tokio::spawn(run_stream(
+                                    gw.clone(), id.clone(), query, identity_columns, mode,
+                                    resume_from_seq, auth_token, out_tx.clone(), ready_tx.clone(),
+                                ));
+                                // Placeholder sub_id until `ready_rx` resolves it; a
+                                // heartbeat/ack/complete racing ahead of that is a
+                                // harmless no-op against this ID, not the real one.
+                                if let Some(old) = streams.insert(id, WsStream { task, sub_id: Arc::from("") }) {
+                                    old.task.abort();
+                                }
+                            }
+                            ClientFrame::Complete { id } => {
+                                if let Some(s) = streams.remove(&id) {
+                                    s.task.abort();
+                                }
+                            }
+                            ClientFrame::Heartbeat { id } => {
+                                if let Some(s) = streams.get(&id) {
+                                    gw.subs.heartbeat(&s.sub_id);
+                                }
+                            }
+                            ClientFrame::Ack { id, seq } => {
+                                if let Some(s) = streams.get(&id) {
+                                    gw.subs.ack(&s.sub_id, seq);
+                                }
+                            }
+                            ClientFrame::AckGone { id, seq } => {
+                                let gone = streams.get(&id).is_some_and(|s| gw.subs.ack_gone(&s.sub_id, seq));
+                                if gone {
+                                    if let Some(s) = streams.remove(&id) {
+                                        s.task.abort();
+                                    }
+                                }
+                            }
+                            ClientFrame::Pong => {}
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Err(_)) => break,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    for (_, s) in streams {
+        s.task.abort();
+    }
+}
+
+/// `GET /socket.io/?EIO=4&transport=websocket` - a Socket.IO v4 / Engine.IO
+/// v4 compatible transport for browsers using `socket.io-client`, alongside
+/// the native `/ws` multiplexed protocol above (see `infra::transport::socketio`
+/// for the frame encode/decode). Unlike `/ws`, one socket carries exactly one
+/// live query rather than many multiplexed by a client-chosen `id` - the
+/// client emits a `subscribe` event shaped like `SocketIoSubscribe`, and the
+/// server's "room" for that query is just the resolved `subscription_id`;
+/// there's only ever one member (this socket), since - like every other
+/// transport here - it opens its own NATS relay rather than fanning a
+/// shared subscription out to several sockets. `EventBatch`es are re-emitted
+/// as `events` Socket.IO events; a reconnecting client can pass
+/// `resume_from_seq` on a fresh `subscribe` the same way `/ws`'s
+/// `ClientFrame::Subscribe` does.
+async fn socketio_handler(State(gw): State<Gateway>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| handle_socketio(socket, gw))
+}
+
+#[derive(serde::Deserialize)]
+struct SocketIoSubscribe {
+    query: String,
+    #[serde(default)]
+    identity_columns: Option<Vec<String>>,
+    #[serde(default)]
+    mode: SubscriptionMode,
+    #[serde(default)]
+    resume_from_seq: Option<u64>,
+    /// Bearer credential for `AuthProvider::authenticate`; ignored under the
+    /// default `AllowAllProvider`.
+    #[serde(default)]
+    auth_token: Option<String>,
+}
+
+async fn handle_socketio(mut socket: WebSocket, gw: Gateway) {
+    let sid = gen_sub_id();
+    let open = socketio::encode_open(
+        &sid,
+        gw.cfg.heartbeat_interval().as_millis() as u64,
+        gw.cfg.client_timeout().as_millis() as u64,
+    );
+    if socket.send(Message::Text(open.into())).await.is_err() {
+        return;
+    }
+
+    let mut guard: Option<UnsubscribeOnDrop> = None;
+    let mut relay: Option<async_nats::Subscriber> = None;
+    let mut tick = tokio::time::interval(gw.cfg.heartbeat_interval());
+    tick.tick().await;
+
+    loop {
+        tokio::select! {
+            msg = async { relay.as_mut().unwrap().next().await }, if relay.is_some() => {
+                let Some(msg) = msg else { relay = None; continue };
+                if let Ok(text) = String::from_utf8(msg.payload.to_vec()) {
+                    let payload: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+                    if socket.send(Message::Text(socketio::encode_event("events", &payload).into())).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            _ = tick.tick() => {
+                if let Some(g) = &guard {
+                    gw.subs.heartbeat(&g.sub_id);
+                }
+                if socket.send(Message::Text(socketio::encode_ping().into())).await.is_err() {
+                    break;
+                }
+            }
+            incoming = socket.recv() => {
+                let Some((engine_type, packet)) = (match incoming {
+                    Some(Ok(Message::Text(raw))) => socketio::decode(&raw),
+                    Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                    _ => continue,
+                }) else { continue };
+
+                match engine_type {
+                    socketio::EngineType::Close => break,
+                    socketio::EngineType::Message => {
+                        let Some(packet) = packet else { continue };
+                        match packet.kind {
+                            socketio::SocketType::Connect => {
+                                let _ = socket.send(Message::Text(socketio::encode_connect_ack().into())).await;
+                            }
+                            socketio::SocketType::Event => {
+                                let Some(arr) = packet.data.as_array() else { continue };
+                                let (Some("subscribe"), Some(args)) =
+                                    (arr.first().and_then(Value::as_str), arr.get(1)) else { continue };
+                                let Ok(req) = serde_json::from_value::<SocketIoSubscribe>(args.clone()) else { continue };
+                                let credential = req.auth_token.unwrap_or_default();
+                                let resp = execute_subscribe(
+                                    &gw.cfg, &gw.subs, &gw.db, sid.clone(), req.query,
+                                    req.identity_columns, req.mode, WireFormat::Json,
+                                    req.resume_from_seq, &credential,
+                                ).await;
+                                if !resp.success {
+                                    let _ = socket.send(Message::Text(socketio::encode_event("error", &resp).into())).await;
+                                    continue;
+                                }
+                                let sub_id: Arc<str> = Arc::from(resp.subscription_id.clone().unwrap_or_default().as_str());
+                                guard = Some(UnsubscribeOnDrop { subs: gw.subs.clone(), sub_id: sub_id.clone() });
+                                relay = gw.nc.subscribe(resp.subject.clone().unwrap_or_default()).await.ok();
+                                let _ = socket.send(Message::Text(socketio::encode_event("snapshot", &resp).into())).await;
+                            }
+                            _ => {}
+                        }
+                    }
+                    _ => {} // Open/Ping/Pong/Upgrade/Noop need no reply here
+                }
+            }
+        }
+    }
+}
+
+/// `GET /metrics` - the same Prometheus text exposition the NATS
+/// `{prefix}.debug.metrics` subject replies with (see `infra::nats`), for
+/// operators who'd rather point a Prometheus scrape config at an HTTP path
+/// than a NATS request.
+async fn metrics_handler(State(gw): State<Gateway>) -> Response {
+    let mut text = render_prometheus(&gw.subs.metrics_snapshot());
+    text.push_str(&gw.db.render_metrics());
+    ([(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")], text).into_response()
+}
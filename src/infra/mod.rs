@@ -1,10 +1,15 @@
 pub mod db;
+pub mod gateway;
 pub mod nats;
 pub mod pgoutput;
+mod subscribe;
+mod transport;
+mod typecat;
 pub mod wal_stream;
 
 pub use db::{DbPool, intern_col_name};
+pub use gateway::Gateway;
 pub use nats::NatsHandler;
 #[allow(unused_imports)] // Exported for benchmarks
-pub use pgoutput::{ColMeta, PgOutputDecoder, WalChange};
-pub use wal_stream::WalStreamer;
+pub use pgoutput::{ColMeta, Conversion, OldTuple, PgOutputDecoder, WalChange};
+pub use wal_stream::{publish_coalesced, reap_stale, WalStreamer};
@@ -23,10 +23,22 @@ fn sub_id_from_subject<'a>(prefix: &str, subject: &'a str, tail: &str) -> Option
     }
 }
 
+use crate::core::config::JetStreamStorage;
 use crate::core::event::*;
-use crate::core::subscription::SubscriptionManager;
+use crate::core::subscription::{render_prometheus, SubscriptionManager};
 use crate::core::Config;
+use crate::infra::subscribe::execute_subscribe;
 use crate::infra::DbPool;
+use async_nats::jetstream::{self, consumer::DeliverPolicy};
+
+impl From<JetStreamStorage> for jetstream::stream::StorageType {
+    fn from(storage: JetStreamStorage) -> Self {
+        match storage {
+            JetStreamStorage::File => jetstream::stream::StorageType::File,
+            JetStreamStorage::Memory => jetstream::stream::StorageType::Memory,
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct NatsHandler {
@@ -35,6 +47,10 @@ pub struct NatsHandler {
     subs: Arc<SubscriptionManager>,
     msgs_in: Arc<AtomicU64>,
     msgs_out: Arc<AtomicU64>,
+    /// Set when `Config::jetstream_enabled`, switching `publish_bytes`/
+    /// `publish_batch` from core-NATS fire-and-forget to JetStream
+    /// at-least-once delivery - see `ensure_jetstream_stream`.
+    js: Option<jetstream::Context>,
 }
 
 impl NatsHandler {
@@ -42,15 +58,41 @@ impl NatsHandler {
         let nc = async_nats::connect(&cfg.nats_url)
             .await
             .context("NATS connect")?;
+        let js = if cfg.jetstream_enabled {
+            let js = jetstream::new(nc.clone());
+            Self::ensure_jetstream_stream(&js, &cfg).await?;
+            Some(js)
+        } else {
+            None
+        };
         Ok(Self {
             nc,
             cfg,
             subs,
             msgs_in: Arc::new(AtomicU64::new(0)),
             msgs_out: Arc::new(AtomicU64::new(0)),
+            js,
         })
     }
 
+    /// Idempotently create (or pick up the existing) `jetstream_stream_name`
+    /// stream bound to `{nats_prefix}.*.events`, so every subscription's
+    /// events subject is durably retained once JetStream mode is on.
+    async fn ensure_jetstream_stream(js: &jetstream::Context, cfg: &Config) -> Result<()> {
+        let prefix = cfg.nats_prefix.trim_end_matches('.');
+        js.get_or_create_stream(jetstream::stream::Config {
+            name: cfg.jetstream_stream_name.clone(),
+            subjects: vec![format!("{prefix}.*.events")],
+            storage: cfg.jetstream_storage.into(),
+            max_age: std::time::Duration::from_secs(cfg.jetstream_max_age_secs),
+            max_bytes: cfg.jetstream_max_bytes,
+            ..Default::default()
+        })
+        .await
+        .context("creating/verifying JetStream stream")?;
+        Ok(())
+    }
+
     pub async fn run(&self, db: Arc<DbPool>) -> Result<()> {
         // Subscribe to wildcard subjects: livequery.*.subscribe, etc.
         let prefix = self.cfg.nats_prefix.trim_end_matches('.');
@@ -60,10 +102,16 @@ impl NatsHandler {
             .subscribe(format!("{}.*.unsubscribe", prefix))
             .await?;
         let mut sub_heartbeat = self.nc.subscribe(format!("{}.*.heartbeat", prefix)).await?;
+        let mut sub_ack_gone = self.nc.subscribe(format!("{}.*.ack_gone", prefix)).await?;
+        let mut sub_ack = self.nc.subscribe(format!("{}.*.ack", prefix)).await?;
         let mut sub_health = self.nc.subscribe(format!("{}.health", prefix)).await?;
+        let mut sub_topology = self.nc.subscribe(format!("{}.debug.topology", prefix)).await?;
+        let mut sub_metrics = self.nc.subscribe(format!("{}.debug.metrics", prefix)).await?;
+        let mut sub_admin_ban = self.nc.subscribe(format!("{}.admin.ban", prefix)).await?;
+        let mut sub_admin_unban = self.nc.subscribe(format!("{}.admin.unban", prefix)).await?;
         info!(
-            "NATS listening on {}.*.{{subscribe|unsubscribe|heartbeat}} + {}.health",
-            prefix, prefix
+            "NATS listening on {}.*.{{subscribe|unsubscribe|heartbeat|ack_gone|ack}} + {}.health + {}.debug.topology + {}.debug.metrics + {}.admin.{{ban|unban}}",
+            prefix, prefix, prefix, prefix, prefix
         );
 
         loop {
@@ -71,7 +119,7 @@ impl NatsHandler {
                 Some(m) = sub_subscribe.next() => {
                     self.msgs_in.fetch_add(1, Relaxed);
                     let sub_id = sub_id_from_subject(&self.cfg.nats_prefix, m.subject.as_str(), "subscribe");
-                    let r = self.on_subscribe(&m.payload, &db, sub_id).await;
+                    let r = self.on_subscribe(&m, &db, sub_id).await;
                     self.reply(&m.reply, &r).await;
                 }
                 Some(m) = sub_unsubscribe.next() => {
@@ -94,98 +142,148 @@ impl NatsHandler {
                         self.reply(&m.reply, &serde_json::json!({"success": false, "error": "Missing subscription_id in subject"})).await;
                     }
                 }
+                Some(m) = sub_ack_gone.next() => {
+                    self.msgs_in.fetch_add(1, Relaxed);
+                    let sub_id = sub_id_from_subject(&self.cfg.nats_prefix, m.subject.as_str(), "ack_gone");
+                    if let Some(id) = sub_id {
+                        let seq = serde_json::from_slice::<serde_json::Value>(&m.payload)
+                            .ok()
+                            .and_then(|v| v.get("seq").and_then(|s| s.as_u64()))
+                            .unwrap_or(0);
+                        let ok = self.subs.ack_gone(id, seq);
+                        self.reply(&m.reply, &serde_json::json!({"success": ok})).await;
+                    } else {
+                        self.reply(&m.reply, &serde_json::json!({"success": false, "error": "Missing subscription_id in subject"})).await;
+                    }
+                }
+                Some(m) = sub_ack.next() => {
+                    self.msgs_in.fetch_add(1, Relaxed);
+                    let sub_id = sub_id_from_subject(&self.cfg.nats_prefix, m.subject.as_str(), "ack");
+                    if let Some(id) = sub_id {
+                        let seq = serde_json::from_slice::<serde_json::Value>(&m.payload)
+                            .ok()
+                            .and_then(|v| v.get("seq").and_then(|s| s.as_u64()))
+                            .unwrap_or(0);
+                        let ok = self.subs.ack(id, seq);
+                        self.reply(&m.reply, &serde_json::json!({"success": ok})).await;
+                    } else {
+                        self.reply(&m.reply, &serde_json::json!({"success": false, "error": "Missing subscription_id in subject"})).await;
+                    }
+                }
                 Some(m) = sub_health.next() => {
                     self.msgs_in.fetch_add(1, Relaxed);
                     let (subs, queries) = self.subs.stats();
                     let (msgs_in, msgs_out) = self.stats();
+                    let metrics = self.subs.metrics_snapshot();
                     self.reply(&m.reply, &serde_json::json!({
                         "status": "healthy",
                         "server_id": self.cfg.server_id,
                         "subscriptions": subs,
                         "queries": queries,
                         "msgs_in": msgs_in,
-                        "msgs_out": msgs_out
+                        "msgs_out": msgs_out,
+                        "rejected_bad_request": metrics.rejected_bad_request,
+                        "rejected_overloaded": metrics.rejected_overloaded,
+                        "rejected_query_failed": metrics.rejected_query_failed,
+                        "rejected_not_found": metrics.rejected_not_found,
+                        "rejected_banned": metrics.rejected_banned,
+                        "expired_timeout": metrics.expired_timeout
                     })).await;
                 }
+                Some(m) = sub_topology.next() => {
+                    self.msgs_in.fetch_add(1, Relaxed);
+                    let dot = self.subs.topology_dot();
+                    if let Some(subj) = &m.reply {
+                        self.msgs_out.fetch_add(1, Relaxed);
+                        if let Err(e) = self.nc.publish(subj.clone(), dot.into()).await {
+                            warn!("Reply error: {e}");
+                        }
+                    }
+                }
+                Some(m) = sub_metrics.next() => {
+                    self.msgs_in.fetch_add(1, Relaxed);
+                    let mut text = render_prometheus(&self.subs.metrics_snapshot());
+                    text.push_str(&db.render_metrics());
+                    if let Some(subj) = &m.reply {
+                        self.msgs_out.fetch_add(1, Relaxed);
+                        if let Err(e) = self.nc.publish(subj.clone(), text.into()).await {
+                            warn!("Reply error: {e}");
+                        }
+                    }
+                }
+                Some(m) = sub_admin_ban.next() => {
+                    self.msgs_in.fetch_add(1, Relaxed);
+                    if let Some(principal_id) = Self::admin_principal(&m.payload) {
+                        let unsubscribed = self.subs.ban(&principal_id);
+                        warn!("Admin: banned principal '{principal_id}' ({unsubscribed} subscriptions dropped)");
+                        self.reply(&m.reply, &serde_json::json!({"success": true, "unsubscribed": unsubscribed})).await;
+                    } else {
+                        self.reply(&m.reply, &serde_json::json!({"success": false, "error": "Missing principal_id"})).await;
+                    }
+                }
+                Some(m) = sub_admin_unban.next() => {
+                    self.msgs_in.fetch_add(1, Relaxed);
+                    if let Some(principal_id) = Self::admin_principal(&m.payload) {
+                        let ok = self.subs.unban(&principal_id);
+                        self.reply(&m.reply, &serde_json::json!({"success": ok})).await;
+                    } else {
+                        self.reply(&m.reply, &serde_json::json!({"success": false, "error": "Missing principal_id"})).await;
+                    }
+                }
             }
         }
     }
 
+    /// Extract `{"principal_id": "..."}` from an admin ban/unban request body.
+    fn admin_principal(payload: &[u8]) -> Option<String> {
+        serde_json::from_slice::<serde_json::Value>(payload)
+            .ok()?
+            .get("principal_id")?
+            .as_str()
+            .map(String::from)
+    }
+
     async fn on_subscribe(
         &self,
-        payload: &[u8],
+        msg: &async_nats::Message,
         db: &DbPool,
         subject_sub_id: Option<&str>,
     ) -> SubscribeResponse {
-        let req: SubscribeRequest = match serde_json::from_slice(payload) {
+        let req: SubscribeRequest = match serde_json::from_slice(&msg.payload) {
             Ok(r) => r,
-            Err(_) => return SubscribeResponse::err("Invalid request JSON"),
+            Err(_) => return SubscribeResponse::err(SubError::BadRequest, "Invalid request JSON"),
         };
         let sub_id = subject_sub_id
             .map(String::from)
-            .unwrap_or(req.subscription_id);
-        let (query, identity_columns, mode) = (req.query, req.identity_columns, req.mode);
+            .unwrap_or_else(|| req.subscription_id.clone());
+        let format = req.format.unwrap_or(self.cfg.wire_format);
 
-        info!("Sub [{}] {:.60}", sub_id, query);
+        // `auth_token` in the request body takes precedence; otherwise fall
+        // back to an `Auth-Token` NATS message header, for clients that'd
+        // rather keep credentials out of the JSON body.
+        let credential = req.auth_token.clone().unwrap_or_else(|| {
+            msg.headers
+                .as_ref()
+                .and_then(|h| h.get("Auth-Token"))
+                .map(|v| v.to_string())
+                .unwrap_or_default()
+        });
 
-        // Subscribe with client-provided subscription_id
-        let result = match self.subs.subscribe(&sub_id, &query, identity_columns, mode) {
-            Ok(r) => r,
-            Err(e) => return SubscribeResponse::err(&e),
-        };
-
-        // Subject format: livequery.{subscription_id}.events
-        let subject = self.cfg.sub_events_subject(&sub_id);
-        let sub_id = result.subscription_id.to_string();
-
-        if result.is_new_query {
-            // New query - execute and initialize snapshot
-            let rows = match db.query_rows_typed(&query).await {
-                Ok(r) => r,
-                Err(e) => {
-                    self.subs.unsubscribe(&sub_id);
-                    return SubscribeResponse::err(&format!("Query failed: {e}"));
-                }
-            };
-
-            let query = self.subs.get_query(&result.query_id);
-            return match mode {
-                SubscriptionMode::Events => {
-                    let snapshot = query
-                        .map(|q| q.snap.write().init_rows(rows, &q.cols))
-                        .unwrap_or_default();
-                    SubscribeResponse::ok_events(sub_id, subject, true, 0, snapshot)
-                }
-                SubscriptionMode::Snapshot => {
-                    let rows = query
-                        .map(|q| q.snap.write().init_rows_snapshot(rows, &q.cols))
-                        .unwrap_or_default();
-                    SubscribeResponse::ok_snapshot(sub_id, subject, true, 0, rows)
-                }
-            };
-        }
+        info!("Sub [{}] {:.60}", sub_id, req.query);
 
-        // Existing query - return current snapshot
-        let Some(query) = self.subs.get_query(&result.query_id) else {
-            return SubscribeResponse::err("Query not found");
-        };
-        let sub = self.subs.get_sub(&sub_id);
-        let mode = sub.map(|s| s.mode).unwrap_or_default();
-
-        match mode {
-            SubscriptionMode::Events => {
-                let rows = query.snap.read().get_all_rows();
-                let snapshot: Vec<_> = rows
-                    .into_iter()
-                    .map(|d| SubscribeEvent::insert_arc(0, d))
-                    .collect();
-                SubscribeResponse::ok_events(sub_id, subject, false, result.seq, snapshot)
-            }
-            SubscriptionMode::Snapshot => {
-                let rows = query.snap.read().get_all_rows();
-                SubscribeResponse::ok_snapshot(sub_id, subject, false, result.seq, rows)
-            }
-        }
+        execute_subscribe(
+            &self.cfg,
+            &self.subs,
+            db,
+            sub_id,
+            req.query,
+            req.identity_columns,
+            req.mode,
+            format,
+            req.resume_from_seq,
+            &credential,
+        )
+        .await
     }
 
     async fn reply<T: serde::Serialize>(&self, reply_to: &Option<async_nats::Subject>, data: &T) {
@@ -199,30 +297,105 @@ impl NatsHandler {
         }
     }
 
-    /// Publish pre-serialized bytes to subscription subject (zero-copy)
+    /// Publish pre-serialized bytes to subscription subject (zero-copy).
+    /// Routes through JetStream (awaiting the `PubAck`, surfacing publish
+    /// errors/backpressure instead of swallowing them) when
+    /// `Config::jetstream_enabled`; otherwise core-NATS fire-and-forget.
     #[inline]
     pub async fn publish_bytes(&self, sub_id: &str, bytes: Bytes) -> Result<()> {
         let subject = self.cfg.sub_events_subject(sub_id);
         self.msgs_out.fetch_add(1, Relaxed);
-        self.nc.publish(subject, bytes).await?;
+        match &self.js {
+            Some(js) => {
+                js.publish(subject, bytes).await?.await?;
+            }
+            None => {
+                self.nc.publish(subject, bytes).await?;
+            }
+        }
         Ok(())
     }
 
-    /// Batch publish - accumulate messages and flush once (reduces syscalls)
+    /// Batch publish - accumulate messages and flush once (reduces syscalls).
+    /// Each message carries a content-type hint for the wire format it was
+    /// encoded with, so subscribers negotiating MessagePack/CBOR can decode
+    /// without guessing. In JetStream mode each publish is awaited for its
+    /// `PubAck` individually (JetStream has no batched-flush equivalent of
+    /// core NATS's `flush`), so a slow/unavailable stream backpressures the
+    /// caller instead of silently dropping events.
     #[inline]
-    pub async fn publish_batch(&self, messages: &[(&str, Bytes)]) -> Result<()> {
+    pub async fn publish_batch(&self, messages: &[(&str, Bytes, WireFormat)]) -> Result<()> {
         if messages.is_empty() {
             return Ok(());
         }
-        for (sub_id, bytes) in messages {
+        for (sub_id, bytes, format) in messages {
             let subject = self.cfg.sub_events_subject(sub_id);
-            self.nc.publish(subject, bytes.clone()).await?;
+            let mut headers = async_nats::HeaderMap::new();
+            headers.insert("Content-Type", format.content_type());
+            match &self.js {
+                Some(js) => {
+                    js.publish_with_headers(subject, headers, bytes.clone())
+                        .await?
+                        .await?;
+                }
+                None => {
+                    self.nc
+                        .publish_with_headers(subject, headers, bytes.clone())
+                        .await?;
+                }
+            }
         }
         self.msgs_out.fetch_add(messages.len() as u64, Relaxed);
-        self.nc.flush().await?;
+        if self.js.is_none() {
+            self.nc.flush().await?;
+        }
         Ok(())
     }
 
+    /// Replay the missed tail of `sub_id`'s JetStream-retained events from
+    /// `start_seq` (the JetStream stream sequence, not `SharedQuery`'s own
+    /// batch `seq`, which is what `infra::subscribe::execute_subscribe`'s
+    /// `resume_from_seq` counts) onward, for a reconnecting
+    /// client whose gap has fallen outside the in-memory `replay_buffer_cap`
+    /// - an ephemeral pull consumer scoped to just this call, since the
+    /// replay itself is a one-shot catch-up rather than an ongoing delivery
+    /// path. Returns `Ok(None)` when JetStream mode is off.
+    ///
+    /// Note: wiring a reconnecting client's `resume_from_seq` into this
+    /// replay (instead of today's `ReplayResult::ResyncRequired` fallback to
+    /// a full resnapshot once the in-memory buffer is exhausted) needs the
+    /// client to track the JetStream stream sequence from each delivered
+    /// event rather than `SharedQuery`'s batch seq; left for a follow-up
+    /// that threads a `mz_js_seq` hint onto delivered events, same as
+    /// `ack`/`ack_gone`/ `heartbeat` are separate control subjects from the
+    /// data path today.
+    pub async fn replay_from_jetstream(
+        &self,
+        sub_id: &str,
+        start_seq: u64,
+    ) -> Result<Option<Vec<Bytes>>> {
+        let Some(js) = &self.js else {
+            return Ok(None);
+        };
+        let stream = js.get_stream(&self.cfg.jetstream_stream_name).await?;
+        let consumer = stream
+            .create_consumer(jetstream::consumer::pull::Config {
+                filter_subject: self.cfg.sub_events_subject(sub_id),
+                deliver_policy: DeliverPolicy::ByStartSequence {
+                    start_sequence: start_seq,
+                },
+                ..Default::default()
+            })
+            .await?;
+
+        let mut messages = consumer.fetch().max_messages(1000).messages().await?;
+        let mut payloads = Vec::new();
+        while let Some(Ok(msg)) = messages.next().await {
+            payloads.push(msg.payload.clone());
+        }
+        Ok(Some(payloads))
+    }
+
     /// Get stats: (messages_in, messages_out)
     #[inline]
     pub fn stats(&self) -> (u64, u64) {
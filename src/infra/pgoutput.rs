@@ -2,14 +2,172 @@
 
 use crate::core::row::{RowData, RowValue};
 use crate::infra::intern_col_name;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHasher};
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
+/// OIDs the built-in table recognizes directly; see [`ColParser::resolve`].
+mod oid {
+    pub const BOOL: u32 = 16;
+    pub const INT8: u32 = 20;
+    pub const INT2: u32 = 21;
+    pub const INT4: u32 = 23;
+    pub const FLOAT4: u32 = 700;
+    pub const FLOAT8: u32 = 701;
+    pub const JSON: u32 = 114;
+    pub const JSONB: u32 = 3802;
+    pub const DATE: u32 = 1082;
+    pub const TIME: u32 = 1083;
+    pub const TIMESTAMP: u32 = 1114;
+    pub const TIMESTAMPTZ: u32 = 1184;
+    pub const NUMERIC: u32 = 1700;
+    pub const UUID: u32 = 2950;
+}
+
+/// Per-OID decoding override for [`PgOutputDecoder`], letting callers teach
+/// the decoder how to interpret columns the built-in OID table doesn't know
+/// (e.g. a domain type) or doesn't decode the way they want.
+#[derive(Debug, Clone)]
+pub enum Conversion {
+    Bytes,
+    Integer,
+    Float,
+    Boolean,
+    /// Parse as `%Y-%m-%d %H:%M:%S%.f` (Postgres's default timestamp
+    /// text output), naive - treated as UTC.
+    Timestamp,
+    /// Parse a naive timestamp with a caller-supplied `chrono` format,
+    /// treated as UTC.
+    TimestampFmt(String),
+    /// Parse a timezone-aware timestamp with a caller-supplied `chrono`
+    /// format, normalized to UTC.
+    TimestampTzFmt(String),
+}
+
+impl Conversion {
+    /// Apply this conversion to `s`, falling back to an interned string on
+    /// parse failure so a row is never dropped.
+    fn apply(&self, s: &str) -> RowValue {
+        match self {
+            Conversion::Bytes => RowValue::Bytes(s.as_bytes().to_vec()),
+            Conversion::Integer => s
+                .parse()
+                .map(RowValue::Int)
+                .unwrap_or_else(|_| RowValue::intern_str(s)),
+            Conversion::Float => s
+                .parse()
+                .map(RowValue::Float)
+                .unwrap_or_else(|_| RowValue::intern_str(s)),
+            Conversion::Boolean => match s.as_bytes().first() {
+                Some(b't') => RowValue::Bool(true),
+                Some(b'f') => RowValue::Bool(false),
+                _ => RowValue::intern_str(s),
+            },
+            Conversion::Timestamp => parse_timestamp_micros(s, DEFAULT_TS_FMT)
+                .map(RowValue::Timestamp)
+                .unwrap_or_else(|| RowValue::intern_str(s)),
+            Conversion::TimestampFmt(fmt) => parse_timestamp_micros(s, fmt)
+                .map(RowValue::Timestamp)
+                .unwrap_or_else(|| RowValue::intern_str(s)),
+            Conversion::TimestampTzFmt(fmt) => parse_timestamptz_micros(s, fmt)
+                .map(RowValue::Timestamp)
+                .unwrap_or_else(|| RowValue::intern_str(s)),
+        }
+    }
+}
+
 /// Column metadata (interned name + type OID for parsing)
 #[derive(Clone)]
 pub struct ColMeta {
     pub name: Arc<str>,
     pub oid: u32,
+    /// `oid` resolved to a parsing strategy once, at `Relation`-decode time,
+    /// so `parse_tuple` never re-probes `conversions` or re-matches on `oid`
+    /// per column per row - see [`ColParser`].
+    parser: ColParser,
+}
+
+/// A column's resolved parsing strategy, computed once per `ColMeta` from
+/// its OID (and any `Conversion` override) instead of re-dispatching on OID
+/// for every value of every row. Plays the role of a per-column function
+/// pointer table; an enum rather than a bare `fn(&str) -> RowValue` because
+/// `Conversion::TimestampFmt`/`TimestampTzFmt` close over an owned format
+/// string a raw pointer can't carry.
+#[derive(Clone)]
+enum ColParser {
+    Bool,
+    Int,
+    Float,
+    Json,
+    Timestamp,
+    TimestampTz,
+    Date,
+    Time,
+    Numeric,
+    Uuid,
+    Str,
+    Override(Conversion),
+}
+
+impl ColParser {
+    fn resolve(oid: u32, conversions: &FxHashMap<u32, Conversion>) -> Self {
+        if let Some(c) = conversions.get(&oid) {
+            return ColParser::Override(c.clone());
+        }
+        match oid {
+            oid::BOOL => ColParser::Bool,
+            oid::INT8 | oid::INT2 | oid::INT4 => ColParser::Int,
+            oid::FLOAT4 | oid::FLOAT8 => ColParser::Float,
+            oid::JSON | oid::JSONB => ColParser::Json,
+            oid::TIMESTAMP => ColParser::Timestamp,
+            oid::TIMESTAMPTZ => ColParser::TimestampTz,
+            oid::DATE => ColParser::Date,
+            oid::TIME => ColParser::Time,
+            oid::NUMERIC => ColParser::Numeric,
+            oid::UUID => ColParser::Uuid,
+            _ => ColParser::Str,
+        }
+    }
+
+    #[inline(always)]
+    fn parse(&self, s: &str) -> RowValue {
+        match self {
+            ColParser::Override(c) => c.apply(s),
+            ColParser::Bool => match s.as_bytes().first() {
+                Some(b't') => RowValue::Bool(true),
+                Some(b'f') => RowValue::Bool(false),
+                _ => RowValue::intern_str(s),
+            },
+            ColParser::Int => s
+                .parse()
+                .map(RowValue::Int)
+                .unwrap_or_else(|_| RowValue::intern_str(s)),
+            ColParser::Float => s
+                .parse()
+                .map(RowValue::Float)
+                .unwrap_or_else(|_| RowValue::intern_str(s)),
+            ColParser::Json => serde_json::from_str(s)
+                .map(RowValue::Json)
+                .unwrap_or_else(|_| RowValue::intern_str(s)),
+            ColParser::Timestamp => parse_timestamp_micros(s, DEFAULT_TS_FMT)
+                .map(RowValue::Timestamp)
+                .unwrap_or_else(|| RowValue::intern_str(s)),
+            ColParser::TimestampTz => parse_timestamptz_micros(s, DEFAULT_TSTZ_FMT)
+                .map(RowValue::Timestamp)
+                .unwrap_or_else(|| RowValue::intern_str(s)),
+            ColParser::Date => parse_date_micros(s)
+                .map(RowValue::Timestamp)
+                .unwrap_or_else(|| RowValue::intern_str(s)),
+            ColParser::Time => parse_time_micros(s)
+                .map(RowValue::Timestamp)
+                .unwrap_or_else(|| RowValue::intern_str(s)),
+            ColParser::Numeric => RowValue::Numeric(Arc::from(s)),
+            ColParser::Uuid => parse_uuid_bytes(s)
+                .map(RowValue::Uuid)
+                .unwrap_or_else(|| RowValue::intern_str(s)),
+            ColParser::Str => RowValue::intern_str(s),
+        }
+    }
 }
 
 /// Decoded WAL change - simplified enum for hot path
@@ -18,42 +176,216 @@ pub enum WalChange {
     Begin,
     Commit,
     Insert { rel: u32, row: RowData },
-    Update { rel: u32, row: RowData },
-    Delete { rel: u32 },
+    Update { rel: u32, row: RowData, old: Option<OldTuple> },
+    /// `row` carries the deleted tuple's K (key-only, non-key columns Null)
+    /// or O (full, under `REPLICA IDENTITY FULL`) data, so retraction events
+    /// can include identity columns instead of forcing a full requery.
+    Delete { rel: u32, row: RowData },
     Truncate { rels: Vec<u32> },
     Other,
 }
 
+/// Old tuple carried by an UPDATE's K/O section, used for column-level change
+/// detection. `values[i] == None` means the column was omitted by pgoutput
+/// (unchanged TOAST, or not part of the replica identity) rather than truly
+/// NULL - callers must treat omitted columns as unchanged, not as a diff.
+#[derive(Debug)]
+pub struct OldTuple {
+    pub col_names: Arc<[Arc<str>]>,
+    pub values: Vec<Option<RowValue>>,
+}
+
+/// Cap on last-known rows cached per relation (see `RelCache::row_cache`),
+/// bounding memory under high key cardinality via FIFO eviction.
+const ROW_CACHE_CAP: usize = 4096;
+
 /// Relation cache entry
 struct RelCache {
     table: Arc<str>,
     cols: Arc<[ColMeta]>,
     /// Pre-computed column names for RowData creation (avoids re-cloning per row)
     col_names: Arc<[Arc<str>]>,
+    /// Columns used to key `row_cache`, learned from the first K/O tuple we
+    /// see on an UPDATE for this relation (the present columns of a K tuple
+    /// are exactly the replica identity; for O they're the whole row, which
+    /// still works as a key, just a larger one).
+    identity_cols: Option<Arc<[Arc<str>]>>,
+    /// Last-known full row per identity-column hash, used to backfill
+    /// TOASTed columns pgoutput omits (`u`) when they weren't changed.
+    row_cache: FxHashMap<u64, RowData>,
+    /// Insertion order for FIFO eviction once `row_cache` hits `ROW_CACHE_CAP`.
+    row_cache_order: std::collections::VecDeque<u64>,
+}
+
+impl RelCache {
+    fn cache_row(&mut self, key: u64, row: RowData) {
+        if !self.row_cache.contains_key(&key) {
+            if self.row_cache_order.len() >= ROW_CACHE_CAP {
+                if let Some(oldest) = self.row_cache_order.pop_front() {
+                    self.row_cache.remove(&oldest);
+                }
+            }
+            self.row_cache_order.push_back(key);
+        }
+        self.row_cache.insert(key, row);
+    }
+}
+
+/// Hash the identity columns' values out of an UPDATE's old tuple (pre-image),
+/// which stay stable across updates to the same row even as other columns
+/// change - usable as a cache key before the new row exists.
+fn key_from_old(old: &OldTuple, identity_cols: &[Arc<str>]) -> u64 {
+    let mut h = FxHasher::default();
+    for name in identity_cols {
+        name.hash(&mut h);
+        if let Some(pos) = old.col_names.iter().position(|c| c == name) {
+            if let Some(Some(v)) = old.values.get(pos) {
+                v.hash_into(&mut h);
+            }
+        }
+    }
+    h.finish()
+}
+
+/// Hash the identity columns' values out of a decoded row, for caching it
+/// after an INSERT or UPDATE.
+fn key_from_row(row: &RowData, identity_cols: &[Arc<str>]) -> u64 {
+    let mut h = FxHasher::default();
+    for name in identity_cols {
+        name.hash(&mut h);
+        if let Some(v) = row.get(name) {
+            v.hash_into(&mut h);
+        }
+    }
+    h.finish()
+}
+
+/// The identity columns for a relation are the ones present (`Some`) in its
+/// first observed K/O tuple.
+fn identity_cols_from_old(old: &OldTuple) -> Arc<[Arc<str>]> {
+    Arc::from(
+        old.col_names
+            .iter()
+            .zip(old.values.iter())
+            .filter_map(|(name, v)| v.is_some().then(|| name.clone()))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// How far above `base_oid` [`RelTable`]'s dense array will grow before an
+/// outlier OID falls back to `overflow` instead - bounds memory if relation
+/// OIDs turn out sparser than the "clustered within one publication"
+/// assumption holds for.
+const DENSE_CAP: usize = 4096;
+
+/// Relation cache keyed by pgoutput relation OID, indexed as a dense array
+/// instead of a hash map. A publication's relation OIDs are assigned by the
+/// catalog and cluster tightly, so after the first-seen OID fixes
+/// `base_oid`, every later lookup is `oid - base_oid` into `dense` - an O(1)
+/// array index - rather than a hash probe. OIDs below `base_oid` or more
+/// than `DENSE_CAP` above it are rare outliers and go in `overflow`.
+struct RelTable {
+    base_oid: Option<u32>,
+    dense: Vec<Option<RelCache>>,
+    overflow: FxHashMap<u32, RelCache>,
+}
+
+impl RelTable {
+    fn new() -> Self {
+        Self {
+            base_oid: None,
+            dense: Vec::new(),
+            overflow: FxHashMap::default(),
+        }
+    }
+
+    #[inline(always)]
+    fn dense_index(&self, oid: u32) -> Option<usize> {
+        let base = self.base_oid?;
+        let idx = oid.checked_sub(base)? as usize;
+        (idx < DENSE_CAP).then_some(idx)
+    }
+
+    #[inline(always)]
+    fn get(&self, oid: u32) -> Option<&RelCache> {
+        match self.dense_index(oid) {
+            Some(idx) => self.dense.get(idx).and_then(|s| s.as_ref()),
+            None => self.overflow.get(&oid),
+        }
+    }
+
+    #[inline(always)]
+    fn get_mut(&mut self, oid: u32) -> Option<&mut RelCache> {
+        match self.dense_index(oid) {
+            Some(idx) => self.dense.get_mut(idx).and_then(|s| s.as_mut()),
+            None => self.overflow.get_mut(&oid),
+        }
+    }
+
+    fn insert(&mut self, oid: u32, rel: RelCache) {
+        if self.base_oid.is_none() {
+            self.base_oid = Some(oid);
+        }
+        match self.dense_index(oid) {
+            Some(idx) => {
+                if idx >= self.dense.len() {
+                    self.dense.resize_with(idx + 1, || None);
+                }
+                self.dense[idx] = Some(rel);
+            }
+            None => {
+                self.overflow.insert(oid, rel);
+            }
+        }
+    }
 }
 
 /// pgoutput decoder with relation cache
 pub struct PgOutputDecoder {
-    rels: FxHashMap<u32, RelCache>,
+    rels: RelTable,
+    /// OID overrides consulted before the built-in table; see [`Conversion`].
+    conversions: FxHashMap<u32, Conversion>,
 }
 
 impl PgOutputDecoder {
     #[inline]
     pub fn new() -> Self {
         Self {
-            rels: FxHashMap::default(),
+            rels: RelTable::new(),
+            conversions: FxHashMap::default(),
+        }
+    }
+
+    /// Create a decoder with per-OID conversion overrides, e.g. to force an
+    /// unrecognized domain type to decode as a timestamp with a custom
+    /// format string.
+    pub fn with_conversions(conversions: FxHashMap<u32, Conversion>) -> Self {
+        Self {
+            rels: RelTable::new(),
+            conversions,
         }
     }
 
     #[inline]
     pub fn get_table(&self, rel: u32) -> Option<Arc<str>> {
-        self.rels.get(&rel).map(|r| r.table.clone())
+        self.rels.get(rel).map(|r| r.table.clone())
     }
 
     /// Decode pgoutput binary message - optimized hot path
     #[inline]
     pub fn decode(&mut self, data: &[u8]) -> Option<WalChange> {
         let b = data.first()?;
+        let kind = match b {
+            b'B' => "begin",
+            b'C' => "commit",
+            b'R' => "relation",
+            b'I' => "insert",
+            b'U' => "update",
+            b'D' => "delete",
+            b'T' => "truncate",
+            _ => "other",
+        };
+        crate::telemetry::record_pgoutput_decode(kind);
         match b {
             b'B' => (data.len() >= 21).then_some(WalChange::Begin),
             b'C' => (data.len() >= 26).then_some(WalChange::Commit),
@@ -94,6 +426,7 @@ impl PgOutputDecoder {
             cols.push(ColMeta {
                 name: intern_col_name(&name.to_ascii_lowercase()),
                 oid,
+                parser: ColParser::resolve(oid, &self.conversions),
             });
         }
 
@@ -105,47 +438,99 @@ impl PgOutputDecoder {
                 table: table_arc,
                 cols: Arc::from(cols),
                 col_names,
+                identity_cols: None,
+                row_cache: FxHashMap::default(),
+                row_cache_order: std::collections::VecDeque::new(),
             },
         );
         Some(WalChange::Other)
     }
 
-    fn parse_insert(&self, data: &[u8]) -> Option<WalChange> {
+    fn parse_insert(&mut self, data: &[u8]) -> Option<WalChange> {
         let mut p = 1;
         let rel = read_u32(data, &mut p)?;
         if data.get(p)? != &b'N' {
             return None;
         }
         p += 1;
-        let cache = self.rels.get(&rel)?;
-        let row = parse_tuple(data, &mut p, &cache.cols, &cache.col_names)?;
+        let cache = self.rels.get_mut(rel)?;
+        let row = parse_tuple(
+            data,
+            &mut p,
+            &cache.cols,
+            &cache.col_names,
+            None,
+            RowValue::Unchanged,
+        )?;
+        if let Some(ids) = cache.identity_cols.clone() {
+            cache.cache_row(key_from_row(&row, &ids), row.clone());
+        }
         Some(WalChange::Insert { rel, row })
     }
 
-    fn parse_update(&self, data: &[u8]) -> Option<WalChange> {
+    fn parse_update(&mut self, data: &[u8]) -> Option<WalChange> {
         let mut p = 1;
         let rel = read_u32(data, &mut p)?;
-        let cache = self.rels.get(&rel)?;
+        let cache = self.rels.get_mut(rel)?;
 
-        // Skip old tuple if present
+        // Old tuple (K = key columns only, O = full row under REPLICA IDENTITY FULL)
+        let mut old = None;
         if matches!(data.get(p), Some(b'K') | Some(b'O')) {
             p += 1;
-            skip_tuple(data, &mut p)?;
+            let ot = parse_old_tuple(data, &mut p, &cache.cols, &cache.col_names)?;
+            if cache.identity_cols.is_none() {
+                cache.identity_cols = Some(identity_cols_from_old(&ot));
+            }
+            old = Some(ot);
         }
 
         if data.get(p)? != &b'N' {
             return None;
         }
         p += 1;
-        let row = parse_tuple(data, &mut p, &cache.cols, &cache.col_names)?;
-        Some(WalChange::Update { rel, row })
+
+        let mut prior = None;
+        if let (Some(o), Some(ids)) = (&old, &cache.identity_cols) {
+            prior = cache.row_cache.get(&key_from_old(o, ids));
+        }
+
+        let row = parse_tuple(
+            data,
+            &mut p,
+            &cache.cols,
+            &cache.col_names,
+            prior,
+            RowValue::Unchanged,
+        )?;
+
+        if let Some(ids) = cache.identity_cols.clone() {
+            cache.cache_row(key_from_row(&row, &ids), row.clone());
+        }
+        Some(WalChange::Update { rel, row, old })
     }
 
     fn parse_delete(&self, data: &[u8]) -> Option<WalChange> {
         let mut p = 1;
         let rel = read_u32(data, &mut p)?;
-        // We don't need old row data, just trigger requery
-        Some(WalChange::Delete { rel })
+        let cache = self.rels.get(rel)?;
+
+        // K = key columns only, O = full row under REPLICA IDENTITY FULL.
+        // Either way, absent (non-identity) columns come back `Null` here -
+        // deletes don't get TOAST backfill since there's no "new row" to
+        // merge into.
+        if !matches!(data.get(p), Some(b'K') | Some(b'O')) {
+            return None;
+        }
+        p += 1;
+        let row = parse_tuple(
+            data,
+            &mut p,
+            &cache.cols,
+            &cache.col_names,
+            None,
+            RowValue::Null,
+        )?;
+        Some(WalChange::Delete { rel, row })
     }
 
     fn parse_truncate(&self, data: &[u8]) -> Option<WalChange> {
@@ -198,6 +583,8 @@ fn parse_tuple(
     p: &mut usize,
     cols: &[ColMeta],
     col_names: &Arc<[Arc<str>]>,
+    prior: Option<&RowData>,
+    unresolved: RowValue,
 ) -> Option<RowData> {
     let n = read_u16(data, p)? as usize;
     let mut vals: Vec<RowValue> = Vec::with_capacity(n);
@@ -206,21 +593,33 @@ fn parse_tuple(
         let col = cols.get(i)?;
 
         match *data.get(*p)? {
-            b'n' | b'u' => {
+            b'n' => {
                 *p += 1;
                 vals.push(RowValue::Null);
             }
+            b'u' => {
+                *p += 1;
+                // Unchanged TOAST column - backfill from the last cached row
+                // for this identity, or fall back to `unresolved` if we have
+                // none (callers distinguish "don't know" from plain Null).
+                let backfilled = prior
+                    .and_then(|r| r.get(&col.name))
+                    .cloned()
+                    .unwrap_or_else(|| unresolved.clone());
+                vals.push(backfilled);
+            }
             b't' => {
                 *p += 1;
                 let len = read_u32(data, p)? as usize;
                 let text = std::str::from_utf8(data.get(*p..*p + len)?).ok()?;
                 *p += len;
-                vals.push(parse_val(text, col.oid));
+                vals.push(col.parser.parse(text));
             }
             b'b' => {
                 *p += 1;
                 let len = read_u32(data, p)? as usize;
-                vals.push(RowValue::Bytes(data.get(*p..*p + len)?.to_vec()));
+                let bytes = data.get(*p..*p + len)?;
+                vals.push(parse_val_binary(bytes, col.oid));
                 *p += len;
             }
             _ => return None,
@@ -230,50 +629,125 @@ fn parse_tuple(
     Some(RowData::new(col_names.clone(), vals))
 }
 
-/// Skip tuple without parsing (for old row in update)
+/// Parse an UPDATE's old tuple (K/O section), preserving the n/u distinction
+/// so callers can tell "omitted" columns apart from true NULLs.
 #[inline]
-fn skip_tuple(data: &[u8], p: &mut usize) -> Option<()> {
+fn parse_old_tuple(
+    data: &[u8],
+    p: &mut usize,
+    cols: &[ColMeta],
+    col_names: &Arc<[Arc<str>]>,
+) -> Option<OldTuple> {
     let n = read_u16(data, p)? as usize;
-    for _ in 0..n {
+    let mut values = Vec::with_capacity(n);
+
+    for i in 0..n {
+        let col = cols.get(i)?;
         match *data.get(*p)? {
-            b'n' | b'u' => {
+            b'n' => {
+                *p += 1;
+                values.push(Some(RowValue::Null));
+            }
+            b'u' => {
+                *p += 1;
+                values.push(None);
+            }
+            b't' => {
                 *p += 1;
+                let len = read_u32(data, p)? as usize;
+                let text = std::str::from_utf8(data.get(*p..*p + len)?).ok()?;
+                *p += len;
+                values.push(Some(col.parser.parse(text)));
             }
-            b't' | b'b' => {
+            b'b' => {
                 *p += 1;
                 let len = read_u32(data, p)? as usize;
+                let bytes = data.get(*p..*p + len)?;
+                values.push(Some(parse_val_binary(bytes, col.oid)));
                 *p += len;
             }
             _ => return None,
         }
     }
-    Some(())
+    Some(OldTuple {
+        col_names: col_names.clone(),
+        values,
+    })
 }
 
-/// Parse value by OID - optimized with fast paths
+/// Micros between the Unix epoch and the Postgres epoch (2000-01-01 UTC),
+/// which binary timestamp/timestamptz values are counted from.
+const PG_EPOCH_OFFSET_MICROS: i64 = 946_684_800_000_000;
+
+/// Decode a `b`-marked binary column value for the int, float, bool,
+/// timestamp/timestamptz and uuid OIDs, giving the same `RowValue` variants
+/// the text path gives them. Falls back to raw `RowValue::Bytes` for every
+/// other OID (json/jsonb, date, time and numeric included, which the text
+/// path does decode) and for malformed lengths.
 #[inline(always)]
-fn parse_val(s: &str, oid: u32) -> RowValue {
+fn parse_val_binary(data: &[u8], oid: u32) -> RowValue {
     match oid {
-        16 => match s.as_bytes().first() {
-            Some(b't') => RowValue::Bool(true),
-            Some(b'f') => RowValue::Bool(false),
-            _ => RowValue::intern_str(s),
-        },
-        20 | 21 | 23 => s
-            .parse()
-            .map(RowValue::Int)
-            .unwrap_or_else(|_| RowValue::intern_str(s)),
-        700 | 701 => s
-            .parse()
-            .map(RowValue::Float)
-            .unwrap_or_else(|_| RowValue::intern_str(s)),
-        114 | 3802 => serde_json::from_str(s)
-            .map(RowValue::Json)
-            .unwrap_or_else(|_| RowValue::intern_str(s)),
-        _ => RowValue::intern_str(s),
+        oid::INT2 if data.len() == 2 => {
+            RowValue::Int(i16::from_be_bytes(data.try_into().unwrap()) as i64)
+        }
+        oid::INT4 if data.len() == 4 => {
+            RowValue::Int(i32::from_be_bytes(data.try_into().unwrap()) as i64)
+        }
+        oid::INT8 if data.len() == 8 => {
+            RowValue::Int(i64::from_be_bytes(data.try_into().unwrap()))
+        }
+        oid::FLOAT4 if data.len() == 4 => {
+            RowValue::Float(f32::from_be_bytes(data.try_into().unwrap()) as f64)
+        }
+        oid::FLOAT8 if data.len() == 8 => {
+            RowValue::Float(f64::from_be_bytes(data.try_into().unwrap()))
+        }
+        oid::BOOL if data.len() == 1 => RowValue::Bool(data[0] != 0),
+        oid::TIMESTAMP | oid::TIMESTAMPTZ if data.len() == 8 => {
+            let pg_micros = i64::from_be_bytes(data.try_into().unwrap());
+            RowValue::Timestamp(pg_micros.saturating_add(PG_EPOCH_OFFSET_MICROS))
+        }
+        oid::UUID if data.len() == 16 => RowValue::Uuid(data.try_into().unwrap()),
+        _ => RowValue::Bytes(data.to_vec()),
     }
 }
 
+const DEFAULT_TS_FMT: &str = "%Y-%m-%d %H:%M:%S%.f";
+const DEFAULT_TSTZ_FMT: &str = "%Y-%m-%d %H:%M:%S%.f%#z";
+
+/// Parse Postgres's default naive-timestamp text output into UTC micros.
+fn parse_timestamp_micros(s: &str, fmt: &str) -> Option<i64> {
+    chrono::NaiveDateTime::parse_from_str(s, fmt)
+        .ok()
+        .map(|dt| dt.and_utc().timestamp_micros())
+}
+
+/// Parse an offset-qualified timestamp into UTC micros.
+fn parse_timestamptz_micros(s: &str, fmt: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_str(s, fmt)
+        .ok()
+        .map(|dt| dt.with_timezone(&chrono::Utc).timestamp_micros())
+}
+
+/// Parse a date into UTC micros at midnight.
+fn parse_date_micros(s: &str) -> Option<i64> {
+    chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc().timestamp_micros())
+}
+
+/// Parse a time-of-day into micros since midnight.
+fn parse_time_micros(s: &str) -> Option<i64> {
+    chrono::NaiveTime::parse_from_str(s, "%H:%M:%S%.f")
+        .ok()
+        .and_then(|t| (t - chrono::NaiveTime::MIN).num_microseconds())
+}
+
+fn parse_uuid_bytes(s: &str) -> Option<[u8; 16]> {
+    uuid::Uuid::parse_str(s).ok().map(|u| *u.as_bytes())
+}
+
 impl Default for PgOutputDecoder {
     fn default() -> Self {
         Self::new()
@@ -294,11 +768,216 @@ mod tests {
         assert!(matches!(decoder.decode(&data), Some(WalChange::Begin)));
     }
 
+    fn parse_val(s: &str, oid: u32, conversions: &FxHashMap<u32, Conversion>) -> RowValue {
+        ColParser::resolve(oid, conversions).parse(s)
+    }
+
     #[test]
     fn test_parse_val() {
-        assert!(matches!(parse_val("t", 16), RowValue::Bool(true)));
-        assert!(matches!(parse_val("f", 16), RowValue::Bool(false)));
-        assert!(matches!(parse_val("42", 23), RowValue::Int(42)));
-        assert!(matches!(parse_val("3.14", 701), RowValue::Float(_)));
+        let conv = FxHashMap::default();
+        assert!(matches!(parse_val("t", 16, &conv), RowValue::Bool(true)));
+        assert!(matches!(parse_val("f", 16, &conv), RowValue::Bool(false)));
+        assert!(matches!(parse_val("42", 23, &conv), RowValue::Int(42)));
+        assert!(matches!(parse_val("3.14", 701, &conv), RowValue::Float(_)));
+    }
+
+    #[test]
+    fn test_parse_val_timestamp_numeric_uuid() {
+        let conv = FxHashMap::default();
+        assert!(matches!(
+            parse_val("2024-01-01 12:00:00.5", 1114, &conv),
+            RowValue::Timestamp(_)
+        ));
+        assert!(matches!(
+            parse_val("2024-01-01 12:00:00+00", 1184, &conv),
+            RowValue::Timestamp(_)
+        ));
+        assert!(matches!(
+            parse_val("123.456", 1700, &conv),
+            RowValue::Numeric(_)
+        ));
+        assert!(matches!(
+            parse_val("a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11", 2950, &conv),
+            RowValue::Uuid(_)
+        ));
+    }
+
+    #[test]
+    fn test_parse_val_binary() {
+        assert!(matches!(
+            parse_val_binary(&42i32.to_be_bytes(), 23),
+            RowValue::Int(42)
+        ));
+        assert!(matches!(
+            parse_val_binary(&[1], 16),
+            RowValue::Bool(true)
+        ));
+        let uuid_bytes = [0u8; 16];
+        assert!(matches!(
+            parse_val_binary(&uuid_bytes, 2950),
+            RowValue::Uuid(_)
+        ));
+        // Unknown OID falls back to raw bytes
+        assert!(matches!(
+            parse_val_binary(&[1, 2, 3], 99999),
+            RowValue::Bytes(_)
+        ));
+    }
+
+    #[test]
+    fn test_parse_val_conversion_override() {
+        let mut conv = FxHashMap::default();
+        conv.insert(99999, Conversion::Integer);
+        assert!(matches!(parse_val("7", 99999, &conv), RowValue::Int(7)));
+    }
+
+    fn relation_msg(rel: u32, cols: &[(&str, u32)]) -> Vec<u8> {
+        let mut data = vec![b'R'];
+        data.extend_from_slice(&rel.to_be_bytes());
+        data.push(0); // schema
+        data.extend_from_slice(b"public\0");
+        data.push(b'd'); // replica identity
+        data.extend_from_slice(&(cols.len() as u16).to_be_bytes());
+        for (name, oid) in cols {
+            data.push(0); // flags
+            data.extend_from_slice(name.as_bytes());
+            data.push(0);
+            data.extend_from_slice(&oid.to_be_bytes());
+            data.extend_from_slice(&0u32.to_be_bytes());
+        }
+        data
+    }
+
+    fn text_col(v: &str) -> Vec<u8> {
+        let mut out = vec![b't'];
+        out.extend_from_slice(&(v.len() as u32).to_be_bytes());
+        out.extend_from_slice(v.as_bytes());
+        out
+    }
+
+    #[test]
+    fn test_update_backfills_unchanged_toast_from_cache() {
+        let mut decoder = PgOutputDecoder::new();
+        decoder.decode(&relation_msg(1, &[("id", 23), ("body", 25)]));
+
+        // First UPDATE carries a full new tuple and a K old-tuple, which
+        // teaches the decoder `id` is the identity column and caches the
+        // full row under it.
+        let mut update1 = vec![b'U'];
+        update1.extend_from_slice(&1u32.to_be_bytes());
+        update1.push(b'K');
+        update1.extend_from_slice(&1u16.to_be_bytes());
+        update1.extend(text_col("1"));
+        update1.push(b'N');
+        update1.extend_from_slice(&2u16.to_be_bytes());
+        update1.extend(text_col("1"));
+        update1.extend(text_col("hello"));
+        let WalChange::Update { row: first, .. } = decoder.decode(&update1).unwrap() else {
+            panic!("expected update");
+        };
+        assert_eq!(first.get("body"), Some(&RowValue::intern_str("hello")));
+
+        // Second UPDATE to the same identity omits body ('u') - should
+        // backfill from the row cached by the first update, not fall back
+        // to Unchanged.
+        let mut update2 = vec![b'U'];
+        update2.extend_from_slice(&1u32.to_be_bytes());
+        update2.push(b'K');
+        update2.extend_from_slice(&1u16.to_be_bytes());
+        update2.extend(text_col("1"));
+        update2.push(b'N');
+        update2.extend_from_slice(&2u16.to_be_bytes());
+        update2.extend(text_col("1"));
+        update2.push(b'u');
+
+        let WalChange::Update { row: second, .. } = decoder.decode(&update2).unwrap() else {
+            panic!("expected update");
+        };
+        assert_eq!(second.get("body"), Some(&RowValue::intern_str("hello")));
+    }
+
+    #[test]
+    fn test_update_unresolved_toast_without_cache_is_unchanged() {
+        let mut decoder = PgOutputDecoder::new();
+        decoder.decode(&relation_msg(2, &[("id", 23), ("body", 25)]));
+
+        // UPDATE with no prior cached row for this identity - the omitted
+        // column has no value to backfill from.
+        let mut update = vec![b'U'];
+        update.extend_from_slice(&2u32.to_be_bytes());
+        update.push(b'K');
+        update.extend_from_slice(&1u16.to_be_bytes());
+        update.extend(text_col("9"));
+        update.push(b'N');
+        update.extend_from_slice(&2u16.to_be_bytes());
+        update.extend(text_col("9"));
+        update.push(b'u');
+
+        let WalChange::Update { row, .. } = decoder.decode(&update).unwrap() else {
+            panic!("expected update");
+        };
+        assert_eq!(row.get("body"), Some(&RowValue::Unchanged));
+    }
+
+    #[test]
+    fn test_rel_table_dense_lookup_and_outlier_overflow() {
+        let mut t = RelTable::new();
+        let rel = |table: &str| RelCache {
+            table: Arc::from(table),
+            cols: Arc::from(Vec::<ColMeta>::new()),
+            col_names: Arc::from(Vec::<Arc<str>>::new()),
+            identity_cols: None,
+            row_cache: FxHashMap::default(),
+            row_cache_order: std::collections::VecDeque::new(),
+        };
+
+        // First insert fixes base_oid; a neighboring OID lands in `dense`.
+        t.insert(16384, rel("users"));
+        t.insert(16385, rel("orders"));
+        assert_eq!(t.get(16384).unwrap().table.as_ref(), "users");
+        assert_eq!(t.get(16385).unwrap().table.as_ref(), "orders");
+
+        // An OID far above base_oid (outside DENSE_CAP) overflows instead of
+        // growing `dense` to match it.
+        let far = 16384 + DENSE_CAP as u32 + 1;
+        t.insert(far, rel("archive"));
+        assert_eq!(t.get(far).unwrap().table.as_ref(), "archive");
+        assert!(t.dense.len() < DENSE_CAP);
+
+        // An OID below base_oid also overflows rather than panicking.
+        t.insert(100, rel("early"));
+        assert_eq!(t.get(100).unwrap().table.as_ref(), "early");
+
+        assert!(t.get(99999999).is_none());
+    }
+
+    #[test]
+    fn test_decode_interleaved_relations_use_correct_column_sets() {
+        let mut decoder = PgOutputDecoder::new();
+        decoder.decode(&relation_msg(16384, &[("id", 23), ("name", 25)]));
+        decoder.decode(&relation_msg(16385, &[("id", 23), ("total", 701)]));
+
+        let mut insert_users = vec![b'I'];
+        insert_users.extend_from_slice(&16384u32.to_be_bytes());
+        insert_users.push(b'N');
+        insert_users.extend_from_slice(&2u16.to_be_bytes());
+        insert_users.extend(text_col("1"));
+        insert_users.extend(text_col("alice"));
+
+        let mut insert_orders = vec![b'I'];
+        insert_orders.extend_from_slice(&16385u32.to_be_bytes());
+        insert_orders.push(b'N');
+        insert_orders.extend_from_slice(&2u16.to_be_bytes());
+        insert_orders.extend(text_col("1"));
+        insert_orders.extend(text_col("9.5"));
+
+        let WalChange::Insert { row: user, .. } = decoder.decode(&insert_users).unwrap() else {
+            panic!("expected insert");
+        };
+        let WalChange::Insert { row: order, .. } = decoder.decode(&insert_orders).unwrap() else {
+            panic!("expected insert");
+        };
+        assert_eq!(user.get("name"), Some(&RowValue::intern_str("alice")));
+        assert_eq!(order.get("total"), Some(&RowValue::Float(9.5)));
     }
 }
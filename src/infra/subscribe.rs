@@ -0,0 +1,121 @@
+//! Shared subscribe flow - register with `SubscriptionManager`, run the
+//! initial query for a brand-new shared query, and build the response the
+//! caller's transport serializes onto the wire. Used by both
+//! `infra::nats`'s NATS request/reply subject and `infra::gateway`'s
+//! SSE/WebSocket handlers, so the two transports never drift apart on
+//! subscribe semantics (replay, resync, snapshot shape).
+
+use crate::core::event::*;
+use crate::core::subscription::{ReplayResult, SubscriptionManager};
+use crate::core::Config;
+use crate::infra::DbPool;
+
+/// Register `sub_id` on `query` and build its `SubscribeResponse`: for a
+/// brand-new shared query this runs `query` against `db` and seeds the
+/// snapshot; for an existing one it returns the current snapshot (or a
+/// replay from `resume_from_seq`, if given). `credential` is passed through
+/// to `SubscriptionManager::subscribe` for `AuthProvider` authentication.
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_subscribe(
+    cfg: &Config,
+    subs: &SubscriptionManager,
+    db: &DbPool,
+    sub_id: String,
+    query: String,
+    identity_columns: Option<Vec<String>>,
+    mode: SubscriptionMode,
+    format: WireFormat,
+    resume_from_seq: Option<u64>,
+    credential: &str,
+) -> SubscribeResponse {
+    let result = match subs.subscribe(&sub_id, &query, identity_columns, mode, format, credential) {
+        Ok(r) => r,
+        Err((code, msg)) => return SubscribeResponse::err(code, &msg),
+    };
+
+    let subject = cfg.sub_events_subject(&sub_id);
+    let sub_id = result.subscription_id.to_string();
+
+    if result.is_new_query {
+        // New query - execute and initialize snapshot, bounded by
+        // max_in_flight_snapshots independently of the subscription/query
+        // caps already enforced by `subscribe` above.
+        if let Err(code) = subs.try_reserve_snapshot_slot() {
+            subs.unsubscribe(&sub_id);
+            return SubscribeResponse::err(code, "Too many concurrent snapshot queries");
+        }
+        let rows = db.query_rows_typed(&query).await;
+        subs.release_snapshot_slot();
+        let rows = match rows {
+            Ok(r) => r,
+            Err(e) => {
+                subs.unsubscribe(&sub_id);
+                subs.record_rejection(SubError::QueryFailed);
+                return SubscribeResponse::err(SubError::QueryFailed, &format!("Query failed: {e}"));
+            }
+        };
+
+        let shared = subs.get_query(&result.query_id);
+        return match mode {
+            // Coalesced has nothing pending yet on a brand-new query, so
+            // its initial snapshot is just the insert events, same as
+            // Events - coalescing only kicks in on later changes.
+            SubscriptionMode::Events | SubscriptionMode::Coalesced => {
+                let snapshot = shared
+                    .map(|q| q.snap.write().init_rows(rows, &q.cols, q.clock.as_ref()))
+                    .unwrap_or_default();
+                SubscribeResponse::ok_events(sub_id, subject, true, 0, snapshot, false)
+            }
+            SubscriptionMode::Snapshot => {
+                let rows = shared
+                    .map(|q| q.snap.write().init_rows_snapshot(rows, &q.cols))
+                    .unwrap_or_default();
+                SubscribeResponse::ok_snapshot(sub_id, subject, true, 0, rows, false)
+            }
+        };
+    }
+
+    // Existing query - return current snapshot
+    let Some(shared) = subs.get_query(&result.query_id) else {
+        subs.record_rejection(SubError::NotFound);
+        return SubscribeResponse::err(SubError::NotFound, "Query not found");
+    };
+    let sub = subs.get_sub(&sub_id);
+    let mode = sub.map(|s| s.mode).unwrap_or_default();
+
+    if let Some(last_acked) = resume_from_seq {
+        match shared.replay_since(last_acked) {
+            ReplayResult::ResyncRequired => {
+                return SubscribeResponse::resync_required(sub_id, subject)
+            }
+            ReplayResult::Batches(batches) => {
+                let seq = batches.last().map(|b| b.seq).unwrap_or(result.seq);
+                return match mode {
+                    SubscriptionMode::Events | SubscriptionMode::Coalesced => {
+                        let events = batches.into_iter().flat_map(|b| b.events).collect();
+                        SubscribeResponse::ok_events(sub_id, subject, false, seq, events, true)
+                    }
+                    SubscriptionMode::Snapshot => {
+                        let rows = shared.snap.read().get_all_rows();
+                        SubscribeResponse::ok_snapshot(sub_id, subject, false, seq, rows, true)
+                    }
+                };
+            }
+        }
+    }
+
+    match mode {
+        SubscriptionMode::Events | SubscriptionMode::Coalesced => {
+            let rows = shared.snap.read().get_all_rows();
+            let snapshot: Vec<_> = rows
+                .into_iter()
+                .map(|d| SubscribeEvent::insert_arc(0, d))
+                .collect();
+            SubscribeResponse::ok_events(sub_id, subject, false, result.seq, snapshot, false)
+        }
+        SubscriptionMode::Snapshot => {
+            let rows = shared.snap.read().get_all_rows();
+            SubscribeResponse::ok_snapshot(sub_id, subject, false, result.seq, rows, false)
+        }
+    }
+}
@@ -0,0 +1,5 @@
+//! Alternative client-facing transports that sit alongside `infra::gateway`'s
+//! native SSE/WS framing, for ecosystems with an existing client library
+//! that expects a specific protocol on the wire.
+
+pub mod socketio;
@@ -0,0 +1,212 @@
+//! Engine.IO v4 / Socket.IO v4 text-frame protocol, for browsers using
+//! `socket.io-client` instead of `infra::gateway`'s native WS framing. This
+//! module only encodes/decodes frames; `infra::gateway::socketio_handler`
+//! owns the actual WebSocket loop and maps one Socket.IO room per query
+//! subscription id, same as `infra::gateway::ws_handler` maps one `id` per
+//! `SubscriptionManager` subscription.
+//!
+//! Scope: WebSocket transport only. Engine.IO also defines an HTTP
+//! long-polling transport (the fallback a browser uses before it knows a
+//! WebSocket will succeed) - that's a separate request/response protocol
+//! this module doesn't implement, so a client must be configured with
+//! `transports: ["websocket"]` to skip the polling handshake entirely.
+//! Only the default `/` namespace is handled; namespaced (`/ns,...`) and
+//! acked (`42<id>...`) packets are not decoded (see [`decode`]).
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Engine.IO packet type - the first character of every text frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngineType {
+    Open,
+    Close,
+    Ping,
+    Pong,
+    Message,
+    Upgrade,
+    Noop,
+}
+
+impl EngineType {
+    fn code(self) -> char {
+        match self {
+            EngineType::Open => '0',
+            EngineType::Close => '1',
+            EngineType::Ping => '2',
+            EngineType::Pong => '3',
+            EngineType::Message => '4',
+            EngineType::Upgrade => '5',
+            EngineType::Noop => '6',
+        }
+    }
+
+    fn from_code(c: char) -> Option<Self> {
+        match c {
+            '0' => Some(EngineType::Open),
+            '1' => Some(EngineType::Close),
+            '2' => Some(EngineType::Ping),
+            '3' => Some(EngineType::Pong),
+            '4' => Some(EngineType::Message),
+            '5' => Some(EngineType::Upgrade),
+            '6' => Some(EngineType::Noop),
+            _ => None,
+        }
+    }
+}
+
+/// Socket.IO packet type - the character immediately after an Engine.IO `4`
+/// (message) prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketType {
+    Connect,
+    Disconnect,
+    Event,
+    Ack,
+    ConnectError,
+}
+
+impl SocketType {
+    fn from_code(c: char) -> Option<Self> {
+        match c {
+            '0' => Some(SocketType::Connect),
+            '1' => Some(SocketType::Disconnect),
+            '2' => Some(SocketType::Event),
+            '3' => Some(SocketType::Ack),
+            '4' => Some(SocketType::ConnectError),
+            _ => None,
+        }
+    }
+}
+
+/// A decoded Socket.IO frame, stripped of its Engine.IO `4` wrapper. Only
+/// the default `/` namespace is supported - see module docs.
+#[derive(Debug, Clone)]
+pub struct SocketPacket {
+    pub kind: SocketType,
+    pub data: Value,
+}
+
+#[derive(Serialize)]
+struct HandshakeData<'a> {
+    sid: &'a str,
+    upgrades: [&'static str; 0],
+    #[serde(rename = "pingInterval")]
+    ping_interval: u64,
+    #[serde(rename = "pingTimeout")]
+    ping_timeout: u64,
+}
+
+/// `0{...}` - the Engine.IO handshake, first frame sent on every connection.
+pub fn encode_open(sid: &str, ping_interval_ms: u64, ping_timeout_ms: u64) -> String {
+    let data = HandshakeData {
+        sid,
+        upgrades: [],
+        ping_interval: ping_interval_ms,
+        ping_timeout: ping_timeout_ms,
+    };
+    format!("{}{}", EngineType::Open.code(), serde_json::to_string(&data).unwrap_or_default())
+}
+
+/// `2` - Engine.IO liveness probe; the client must reply with a `3` (pong)
+/// frame within `ping_timeout_ms` of `encode_open`'s handshake or be disconnected.
+pub fn encode_ping() -> String {
+    EngineType::Ping.code().to_string()
+}
+
+/// `40{}` - Socket.IO CONNECT ack for the default namespace, sent in reply
+/// to the client's own `40`.
+pub fn encode_connect_ack() -> String {
+    format!("{}{}{{}}", EngineType::Message.code(), '0')
+}
+
+/// `42["event",payload]` - a Socket.IO EVENT packet on the default
+/// namespace, the frame every `SubscribeEvent`/snapshot push is wrapped in.
+pub fn encode_event<T: Serialize>(event: &str, payload: &T) -> String {
+    let body = serde_json::json!([event, payload]);
+    format!(
+        "{}{}{}",
+        EngineType::Message.code(),
+        '2',
+        serde_json::to_string(&body).unwrap_or_default()
+    )
+}
+
+/// Parse one text frame's Engine.IO type, and - for `message` frames - the
+/// Socket.IO packet inside it. A namespaced (`/ns,...`) or acked (`42<id>...`)
+/// packet returns `None` for the inner packet since neither is supported;
+/// the Engine.IO type is still returned so e.g. a stray `pong` isn't treated
+/// as a protocol error.
+pub fn decode(frame: &str) -> Option<(EngineType, Option<SocketPacket>)> {
+    let mut chars = frame.chars();
+    let engine_type = EngineType::from_code(chars.next()?)?;
+    if engine_type != EngineType::Message {
+        return Some((engine_type, None));
+    }
+    let rest = chars.as_str();
+    let mut rest_chars = rest.chars();
+    let Some(socket_type) = SocketType::from_code(rest_chars.next().unwrap_or('?')) else {
+        return Some((engine_type, None));
+    };
+    let body = rest_chars.as_str();
+    if body.starts_with('/') || body.starts_with(|c: char| c.is_ascii_digit()) {
+        // Namespaced or acked packet - unsupported, see module docs.
+        return Some((engine_type, None));
+    }
+    let data = if body.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_str(body).ok()?
+    };
+    Some((engine_type, Some(SocketPacket { kind: socket_type, data })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshake_round_trips() {
+        let open = encode_open("abc123", 25000, 20000);
+        assert!(open.starts_with('0'));
+        let (ty, packet) = decode(&open).unwrap();
+        assert_eq!(ty, EngineType::Open);
+        assert!(packet.is_none()); // open isn't a `message` frame
+
+        let ack = encode_connect_ack();
+        let (ty, packet) = decode(&ack).unwrap();
+        assert_eq!(ty, EngineType::Message);
+        assert_eq!(packet.unwrap().kind, SocketType::Connect);
+    }
+
+    #[test]
+    fn event_round_trips_insert_and_delete() {
+        let insert = serde_json::json!({"mz_timestamp": 1, "mz_diff": 1, "data": {"id": 1}});
+        let frame = encode_event("events", &insert);
+        assert!(frame.starts_with("42"));
+        let (ty, packet) = decode(&frame).unwrap();
+        assert_eq!(ty, EngineType::Message);
+        let packet = packet.unwrap();
+        assert_eq!(packet.kind, SocketType::Event);
+        assert_eq!(packet.data, serde_json::json!(["events", insert]));
+
+        let delete = serde_json::json!({"mz_timestamp": 2, "mz_diff": -1, "data": {"id": 1}});
+        let frame = encode_event("events", &delete);
+        let (_, packet) = decode(&frame).unwrap();
+        assert_eq!(packet.unwrap().data, serde_json::json!(["events", delete]));
+    }
+
+    #[test]
+    fn ping_has_no_inner_packet() {
+        let (ty, packet) = decode(&encode_ping()).unwrap();
+        assert_eq!(ty, EngineType::Ping);
+        assert!(packet.is_none());
+    }
+
+    #[test]
+    fn namespaced_packet_is_rejected() {
+        let (ty, packet) = decode("42/chat,[\"x\"]").unwrap();
+        assert_eq!(ty, EngineType::Message);
+        assert!(packet.is_none());
+    }
+}
@@ -0,0 +1,135 @@
+//! Resolves enum, composite, and domain OIDs into a fully `Kind`-tagged
+//! `tokio_postgres::types::Type`, the same job the `postgres` crate's own
+//! internal `typeinfo`/`typeinfo_enum`/`typeinfo_composite` statement
+//! caching does per connection - except cached once per `DbPool` instead
+//! of once per pooled connection, so two connections that both see the
+//! same custom type don't each pay for the `pg_type`/`pg_enum`/
+//! `pg_attribute` round trips.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use anyhow::{Context, Result};
+use deadpool_postgres::GenericClient;
+use dashmap::DashMap;
+use rustc_hash::FxBuildHasher;
+use tokio_postgres::types::{Field, Kind, Oid, Type};
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// `Type` cache keyed by OID, shared across every connection a `DbPool`
+/// hands out.
+pub struct TypeCatalog {
+    cache: DashMap<Oid, Type, FxBuildHasher>,
+}
+
+impl TypeCatalog {
+    pub fn new() -> Self {
+        Self {
+            cache: DashMap::with_hasher(FxBuildHasher),
+        }
+    }
+
+    /// Return `ty` as-is if it's already fully resolved (a builtin, or
+    /// something the connection's own `prepare` already described with a
+    /// non-`Simple` `Kind`) - otherwise resolve and cache it by OID.
+    pub async fn resolve<C: GenericClient + Sync>(&self, client: &C, ty: &Type) -> Result<Type> {
+        if Type::from_oid(ty.oid()).is_some() || !matches!(ty.kind(), Kind::Simple) {
+            return Ok(ty.clone());
+        }
+        self.resolve_oid(client, ty.oid()).await
+    }
+
+    fn resolve_oid<'a, C: GenericClient + Sync>(
+        &'a self,
+        client: &'a C,
+        oid: Oid,
+    ) -> BoxFuture<'a, Result<Type>> {
+        Box::pin(async move {
+            if let Some(builtin) = Type::from_oid(oid) {
+                return Ok(builtin);
+            }
+            if let Some(cached) = self.cache.get(&oid) {
+                return Ok(cached.clone());
+            }
+            let resolved = self.resolve_uncached(client, oid).await?;
+            self.cache.insert(oid, resolved.clone());
+            Ok(resolved)
+        })
+    }
+
+    async fn resolve_uncached<C: GenericClient + Sync>(
+        &self,
+        client: &C,
+        oid: Oid,
+    ) -> Result<Type> {
+        let row = client
+            .query_one(
+                "SELECT t.typname, t.typtype::text, t.typbasetype, t.typelem, t.typrelid, \
+                 n.nspname \
+                 FROM pg_type t JOIN pg_namespace n ON n.oid = t.typnamespace \
+                 WHERE t.oid = $1",
+                &[&oid],
+            )
+            .await
+            .with_context(|| format!("resolving pg_type for oid {oid}"))?;
+
+        let name: String = row.get(0);
+        let typtype: String = row.get(1);
+        let typbasetype: Oid = row.get(2);
+        let typelem: Oid = row.get(3);
+        let typrelid: Oid = row.get(4);
+        let nspname: String = row.get(5);
+
+        let kind = match typtype.as_str() {
+            // Enum: the set of labels, in declaration order.
+            "e" => {
+                let labels = client
+                    .query(
+                        "SELECT enumlabel FROM pg_enum WHERE enumtypid = $1 ORDER BY enumsortorder",
+                        &[&oid],
+                    )
+                    .await
+                    .with_context(|| format!("resolving pg_enum labels for oid {oid}"))?
+                    .iter()
+                    .map(|r| r.get(0))
+                    .collect();
+                Kind::Enum(labels)
+            }
+            // Composite: one Field per live column of the backing pg_class row.
+            "c" => {
+                let attrs = client
+                    .query(
+                        "SELECT attname, atttypid FROM pg_attribute \
+                         WHERE attrelid = $1 AND attnum > 0 AND NOT attisdropped \
+                         ORDER BY attnum",
+                        &[&typrelid],
+                    )
+                    .await
+                    .with_context(|| format!("resolving pg_attribute for composite oid {oid}"))?;
+                let mut fields = Vec::with_capacity(attrs.len());
+                for attr in &attrs {
+                    let field_name: String = attr.get(0);
+                    let field_oid: Oid = attr.get(1);
+                    let field_ty = self.resolve_oid(client, field_oid).await?;
+                    fields.push(Field::new(field_name, field_ty));
+                }
+                Kind::Composite(fields)
+            }
+            // Domain: decode as whatever the domain is layered on top of.
+            "d" => Kind::Domain(self.resolve_oid(client, typbasetype).await?),
+            // Not an enum/composite/domain but has an element type - it's an
+            // array of something `Type::from_oid` didn't already cover.
+            _ if typelem != 0 => Kind::Array(self.resolve_oid(client, typelem).await?),
+            _ => Kind::Simple,
+        };
+
+        Ok(Type::new(name, oid, kind, nspname))
+    }
+}
+
+impl Default for TypeCatalog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -7,19 +7,221 @@ use pgwire_replication::{Lsn, ReplicationClient, ReplicationConfig, ReplicationE
 use rustc_hash::{FxHashMap, FxHashSet};
 use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tracing::{debug, error, info, trace};
 use url::Url;
 
-use crate::core::event::SubscriptionMode;
+use crate::core::codec::{self, RowCodec};
+use crate::core::event::{EventBatch, SubscriptionMode, WireFormat};
 use crate::core::query::{EvalResult, WhereFilter};
-use crate::core::row::RowData;
+use crate::core::row::{RowData, RowValue};
+use crate::core::wire::encode_rows_arrow;
+use crate::core::subscription::push_pending_op_events;
 use crate::core::{Config, SubscriptionManager};
-use crate::infra::pgoutput::{PgOutputDecoder, WalChange};
+use crate::infra::pgoutput::{OldTuple, PgOutputDecoder, WalChange};
 use crate::infra::{DbPool, NatsHandler};
 
 const MAX_CONCURRENT: usize = 8;
 
+/// Accumulated changes for one table within a single WAL transaction.
+#[derive(Default)]
+struct TableChanges {
+    rows: Vec<RowData>,
+    /// Deleted rows this transaction, carrying identity (REPLICA IDENTITY)
+    /// columns only - non-identity columns come back `Null` (see
+    /// `pgoutput::parse_delete`). Kept separate from `rows` since a delete
+    /// has no content to diff against a query's filter: membership in
+    /// `Snapshot::rows` is the only thing that decides whether it mattered
+    /// (see `WalStreamer::apply_incremental`).
+    deletes: Vec<RowData>,
+    /// Columns known to have changed, populated only when every change this
+    /// transaction is an UPDATE with an old-tuple we could diff against.
+    changed_cols: FxHashSet<Arc<str>>,
+    /// True once we can no longer narrow the change to specific columns
+    /// (insert, truncate, or an update with no usable old tuple).
+    all_changed: bool,
+}
+
+/// Mark columns that differ between the old and new tuple of an UPDATE.
+/// Columns pgoutput omitted from the old tuple (TOASTed-but-unchanged, or not
+/// part of the replica identity) are treated as unchanged, not as a diff.
+fn mark_changed_cols(tc: &mut TableChanges, old: &OldTuple, new: &RowData) {
+    for (i, col) in old.col_names.iter().enumerate() {
+        let Some(Some(old_val)) = old.values.get(i) else {
+            continue; // omitted column - unknown, assume unchanged
+        };
+        let changed = match new.get(col) {
+            // Column wasn't resolvable from the TOAST-backfill cache either -
+            // no information either way, so don't assume it changed.
+            Some(RowValue::Unchanged) => false,
+            Some(new_val) => new_val != old_val,
+            None => true,
+        };
+        if changed {
+            tc.changed_cols.insert(col.clone());
+        }
+    }
+}
+
+/// Whether `tc`'s changes to one table can be skipped entirely for a query
+/// with this `filter`/`ref_cols`, instead of being routed to requery or
+/// incremental maintenance - extracted out of `WalStreamer::process` so the
+/// two optimizations are unit-testable without a live `SubscriptionManager`/
+/// NATS connection.
+fn can_skip(is_simple: bool, filter: &WhereFilter, ref_cols: Option<&[Box<str>]>, tc: &TableChanges) -> bool {
+    // WHERE filter optimization: no changed row can match. Doesn't apply to
+    // deletes - whether a deleted row mattered depends on snapshot
+    // membership, not on whether it matches the filter (the row may have
+    // matched before whatever change made it no longer exist).
+    let filter_skip = is_simple
+        && !tc.rows.is_empty()
+        && tc.deletes.is_empty()
+        && !matches!(filter, WhereFilter::None)
+        && !tc.rows.iter().any(|r| !matches!(filter.eval_row(r), EvalResult::NoMatch));
+
+    // Column-dependency optimization: none of the query's referenced columns
+    // actually changed this transaction. Doesn't apply to deletes - whether a
+    // deleted row mattered depends on snapshot membership, not on which
+    // columns changed.
+    let column_skip = !filter_skip
+        && is_simple
+        && !tc.all_changed
+        && tc.deletes.is_empty()
+        && ref_cols.is_some_and(|cols| !cols.iter().any(|c| tc.changed_cols.contains(c.as_ref())));
+
+    filter_skip || column_skip
+}
+
+/// Encode `batch` once per distinct wire format among `groups` and append a
+/// `(subject, bytes, format)` entry per subscriber, so the same batch isn't
+/// re-encoded for every subscriber sharing a format. `WireFormat::Binary`
+/// gets the columnar `EventBatch::encode_binary` framing and
+/// `WireFormat::Arrow` gets `EventBatch::encode_arrow` (both using the
+/// query's fixed SELECT column list, if any) instead of the generic
+/// MessagePack fallback `WireFormat::encode` would otherwise use. `Arrow`
+/// without a fixed column list has no schema to build a `RecordBatch`
+/// against, so it falls back to MessagePack same as `Binary` does.
+fn push_encoded_batch<'a>(
+    messages: &mut Vec<(&'a str, Bytes, WireFormat)>,
+    groups: &'a FxHashMap<WireFormat, Vec<Arc<str>>>,
+    batch: &EventBatch,
+    select_cols: Option<&[Arc<str>]>,
+) {
+    for (fmt, ids) in groups {
+        let bytes = match (fmt, select_cols) {
+            (WireFormat::Binary, _) => Bytes::from(batch.encode_binary(select_cols)),
+            (WireFormat::Arrow, Some(cols)) => Bytes::from(batch.encode_arrow(cols)),
+            _ => Bytes::from(fmt.encode(batch)),
+        };
+        for sid in ids {
+            messages.push((sid.as_ref(), bytes.clone(), *fmt));
+        }
+    }
+}
+
+/// Same as `push_encoded_batch`, but for a `Snapshot`-mode publish's `(seq,
+/// ts, rows)` triple specifically: `WireFormat::MessagePack` gets
+/// `core::codec::encode_rows`'s dictionary-encoded framing (column names and
+/// repeated string/numeric values interned once per message instead of once
+/// per row) behind an 8-byte `[seq][ts]` header, and `WireFormat::Arrow` gets
+/// `core::wire::encode_rows_arrow`'s IPC stream (using the query's fixed
+/// SELECT column list, if any), rather than the generic `WireFormat::encode`
+/// falling back to plain `rmp_serde` of the JSON rows for both. `Arrow`
+/// without a fixed column list has no schema to build a `RecordBatch`
+/// against, so it falls back to `MessagePack` same as `push_encoded_batch`'s
+/// `Binary`/`Arrow` handling does. Every other format keeps encoding
+/// `{seq, ts, rows}` as a single JSON-shaped value.
+fn push_encoded_snapshot<'a>(
+    messages: &mut Vec<(&'a str, Bytes, WireFormat)>,
+    groups: &'a FxHashMap<WireFormat, Vec<Arc<str>>>,
+    seq: u64,
+    ts: u64,
+    rows: &[Arc<serde_json::Value>],
+    select_cols: Option<&[Arc<str>]>,
+) {
+    let row_data = || -> Vec<RowData> { rows.iter().map(|v| RowData::from_value(v)).collect() };
+    for (fmt, ids) in groups {
+        let bytes = match (fmt, select_cols) {
+            (WireFormat::MessagePack, _) | (WireFormat::Arrow, None) => {
+                let mut buf = Vec::with_capacity(16);
+                buf.extend_from_slice(&seq.to_le_bytes());
+                buf.extend_from_slice(&ts.to_le_bytes());
+                buf.extend_from_slice(&codec::encode_rows(RowCodec::MessagePack, &row_data()));
+                Bytes::from(buf)
+            }
+            (WireFormat::Arrow, Some(cols)) => Bytes::from(encode_rows_arrow(&row_data(), cols)),
+            _ => Bytes::from(fmt.encode(&serde_json::json!({ "seq": seq, "ts": ts, "rows": rows }))),
+        };
+        for sid in ids {
+            messages.push((sid.as_ref(), bytes.clone(), *fmt));
+        }
+    }
+}
+
+/// Drain `qid`'s coalescing buffer and publish the net events to its
+/// `SubscriptionMode::Coalesced` subscribers. Called both from `requery`
+/// (once `Config::coalesce_max_pending` is crossed mid-stream) and the
+/// periodic flush task in `main` (driven by `Config::coalesce_window_ms`).
+pub async fn publish_coalesced(qid: &Arc<str>, subs: &SubscriptionManager, nats: &NatsHandler) {
+    let Some(q) = subs.get_query(qid) else {
+        return;
+    };
+    let events = q.flush_coalesced();
+    if events.is_empty() {
+        return;
+    }
+
+    let mut groups: FxHashMap<WireFormat, Vec<Arc<str>>> = FxHashMap::default();
+    for sid in q.subscribers.read().iter() {
+        if let Some(s) = subs.get_sub(sid) {
+            if s.mode == SubscriptionMode::Coalesced {
+                groups.entry(s.format).or_default().push(sid.clone());
+            }
+        }
+    }
+    if groups.is_empty() {
+        return;
+    }
+
+    let select_cols = q.select_cols.clone();
+    let Some(batch) = q.make_batch(events) else {
+        return;
+    };
+    drop(q);
+
+    let mut messages: Vec<(&str, Bytes, WireFormat)> = Vec::new();
+    push_encoded_batch(&mut messages, &groups, &batch, select_cols.as_deref());
+    if nats.publish_batch(&messages).await.is_err() {
+        subs.record_publish_failure();
+    }
+}
+
+/// Reap subscriptions whose heartbeat has gone stale past `timeout` (see
+/// `SubscriptionManager::cleanup`) and publish each one's terminal `Gone`
+/// batch to its own subject, in its own negotiated wire format. Called from
+/// the periodic cleanup task in `main`. Returns the number reaped.
+pub async fn reap_stale(subs: &SubscriptionManager, nats: &NatsHandler, timeout: Duration) -> usize {
+    let reaped = subs.cleanup(timeout);
+    for (sub_id, batch) in &reaped {
+        let Some(sub) = subs.get_sub(sub_id) else {
+            continue;
+        };
+        let format = sub.format;
+        let select_cols = subs.get_query(&sub.query_id).and_then(|q| q.select_cols.clone());
+        drop(sub);
+
+        let bytes = match (format, select_cols.as_deref()) {
+            (WireFormat::Binary, cols) => Bytes::from(batch.encode_binary(cols)),
+            (WireFormat::Arrow, Some(cols)) => Bytes::from(batch.encode_arrow(cols)),
+            _ => Bytes::from(format.encode(batch)),
+        };
+        if nats.publish_bytes(sub_id, bytes).await.is_err() {
+            subs.record_publish_failure();
+        }
+    }
+    reaped.len()
+}
+
 /// Streaming WAL reader using native PostgreSQL replication protocol
 pub struct WalStreamer {
     cfg: Arc<Config>,
@@ -32,6 +234,7 @@ struct WalStats {
     processed: AtomicU64,
     requeries: AtomicU64,
     skipped: AtomicU64,
+    incremental: AtomicU64,
 }
 
 impl WalStreamer {
@@ -44,6 +247,7 @@ impl WalStreamer {
                 processed: AtomicU64::new(0),
                 requeries: AtomicU64::new(0),
                 skipped: AtomicU64::new(0),
+                incremental: AtomicU64::new(0),
             },
         }
     }
@@ -114,7 +318,7 @@ impl WalStreamer {
             self.cfg.wal_slot, self.cfg.wal_publication
         );
 
-        let mut tx: FxHashMap<Arc<str>, Vec<RowData>> = FxHashMap::default();
+        let mut tx: FxHashMap<Arc<str>, TableChanges> = FxHashMap::default();
         let mut in_tx = false;
 
         while let Some(event) = client.recv().await? {
@@ -136,17 +340,31 @@ impl WalStreamer {
                                 in_tx = false;
                                 client.update_applied_lsn(wal_end);
                             }
-                            WalChange::Insert { rel, row } | WalChange::Update { rel, row } => {
+                            WalChange::Insert { rel, row } => {
+                                if let Some(t) = self.decoder.get_table(rel) {
+                                    if subs.has_table(t) {
+                                        let tc = tx.entry(Arc::from(t)).or_default();
+                                        tc.all_changed = true;
+                                        tc.rows.push(row);
+                                    }
+                                }
+                            }
+                            WalChange::Update { rel, row, old } => {
                                 if let Some(t) = self.decoder.get_table(rel) {
                                     if subs.has_table(t) {
-                                        tx.entry(Arc::from(t)).or_default().push(row);
+                                        let tc = tx.entry(Arc::from(t)).or_default();
+                                        match &old {
+                                            Some(o) => mark_changed_cols(tc, o, &row),
+                                            None => tc.all_changed = true,
+                                        }
+                                        tc.rows.push(row);
                                     }
                                 }
                             }
-                            WalChange::Delete { rel } => {
+                            WalChange::Delete { rel, row } => {
                                 if let Some(t) = self.decoder.get_table(rel) {
                                     if subs.has_table(t) {
-                                        tx.entry(Arc::from(t)).or_default();
+                                        tx.entry(Arc::from(t)).or_default().deletes.push(row);
                                     }
                                 }
                             }
@@ -154,7 +372,7 @@ impl WalStreamer {
                                 for r in rels {
                                     if let Some(t) = self.decoder.get_table(r) {
                                         if subs.has_table(t) {
-                                            tx.entry(Arc::from(t)).or_default();
+                                            tx.entry(Arc::from(t)).or_default().all_changed = true;
                                         }
                                     }
                                 }
@@ -182,14 +400,15 @@ impl WalStreamer {
 
     async fn process(
         &self,
-        changes: &FxHashMap<Arc<str>, Vec<RowData>>,
+        changes: &FxHashMap<Arc<str>, TableChanges>,
         subs: &SubscriptionManager,
         nats: &NatsHandler,
     ) {
         let mut to_requery: FxHashSet<Arc<str>> = FxHashSet::default();
+        let mut incremental_candidates: Vec<(Arc<str>, Arc<str>)> = Vec::new();
         let mut skipped = 0usize;
 
-        for (table, rows) in changes {
+        for (table, tc) in changes {
             subs.for_table_queries(table, |qid| {
                 if !to_requery.insert(qid.clone()) {
                     return;
@@ -200,22 +419,41 @@ impl WalStreamer {
                     return;
                 };
 
-                // WHERE filter optimization
-                if q.is_simple
-                    && !rows.is_empty()
-                    && !matches!(q.filter, WhereFilter::None)
-                    && !rows
-                        .iter()
-                        .any(|r| !matches!(q.filter.eval_row(r), EvalResult::NoMatch))
-                {
+                if can_skip(q.is_simple, &q.filter, q.ref_cols.as_deref(), tc) {
                     skipped += 1;
                     self.stats.skipped.fetch_add(1, Relaxed);
                     debug!("skip {}", &qid[..8.min(qid.len())]);
                     to_requery.remove(qid);
+                } else if q.can_incremental && !tc.all_changed {
+                    incremental_candidates.push((qid.clone(), table.clone()));
                 }
             });
         }
 
+        if !incremental_candidates.is_empty() {
+            let handled: Vec<Arc<str>> = futures::stream::iter(incremental_candidates)
+                .map(|(qid, table)| async move {
+                    let ok = match changes.get(&table) {
+                        Some(tc) => self.apply_incremental(tc, &qid, subs, nats).await,
+                        None => false,
+                    };
+                    ok.then_some(qid)
+                })
+                .buffer_unordered(MAX_CONCURRENT)
+                .filter_map(|r| async move { r })
+                .collect()
+                .await;
+
+            if !handled.is_empty() {
+                self.stats
+                    .incremental
+                    .fetch_add(handled.len() as u64, Relaxed);
+                for qid in handled {
+                    to_requery.remove(&qid);
+                }
+            }
+        }
+
         if to_requery.is_empty() {
             return;
         }
@@ -235,6 +473,120 @@ impl WalStreamer {
             .await;
     }
 
+    /// Maintain a single-table query's snapshot directly from its WAL rows,
+    /// skipping the SQL round-trip `requery` would otherwise need. Returns
+    /// false if a row's filter match couldn't be resolved locally, leaving
+    /// the caller to fall back to a full requery.
+    ///
+    /// Spans this diff-and-publish cycle with `qid` standing in for "the
+    /// subscription" - one call here fans a single query's diff out to every
+    /// one of its subscribers at once, so there's no single subscription id
+    /// to attach instead - plus `seq`, recorded once the outgoing batch's
+    /// sequence number is known.
+    #[tracing::instrument(skip_all, fields(qid = %qid, seq = tracing::field::Empty))]
+    async fn apply_incremental(
+        &self,
+        tc: &TableChanges,
+        qid: &Arc<str>,
+        subs: &SubscriptionManager,
+        nats: &NatsHandler,
+    ) -> bool {
+        let Some(q) = subs.get_query(qid) else {
+            return true; // query is gone, nothing left to requery either
+        };
+
+        let mut decisions = Vec::with_capacity(tc.rows.len());
+        for row in &tc.rows {
+            match q.filter.eval_row(row) {
+                EvalResult::Unknown => return false,
+                decision => decisions.push(decision),
+            }
+        }
+
+        let mut events = Vec::new();
+        {
+            let started = Instant::now();
+            let mut snap = q.snap.write();
+            for (row, decision) in tc.rows.iter().zip(decisions) {
+                match decision {
+                    EvalResult::Match => {
+                        events.extend(snap.upsert_row(row.clone(), &q.cols, q.clock.as_ref()))
+                    }
+                    EvalResult::NoMatch => {
+                        events.extend(snap.remove_row(row, &q.cols, q.clock.as_ref()))
+                    }
+                    EvalResult::Unknown => unreachable!(),
+                }
+            }
+            // A delete's row never needs a filter check: `remove_row` is a
+            // no-op unless the identity was already tracked in `snap`, which
+            // is exactly "was this PK a member?" - the same fallback the
+            // ambiguous REPLICA IDENTITY DEFAULT case for updates needs,
+            // gotten for free here since deletes never carry enough of the
+            // old row to evaluate the filter directly.
+            for row in &tc.deletes {
+                events.extend(snap.remove_row(row, &q.cols, q.clock.as_ref()));
+            }
+            drop(snap);
+            q.record_diff_time(started.elapsed());
+        }
+        if events.is_empty() {
+            return true;
+        }
+
+        let sub_ids: Vec<_> = q.subscribers.read().iter().cloned().collect();
+        let (mut ev_groups, mut snap_groups): (
+            FxHashMap<WireFormat, Vec<Arc<str>>>,
+            FxHashMap<WireFormat, Vec<Arc<str>>>,
+        ) = (FxHashMap::default(), FxHashMap::default());
+        for sid in sub_ids {
+            if let Some(s) = subs.get_sub(&sid) {
+                let bucket = match s.mode {
+                    SubscriptionMode::Events => &mut ev_groups,
+                    SubscriptionMode::Snapshot => &mut snap_groups,
+                    // This path only sees already-flattened insert/delete
+                    // events, not the classified per-row ops `fold_coalesced`
+                    // needs, so there's nothing to fold here - deliver
+                    // immediately rather than silently dropping the change.
+                    // `requery`'s classified diff is the real coalescing path.
+                    SubscriptionMode::Coalesced => &mut ev_groups,
+                };
+                bucket.entry(s.format).or_default().push(sid);
+            }
+        }
+        if ev_groups.is_empty() && snap_groups.is_empty() {
+            return true;
+        }
+
+        let snap_rows = if !snap_groups.is_empty() {
+            Some(q.snap.read().get_all_rows())
+        } else {
+            None
+        };
+        let select_cols = q.select_cols.clone();
+        let Some(batch) = q.make_batch(events) else {
+            return true;
+        };
+        drop(q);
+        tracing::Span::current().record("seq", batch.seq);
+
+        let mut messages: Vec<(&str, Bytes, WireFormat)> = Vec::new();
+
+        if !ev_groups.is_empty() {
+            push_encoded_batch(&mut messages, &ev_groups, &batch, select_cols.as_deref());
+        }
+
+        if let Some(rows) = snap_rows {
+            push_encoded_snapshot(&mut messages, &snap_groups, batch.seq, batch.ts, &rows, select_cols.as_deref());
+        }
+
+        if nats.publish_batch(&messages).await.is_err() {
+            subs.record_publish_failure();
+        }
+        true
+    }
+
+    #[tracing::instrument(skip_all, fields(qid = %qid, seq = tracing::field::Empty))]
     async fn requery(&self, qid: Arc<str>, subs: &SubscriptionManager, nats: &NatsHandler) {
         let Some(q) = subs.get_query(&qid) else {
             return;
@@ -254,59 +606,135 @@ impl WalStreamer {
             return;
         };
 
-        let events = q.snap.write().diff_rows(rows, &cols);
-        if events.is_empty() {
+        let started = Instant::now();
+        let ops = q.snap.write().diff_rows_classified(rows, &cols);
+        q.record_diff_time(started.elapsed());
+        if ops.is_empty() {
             return;
         }
 
-        let (mut ev_subs, mut snap_subs) = (Vec::new(), Vec::new());
+        let (mut ev_groups, mut snap_groups, mut has_coalesced): (
+            FxHashMap<WireFormat, Vec<Arc<str>>>,
+            FxHashMap<WireFormat, Vec<Arc<str>>>,
+            bool,
+        ) = (FxHashMap::default(), FxHashMap::default(), false);
         for sid in sub_ids {
             if let Some(s) = subs.get_sub(&sid) {
                 match s.mode {
-                    SubscriptionMode::Events => ev_subs.push(sid),
-                    SubscriptionMode::Snapshot => snap_subs.push(sid),
-                }
+                    SubscriptionMode::Events => ev_groups.entry(s.format).or_default().push(sid),
+                    SubscriptionMode::Snapshot => {
+                        snap_groups.entry(s.format).or_default().push(sid)
+                    }
+                    SubscriptionMode::Coalesced => has_coalesced = true,
+                };
             }
         }
 
-        if ev_subs.is_empty() && snap_subs.is_empty() {
-            return;
+        // Fold the classified diff into the coalescing buffer for `Coalesced`
+        // subscribers and/or flatten it into insert/delete events for
+        // `Events`/`Snapshot` subscribers - both read from the same diff pass.
+        let need_flat = !ev_groups.is_empty() || !snap_groups.is_empty();
+        let t = q.clock.now_millis() as i64;
+        let mut events = Vec::with_capacity(if need_flat { ops.len() + ops.len() / 4 } else { 0 });
+        let mut should_flush_coalesced = false;
+        for (id_hash, op) in ops {
+            match (has_coalesced, need_flat) {
+                (true, true) => {
+                    let pending_len = q.fold_coalesced(id_hash, op.clone());
+                    should_flush_coalesced |= pending_len >= self.cfg.coalesce_max_pending;
+                    push_pending_op_events(t, op, &mut events);
+                }
+                (true, false) => {
+                    let pending_len = q.fold_coalesced(id_hash, op);
+                    should_flush_coalesced |= pending_len >= self.cfg.coalesce_max_pending;
+                }
+                (false, true) => push_pending_op_events(t, op, &mut events),
+                (false, false) => {}
+            }
         }
 
-        // Lazy: only read snapshot if we have snapshot subscribers
-        let snap_rows = if !snap_subs.is_empty() {
-            Some(q.snap.read().get_all_rows())
-        } else {
-            None
-        };
-        let Some(batch) = q.make_batch(events) else {
-            return;
-        };
-        drop(q);
+        if need_flat {
+            // Lazy: only read snapshot if we have snapshot subscribers
+            let snap_rows = if !snap_groups.is_empty() {
+                Some(q.snap.read().get_all_rows())
+            } else {
+                None
+            };
+            let select_cols = q.select_cols.clone();
+            let batch = q.make_batch(events);
+            drop(q);
+
+            if let Some(batch) = batch {
+                tracing::Span::current().record("seq", batch.seq);
+                // Batch publish: collect all messages and flush once (reduces syscalls)
+                let mut messages: Vec<(&str, Bytes, WireFormat)> = Vec::new();
+
+                if !ev_groups.is_empty() {
+                    push_encoded_batch(&mut messages, &ev_groups, &batch, select_cols.as_deref());
+                }
 
-        // Batch publish: collect all messages and flush once (reduces syscalls)
-        let mut messages: Vec<(&str, Bytes)> = Vec::with_capacity(ev_subs.len() + snap_subs.len());
+                if let Some(rows) = snap_rows {
+                    push_encoded_snapshot(&mut messages, &snap_groups, batch.seq, batch.ts, &rows, select_cols.as_deref());
+                }
 
-        if !ev_subs.is_empty() {
-            let bytes = Bytes::from(serde_json::to_vec(&batch).unwrap_or_default());
-            for sid in &ev_subs {
-                messages.push((sid.as_ref(), bytes.clone()));
+                // Single flush for all messages
+                if nats.publish_batch(&messages).await.is_err() {
+                    subs.record_publish_failure();
+                }
             }
+        } else {
+            drop(q);
         }
 
-        if let Some(rows) = snap_rows {
-            let bytes = Bytes::from(
-                serde_json::to_vec(
-                    &serde_json::json!({ "seq": batch.seq, "ts": batch.ts, "rows": rows }),
-                )
-                .unwrap_or_default(),
-            );
-            for sid in &snap_subs {
-                messages.push((sid.as_ref(), bytes.clone()));
-            }
+        if should_flush_coalesced {
+            publish_coalesced(&qid, subs, nats).await;
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::query::analyze;
+
+    fn row(id: i64) -> RowData {
+        RowData::from_value(&serde_json::json!({"id": id, "status": "active"}))
+    }
 
-        // Single flush for all messages
-        let _ = nats.publish_batch(&messages).await;
+    #[test]
+    fn test_can_skip_filter_skip_when_no_row_matches() {
+        let analysis = analyze("SELECT * FROM t WHERE status = 'inactive'");
+        let tc = TableChanges {
+            rows: vec![row(1), row(2)],
+            ..Default::default()
+        };
+        assert!(can_skip(analysis.is_simple, &analysis.filter, None, &tc));
+    }
+
+    #[test]
+    fn test_can_skip_does_not_skip_a_transaction_with_a_delete() {
+        // A non-matching UPDATE alongside a DELETE of a row the snapshot may
+        // be tracking must not be skipped - the delete still needs to reach
+        // incremental maintenance (or a requery), since "does it match the
+        // filter" doesn't apply to deletes (see the comments in `can_skip`).
+        let analysis = analyze("SELECT * FROM t WHERE status = 'inactive'");
+        let tc = TableChanges {
+            rows: vec![row(1)], // doesn't match the filter on its own
+            deletes: vec![row(2)],
+            ..Default::default()
+        };
+        assert!(!can_skip(analysis.is_simple, &analysis.filter, None, &tc));
+    }
+
+    #[test]
+    fn test_can_skip_column_skip_ignored_when_deletes_present() {
+        let analysis = analyze("SELECT * FROM t");
+        let ref_cols: Arc<[Box<str>]> = Arc::from(vec!["status".into()]);
+        let tc = TableChanges {
+            deletes: vec![row(2)],
+            all_changed: false,
+            ..Default::default()
+        };
+        assert!(!can_skip(analysis.is_simple, &analysis.filter, Some(&ref_cols), &tc));
     }
 }
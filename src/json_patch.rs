@@ -0,0 +1,75 @@
+//! Minimal RFC 6902 JSON Patch diffing between two row snapshots, used by
+//! `crate::unified_subscribe::Snapshot` to emit a single `SubscribeEvent::update`
+//! instead of a delete+insert pair for identity-stable row changes.
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOp {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+}
+
+/// Diff two JSON objects key by key into a flat list of RFC 6902 operations.
+/// Only top-level key changes are modeled - a changed nested object or array
+/// always produces a single `replace` for the whole value, matching how
+/// callers already treat rows as flat column maps.
+pub fn diff(old: &Map<String, Value>, new: &Map<String, Value>) -> Vec<PatchOp> {
+    let mut ops = Vec::new();
+
+    for (key, old_value) in old {
+        match new.get(key) {
+            Some(new_value) if new_value != old_value => {
+                ops.push(PatchOp::Replace { path: format!("/{}", key), value: new_value.clone() });
+            }
+            Some(_) => {}
+            None => ops.push(PatchOp::Remove { path: format!("/{}", key) }),
+        }
+    }
+
+    for (key, new_value) in new {
+        if !old.contains_key(key) {
+            ops.push(PatchOp::Add { path: format!("/{}", key), value: new_value.clone() });
+        }
+    }
+
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn obj(v: Value) -> Map<String, Value> {
+        v.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_replace_changed_key() {
+        let old = obj(json!({"id": 1, "status": "open"}));
+        let new = obj(json!({"id": 1, "status": "closed"}));
+        let ops = diff(&old, &new);
+        assert_eq!(ops.len(), 1);
+        assert!(matches!(&ops[0], PatchOp::Replace { path, value } if path == "/status" && value == "closed"));
+    }
+
+    #[test]
+    fn test_add_and_remove_keys() {
+        let old = obj(json!({"id": 1, "legacy": true}));
+        let new = obj(json!({"id": 1, "fresh": false}));
+        let ops = diff(&old, &new);
+        assert_eq!(ops.len(), 2);
+        assert!(ops.iter().any(|o| matches!(o, PatchOp::Remove { path } if path == "/legacy")));
+        assert!(ops.iter().any(|o| matches!(o, PatchOp::Add { path, .. } if path == "/fresh")));
+    }
+
+    #[test]
+    fn test_identical_objects_produce_no_ops() {
+        let old = obj(json!({"id": 1}));
+        assert!(diff(&old, &old).is_empty());
+    }
+}
@@ -19,9 +19,15 @@ use pgrx::prelude::*;
 use pgrx::{GucContext, GucFlags, GucRegistry};
 
 mod event;
+mod filter_spec;
+mod json_patch;
+mod overflow;
 mod query_analyzer;
 mod query_dedup;
+mod query_index;
+mod row_filter;
 mod shmem;
+mod sqlstate;
 mod streaming;
 mod trigger;
 mod types;
@@ -60,6 +66,18 @@ fn register_gucs() {
     
     // Note: max_slots and buffer sizes are now compile-time constants in shmem.rs
     // See shmem::MAX_SLOTS, shmem::MAX_EVENTS_PER_SLOT, shmem::MAX_EVENT_PAYLOAD
+
+    // Idle threshold before a slot/table registry entry is eligible for LRU reclaim
+    GucRegistry::define_int_guc(
+        "pg_subscribe.lru_idle_threshold_secs",
+        "Seconds a subscription slot or table registry entry must sit idle before it can be reclaimed under capacity pressure",
+        "Default is 300 seconds",
+        &shmem::LRU_IDLE_THRESHOLD_SECS,
+        0,
+        86400,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
 }
 
 /// Main SUBSCRIBE function - unified reactive subscriptions for ANY SQL query
@@ -71,35 +89,133 @@ fn register_gucs() {
 /// * `query` - SQL SELECT query to subscribe to
 /// * `identity_columns` - Optional columns for row identity (like PRIMARY KEY)
 ///                        Improves diff performance. If not specified, uses row hash.
-/// 
+/// * `filters` - Optional JSON array of `{"column", "op", "value"}` predicates,
+///               ANDed together and applied to each row before diffing (see
+///               `row_filter::FilterOp` for the supported operators). Lets many
+///               clients share one underlying query slot while each only sees
+///               its own relevant subset of rows.
+/// * `progress` - When true, emit a `mz_progressed` heartbeat row (on the
+///                `pg_subscribe.heartbeat_interval_ms` GUC cadence) whenever
+///                a poll interval passes with no change, and a trailing one
+///                after every batch of data rows. Once a client has seen a
+///                progress row at timestamp T, every later data row is
+///                guaranteed to have `mz_timestamp > T`.
+/// * `patch_updates` - When true, a row that changes at the same identity is
+///                      sent as one `mz_diff = 2` row carrying an RFC 6902
+///                      JSON Patch (`{"identity", "patch"}`) instead of a
+///                      delete+insert pair. Defaults to false for backward
+///                      compatibility with existing `-1`/`+1` consumers.
+/// * `full_updates` - When true, a row that changes at the same identity is
+///                     sent as one `mz_diff = 2` row carrying the full old
+///                     and new row (`{"identity", "old", "new"}`) instead of
+///                     a delete+insert pair. Takes priority over
+///                     `patch_updates` if both are set. Defaults to false.
+/// * `restrictive_filters` - Optional SQL predicates (as text, e.g. `'tenant_id = 1'`)
+///                           ANDed into the query's `WHERE` before it's ever
+///                           registered or diffed. Unlike `filters`, these change
+///                           the query text itself, so clients with different
+///                           restrictive predicates never share a subscription.
+///                           A predicate that fails to parse refuses the
+///                           subscription entirely (fail closed).
+/// * `permissive_filters` - Optional SQL predicates ORed together, then ANDed
+///                          with the restrictive set - the usual row-level-security
+///                          shape of `(permissive_1 OR permissive_2 OR ...) AND restrictive...`.
+///                          A predicate that fails to parse is just skipped, which
+///                          narrows what the client sees - unless every permissive
+///                          predicate fails, in which case none applies and all
+///                          rows pass this step.
+/// * `visibility_predicate` - Optional SQL boolean expression re-checked against
+///                            every row before an INSERT/DELETE/UPDATE event for
+///                            it is emitted, even for rows reached through the
+///                            `incremental`/trigger fast path rather than a
+///                            requery. Unlike `restrictive_filters`/`permissive_filters`,
+///                            this isn't baked into the shared query text, so
+///                            subscribers with different visibility (e.g. captured
+///                            from their role at `subscribe` time) can still share
+///                            one dedup'd subscription slot. A row the predicate
+///                            can't be evaluated for is treated as not visible
+///                            (fail closed).
+///
 /// # Examples
 /// ```sql
 /// -- Simple query
 /// SELECT * FROM subscribe('SELECT * FROM users WHERE active = true');
-/// 
+///
 /// -- With identity column for efficient diffing
 /// SELECT * FROM subscribe(
 ///     'SELECT * FROM orders WHERE user_id = 123',
 ///     identity_columns => ARRAY['id']
 /// );
-/// 
+///
 /// -- Complex JOIN with aggregation
 /// SELECT * FROM subscribe(
 ///     'SELECT u.name, COUNT(o.id) FROM users u JOIN orders o ON u.id = o.user_id GROUP BY u.name',
 ///     identity_columns => ARRAY['name']
 /// );
+///
+/// -- Server-side filter, shared with other clients watching the same query
+/// SELECT * FROM subscribe(
+///     'SELECT * FROM orders',
+///     identity_columns => ARRAY['id'],
+///     filters => '[{"column": "status", "op": "eq", "value": "open"}]'
+/// );
+///
+/// -- Heartbeat rows so an idle subscription doesn't look frozen
+/// SELECT * FROM subscribe('SELECT * FROM orders', progress => true);
+///
+/// -- Field-level updates instead of delete+insert pairs
+/// SELECT * FROM subscribe(
+///     'SELECT * FROM orders',
+///     identity_columns => ARRAY['id'],
+///     patch_updates => true
+/// );
+///
+/// -- Whole-row updates instead of delete+insert pairs
+/// SELECT * FROM subscribe(
+///     'SELECT * FROM orders',
+///     identity_columns => ARRAY['id'],
+///     full_updates => true
+/// );
+///
+/// -- Row-security policy: each tenant only ever sees their own rows,
+/// -- and tenant 1's and tenant 2's clients never share a subscription
+/// SELECT * FROM subscribe(
+///     'SELECT * FROM orders',
+///     restrictive_filters => ARRAY['tenant_id = 1']
+/// );
+///
+/// -- Owners and admins see the row, everyone else doesn't
+/// SELECT * FROM subscribe(
+///     'SELECT * FROM orders',
+///     permissive_filters => ARRAY['owner_id = current_user_id()', 'is_admin']
+/// );
+///
+/// -- Many tenants share one underlying subscription slot, but each only
+/// -- ever sees INSERT/DELETE events for their own rows
+/// SELECT * FROM subscribe(
+///     'SELECT * FROM orders',
+///     visibility_predicate => 'tenant_id = current_tenant_id()'
+/// );
 /// ```
 #[pg_extern]
 fn subscribe(
     query: &str,
     identity_columns: default!(Option<Vec<String>>, "NULL"),
+    filters: default!(Option<pgrx::JsonB>, "NULL"),
+    progress: default!(bool, false),
+    patch_updates: default!(bool, false),
+    full_updates: default!(bool, false),
+    restrictive_filters: default!(Option<Vec<String>>, "NULL"),
+    permissive_filters: default!(Option<Vec<String>>, "NULL"),
+    visibility_predicate: default!(Option<String>, "NULL"),
 ) -> TableIterator<'static, (
     name!(mz_timestamp, i64),
     name!(mz_diff, i32),
     name!(mz_progressed, bool),
+    name!(mz_errcode, Option<String>),
     name!(data, pgrx::JsonB),
 )> {
-    unified_subscribe::create_unified_subscription(query, identity_columns)
+    unified_subscribe::create_unified_subscription(query, identity_columns, filters.map(|f| f.0), progress, patch_updates, full_updates, restrictive_filters, permissive_filters, visibility_predicate)
 }
 
 /// Get extension statistics
@@ -111,6 +227,26 @@ fn pg_subscribe_stats() -> TableIterator<'static, (
     shmem::get_statistics()
 }
 
+/// p50/p95/p99 latency, in microseconds, for one of the histograms kept in
+/// `shmem` - `'diff'` for `Snapshot::execute_and_diff` compute time, or
+/// `'delivery'` for the `mz_timestamp`-to-`pg_subscribe_pull_event`
+/// end-to-end lag. Errors on any other `metric`.
+#[pg_extern]
+fn pg_subscribe_latency(metric: &str) -> TableIterator<'static, (
+    name!(percentile, String),
+    name!(micros, i64),
+)> {
+    let Some(percentiles) = shmem::get_latency_percentiles(metric) else {
+        pgrx::error!("Unknown latency metric '{}', expected 'diff' or 'delivery'", metric);
+    };
+
+    TableIterator::new(vec![
+        ("p50".to_string(), percentiles.p50 as i64),
+        ("p95".to_string(), percentiles.p95 as i64),
+        ("p99".to_string(), percentiles.p99 as i64),
+    ])
+}
+
 /// List all tables with shared triggers installed
 /// Shows table name, number of interested subscriptions, and trigger status
 #[pg_extern]
@@ -138,36 +274,139 @@ fn pg_subscribe_analyze_query(query: &str) -> pgrx::JsonB {
 fn subscribe_snapshot(
     query: &str,
     identity_columns: default!(Option<Vec<String>>, "NULL"),
+    filters: default!(Option<pgrx::JsonB>, "NULL"),
 ) -> TableIterator<'static, (
     name!(mz_timestamp, i64),
     name!(mz_diff, i32),
     name!(mz_progressed, bool),
+    name!(mz_errcode, Option<String>),
     name!(data, pgrx::JsonB),
 )> {
-    unified_subscribe::create_snapshot_subscription(query, identity_columns)
+    unified_subscribe::create_snapshot_subscription(query, identity_columns, filters.map(|f| f.0))
+}
+
+/// Streaming, resumable alternative to `subscribe_snapshot` for large
+/// initial loads: returns one `page_size`-row page of `query`'s current
+/// result set, ordered by `identity_columns`, instead of buffering the
+/// whole snapshot. Pass the previous page's `next_after` back in as `after`
+/// to continue; only a page's last row carries `next_after`, and a call
+/// that returns no rows means the snapshot is exhausted. `mz_timestamp` is the same on every row in a page and is
+/// where a client should resume a live `subscribe` from once it has paged
+/// through everything.
+#[pg_extern]
+fn subscribe_snapshot_cursor(
+    query: &str,
+    identity_columns: Vec<String>,
+    after: default!(Option<pgrx::JsonB>, "NULL"),
+    page_size: default!(i64, 1000),
+) -> TableIterator<'static, (
+    name!(mz_timestamp, i64),
+    name!(data, pgrx::JsonB),
+    name!(next_after, Option<pgrx::JsonB>),
+)> {
+    let after_vals = match after.map(|a| a.0) {
+        Some(serde_json::Value::Array(values)) => Some(values),
+        Some(other) => pgrx::error!("after must be a JSON array, got {}", other),
+        None => None,
+    };
+
+    let (timestamp, rows) = match unified_subscribe::execute_snapshot_page(
+        query,
+        &identity_columns,
+        after_vals.as_deref(),
+        page_size,
+    ) {
+        Ok(r) => r,
+        Err(e) => pgrx::error!("{}", e),
+    };
+
+    let last_index = rows.len().saturating_sub(1);
+    let out: Vec<_> = rows
+        .into_iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let next_after = (i == last_index).then(|| {
+                let tuple: Vec<serde_json::Value> = identity_columns
+                    .iter()
+                    .map(|col| row.get(col).cloned().unwrap_or(serde_json::Value::Null))
+                    .collect();
+                pgrx::JsonB(serde_json::Value::Array(tuple))
+            });
+            (timestamp, pgrx::JsonB(row), next_after)
+        })
+        .collect();
+
+    TableIterator::new(out)
+}
+
+/// Declarative alternative to `subscribe` for clients who'd rather not
+/// hand-write SQL: `filters` is a JSON object or array of objects, each one
+/// AND-ing its columns together (a JSON array value OR's multiple allowed
+/// values within that column), with an array of objects OR'd together -
+/// e.g. `'{"status": ["open", "pending"]}'` or
+/// `'[{"status": "open"}, {"owner_id": 7}]'`. `since`/`until` add an extra
+/// AND'd bound on `since_column`. See `filter_spec::compile_select` for the
+/// exact SQL this compiles to - from there it flows through
+/// `query_analyzer`/`query_dedup`/`Snapshot` exactly like a hand-written
+/// `subscribe(...)` query.
+#[pg_extern]
+fn subscribe_filter(
+    table: &str,
+    filters: default!(Option<pgrx::JsonB>, "NULL"),
+    identity_columns: default!(Option<Vec<String>>, "NULL"),
+    since_column: default!(Option<String>, "NULL"),
+    since: default!(Option<String>, "NULL"),
+    until: default!(Option<String>, "NULL"),
+    limit: default!(Option<i64>, "NULL"),
+    progress: default!(bool, false),
+) -> TableIterator<'static, (
+    name!(mz_timestamp, i64),
+    name!(mz_diff, i32),
+    name!(mz_progressed, bool),
+    name!(mz_errcode, Option<String>),
+    name!(data, pgrx::JsonB),
+)> {
+    let groups = match filters.map(|f| filter_spec::parse_groups(&f.0)) {
+        Some(Ok(groups)) => groups,
+        Some(Err(e)) => pgrx::error!("Invalid filters: {}", e),
+        None => Vec::new(),
+    };
+    let bounds = filter_spec::FilterBounds { since_column, since, until, limit };
+    let query = match filter_spec::compile_select(table, &groups, &bounds) {
+        Ok(q) => q,
+        Err(e) => pgrx::error!("Invalid filter spec: {}", e),
+    };
+
+    unified_subscribe::create_unified_subscription(&query, identity_columns, None, progress, false, false, None, None, None)
 }
 
 /// Prepare subscription - allocates slot and installs shared triggers
-/// Returns subscription_id for use with LISTEN channel
+/// Returns subscription_id for use with LISTEN channel.
+///
+/// By default the channel only receives tiny `{slot, seq}` wake signals;
+/// pull the full event with `pg_subscribe_pull_event`. Set `inline_notify`
+/// to have small events (that fit under Postgres' NOTIFY size limit) sent
+/// in full instead, trading that guarantee for one fewer round-trip.
 #[pg_extern]
-fn pg_subscribe_prepare(query: &str) -> String {
+fn pg_subscribe_prepare(query: &str, inline_notify: default!(bool, false)) -> String {
     let analysis = query_analyzer::analyze_query(query);
-    
+
     if !analysis.is_valid {
         pgrx::error!("Invalid query: {}", analysis.incompatibility_reason.unwrap_or_default());
     }
-    
+
     if analysis.referenced_tables.is_empty() {
         pgrx::error!("Query must reference at least one table");
     }
-    
+
     let subscription_id = uuid::Uuid::new_v4().to_string();
-    
+
     let slot_index = match shmem::allocate_slot(&subscription_id) {
         Some(idx) => idx,
         None => pgrx::error!("No available subscription slots"),
     };
-    
+    shmem::set_slot_inline_notify(slot_index, inline_notify);
+
     // Register triggers - cleanup slot on failure
     let mut failed = false;
     for table_ref in &analysis.referenced_tables {
@@ -191,6 +430,111 @@ fn pg_subscribe_prepare(query: &str) -> String {
     subscription_id
 }
 
+/// Open a subscription, reusing an existing deduplicated slot's channel
+/// (see `query_dedup::find_existing_subscription`) when an equivalent query
+/// is already active instead of always allocating a new one. Pairs with
+/// `pg_subscribe_close`, so a client can multiplex many named subscriptions
+/// over whichever LISTEN channels `pg_subscribe_open` hands back, rather
+/// than opening one connection-wide channel per `pg_subscribe_prepare` call.
+#[pg_extern]
+fn pg_subscribe_open(query: &str) -> String {
+    let analysis = query_analyzer::analyze_query(query);
+
+    if !analysis.is_valid {
+        pgrx::error!("Invalid query: {}", analysis.incompatibility_reason.unwrap_or_default());
+    }
+
+    if analysis.referenced_tables.is_empty() {
+        pgrx::error!("Query must reference at least one table");
+    }
+
+    if let Some(existing) = query_dedup::find_existing_subscription(query) {
+        if let Some(info) = shmem::get_slot_info(existing.slot_index) {
+            pgrx::info!("pg_subscribe: Reusing slot {}, {} clients", existing.slot_index, existing.client_count);
+            return info.get_subscription_id();
+        }
+    }
+
+    let subscription_id = uuid::Uuid::new_v4().to_string();
+    let slot_index = match shmem::allocate_slot(&subscription_id) {
+        Some(idx) => idx,
+        None => pgrx::error!("No available subscription slots"),
+    };
+    query_dedup::register_subscription(query, slot_index);
+
+    let mut failed = false;
+    for table_ref in &analysis.referenced_tables {
+        let table_name = table_ref.schema.as_ref()
+            .map(|s| format!("{}.{}", s, table_ref.table))
+            .unwrap_or_else(|| table_ref.table.clone());
+
+        if let Err(e) = trigger::register_subscription_for_table(&table_name, slot_index) {
+            pgrx::warning!("Failed to register trigger on {}: {}", table_name, e);
+            failed = true;
+        }
+    }
+
+    if failed {
+        // Cleanup on partial failure
+        let _ = trigger::cleanup_shared_triggers_for_slot(slot_index);
+        query_dedup::release_subscription(slot_index);
+        shmem::release_slot(slot_index);
+        pgrx::error!("Failed to register triggers for subscription");
+    }
+
+    subscription_id
+}
+
+/// Close a subscription opened with `pg_subscribe_open`. Releases this
+/// client's share of the dedup registry and, only once the last client
+/// sharing the slot has gone, tears down its triggers and releases the slot
+/// - mirroring `UnifiedSubscription`'s `Drop` impl, but callable directly
+/// since a `pg_subscribe_open` subscription isn't scoped to a single SPI
+/// cursor's lifetime.
+#[pg_extern]
+fn pg_subscribe_close(subscription_id: &str) {
+    let Some(slot_index) = shmem::find_slot_by_subscription_id(subscription_id) else {
+        pgrx::warning!("pg_subscribe_close: unknown subscription_id {}", subscription_id);
+        return;
+    };
+
+    if query_dedup::release_subscription(slot_index) {
+        if let Err(e) = trigger::cleanup_shared_triggers_for_slot(slot_index) {
+            pgrx::warning!("Trigger cleanup failed: {}", e);
+        }
+        shmem::release_slot(slot_index);
+    }
+}
+
+/// Pull the full event for a `{slot, seq}` wake signal received on a
+/// `pg_subscribe_prepare` LISTEN channel. Returns NULL if that event has
+/// already been evicted from the ring (the client fell too far behind), or,
+/// when `visibility_predicate` is given, if the event's row fails that
+/// check (see `unified_subscribe::row_visible`) - indistinguishable from an
+/// eviction to the caller, which is the point: a `pg_subscribe_prepare`
+/// slot is shared raw trigger fan-out with no per-client `Snapshot` to hang
+/// a filter off of, so each puller re-checks its own visibility at pull time.
+///
+/// Records the gap between `event.mz_timestamp` and now into
+/// `shmem::DELIVERY_LATENCY_HIST` - end-to-end delivery lag, as opposed to
+/// `execute_and_diff`'s own compute time (`shmem::DIFF_LATENCY_HIST`).
+#[pg_extern]
+fn pg_subscribe_pull_event(
+    slot_index: i32,
+    seq: i64,
+    visibility_predicate: default!(Option<&str>, "NULL"),
+) -> Option<pgrx::JsonB> {
+    let event = shmem::get_event_by_seq(slot_index as usize, seq as u64)?;
+    if let Some(row) = &event.data {
+        if !unified_subscribe::row_visible(visibility_predicate, row) {
+            return None;
+        }
+    }
+    let delivery_micros = (streaming::get_current_timestamp() - event.mz_timestamp).max(0) as u64;
+    shmem::record_delivery_latency(delivery_micros);
+    Some(pgrx::JsonB(serde_json::to_value(&event).unwrap_or_default()))
+}
+
 /// Get query deduplication statistics
 #[pg_extern]
 fn pg_subscribe_dedup_stats() -> TableIterator<'static, (
@@ -15,16 +15,30 @@ const VERSION: &str = env!("CARGO_PKG_VERSION");
 async fn main() -> Result<()> {
     dotenvy::dotenv().ok();
     
-    let cfg = Arc::new(core::Config::from_env()?);
-    
+    let cfg = Arc::new(core::Config::load()?);
+    core::vars::init(&cfg);
+
     // Init tracing
     telemetry::init(&cfg)?;
+
+    // Cap the global string interner (core::row) per config
+    core::row::init_interner(cfg.max_interned_strings);
     
     info!("LiveQuery v{} [{}]", VERSION, cfg.server_id);
     cfg.log_summary();
     
     // Init components
-    let subs = Arc::new(core::SubscriptionManager::new(cfg.max_subscriptions));
+    // No Config-driven AuthProvider exists yet, so this runs with the
+    // default AllowAllProvider (every credential accepted, see
+    // core::auth); an embedder wanting real authentication constructs
+    // SubscriptionManager::with_auth directly with its own AuthProvider.
+    let subs = Arc::new(core::SubscriptionManager::with_auth(
+        cfg.max_subscriptions,
+        cfg.replay_buffer_cap,
+        cfg.max_distinct_queries,
+        cfg.max_in_flight_snapshots,
+        Arc::new(core::AllowAllProvider),
+    ));
     let db = Arc::new(infra::DbPool::new(&cfg)?);
     
     // Verify DB connection
@@ -67,14 +81,16 @@ async fn main() -> Result<()> {
     
     // Task: Client cleanup
     let h3 = tokio::spawn({
-        let (s, c, mut rx) = (subs.clone(), cfg.clone(), shutdown_tx.subscribe());
+        let (s, c, n, mut rx) = (subs.clone(), cfg.clone(), nats.clone(), shutdown_tx.subscribe());
         async move {
             let mut tick = tokio::time::interval(c.cleanup_interval());
             loop {
                 tokio::select! {
                     _ = tick.tick() => {
-                        let removed = s.cleanup(c.client_timeout());
-                        if !removed.is_empty() { info!("Cleanup: {} clients", removed.len()); }
+                        let reaped = infra::reap_stale(&s, &n, c.client_timeout()).await;
+                        if reaped > 0 { info!("Cleanup: {} clients timed out", reaped); }
+                        let expired = s.expire_gone(c.gone_grace());
+                        if !expired.is_empty() { info!("Cleanup: {} gone-grace expired", expired.len()); }
                     }
                     _ = rx.recv() => { info!("Cleanup task stopped"); break; }
                 }
@@ -91,10 +107,10 @@ async fn main() -> Result<()> {
                 tokio::select! {
                     _ = tick.tick() => {
                         let (active, avail, max) = d.pool_status();
-                        let (queries, errs, avg_ms) = d.query_stats();
+                        let (queries, errs_transient, errs_fatal, avg_ms) = d.query_stats();
                         let (sub_count, query_count) = s.stats();
-                        info!("Stats: subs={} queries={} pool={}/{}/{} db_queries={} errors={} avg={}ms",
-                            sub_count, query_count, active, avail, max, queries, errs, avg_ms);
+                        info!("Stats: subs={} queries={} pool={}/{}/{} db_queries={} errors_transient={} errors_fatal={} avg={}ms",
+                            sub_count, query_count, active, avail, max, queries, errs_transient, errs_fatal, avg_ms);
                     }
                     _ = rx.recv() => { break; }
                 }
@@ -102,18 +118,70 @@ async fn main() -> Result<()> {
         }
     });
     
+    // Task: periodic flush of coalesced (SubscriptionMode::Coalesced) queries
+    let h6 = tokio::spawn({
+        let (s, c, n, mut rx) = (subs.clone(), cfg.clone(), nats.clone(), shutdown_tx.subscribe());
+        async move {
+            let mut tick = tokio::time::interval(c.coalesce_window());
+            loop {
+                tokio::select! {
+                    _ = tick.tick() => {
+                        for qid in s.coalesced_query_ids() {
+                            infra::publish_coalesced(&qid, &s, &n).await;
+                        }
+                    }
+                    _ = rx.recv() => { info!("Coalesce flush task stopped"); break; }
+                }
+            }
+        }
+    });
+
+    // Task: hot config reload on SIGHUP
+    let h5 = tokio::spawn({
+        let (c, mut rx) = (cfg.clone(), shutdown_tx.subscribe());
+        async move {
+            let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+                Ok(s) => s,
+                Err(e) => { error!("SIGHUP handler: {e}"); return; }
+            };
+            loop {
+                tokio::select! {
+                    _ = hangup.recv() => {
+                        info!("SIGHUP received, reloading config...");
+                        match c.reload_from_source() {
+                            Ok(diff) => diff.log_summary(),
+                            Err(e) => error!("Config reload failed, keeping previous config: {e}"),
+                        }
+                    }
+                    _ = rx.recv() => { info!("Reload task stopped"); break; }
+                }
+            }
+        }
+    });
+
+    // Task: client-facing HTTP gateway (SSE /subscribe + WebSocket /ws)
+    let h7 = tokio::spawn({
+        let gateway = infra::Gateway::new(cfg.clone(), subs.clone(), db.clone(), nats.nc.clone());
+        let rx = shutdown_tx.subscribe();
+        async move {
+            if let Err(e) = gateway.run(rx).await {
+                error!("Gateway task: {e}");
+            }
+        }
+    });
+
     info!("✓ Ready - press Ctrl+C to stop");
-    
+
     // Wait for shutdown signal
     tokio::signal::ctrl_c().await?;
     info!("Shutting down (timeout={}s)...", cfg.shutdown_timeout_secs);
-    
+
     // Signal all tasks to stop
     let _ = shutdown_tx.send(());
-    
+
     // Wait for graceful shutdown with timeout
     let shutdown = async {
-        let _ = tokio::join!(h1, h2, h3, h4);
+        let _ = tokio::join!(h1, h2, h3, h4, h5, h6, h7);
     };
     
     if tokio::time::timeout(cfg.shutdown_timeout(), shutdown).await.is_err() {
@@ -0,0 +1,207 @@
+//! Disk-backed overflow log for a lagging subscription's event buffer.
+//!
+//! `shmem::push_event` keeps the most recent `MAX_EVENTS_PER_SLOT` events in
+//! a shared-memory ring; once that ring is full it used to just overwrite
+//! the oldest entry, permanently losing it for a client that can't keep up.
+//! Instead it now appends the evicted event here: one append-only segment
+//! file per slot under `<data_directory>/pg_subscribe/<subscription_id>/`,
+//! each record laid out as
+//! `[write_version: u64][timestamp: i64][diff: i32][len: u32][payload]`.
+//!
+//! There is a single writer per slot - `push_event` only spills while
+//! holding that slot's `SLOT_DATA` lock exclusively, so appends to one
+//! slot's log are naturally serialized, independent of every other slot -
+//! and any number of readers, which `mmap` the segment and walk it by byte
+//! offset without taking that lock. The (segment, offset) of the oldest
+//! unconsumed record and of the write cursor live in `SlotInfo`
+//! (`overflow_read_*`/`overflow_write_*`), updated by `shmem::push_event`/
+//! `shmem::pop_event`; this module only knows how to read and write bytes.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use memmap2::Mmap;
+
+use crate::event::SubscribeEvent;
+
+/// Roll over to a fresh segment once the current one would exceed this size.
+const MAX_SEGMENT_BYTES: u64 = 16 * 1024 * 1024;
+
+const RECORD_HEADER_LEN: u64 = 8 + 8 + 4 + 4;
+
+fn data_dir() -> PathBuf {
+    let dir = unsafe {
+        let ptr = pgrx::pg_sys::DataDir;
+        if ptr.is_null() {
+            None
+        } else {
+            Some(std::ffi::CStr::from_ptr(ptr).to_string_lossy().into_owned())
+        }
+    };
+    Path::new(&dir.unwrap_or_else(|| ".".into())).join("pg_subscribe")
+}
+
+fn slot_dir(subscription_id: &str) -> PathBuf {
+    data_dir().join(subscription_id)
+}
+
+fn segment_path(subscription_id: &str, segment_id: u64) -> PathBuf {
+    slot_dir(subscription_id).join(format!("{segment_id:020}.log"))
+}
+
+/// Append one event to a slot's log, rolling over to a new segment first if
+/// it wouldn't fit in `MAX_SEGMENT_BYTES`. Returns the (segment, offset) the
+/// *next* append should use.
+pub fn append(
+    subscription_id: &str,
+    write_version: u64,
+    write_segment: u64,
+    write_offset: u64,
+    event: &SubscribeEvent,
+) -> std::io::Result<(u64, u64)> {
+    fs::create_dir_all(slot_dir(subscription_id))?;
+
+    let payload = serde_json::to_vec(&event.data).unwrap_or_default();
+    let record_len = RECORD_HEADER_LEN + payload.len() as u64;
+
+    let (segment_id, offset) = if write_offset > 0 && write_offset + record_len > MAX_SEGMENT_BYTES {
+        (write_segment + 1, 0)
+    } else {
+        (write_segment, write_offset)
+    };
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(segment_path(subscription_id, segment_id))?;
+    file.write_all(&write_version.to_le_bytes())?;
+    file.write_all(&event.mz_timestamp.to_le_bytes())?;
+    file.write_all(&event.mz_diff.to_le_bytes())?;
+    file.write_all(&(payload.len() as u32).to_le_bytes())?;
+    file.write_all(&payload)?;
+
+    Ok((segment_id, offset + record_len))
+}
+
+/// Read the record at `(segment_id, offset)` via a read-only `mmap`, without
+/// ever touching `SLOT_DATA`. Returns the decoded event plus the offset of
+/// the next record in the same segment, or `None` if nothing is there yet
+/// (segment missing, or `offset` at/past what's been appended so far).
+pub fn read_at(
+    subscription_id: &str,
+    segment_id: u64,
+    offset: u64,
+) -> std::io::Result<Option<(SubscribeEvent, u64)>> {
+    let path = segment_path(subscription_id, segment_id);
+    let file = match File::open(&path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let mmap = unsafe { Mmap::map(&file)? };
+    let offset = offset as usize;
+    if offset + RECORD_HEADER_LEN as usize > mmap.len() {
+        return Ok(None);
+    }
+
+    let header = &mmap[offset..offset + RECORD_HEADER_LEN as usize];
+    let write_version = u64::from_le_bytes(header[0..8].try_into().unwrap());
+    let timestamp = i64::from_le_bytes(header[8..16].try_into().unwrap());
+    let diff = i32::from_le_bytes(header[16..20].try_into().unwrap());
+    let payload_len = u32::from_le_bytes(header[20..24].try_into().unwrap()) as usize;
+
+    let payload_start = offset + RECORD_HEADER_LEN as usize;
+    let payload_end = payload_start + payload_len;
+    if payload_end > mmap.len() {
+        return Ok(None);
+    }
+    let data: Option<serde_json::Value> = serde_json::from_slice(&mmap[payload_start..payload_end]).ok();
+
+    let event = SubscribeEvent {
+        mz_timestamp: timestamp,
+        mz_diff: diff,
+        mz_progressed: diff == 0,
+        mz_errcode: None,
+        mz_write_version: write_version,
+        data,
+    };
+
+    Ok(Some((event, payload_end as u64)))
+}
+
+/// Delete every segment strictly older than `keep_from_segment` - the
+/// segment the read cursor now lives in - since nothing will ever read them
+/// again. Called by `shmem::pop_event` whenever the read cursor crosses a
+/// segment boundary.
+pub fn gc_segments(subscription_id: &str, keep_from_segment: u64) {
+    let Ok(entries) = fs::read_dir(slot_dir(subscription_id)) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(stem) = name.to_str().and_then(|n| n.strip_suffix(".log")) else {
+            continue;
+        };
+        let Ok(id) = stem.parse::<u64>() else {
+            continue;
+        };
+        if id < keep_from_segment {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Remove a slot's whole overflow directory, e.g. once `shmem::release_slot`
+/// tears the subscription down.
+pub fn remove_slot_dir(subscription_id: &str) {
+    let _ = fs::remove_dir_all(slot_dir(subscription_id));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(ts: i64) -> SubscribeEvent {
+        SubscribeEvent::insert(ts, serde_json::json!({"ts": ts}))
+    }
+
+    #[test]
+    fn append_then_read_roundtrips() {
+        let sub_id = format!("test-overflow-{}", std::process::id());
+        remove_slot_dir(&sub_id);
+
+        let (seg, off) = append(&sub_id, 1, 0, 0, &event(100)).unwrap();
+        let (restored, next_off) = read_at(&sub_id, seg, 0).unwrap().unwrap();
+        assert_eq!(restored.mz_timestamp, 100);
+        assert_eq!(next_off, off);
+
+        remove_slot_dir(&sub_id);
+    }
+
+    #[test]
+    fn read_past_write_cursor_returns_none() {
+        let sub_id = format!("test-overflow-empty-{}", std::process::id());
+        remove_slot_dir(&sub_id);
+        assert!(read_at(&sub_id, 0, 0).unwrap().is_none());
+    }
+
+    #[test]
+    fn gc_removes_only_older_segments() {
+        let sub_id = format!("test-overflow-gc-{}", std::process::id());
+        remove_slot_dir(&sub_id);
+
+        append(&sub_id, 1, 0, 0, &event(1)).unwrap();
+        append(&sub_id, 1, 1, 0, &event(2)).unwrap();
+        append(&sub_id, 1, 2, 0, &event(3)).unwrap();
+
+        gc_segments(&sub_id, 2);
+
+        assert!(!segment_path(&sub_id, 0).exists());
+        assert!(!segment_path(&sub_id, 1).exists());
+        assert!(segment_path(&sub_id, 2).exists());
+
+        remove_slot_dir(&sub_id);
+    }
+}
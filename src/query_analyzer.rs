@@ -23,8 +23,28 @@ pub struct QueryAnalysis {
     pub has_distinct: bool,
     pub has_order_by: bool,
     pub has_limit: bool,
+    pub limit_kind: LimitKind,
+    pub order_by_keys: Vec<String>,
+    /// Structured `(column, ascending)` ORDER BY keys, for bounded top-N
+    /// window maintenance (see `QueryAnalysis::classify_window_row`). Empty
+    /// whenever `order_by_simple` is false.
+    pub order_by: Vec<(Box<str>, bool)>,
+    /// Whether every `ORDER BY` key is a plain column reference - only then
+    /// can a changed row's position relative to the window boundary be
+    /// determined without requerying.
+    pub order_by_simple: bool,
+    /// `LIMIT` row count when it's a plain integer literal (mirrors
+    /// `limit_kind`, but as a bare value for window-boundary arithmetic).
+    pub limit: Option<u64>,
+    /// `OFFSET` row count when it's a plain integer literal.
+    pub offset: Option<u64>,
+    pub has_locking_clause: bool,
+    pub lock_clauses: Vec<String>,
     pub select_columns: Vec<String>,
     pub complexity_score: u32,
+    /// WHERE clause compiled for row-level evaluation (see `WhereFilter::eval`),
+    /// used to skip shared-trigger broadcast fan-out for non-matching rows.
+    pub filter: WhereFilter,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,6 +54,203 @@ pub struct TableReference {
     pub alias: Option<String>,
 }
 
+/// Classifies a query's `LIMIT`/`FETCH` clause for IVM maintainability - a
+/// plain row-count limit can be served by a bounded per-group heap, but
+/// `WITH TIES`/percentage fetches can't since the cutoff isn't a fixed size.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub enum LimitKind {
+    #[default]
+    None,
+    LimitRows(u64),
+    LimitRank,
+}
+
+/// Filter compiled from a simple WHERE clause. Only comparisons (on a column
+/// or a `ColExpr`), [NOT] IN, IS [NOT] NULL, [NOT] [I]LIKE, ranges/BETWEEN,
+/// and their AND/OR/NOT combinations are modeled; anything else
+/// compiles to `Complex`, which always evaluates as `Unknown` so callers fall
+/// back to treating the row as relevant.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub enum WhereFilter {
+    Eq { expr: ColExpr, val: FilterValue },
+    Ne { expr: ColExpr, val: FilterValue },
+    Gt { expr: ColExpr, val: FilterValue },
+    Gte { expr: ColExpr, val: FilterValue },
+    Lt { expr: ColExpr, val: FilterValue },
+    Lte { expr: ColExpr, val: FilterValue },
+    In { path: ColumnPath, vals: Vec<FilterValue> },
+    /// `col NOT IN (vals)`: matches unless the column equals one of `vals`.
+    NotIn { path: ColumnPath, vals: Vec<FilterValue> },
+    IsNull { path: ColumnPath },
+    IsNotNull { path: ColumnPath },
+    /// `col [NOT] [I]LIKE pattern`, SQL wildcards only (`%`, `_`, `\`-escaped).
+    /// `parts` is `pattern` pre-split at parse time (see
+    /// `compile_like_pattern`) so repeated `eval` calls don't re-scan escapes.
+    Like { path: ColumnPath, parts: Vec<LikePart>, case_insensitive: bool, negated: bool },
+    /// `lo [<=|<] expr [<=|<] hi`: a closed/open bound on one `ColExpr`,
+    /// either side optional. Produced by `simplify()` folding same-column
+    /// `Gt`/`Gte`/`Lt`/`Lte` conjuncts (e.g. a desugared `BETWEEN`, or
+    /// `age >= 18 AND age <= 65`) into a single node instead of checking two
+    /// separate comparisons per row; a contradictory combination (`age > 100
+    /// AND age < 10`) folds all the way to `Never` instead.
+    Range { expr: ColExpr, lower: Option<(FilterValue, bool)>, upper: Option<(FilterValue, bool)> },
+    And(Vec<WhereFilter>),
+    Or(Vec<WhereFilter>),
+    Complex,
+    /// Constant-`NoMatch`: the complement of `None`, produced by folding a
+    /// literal-vs-literal comparison (e.g. `1 = 2`) at parse time, or by
+    /// `simplify()` collapsing an empty `Or`.
+    Never,
+    #[default]
+    None,
+}
+
+/// One hop of a `ColumnPath`: descend into a JSON object by key, or a JSON
+/// array by index. A bare column (`status`) is a single-`Key` path; nested
+/// access uses Postgres's own `->`/`->>` JSON operators (`address->'city'`,
+/// `items->0->>'sku'`), since that's what the dialect actually parses -
+/// there's no literal dot/bracket JSON syntax in SQL itself.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// A column reference, possibly descending into nested JSON via `->`/`->>`.
+pub type ColumnPath = Vec<PathSegment>;
+
+/// Walk `path` into `row`, stopping (returning `None`) at a missing key, an
+/// out-of-range index, or a type mismatch (e.g. indexing into an object).
+/// Descending through a JSON `null` also falls through to `None` here, same
+/// as any other dead end - only the *final* segment's value may legitimately
+/// be `null` (callers see that as `FilterValue::Null`, not `None`).
+fn walk_path<'a>(row: &'a serde_json::Value, path: &[PathSegment]) -> Option<&'a serde_json::Value> {
+    let mut cur = row;
+    for seg in path {
+        cur = match (seg, cur) {
+            (PathSegment::Key(k), serde_json::Value::Object(o)) => o.get(k)?,
+            (PathSegment::Index(i), serde_json::Value::Array(a)) => a.get(*i)?,
+            _ => return None,
+        };
+    }
+    Some(cur)
+}
+
+/// Scalar expression on the column side of a comparison - widens `cmp_filter`
+/// beyond a bare column to simple arithmetic and a whitelist of pure scalar
+/// functions, so e.g. `price * quantity > 100` or `lower(name) = 'bob'` can
+/// still be decided in-process instead of falling back to `Complex`.
+/// `Col` is the fast path: it collapses to a direct `row.get` lookup, same
+/// as a bare column always did, so simple queries pay nothing extra.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ColExpr {
+    Col(ColumnPath),
+    IntLit(i64),
+    FloatLit(f64),
+    Add(Box<ColExpr>, Box<ColExpr>),
+    Sub(Box<ColExpr>, Box<ColExpr>),
+    Mul(Box<ColExpr>, Box<ColExpr>),
+    Div(Box<ColExpr>, Box<ColExpr>),
+    Func(ScalarFn, Vec<ColExpr>),
+}
+
+/// Whitelist of pure scalar functions `parse_col_expr` will fold into a
+/// `ColExpr::Func` instead of giving up with `Complex`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScalarFn {
+    Lower,
+    Upper,
+    Abs,
+    Coalesce,
+}
+
+/// Literal value for comparison against a JSON row column.
+///
+/// `PartialEq`/`Eq`/`Hash` are hand-rolled (`Float` compares/hashes by bit
+/// pattern) so this can key the `query_index::FilterIndex` inverted index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum FilterValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+/// Result of evaluating a `WhereFilter` against one row - SQL's tri-valued
+/// (Kleene) logic, not a plain bool. `And`/`Or` combine results via the
+/// standard truth tables (`NoMatch` absorbs in `And`, `Match` absorbs in
+/// `Or`; otherwise any `Unknown` operand makes the whole expression
+/// `Unknown`), and a comparison against a JSON `null` column value is always
+/// `Unknown`, matching how SQL NULL behaves under `=`/`<>`/`IN`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvalResult {
+    /// Row matches the filter.
+    Match,
+    /// Row doesn't match the filter.
+    NoMatch,
+    /// Can't determine from this filter/row alone (e.g. `Complex`, the row
+    /// is missing the column, or the column is SQL NULL) - callers should
+    /// treat this as relevant.
+    Unknown,
+}
+
+/// One security-context claim, compiled into an equality or membership
+/// predicate against the row (see `SecurityContext::predicate`) - covers
+/// both the `tenant_id = <id>` and `owner_id IN (...)` shapes a row-level
+/// security rule typically needs.
+#[derive(Debug, Clone)]
+pub enum ClaimPredicate {
+    Eq(FilterValue),
+    In(Vec<FilterValue>),
+}
+
+/// Per-connection security context: the current user's identity, roles, and
+/// any additional claims the server wants to enforce on every live query,
+/// regardless of what the client's own WHERE clause requests. See
+/// `QueryAnalysis::with_security`.
+#[derive(Debug, Clone, Default)]
+pub struct SecurityContext {
+    pub user_id: Option<FilterValue>,
+    pub roles: Vec<String>,
+    pub claims: Vec<(String, ClaimPredicate)>,
+}
+
+impl SecurityContext {
+    /// A connection holding this role enforces no predicate at all - the
+    /// escape hatch for a superuser/admin connection that's meant to see
+    /// every row its own WHERE clause allows.
+    pub const BYPASS_ROLE: &'static str = "admin";
+
+    /// Build the mandatory predicate this context enforces: `user_id`
+    /// equality plus each claim, AND-combined. `None` (no restriction) if
+    /// the context carries nothing to enforce, or holds `BYPASS_ROLE`.
+    pub fn predicate(&self) -> WhereFilter {
+        if self.roles.iter().any(|r| r == Self::BYPASS_ROLE) {
+            return WhereFilter::None;
+        }
+        let mut clauses = Vec::new();
+        if let Some(uid) = &self.user_id {
+            clauses.push(WhereFilter::Eq {
+                expr: ColExpr::Col(vec![PathSegment::Key("user_id".into())]),
+                val: uid.clone(),
+            });
+        }
+        for (key, pred) in &self.claims {
+            let path = vec![PathSegment::Key(key.clone())];
+            clauses.push(match pred {
+                ClaimPredicate::Eq(v) => WhereFilter::Eq { expr: ColExpr::Col(path), val: v.clone() },
+                ClaimPredicate::In(vals) => WhereFilter::In { path, vals: vals.clone() },
+            });
+        }
+        match clauses.len() {
+            0 => WhereFilter::None,
+            1 => clauses.into_iter().next().unwrap(),
+            _ => WhereFilter::And(clauses),
+        }
+    }
+}
+
 pub fn analyze_query(query: &str) -> QueryAnalysis {
     let mut a = QueryAnalysis { query: query.into(), ..Default::default() };
     
@@ -59,7 +276,10 @@ pub fn analyze_query(query: &str) -> QueryAnalysis {
     }
     if a.has_subqueries { reasons.push("Subqueries have limited support"); }
     if a.has_cte { reasons.push("CTEs have limited support"); }
-    
+    if a.has_locking_clause { reasons.push("Row-level locking (FOR UPDATE/SHARE) not supported"); }
+    if matches!(a.limit_kind, LimitKind::LimitRank) { reasons.push("FETCH ... WITH TIES not supported"); }
+    if has_positive_offset(query_ast) { reasons.push("OFFSET not supported for incremental maintenance"); }
+
     a.ivm_compatible = reasons.is_empty();
     if !reasons.is_empty() { a.incompatibility_reason = Some(reasons.join("; ")); }
     a.complexity_score = calculate_complexity(&a);
@@ -73,9 +293,82 @@ fn analyze_query_ast(a: &mut QueryAnalysis, query: &Query) {
     }
     a.has_order_by = query.order_by.is_some();
     a.has_limit = query.limit.is_some();
+
+    if let Some(ob) = &query.order_by {
+        a.order_by_keys = ob.exprs.iter().map(|e| {
+            let dir = if e.asc == Some(false) { "DESC" } else { "ASC" };
+            format!("{} {}", e.expr, dir)
+        }).collect();
+
+        a.order_by_simple = true;
+        for e in &ob.exprs {
+            match filter_col_name(&e.expr) {
+                Some(col) => a.order_by.push((col.into(), e.asc != Some(false))),
+                None => {
+                    a.order_by_simple = false;
+                    a.order_by.clear();
+                    break;
+                }
+            }
+        }
+    }
+
+    a.limit_kind = match &query.fetch {
+        Some(f) if f.with_ties => LimitKind::LimitRank,
+        Some(f) => limit_rows(f.quantity.as_ref()),
+        None => limit_rows(query.limit.as_ref()),
+    };
+    a.limit = match a.limit_kind {
+        LimitKind::LimitRows(n) => Some(n),
+        _ => None,
+    };
+    a.offset = query.offset.as_ref().and_then(|o| literal_u64(Some(&o.value)));
+
+    for lock in &query.locks {
+        a.has_locking_clause = true;
+        let strength = match lock.lock_type {
+            LockType::Share => "SHARE",
+            LockType::Update => "UPDATE",
+        };
+        let mut clause = format!("FOR {}", strength);
+        if let Some(of) = &lock.of {
+            clause.push_str(" OF ");
+            clause.push_str(&of.iter().map(|o| o.to_string()).collect::<Vec<_>>().join(", "));
+        }
+        match lock.nonblock {
+            Some(NonBlock::Nowait) => clause.push_str(" NOWAIT"),
+            Some(NonBlock::SkipLocked) => clause.push_str(" SKIP LOCKED"),
+            None => {}
+        }
+        if !a.lock_clauses.contains(&clause) { a.lock_clauses.push(clause); }
+    }
+
     analyze_set_expr(a, &query.body);
 }
 
+/// A bare integer-literal quantity becomes a row-bounded limit; anything
+/// else (a placeholder, an expression) can't be sized ahead of time.
+fn limit_rows(expr: Option<&Expr>) -> LimitKind {
+    match expr {
+        Some(Expr::Value(Value::Number(n, _))) => n.parse().map(LimitKind::LimitRows).unwrap_or(LimitKind::None),
+        _ => LimitKind::None,
+    }
+}
+
+fn has_positive_offset(query: &Query) -> bool {
+    match &query.offset {
+        Some(o) => !matches!(&o.value, Expr::Value(Value::Number(n, _)) if n == "0"),
+        None => false,
+    }
+}
+
+fn literal_u64(expr: Option<&Expr>) -> Option<u64> {
+    match expr {
+        Some(Expr::Value(Value::Number(n, _))) => n.parse().ok(),
+        _ => None,
+    }
+}
+
 fn analyze_set_expr(a: &mut QueryAnalysis, expr: &SetExpr) {
     match expr {
         SetExpr::Select(s) => analyze_select(a, s),
@@ -109,7 +402,10 @@ fn analyze_select(a: &mut QueryAnalysis, select: &Select) {
         GroupByExpr::Expressions(e, _) => !e.is_empty(),
     };
     
-    if let Some(w) = &select.selection { analyze_expr(a, w); }
+    if let Some(w) = &select.selection {
+        analyze_expr(a, w);
+        a.filter = parse_filter_expr(w).simplify();
+    }
     if let Some(h) = &select.having { analyze_expr(a, h); }
 }
 
@@ -201,57 +497,1855 @@ fn analyze_expr(a: &mut QueryAnalysis, expr: &Expr) {
     }
 }
 
-fn calculate_complexity(a: &QueryAnalysis) -> u32 {
-    let mut s = 10u32;
-    s += a.referenced_tables.len() as u32 * 10;
-    if a.has_join { s += 15 + a.join_types.len() as u32 * 5; }
-    if a.has_aggregation { s += 10 + a.aggregation_functions.len() as u32 * 5; }
-    if a.has_group_by { s += 10; }
-    if a.has_window_functions { s += 25; }
-    if a.has_subqueries { s += 20; }
-    if a.has_cte { s += 15; }
-    s.min(100)
+// === Canonical Fingerprinting ===
+
+/// Rewrite `query` into a canonical form so that cosmetically different
+/// queries (case, spacing, parens around an atomic expression or around the
+/// whole WHERE, cosmetic aliases, unqualified columns) hash to the same
+/// dedup key. Returns `None` on parse failure so callers
+/// can fall back to the plain whitespace normalizer.
+pub fn canonicalize_query(query: &str) -> Option<String> {
+    let mut stmts = Parser::parse_sql(&PostgreSqlDialect {}, query).ok()?;
+    if stmts.len() != 1 {
+        return None;
+    }
+    match &mut stmts[0] {
+        Statement::Query(q) => canon_query(q),
+        _ => return None,
+    }
+    Some(stmts[0].to_string())
 }
 
+fn canon_query(query: &mut Query) {
+    if let Some(with) = &mut query.with {
+        with.cte_tables.iter_mut().for_each(|cte| canon_query(&mut cte.query));
+    }
+    canon_set_expr(&mut query.body);
+}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    
-    #[test]
-    fn test_simple_select() {
-        let analysis = analyze_query("SELECT * FROM users");
-        assert!(analysis.is_valid);
-        assert!(!analysis.has_join);
-        assert!(!analysis.has_aggregation);
+fn canon_set_expr(expr: &mut SetExpr) {
+    match expr {
+        SetExpr::Select(s) => canon_select(s),
+        SetExpr::Query(q) => canon_query(q),
+        SetExpr::SetOperation { left, right, .. } => {
+            canon_set_expr(left);
+            canon_set_expr(right);
+        }
+        _ => {}
     }
-    
-    #[test]
-    fn test_join_detection() {
-        let analysis = analyze_query(
-            "SELECT u.name, o.amount FROM users u INNER JOIN orders o ON u.id = o.user_id"
-        );
-        assert!(analysis.has_join);
-        assert!(analysis.join_types.contains(&"INNER".to_string()));
+}
+
+fn canon_select(select: &mut Select) {
+    for twj in &mut select.from {
+        canon_table_with_joins(twj);
     }
-    
-    #[test]
-    fn test_aggregation_detection() {
-        let analysis = analyze_query(
-            "SELECT user_id, COUNT(*), SUM(amount) FROM orders GROUP BY user_id"
-        );
-        assert!(analysis.has_aggregation);
-        assert!(analysis.has_group_by);
-        assert!(analysis.aggregation_functions.contains(&"COUNT".to_string()));
-        assert!(analysis.aggregation_functions.contains(&"SUM".to_string()));
+
+    // Only a single, unjoined base table makes qualifying a bare column
+    // reference unambiguous - anything wider (joins, derived tables) is
+    // left alone rather than risk qualifying against the wrong table.
+    let table = single_table_name(&select.from);
+
+    for item in &mut select.projection {
+        match item {
+            SelectItem::UnnamedExpr(e) => canon_expr(e, table.as_deref()),
+            SelectItem::ExprWithAlias { expr, alias } => {
+                canon_expr(expr, table.as_deref());
+                if is_cosmetic_alias(expr, alias) {
+                    *item = SelectItem::UnnamedExpr(expr.clone());
+                }
+            }
+            _ => {}
+        }
     }
-    
-    #[test]
-    fn test_window_function_detection() {
-        let analysis = analyze_query(
-            "SELECT user_id, ROW_NUMBER() OVER (PARTITION BY user_id) FROM orders"
-        );
-        assert!(analysis.has_window_functions);
-        assert!(!analysis.ivm_compatible);
+
+    if let Some(w) = &mut select.selection {
+        canon_expr(w, table.as_deref());
+        // Parens around the whole WHERE never change precedence.
+        while let Expr::Nested(inner) = w { *w = (**inner).clone(); }
+    }
+    if let Some(h) = &mut select.having {
+        canon_expr(h, table.as_deref());
+    }
+}
+
+fn canon_table_with_joins(twj: &mut TableWithJoins) {
+    canon_table_factor(&mut twj.relation);
+
+    for join in &mut twj.joins {
+        canon_table_factor(&mut join.relation);
+        if let JoinOperator::Inner(JoinConstraint::On(e))
+        | JoinOperator::LeftOuter(JoinConstraint::On(e))
+        | JoinOperator::RightOuter(JoinConstraint::On(e))
+        | JoinOperator::FullOuter(JoinConstraint::On(e)) = &mut join.join_operator
+        {
+            canon_expr(e, None);
+        }
+    }
+}
+
+fn canon_table_factor(tf: &mut TableFactor) {
+    match tf {
+        TableFactor::Derived { subquery, .. } => canon_query(subquery),
+        TableFactor::NestedJoin { table_with_joins, .. } => canon_table_with_joins(table_with_joins),
+        _ => {}
+    }
+}
+
+fn single_table_name(from: &[TableWithJoins]) -> Option<String> {
+    let [twj] = from else { return None };
+    if !twj.joins.is_empty() {
+        return None;
+    }
+    match &twj.relation {
+        TableFactor::Table { name, alias, .. } => Some(match alias {
+            Some(a) => a.name.value.clone(),
+            None => name.0.last()?.value.clone(),
+        }),
+        _ => None,
+    }
+}
+
+fn canon_expr(expr: &mut Expr, table: Option<&str>) {
+    match expr {
+        Expr::Nested(inner) => {
+            canon_expr(inner, table);
+            if is_atomic(inner) { *expr = (**inner).clone(); }
+        }
+        Expr::Identifier(id) => {
+            if let Some(t) = table { *expr = Expr::CompoundIdentifier(vec![Ident::new(t), id.clone()]); }
+        }
+        Expr::Function(f) => {
+            f.name.0.iter_mut().for_each(|p| p.value = p.value.to_uppercase());
+            if let FunctionArguments::List(args) = &mut f.args {
+                for arg in &mut args.args {
+                    let e = match arg {
+                        FunctionArg::Named { arg, .. } => arg,
+                        FunctionArg::Unnamed(a) => a,
+                    };
+                    if let FunctionArgExpr::Expr(ex) = e { canon_expr(ex, table); }
+                }
+            }
+        }
+        Expr::Subquery(q) | Expr::InSubquery { subquery: q, .. } | Expr::Exists { subquery: q, .. } => {
+            canon_query(q);
+        }
+        Expr::BinaryOp { left, right, .. } => { canon_expr(left, table); canon_expr(right, table); }
+        Expr::UnaryOp { expr: e, .. } | Expr::Cast { expr: e, .. } => canon_expr(e, table),
+        Expr::IsNull(e) | Expr::IsNotNull(e) => canon_expr(e, table),
+        Expr::InList { expr: e, list, .. } => {
+            canon_expr(e, table);
+            list.iter_mut().for_each(|v| canon_expr(v, table));
+        }
+        Expr::Case { operand, conditions, results, else_result, .. } => {
+            if let Some(o) = operand { canon_expr(o, table); }
+            conditions.iter_mut().for_each(|c| canon_expr(c, table));
+            results.iter_mut().for_each(|r| canon_expr(r, table));
+            if let Some(e) = else_result { canon_expr(e, table); }
+        }
+        _ => {}
+    }
+}
+
+/// `Expr::Nested` around anything that already binds tighter than every
+/// operator (identifiers, literals, function calls, other parens) never
+/// changes precedence, so the parens are cosmetic and safe to drop.
+fn is_atomic(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::Identifier(_) | Expr::CompoundIdentifier(_) | Expr::Value(_) | Expr::Function(_) | Expr::Nested(_)
+    )
+}
+
+fn is_cosmetic_alias(expr: &Expr, alias: &Ident) -> bool {
+    match expr {
+        Expr::Identifier(id) => id.value.eq_ignore_ascii_case(&alias.value),
+        Expr::CompoundIdentifier(parts) => parts.last().is_some_and(|id| id.value.eq_ignore_ascii_case(&alias.value)),
+        _ => false,
+    }
+}
+
+// === Row-Security Filter Injection ===
+
+/// Inject per-client row-security predicates into `query`'s top-level
+/// `WHERE`, composing them the way layered row-level-security policies do:
+/// `(perm_1 OR perm_2 OR ...) AND restr_1 AND restr_2 ...` - an absent (or,
+/// after parse failures, empty) permissive set permits all rows, an absent
+/// restrictive set adds no constraint. Fails closed: if any restrictive
+/// predicate fails to parse the whole query is rejected (`None`) rather
+/// than silently dropping a security constraint; a permissive predicate
+/// that fails to parse is just skipped (it can only broaden, never narrow,
+/// the result). Callers should warn the caller when this returns `None`.
+///
+/// Because this changes the query text, callers must fingerprint the
+/// *returned* string for dedup (see `query_dedup::effective_query`) so
+/// clients with different policies land in distinct registry entries
+/// instead of sharing rows they're not entitled to see.
+pub fn apply_row_filters(query: &str, restrictive: &[String], permissive: &[String]) -> Option<String> {
+    if restrictive.is_empty() && permissive.is_empty() {
+        return Some(query.to_string());
+    }
+
+    let mut stmts = Parser::parse_sql(&PostgreSqlDialect {}, query).ok()?;
+    if stmts.len() != 1 {
+        return None;
+    }
+    let Statement::Query(q) = &mut stmts[0] else { return None };
+    let Some(select) = top_level_select(q) else { return None };
+
+    let mut restr_exprs = Vec::with_capacity(restrictive.len());
+    for pred in restrictive {
+        restr_exprs.push(parse_predicate(pred)?);
+    }
+
+    // A permissive predicate that fails to parse is dropped rather than
+    // rejecting the query: omitting it from the OR narrows the effective
+    // filter, unless every permissive predicate fails, which leaves no
+    // permissive clause and so permits all rows (see the doc above).
+    let perm_exprs: Vec<Expr> = permissive.iter().filter_map(|p| parse_predicate(p)).collect();
+
+    let mut combined = perm_exprs.into_iter().reduce(|l, r| and_or(l, BinaryOperator::Or, r));
+    for r in restr_exprs {
+        combined = Some(match combined {
+            Some(c) => and_or(c, BinaryOperator::And, r),
+            None => r,
+        });
+    }
+    let Some(combined) = combined else { return Some(query.to_string()) };
+
+    select.selection = Some(match select.selection.take() {
+        Some(existing) => and_or(existing, BinaryOperator::And, combined),
+        None => combined,
+    });
+
+    Some(stmts[0].to_string())
+}
+
+fn top_level_select(query: &mut Query) -> Option<&mut Select> {
+    match &mut *query.body {
+        SetExpr::Select(s) => Some(&mut **s),
+        _ => None,
+    }
+}
+
+fn parse_predicate(sql: &str) -> Option<Expr> {
+    Parser::new(&PostgreSqlDialect {}).try_with_sql(sql).ok()?.parse_expr().ok()
+}
+
+fn and_or(left: Expr, op: BinaryOperator, right: Expr) -> Expr {
+    // `Expr`'s Display adds no parentheses, so an OR operand of an AND is
+    // wrapped explicitly to keep its grouping in the re-serialized text.
+    let group = |e: Expr| {
+        if matches!(op, BinaryOperator::And) && matches!(e, Expr::BinaryOp { op: BinaryOperator::Or, .. }) {
+            Expr::Nested(Box::new(e))
+        } else {
+            e
+        }
+    };
+    let (left, right) = (group(left), group(right));
+    Expr::BinaryOp { left: Box::new(left), op, right: Box::new(right) }
+}
+
+// === Fast Whitespace Normalization ===
+//
+// `canonicalize_query` above is the semantic fingerprint, but it needs a
+// valid parse; this is the cheap fallback (what the dedup hash hashes
+// when `canonicalize_query` can't parse the query) - an in-process rewrite of the pgrx module's
+// old `btrim(regexp_replace($1, E'\s+', ' ', 'g'))` SPI round-trip, scanning
+// the query bytes directly instead of bouncing through the executor.
+
+const NEEDLES: [u8; 8] = [b' ', b'\t', b'\n', b'\r', b'\'', b'"', b'-', b'/'];
+const SWAR_LO: u128 = u128::from_le_bytes([0x01; 16]);
+const SWAR_HI: u128 = u128::from_le_bytes([0x80; 16]);
+
+#[inline]
+fn is_interesting(b: u8) -> bool {
+    NEEDLES.contains(&b)
+}
+
+/// Classic SWAR "has a zero byte" trick, applied per-needle and OR'd
+/// together: a lane of `v` is all-zero (i.e. matched a needle) iff
+/// `(v - 0x01) & !v & 0x80` is nonzero in that lane.
+#[inline]
+fn haszero(v: u128) -> u128 {
+    v.wrapping_sub(SWAR_LO) & !v & SWAR_HI
+}
+
+#[inline]
+fn needle_mask(chunk: u128) -> u128 {
+    let mut mask = 0u128;
+    for &b in &NEEDLES {
+        mask |= haszero(chunk ^ u128::from_le_bytes([b; 16]));
+    }
+    mask
+}
+
+/// Offset of the first whitespace/quote/comment-starter byte in `bytes`,
+/// or `bytes.len()` if there isn't one. Scans 16 bytes at a time via
+/// `needle_mask`, falling back to a scalar loop on the final partial chunk.
+fn first_interesting(bytes: &[u8]) -> usize {
+    let mut i = 0;
+    while i + 16 <= bytes.len() {
+        let chunk = u128::from_le_bytes(bytes[i..i + 16].try_into().unwrap());
+        let mask = needle_mask(chunk);
+        if mask != 0 {
+            return i + (mask.trailing_zeros() / 8) as usize;
+        }
+        i += 16;
+    }
+    while i < bytes.len() && !is_interesting(bytes[i]) {
+        i += 1;
+    }
+    i
+}
+
+/// Collapse runs of ASCII whitespace to a single space and trim the ends,
+/// skipping over `--` and `/* */` comments and leaving `'...'`/`"..."`
+/// literals (with their `''`/`""` escapes) untouched - the in-process
+/// equivalent of the old `btrim(regexp_replace(...))` SPI call. "Boring"
+/// stretches between whitespace/quotes/comments are copied in bulk via
+/// `first_interesting`'s chunked scan rather than byte-by-byte.
+pub fn normalize_whitespace(query: &str) -> String {
+    let query = query.as_bytes();
+    let mut out = Vec::with_capacity(query.len());
+    let mut i = 0;
+    let mut pending_space = false;
+
+    while i < query.len() {
+        let skip = first_interesting(&query[i..]);
+        if skip > 0 {
+            if pending_space && !out.is_empty() { out.push(b' '); }
+            pending_space = false;
+            out.extend_from_slice(&query[i..i + skip]);
+            i += skip;
+            continue;
+        }
+
+        match query[i] {
+            b'-' if query.get(i + 1) == Some(&b'-') => {
+                i += 2;
+                while i < query.len() && query[i] != b'\n' { i += 1; }
+            }
+            b'/' if query.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i < query.len() && !(query[i] == b'*' && query.get(i + 1) == Some(&b'/')) {
+                    i += 1;
+                }
+                i = (i + 2).min(query.len());
+            }
+            q @ (b'\'' | b'"') => {
+                if pending_space && !out.is_empty() { out.push(b' '); }
+                pending_space = false;
+                out.push(q);
+                i += 1;
+                while i < query.len() {
+                    let c = query[i];
+                    out.push(c);
+                    i += 1;
+                    if c == q {
+                        if query.get(i) == Some(&q) {
+                            out.push(q);
+                            i += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                }
+            }
+            b' ' | b'\t' | b'\n' | b'\r' => {
+                pending_space = true;
+                i += 1;
+            }
+            // A bare `-`/`/` that isn't the start of a `--`/`/* */` comment
+            // (e.g. `price - 10`, `a/b`) - just an ordinary byte that happens
+            // to be in NEEDLES because it can *start* a comment.
+            b @ (b'-' | b'/') => {
+                if pending_space && !out.is_empty() { out.push(b' '); }
+                pending_space = false;
+                out.push(b);
+                i += 1;
+            }
+            _ => unreachable!("first_interesting only stops at NEEDLES bytes"),
+        }
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn parse_filter_expr(e: &Expr) -> WhereFilter {
+    match e {
+        Expr::BinaryOp { left, op, right } => parse_filter_binop(left, op, right),
+        Expr::IsNull(e) => {
+            parse_json_path(e).map_or(WhereFilter::Complex, |p| WhereFilter::IsNull { path: p })
+        }
+        Expr::IsNotNull(e) => {
+            parse_json_path(e).map_or(WhereFilter::Complex, |p| WhereFilter::IsNotNull { path: p })
+        }
+        Expr::InList { expr, list, negated } => {
+            let path = parse_json_path(expr);
+            let vals: Option<Vec<_>> = list.iter().map(parse_filter_value).collect();
+            match (path, vals) {
+                (Some(p), Some(v)) if !v.is_empty() => {
+                    if *negated { WhereFilter::NotIn { path: p, vals: v } } else { WhereFilter::In { path: p, vals: v } }
+                }
+                _ => WhereFilter::Complex,
+            }
+        }
+        Expr::Between { expr, negated, low, high } => {
+            let lower = parse_filter_binop(expr, &BinaryOperator::GtEq, low);
+            let upper = parse_filter_binop(expr, &BinaryOperator::LtEq, high);
+            let between = match (&lower, &upper) {
+                (WhereFilter::Complex, _) | (_, WhereFilter::Complex) => WhereFilter::Complex,
+                _ => WhereFilter::And(vec![lower, upper]),
+            };
+            if *negated { negate(between) } else { between }
+        }
+        Expr::Like { negated, expr, pattern, .. } => parse_like(expr, pattern, *negated, false),
+        Expr::ILike { negated, expr, pattern, .. } => parse_like(expr, pattern, *negated, true),
+        Expr::UnaryOp { op: UnaryOperator::Not, expr } => negate(parse_filter_expr(expr)),
+        Expr::Nested(inner) => parse_filter_expr(inner),
+        _ => WhereFilter::Complex,
+    }
+}
+
+/// Compile `expr [NOT] [I]LIKE pattern` to a `WhereFilter::Like`, or
+/// `Complex` if either side isn't a bare column / string literal.
+fn parse_like(expr: &Expr, pattern: &Expr, negated: bool, case_insensitive: bool) -> WhereFilter {
+    match (parse_json_path(expr), parse_filter_value(pattern)) {
+        (Some(path), Some(FilterValue::Str(pattern))) => {
+            let parts = compile_like_pattern(&pattern, case_insensitive);
+            WhereFilter::Like { path, parts, case_insensitive, negated }
+        }
+        _ => WhereFilter::Complex,
+    }
+}
+
+/// A `LIKE`/`ILIKE` pattern split into literal runs and wildcards at parse
+/// time - `compile_like_pattern` builds this once per query instead of
+/// re-walking the raw pattern string (with its `\`-escapes) on every row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LikePart {
+    Literal(String),
+    /// `_`: matches exactly one character.
+    AnyChar,
+    /// `%`: matches any run of characters, including none.
+    AnyRun,
+}
+
+/// Split a SQL `LIKE` pattern into `LikePart`s: `%` -> `AnyRun`, `_` ->
+/// `AnyChar`, `\x` -> a literal `x`, anything else accumulates into the
+/// current literal run. Literal runs are lowercased up front for
+/// case-insensitive matches, since only the row's value still needs
+/// lowercasing at `eval` time.
+fn compile_like_pattern(pattern: &str, case_insensitive: bool) -> Vec<LikePart> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => {
+                if !literal.is_empty() { parts.push(LikePart::Literal(std::mem::take(&mut literal))); }
+                parts.push(LikePart::AnyRun);
+            }
+            '_' => {
+                if !literal.is_empty() { parts.push(LikePart::Literal(std::mem::take(&mut literal))); }
+                parts.push(LikePart::AnyChar);
+            }
+            '\\' => {
+                if let Some(escaped) = chars.next() { literal.push(escaped); }
+            }
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() { parts.push(LikePart::Literal(literal)); }
+    if case_insensitive {
+        for part in &mut parts {
+            if let LikePart::Literal(s) = part { *s = s.to_lowercase(); }
+        }
+    }
+    parts
+}
+
+/// Push a `NOT` down to the leaves via De Morgan's laws instead of modeling
+/// it as a runtime node, so negated predicates stay indexable/evaluable the
+/// same way their un-negated forms are. A negated subtree that bottoms out
+/// in `Complex` keeps the whole negation `Complex`.
+fn negate(filter: WhereFilter) -> WhereFilter {
+    match filter {
+        WhereFilter::Eq { expr, val } => WhereFilter::Ne { expr, val },
+        WhereFilter::Ne { expr, val } => WhereFilter::Eq { expr, val },
+        WhereFilter::Gt { expr, val } => WhereFilter::Lte { expr, val },
+        WhereFilter::Gte { expr, val } => WhereFilter::Lt { expr, val },
+        WhereFilter::Lt { expr, val } => WhereFilter::Gte { expr, val },
+        WhereFilter::Lte { expr, val } => WhereFilter::Gt { expr, val },
+        WhereFilter::In { path, vals } => WhereFilter::NotIn { path, vals },
+        WhereFilter::NotIn { path, vals } => WhereFilter::In { path, vals },
+        WhereFilter::IsNull { path } => WhereFilter::IsNotNull { path },
+        WhereFilter::IsNotNull { path } => WhereFilter::IsNull { path },
+        WhereFilter::Like { path, parts, case_insensitive, negated } => {
+            WhereFilter::Like { path, parts, case_insensitive, negated: !negated }
+        }
+        WhereFilter::Range { expr, lower, upper } => {
+            let mut parts = Vec::new();
+            if let Some((v, inclusive)) = lower {
+                parts.push(if inclusive { WhereFilter::Lt { expr: expr.clone(), val: v } } else { WhereFilter::Lte { expr: expr.clone(), val: v } });
+            }
+            if let Some((v, inclusive)) = upper {
+                parts.push(if inclusive { WhereFilter::Gt { expr: expr.clone(), val: v } } else { WhereFilter::Gte { expr, val: v } });
+            }
+            match parts.len() {
+                0 => WhereFilter::Never,
+                1 => parts.into_iter().next().unwrap(),
+                _ => WhereFilter::Or(parts),
+            }
+        }
+        WhereFilter::And(filters) => negate_combinator(filters, WhereFilter::Or),
+        WhereFilter::Or(filters) => negate_combinator(filters, WhereFilter::And),
+        WhereFilter::Complex => WhereFilter::Complex,
+        WhereFilter::Never => WhereFilter::None,
+        WhereFilter::None => WhereFilter::Never,
+    }
+}
+
+fn negate_combinator<F>(filters: Vec<WhereFilter>, combine: F) -> WhereFilter
+where
+    F: FnOnce(Vec<WhereFilter>) -> WhereFilter,
+{
+    let negated: Vec<_> = filters.into_iter().map(negate).collect();
+    if negated.iter().any(|f| matches!(f, WhereFilter::Complex)) {
+        WhereFilter::Complex
+    } else {
+        combine(negated)
+    }
+}
+
+fn parse_filter_binop(left: &Expr, op: &BinaryOperator, right: &Expr) -> WhereFilter {
+    match op {
+        BinaryOperator::And => {
+            let l = parse_filter_expr(left);
+            let r = parse_filter_expr(right);
+            match (&l, &r) {
+                (WhereFilter::Complex, _) | (_, WhereFilter::Complex) => WhereFilter::Complex,
+                (WhereFilter::And(a), WhereFilter::And(b)) => {
+                    WhereFilter::And(a.iter().chain(b.iter()).cloned().collect())
+                }
+                (WhereFilter::And(a), _) => {
+                    let mut v = a.clone();
+                    v.push(r);
+                    WhereFilter::And(v)
+                }
+                (_, WhereFilter::And(b)) => {
+                    let mut v = vec![l];
+                    v.extend(b.iter().cloned());
+                    WhereFilter::And(v)
+                }
+                _ => WhereFilter::And(vec![l, r]),
+            }
+        }
+        BinaryOperator::Or => {
+            let l = parse_filter_expr(left);
+            let r = parse_filter_expr(right);
+            if matches!(l, WhereFilter::Complex) || matches!(r, WhereFilter::Complex) {
+                return WhereFilter::Complex;
+            }
+            WhereFilter::Or(vec![l, r])
+        }
+        BinaryOperator::Eq => fold_literal_eq(left, right, true)
+            .unwrap_or_else(|| cmp_filter(left, right, |e, v| WhereFilter::Eq { expr: e, val: v })),
+        BinaryOperator::NotEq => fold_literal_eq(left, right, false)
+            .unwrap_or_else(|| cmp_filter(left, right, |e, v| WhereFilter::Ne { expr: e, val: v })),
+        BinaryOperator::Gt => fold_literal_cmp(left, right, |o| o == std::cmp::Ordering::Greater)
+            .unwrap_or_else(|| cmp_filter(left, right, |e, v| WhereFilter::Gt { expr: e, val: v })),
+        BinaryOperator::GtEq => fold_literal_cmp(left, right, |o| o != std::cmp::Ordering::Less)
+            .unwrap_or_else(|| cmp_filter(left, right, |e, v| WhereFilter::Gte { expr: e, val: v })),
+        BinaryOperator::Lt => fold_literal_cmp(left, right, |o| o == std::cmp::Ordering::Less)
+            .unwrap_or_else(|| cmp_filter(left, right, |e, v| WhereFilter::Lt { expr: e, val: v })),
+        BinaryOperator::LtEq => fold_literal_cmp(left, right, |o| o != std::cmp::Ordering::Greater)
+            .unwrap_or_else(|| cmp_filter(left, right, |e, v| WhereFilter::Lte { expr: e, val: v })),
+        _ => WhereFilter::Complex,
+    }
+}
+
+/// Both sides of `left OP right` are literals (not columns), for folding a
+/// comparison like `1 = 2` to a constant at parse time instead of `Complex`.
+fn literal_pair(left: &Expr, right: &Expr) -> Option<(FilterValue, FilterValue)> {
+    if filter_col_name(left).is_some() || filter_col_name(right).is_some() {
+        return None;
+    }
+    Some((parse_filter_value(left)?, parse_filter_value(right)?))
+}
+
+fn fold_literal_eq(left: &Expr, right: &Expr, want_eq: bool) -> Option<WhereFilter> {
+    let (a, b) = literal_pair(left, right)?;
+    Some(if (a == b) == want_eq { WhereFilter::None } else { WhereFilter::Never })
+}
+
+fn fold_literal_cmp(left: &Expr, right: &Expr, pred: impl FnOnce(std::cmp::Ordering) -> bool) -> Option<WhereFilter> {
+    let (a, b) = literal_pair(left, right)?;
+    let ord = literal_ord(&a, &b)?;
+    Some(if pred(ord) { WhereFilter::None } else { WhereFilter::Never })
+}
+
+fn literal_ord(a: &FilterValue, b: &FilterValue) -> Option<std::cmp::Ordering> {
+    match (a, b) {
+        (FilterValue::Int(x), FilterValue::Int(y)) => Some(x.cmp(y)),
+        (FilterValue::Float(x), FilterValue::Float(y)) => x.partial_cmp(y),
+        (FilterValue::Int(x), FilterValue::Float(y)) => (*x as f64).partial_cmp(y),
+        (FilterValue::Float(x), FilterValue::Int(y)) => x.partial_cmp(&(*y as f64)),
+        (FilterValue::Str(x), FilterValue::Str(y)) => Some(x.as_str().cmp(y.as_str())),
+        _ => None,
+    }
+}
+
+fn cmp_filter<F>(left: &Expr, right: &Expr, f: F) -> WhereFilter
+where
+    F: FnOnce(ColExpr, FilterValue) -> WhereFilter,
+{
+    if let (Some(e), Some(v)) = (parse_col_expr(left), parse_filter_value(right)) {
+        return f(e, v);
+    }
+    if let (Some(v), Some(e)) = (parse_filter_value(left), parse_col_expr(right)) {
+        return f(e, v);
+    }
+    WhereFilter::Complex
+}
+
+fn filter_col_name(e: &Expr) -> Option<String> {
+    match e {
+        Expr::Identifier(id) => Some(id.value.to_lowercase()),
+        Expr::CompoundIdentifier(ids) => ids.last().map(|i| i.value.to_lowercase()),
+        _ => None,
+    }
+}
+
+/// Parse a column reference into a `ColumnPath`: a bare/qualified identifier
+/// is a single-`Key` path, and each `->`/`->>` hop (Postgres's JSON
+/// descent operators) appends one more `PathSegment`, so `address->'city'`
+/// or `items->0->>'sku'` resolve to nested JSON fields instead of `Complex`.
+fn parse_json_path(e: &Expr) -> Option<ColumnPath> {
+    match e {
+        Expr::Identifier(_) | Expr::CompoundIdentifier(_) => filter_col_name(e).map(|c| vec![PathSegment::Key(c)]),
+        Expr::BinaryOp { left, op: BinaryOperator::Arrow, right }
+        | Expr::BinaryOp { left, op: BinaryOperator::LongArrow, right } => {
+            let mut path = parse_json_path(left)?;
+            path.push(path_segment(right)?);
+            Some(path)
+        }
+        Expr::Nested(inner) => parse_json_path(inner),
+        _ => None,
+    }
+}
+
+/// A single `->`/`->>` right-hand operand: a string key or a non-negative
+/// integer index.
+fn path_segment(e: &Expr) -> Option<PathSegment> {
+    match parse_filter_value(e)? {
+        FilterValue::Str(s) => Some(PathSegment::Key(s)),
+        FilterValue::Int(i) if i >= 0 => Some(PathSegment::Index(i as usize)),
+        _ => None,
+    }
+}
+
+/// Whitelist of scalar functions `parse_col_expr` will recognize, mapped
+/// from SQL name to `ScalarFn`. Anything else on the column side of a
+/// comparison stays `Complex`.
+fn scalar_fn_named(name: &str) -> Option<ScalarFn> {
+    match name.to_uppercase().as_str() {
+        "LOWER" => Some(ScalarFn::Lower),
+        "UPPER" => Some(ScalarFn::Upper),
+        "ABS" => Some(ScalarFn::Abs),
+        "COALESCE" => Some(ScalarFn::Coalesce),
+        _ => None,
+    }
+}
+
+/// Parse the column side of a comparison into a `ColExpr`: a bare column,
+/// an integer/float literal, simple `+ - * /` arithmetic over those, or a
+/// whitelisted scalar function call - recursively, so e.g.
+/// `lower(price * quantity)` still resolves. Anything else (a non-whitelisted
+/// function, a `CASE`, a subquery, ...) falls back to `None`, which leaves
+/// the enclosing comparison `Complex`.
+fn parse_col_expr(e: &Expr) -> Option<ColExpr> {
+    if let Expr::Identifier(_) | Expr::CompoundIdentifier(_) = e {
+        return parse_json_path(e).map(ColExpr::Col);
+    }
+    match e {
+        Expr::Value(v) => match &v.value {
+            Value::Number(n, _) => {
+                if let Ok(i) = n.parse::<i64>() { Some(ColExpr::IntLit(i)) } else { n.parse::<f64>().ok().map(ColExpr::FloatLit) }
+            }
+            _ => None,
+        },
+        Expr::UnaryOp { op: UnaryOperator::Minus, expr } => match parse_col_expr(expr)? {
+            ColExpr::IntLit(i) => Some(ColExpr::IntLit(-i)),
+            ColExpr::FloatLit(f) => Some(ColExpr::FloatLit(-f)),
+            _ => None,
+        },
+        Expr::BinaryOp { op: BinaryOperator::Arrow, .. } | Expr::BinaryOp { op: BinaryOperator::LongArrow, .. } => {
+            parse_json_path(e).map(ColExpr::Col)
+        }
+        Expr::BinaryOp { left, op, right } => {
+            let l = Box::new(parse_col_expr(left)?);
+            let r = Box::new(parse_col_expr(right)?);
+            match op {
+                BinaryOperator::Plus => Some(ColExpr::Add(l, r)),
+                BinaryOperator::Minus => Some(ColExpr::Sub(l, r)),
+                BinaryOperator::Multiply => Some(ColExpr::Mul(l, r)),
+                BinaryOperator::Divide => Some(ColExpr::Div(l, r)),
+                _ => None,
+            }
+        }
+        Expr::Function(func) => {
+            let scalar_fn = scalar_fn_named(&func.name.to_string())?;
+            let FunctionArguments::List(args) = &func.args else { return None };
+            let parsed: Option<Vec<ColExpr>> = args.args.iter().map(|arg| {
+                let arg_expr = match arg {
+                    FunctionArg::Named { arg, .. } => arg,
+                    FunctionArg::Unnamed(a) => a,
+                };
+                match arg_expr {
+                    FunctionArgExpr::Expr(e) => parse_col_expr(e),
+                    _ => None,
+                }
+            }).collect();
+            parsed.map(|a| ColExpr::Func(scalar_fn, a))
+        }
+        Expr::Nested(inner) => parse_col_expr(inner),
+        _ => None,
+    }
+}
+
+fn parse_filter_value(e: &Expr) -> Option<FilterValue> {
+    match e {
+        Expr::Value(v) => match &v.value {
+            Value::Null => Some(FilterValue::Null),
+            Value::Boolean(b) => Some(FilterValue::Bool(*b)),
+            Value::Number(n, _) => {
+                if let Ok(i) = n.parse::<i64>() {
+                    Some(FilterValue::Int(i))
+                } else if let Ok(f) = n.parse::<f64>() {
+                    Some(FilterValue::Float(f))
+                } else {
+                    None
+                }
+            }
+            Value::SingleQuotedString(s) | Value::DoubleQuotedString(s) => {
+                Some(FilterValue::Str(s.clone()))
+            }
+            _ => None,
+        },
+        Expr::UnaryOp { op: UnaryOperator::Minus, expr } => match parse_filter_value(expr) {
+            Some(FilterValue::Int(i)) => Some(FilterValue::Int(-i)),
+            Some(FilterValue::Float(f)) => Some(FilterValue::Float(-f)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// === WHERE Filter Evaluation ===
+
+impl WhereFilter {
+    /// Fold constant-decidable structure and flatten nested `And`/`And` and
+    /// `Or`/`Or`, once after parsing and before this filter is cached. Leaf
+    /// literal-vs-literal comparisons are already folded to `None`/`Never`
+    /// at parse time (see `fold_literal_eq`/`fold_literal_cmp`); this pass
+    /// propagates those constants up through the tree: an `And` drops
+    /// constant-`Match` (`None`) children and collapses to `Never` if any
+    /// child is constant-`NoMatch`; an `Or` does the mirror image. Doesn't
+    /// distribute to conjunctive normal form - flattening plus constant
+    /// folding already exposes each conjunct to indexing, and full
+    /// distribution can blow a filter up combinatorially for little gain.
+    pub fn simplify(self) -> WhereFilter {
+        match self {
+            WhereFilter::And(filters) => {
+                let mut flat = Vec::with_capacity(filters.len());
+                for f in filters {
+                    match f.simplify() {
+                        WhereFilter::None => {}
+                        WhereFilter::Never => return WhereFilter::Never,
+                        WhereFilter::And(inner) => flat.extend(inner),
+                        other => flat.push(other),
+                    }
+                }
+                let flat = merge_ranges(flat);
+                if flat.iter().any(|f| matches!(f, WhereFilter::Never)) {
+                    return WhereFilter::Never;
+                }
+                match flat.len() {
+                    0 => WhereFilter::None,
+                    1 => flat.into_iter().next().unwrap(),
+                    _ => WhereFilter::And(flat),
+                }
+            }
+            WhereFilter::Or(filters) => {
+                let mut flat = Vec::with_capacity(filters.len());
+                for f in filters {
+                    match f.simplify() {
+                        WhereFilter::Never => {}
+                        WhereFilter::None => return WhereFilter::None,
+                        WhereFilter::Or(inner) => flat.extend(inner),
+                        other => flat.push(other),
+                    }
+                }
+                match flat.len() {
+                    0 => WhereFilter::Never,
+                    1 => flat.into_iter().next().unwrap(),
+                    _ => WhereFilter::Or(flat),
+                }
+            }
+            other => other,
+        }
+    }
+
+    /// Evaluate this filter against a JSON row (as produced by
+    /// `row_to_json`/`heap_tuple_to_json`).
+    pub fn eval(&self, row: &serde_json::Value) -> EvalResult {
+        match self {
+            WhereFilter::None => EvalResult::Match,
+            WhereFilter::Never => EvalResult::NoMatch,
+            WhereFilter::Complex => EvalResult::Unknown,
+
+            WhereFilter::Eq { expr, val } => eq_eval(expr.eval_row(row), val, true),
+            WhereFilter::Ne { expr, val } => eq_eval(expr.eval_row(row), val, false),
+            WhereFilter::Gt { expr, val } => cmp_eval(row, expr, val, |o| o == std::cmp::Ordering::Greater),
+            WhereFilter::Gte { expr, val } => cmp_eval(row, expr, val, |o| o != std::cmp::Ordering::Less),
+            WhereFilter::Lt { expr, val } => cmp_eval(row, expr, val, |o| o == std::cmp::Ordering::Less),
+            WhereFilter::Lte { expr, val } => cmp_eval(row, expr, val, |o| o != std::cmp::Ordering::Greater),
+
+            WhereFilter::In { path, vals } => match walk_path(row, path) {
+                Some(v) if v.is_null() => EvalResult::Unknown,
+                Some(v) => if vals.iter().any(|fv| fv.matches(v)) { EvalResult::Match } else { EvalResult::NoMatch },
+                None => EvalResult::Unknown,
+            },
+            WhereFilter::NotIn { path, vals } => match walk_path(row, path) {
+                Some(v) if v.is_null() => EvalResult::Unknown,
+                Some(v) => if vals.iter().any(|fv| fv.matches(v)) { EvalResult::NoMatch } else { EvalResult::Match },
+                None => EvalResult::Unknown,
+            },
+            WhereFilter::IsNull { path } => match walk_path(row, path) {
+                Some(v) => if v.is_null() { EvalResult::Match } else { EvalResult::NoMatch },
+                None => EvalResult::Unknown,
+            },
+            WhereFilter::IsNotNull { path } => match walk_path(row, path) {
+                Some(v) => if v.is_null() { EvalResult::NoMatch } else { EvalResult::Match },
+                None => EvalResult::Unknown,
+            },
+            WhereFilter::Like { path, parts, case_insensitive, negated } => match walk_path(row, path) {
+                Some(serde_json::Value::String(s)) => {
+                    let matched = like_matches_compiled(s, parts, *case_insensitive);
+                    if matched != *negated { EvalResult::Match } else { EvalResult::NoMatch }
+                }
+                Some(_) => EvalResult::Unknown,
+                None => EvalResult::Unknown,
+            },
+            WhereFilter::Range { expr, lower, upper } => range_eval(row, expr, lower, upper),
+
+            WhereFilter::And(filters) => {
+                let mut has_unknown = false;
+                for f in filters {
+                    match f.eval(row) {
+                        EvalResult::NoMatch => return EvalResult::NoMatch,
+                        EvalResult::Unknown => has_unknown = true,
+                        EvalResult::Match => {}
+                    }
+                }
+                if has_unknown { EvalResult::Unknown } else { EvalResult::Match }
+            }
+            WhereFilter::Or(filters) => {
+                let mut has_unknown = false;
+                for f in filters {
+                    match f.eval(row) {
+                        EvalResult::Match => return EvalResult::Match,
+                        EvalResult::Unknown => has_unknown = true,
+                        EvalResult::NoMatch => {}
+                    }
+                }
+                if has_unknown { EvalResult::Unknown } else { EvalResult::NoMatch }
+            }
+        }
+    }
+}
+
+/// Match `text` against a pre-compiled `LIKE` pattern (see
+/// `compile_like_pattern`). Lowercases `text` for case-insensitive matches -
+/// the pattern's literal runs are already lowercased at compile time.
+fn like_matches_compiled(text: &str, parts: &[LikePart], case_insensitive: bool) -> bool {
+    fn go(t: &[char], parts: &[LikePart]) -> bool {
+        match parts.first() {
+            None => t.is_empty(),
+            Some(LikePart::AnyRun) => go(t, &parts[1..]) || (!t.is_empty() && go(&t[1..], parts)),
+            Some(LikePart::AnyChar) => !t.is_empty() && go(&t[1..], &parts[1..]),
+            Some(LikePart::Literal(lit)) => {
+                let lit: Vec<char> = lit.chars().collect();
+                t.len() >= lit.len() && t[..lit.len()] == lit[..] && go(&t[lit.len()..], &parts[1..])
+            }
+        }
+    }
+    let text = if case_insensitive { text.to_lowercase() } else { text.to_string() };
+    let text: Vec<char> = text.chars().collect();
+    go(&text, parts)
+}
+
+/// SQL equality is tri-valued: `NULL = x` and `x = NULL` are both `Unknown`,
+/// never `Match`/`NoMatch`, even when `x` is itself `NULL` - same reason
+/// `col = 5` must use `IS NULL` instead to catch a null `col`.
+fn eq_eval(computed: Option<FilterValue>, val: &FilterValue, want_eq: bool) -> EvalResult {
+    match computed {
+        Some(FilterValue::Null) | None => EvalResult::Unknown,
+        _ if matches!(val, FilterValue::Null) => EvalResult::Unknown,
+        Some(v) => if (v == *val) == want_eq { EvalResult::Match } else { EvalResult::NoMatch },
+    }
+}
+
+fn cmp_eval<F>(row: &serde_json::Value, expr: &ColExpr, val: &FilterValue, pred: F) -> EvalResult
+where
+    F: FnOnce(std::cmp::Ordering) -> bool,
+{
+    match expr.eval_row(row) {
+        Some(v) => match literal_ord(&v, val) {
+            Some(ord) => if pred(ord) { EvalResult::Match } else { EvalResult::NoMatch },
+            None => EvalResult::Unknown,
+        },
+        None => EvalResult::Unknown,
+    }
+}
+
+/// Fold same-`ColExpr` `Gt`/`Gte`/`Lt`/`Lte`/`Range` conjuncts from a
+/// flattened `And` into one tightest `Range` per expression, so a desugared
+/// `BETWEEN` or a hand-written `age >= 18 AND age <= 65` checks a single
+/// node instead of two. A contradictory combination (`age > 100 AND age <
+/// 10`) produces a `WhereFilter::Never` entry - the caller treats any
+/// `Never` in the result as the whole `And` collapsing to `Never`.
+/// Conjuncts on different expressions, or anything that isn't a bound, pass
+/// through untouched.
+fn merge_ranges(filters: Vec<WhereFilter>) -> Vec<WhereFilter> {
+    struct Bound {
+        lower: Option<(FilterValue, bool)>,
+        upper: Option<(FilterValue, bool)>,
+    }
+
+    let mut ranges: Vec<(ColExpr, Bound)> = Vec::new();
+    let mut rest = Vec::new();
+    for f in filters {
+        let (expr, lower, upper) = match f {
+            WhereFilter::Gt { expr, val } => (expr, Some((val, false)), None),
+            WhereFilter::Gte { expr, val } => (expr, Some((val, true)), None),
+            WhereFilter::Lt { expr, val } => (expr, None, Some((val, false))),
+            WhereFilter::Lte { expr, val } => (expr, None, Some((val, true))),
+            WhereFilter::Range { expr, lower, upper } => (expr, lower, upper),
+            other => {
+                rest.push(other);
+                continue;
+            }
+        };
+        match ranges.iter_mut().find(|(e, _)| *e == expr) {
+            Some((_, bound)) => {
+                if let Some(b) = lower { tighten_lower(&mut bound.lower, b); }
+                if let Some(b) = upper { tighten_upper(&mut bound.upper, b); }
+            }
+            None => ranges.push((expr, Bound { lower, upper })),
+        }
+    }
+
+    for (expr, bound) in ranges {
+        let contradictory = match (&bound.lower, &bound.upper) {
+            (Some((lo, lo_inc)), Some((hi, hi_inc))) => match literal_ord(lo, hi) {
+                Some(std::cmp::Ordering::Greater) => true,
+                Some(std::cmp::Ordering::Equal) => !(*lo_inc && *hi_inc),
+                _ => false,
+            },
+            _ => false,
+        };
+        rest.push(if contradictory { WhereFilter::Never } else { WhereFilter::Range { expr, lower: bound.lower, upper: bound.upper } });
+    }
+    rest
+}
+
+/// Keep `existing` as the tighter (higher, or equal-but-more-exclusive)
+/// lower bound between itself and `new`.
+fn tighten_lower(existing: &mut Option<(FilterValue, bool)>, new: (FilterValue, bool)) {
+    let replace = match existing {
+        None => true,
+        Some((cur, cur_inc)) => match literal_ord(&new.0, cur) {
+            Some(std::cmp::Ordering::Greater) => true,
+            Some(std::cmp::Ordering::Equal) => *cur_inc && !new.1,
+            _ => false,
+        },
+    };
+    if replace { *existing = Some(new); }
+}
+
+/// Keep `existing` as the tighter (lower, or equal-but-more-exclusive) upper
+/// bound between itself and `new`.
+fn tighten_upper(existing: &mut Option<(FilterValue, bool)>, new: (FilterValue, bool)) {
+    let replace = match existing {
+        None => true,
+        Some((cur, cur_inc)) => match literal_ord(&new.0, cur) {
+            Some(std::cmp::Ordering::Less) => true,
+            Some(std::cmp::Ordering::Equal) => *cur_inc && !new.1,
+            _ => false,
+        },
+    };
+    if replace { *existing = Some(new); }
+}
+
+/// Evaluate a `WhereFilter::Range` bound: `expr` must clear `lower` (if set)
+/// and clear `upper` (if set). Same tri-valued rules as `cmp_eval` - a
+/// missing column or an incomparable pair (e.g. string vs number) is
+/// `Unknown`, not a hard `NoMatch`.
+fn range_eval(row: &serde_json::Value, expr: &ColExpr, lower: &Option<(FilterValue, bool)>, upper: &Option<(FilterValue, bool)>) -> EvalResult {
+    let Some(v) = expr.eval_row(row) else { return EvalResult::Unknown };
+    if let Some((bound, inclusive)) = lower {
+        match literal_ord(&v, bound) {
+            Some(std::cmp::Ordering::Greater) => {}
+            Some(std::cmp::Ordering::Equal) if *inclusive => {}
+            Some(_) => return EvalResult::NoMatch,
+            None => return EvalResult::Unknown,
+        }
+    }
+    if let Some((bound, inclusive)) = upper {
+        match literal_ord(&v, bound) {
+            Some(std::cmp::Ordering::Less) => {}
+            Some(std::cmp::Ordering::Equal) if *inclusive => {}
+            Some(_) => return EvalResult::NoMatch,
+            None => return EvalResult::Unknown,
+        }
+    }
+    EvalResult::Match
+}
+
+impl FilterValue {
+    fn matches(&self, v: &serde_json::Value) -> bool {
+        match (self, v) {
+            (FilterValue::Null, serde_json::Value::Null) => true,
+            (FilterValue::Bool(a), serde_json::Value::Bool(b)) => a == b,
+            (FilterValue::Int(a), serde_json::Value::Number(n)) => n.as_i64() == Some(*a),
+            (FilterValue::Float(a), serde_json::Value::Number(n)) => n.as_f64() == Some(*a),
+            (FilterValue::Str(a), serde_json::Value::String(b)) => a == b,
+            (FilterValue::Int(a), serde_json::Value::String(s)) => s.parse::<i64>().ok() == Some(*a),
+            (FilterValue::Str(a), serde_json::Value::Number(n)) => n.to_string() == *a,
+            _ => false,
+        }
+    }
+
+    /// Order a JSON row value `v` against this literal - `v.cmp(self)`, i.e.
+    /// `Greater` means `v` sorts after `self`. Used for window-boundary
+    /// classification (`classify_window_row`).
+    fn cmp_row_value(&self, v: &serde_json::Value) -> Option<std::cmp::Ordering> {
+        match (self, v) {
+            (FilterValue::Int(a), serde_json::Value::Number(n)) => n.as_i64().map(|b| b.cmp(a)),
+            (FilterValue::Float(a), serde_json::Value::Number(n)) => n.as_f64().and_then(|b| b.partial_cmp(a)),
+            (FilterValue::Str(a), serde_json::Value::String(b)) => Some(b.as_str().cmp(a.as_str())),
+            _ => None,
+        }
+    }
+
+    /// Convert a JSON scalar to the `FilterValue` it would equal, for use as
+    /// an inverted-index key (see `crate::query_index`). Arrays/objects have
+    /// no equality predicate to index against, so they map to `None`.
+    pub(crate) fn from_json(v: &serde_json::Value) -> Option<FilterValue> {
+        match v {
+            serde_json::Value::Null => Some(FilterValue::Null),
+            serde_json::Value::Bool(b) => Some(FilterValue::Bool(*b)),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => Some(FilterValue::Int(i)),
+                None => n.as_f64().map(FilterValue::Float),
+            },
+            serde_json::Value::String(s) => Some(FilterValue::Str(s.clone())),
+            serde_json::Value::Array(_) | serde_json::Value::Object(_) => None,
+        }
+    }
+}
+
+impl ColExpr {
+    /// Compute this expression's value against a JSON row. `None` means
+    /// "can't determine" (a referenced column is missing, or an operand has
+    /// the wrong type) - callers should treat that as `EvalResult::Unknown`,
+    /// same as a missing column already does for a bare `Col`.
+    pub fn eval_row(&self, row: &serde_json::Value) -> Option<FilterValue> {
+        match self {
+            ColExpr::Col(path) => FilterValue::from_json(walk_path(row, path)?),
+            ColExpr::IntLit(i) => Some(FilterValue::Int(*i)),
+            ColExpr::FloatLit(f) => Some(FilterValue::Float(*f)),
+            ColExpr::Add(l, r) => numeric_op(l.eval_row(row)?, r.eval_row(row)?, |a, b| a + b, i64::checked_add),
+            ColExpr::Sub(l, r) => numeric_op(l.eval_row(row)?, r.eval_row(row)?, |a, b| a - b, i64::checked_sub),
+            ColExpr::Mul(l, r) => numeric_op(l.eval_row(row)?, r.eval_row(row)?, |a, b| a * b, i64::checked_mul),
+            ColExpr::Div(l, r) => {
+                let a = as_f64(&l.eval_row(row)?)?;
+                let b = as_f64(&r.eval_row(row)?)?;
+                if b == 0.0 { None } else { Some(FilterValue::Float(a / b)) }
+            }
+            ColExpr::Func(f, args) => eval_scalar_fn(*f, args, row),
+        }
+    }
+}
+
+fn numeric_op(l: FilterValue, r: FilterValue, float_op: fn(f64, f64) -> f64, int_op: fn(i64, i64) -> Option<i64>) -> Option<FilterValue> {
+    if let (FilterValue::Int(a), FilterValue::Int(b)) = (&l, &r) {
+        return int_op(*a, *b).map(FilterValue::Int);
+    }
+    Some(FilterValue::Float(float_op(as_f64(&l)?, as_f64(&r)?)))
+}
+
+fn as_f64(v: &FilterValue) -> Option<f64> {
+    match v {
+        FilterValue::Int(i) => Some(*i as f64),
+        FilterValue::Float(f) => Some(*f),
+        _ => None,
+    }
+}
+
+fn eval_scalar_fn(f: ScalarFn, args: &[ColExpr], row: &serde_json::Value) -> Option<FilterValue> {
+    match f {
+        ScalarFn::Lower | ScalarFn::Upper => {
+            let [a] = args else { return None };
+            match a.eval_row(row)? {
+                FilterValue::Str(s) => Some(FilterValue::Str(if f == ScalarFn::Lower { s.to_lowercase() } else { s.to_uppercase() })),
+                _ => None,
+            }
+        }
+        ScalarFn::Abs => {
+            let [a] = args else { return None };
+            match a.eval_row(row)? {
+                FilterValue::Int(i) => Some(FilterValue::Int(i.abs())),
+                FilterValue::Float(f) => Some(FilterValue::Float(f.abs())),
+                _ => None,
+            }
+        }
+        ScalarFn::Coalesce => {
+            for a in args {
+                match a.eval_row(row)? {
+                    FilterValue::Null => continue,
+                    v => return Some(v),
+                }
+            }
+            Some(FilterValue::Null)
+        }
+    }
+}
+
+impl PartialEq for FilterValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (FilterValue::Null, FilterValue::Null) => true,
+            (FilterValue::Bool(a), FilterValue::Bool(b)) => a == b,
+            (FilterValue::Int(a), FilterValue::Int(b)) => a == b,
+            (FilterValue::Float(a), FilterValue::Float(b)) => a.to_bits() == b.to_bits(),
+            (FilterValue::Str(a), FilterValue::Str(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for FilterValue {}
+
+impl std::hash::Hash for FilterValue {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            FilterValue::Null => {}
+            FilterValue::Bool(b) => b.hash(state),
+            FilterValue::Int(i) => i.hash(state),
+            FilterValue::Float(f) => f.to_bits().hash(state),
+            FilterValue::Str(s) => s.hash(state),
+        }
+    }
+}
+
+impl QueryAnalysis {
+    /// Classify `row` against a bounded top-N window's current boundary
+    /// (the sort-key tuple of the last in-window row, one `FilterValue` per
+    /// `order_by` key, in the same order) without requerying:
+    /// `Match` - `row` definitely belongs inside the window,
+    /// `NoMatch` - `row` definitely falls outside it,
+    /// `Unknown` - ties the boundary, or isn't decidable, so the caller
+    /// should fall back to a requery.
+    ///
+    /// Only meaningful when `order_by_simple` is true and `limit` is set -
+    /// otherwise always `Unknown`.
+    pub fn classify_window_row(&self, row: &serde_json::Value, boundary: &[FilterValue]) -> EvalResult {
+        if !self.order_by_simple || self.limit.is_none() || self.order_by.is_empty() {
+            return EvalResult::Unknown;
+        }
+        if boundary.len() != self.order_by.len() {
+            return EvalResult::Unknown;
+        }
+        for ((col, ascending), bound) in self.order_by.iter().zip(boundary) {
+            let Some(v) = row.get(col.as_ref()) else { return EvalResult::Unknown };
+            match bound.cmp_row_value(v) {
+                Some(std::cmp::Ordering::Equal) => continue,
+                Some(ord) => {
+                    let row_beats_boundary = if *ascending { ord == std::cmp::Ordering::Less } else { ord == std::cmp::Ordering::Greater };
+                    return if row_beats_boundary { EvalResult::Match } else { EvalResult::NoMatch };
+                }
+                None => return EvalResult::Unknown,
+            }
+        }
+        // Tied with the boundary on every key - ambiguous without a stable
+        // tiebreaker, so let the caller requery rather than guess.
+        EvalResult::Unknown
+    }
+
+    /// Inject `ctx`'s mandatory row-level predicate into this analysis's
+    /// filter via `AND`, so `eval` enforces it automatically - a row that
+    /// fails the security predicate evaluates to `NoMatch` regardless of
+    /// what the client's own WHERE clause allows. The combined filter is
+    /// re-simplified (see `WhereFilter::simplify`), so the returned
+    /// analysis's `filter` is still the effective, canonical filter to
+    /// inspect or audit - there's no separate "client filter" left over.
+    pub fn with_security(&self, ctx: &SecurityContext) -> QueryAnalysis {
+        let mut out = self.clone();
+        out.filter = WhereFilter::And(vec![self.filter.clone(), ctx.predicate()]).simplify();
+        out
+    }
+}
+
+fn calculate_complexity(a: &QueryAnalysis) -> u32 {
+    let mut s = 10u32;
+    s += a.referenced_tables.len() as u32 * 10;
+    if a.has_join { s += 15 + a.join_types.len() as u32 * 5; }
+    if a.has_aggregation { s += 10 + a.aggregation_functions.len() as u32 * 5; }
+    if a.has_group_by { s += 10; }
+    if a.has_window_functions { s += 25; }
+    if a.has_subqueries { s += 20; }
+    if a.has_cte { s += 15; }
+    if a.has_locking_clause { s += 15; }
+    s.min(100)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_simple_select() {
+        let analysis = analyze_query("SELECT * FROM users");
+        assert!(analysis.is_valid);
+        assert!(!analysis.has_join);
+        assert!(!analysis.has_aggregation);
+    }
+    
+    #[test]
+    fn test_join_detection() {
+        let analysis = analyze_query(
+            "SELECT u.name, o.amount FROM users u INNER JOIN orders o ON u.id = o.user_id"
+        );
+        assert!(analysis.has_join);
+        assert!(analysis.join_types.contains(&"INNER".to_string()));
+    }
+    
+    #[test]
+    fn test_aggregation_detection() {
+        let analysis = analyze_query(
+            "SELECT user_id, COUNT(*), SUM(amount) FROM orders GROUP BY user_id"
+        );
+        assert!(analysis.has_aggregation);
+        assert!(analysis.has_group_by);
+        assert!(analysis.aggregation_functions.contains(&"COUNT".to_string()));
+        assert!(analysis.aggregation_functions.contains(&"SUM".to_string()));
+    }
+    
+    #[test]
+    fn test_window_function_detection() {
+        let analysis = analyze_query(
+            "SELECT user_id, ROW_NUMBER() OVER (PARTITION BY user_id) FROM orders"
+        );
+        assert!(analysis.has_window_functions);
+        assert!(!analysis.ivm_compatible);
+    }
+
+    #[test]
+    fn test_locking_clause_detection() {
+        let analysis = analyze_query("SELECT * FROM users WHERE id = 1 FOR UPDATE SKIP LOCKED");
+        assert!(analysis.has_locking_clause);
+        assert_eq!(analysis.lock_clauses, vec!["FOR UPDATE SKIP LOCKED".to_string()]);
+        assert!(!analysis.ivm_compatible);
+    }
+
+    #[test]
+    fn test_top_n_is_ivm_compatible() {
+        let analysis = analyze_query("SELECT * FROM users ORDER BY score DESC LIMIT 10");
+        assert!(matches!(analysis.limit_kind, LimitKind::LimitRows(10)));
+        assert_eq!(analysis.order_by_keys, vec!["score DESC".to_string()]);
+        assert!(analysis.ivm_compatible);
+    }
+
+    #[test]
+    fn test_fetch_with_ties_incompatible() {
+        let analysis = analyze_query("SELECT * FROM users ORDER BY score DESC FETCH FIRST 10 ROWS WITH TIES");
+        assert!(matches!(analysis.limit_kind, LimitKind::LimitRank));
+        assert!(!analysis.ivm_compatible);
+    }
+
+    #[test]
+    fn test_offset_incompatible() {
+        let analysis = analyze_query("SELECT * FROM users ORDER BY score DESC LIMIT 10 OFFSET 5");
+        assert!(!analysis.ivm_compatible);
+    }
+
+    #[test]
+    fn test_structured_order_by_limit_offset() {
+        let analysis = analyze_query("SELECT * FROM posts ORDER BY created_at DESC LIMIT 20 OFFSET 5");
+        assert_eq!(analysis.order_by, vec![("created_at".into(), false)]);
+        assert!(analysis.order_by_simple);
+        assert_eq!(analysis.limit, Some(20));
+        assert_eq!(analysis.offset, Some(5));
+    }
+
+    #[test]
+    fn test_order_by_expression_clears_order_by_simple() {
+        let analysis = analyze_query("SELECT * FROM posts ORDER BY lower(title) LIMIT 20");
+        assert!(!analysis.order_by_simple);
+        assert!(analysis.order_by.is_empty());
+    }
+
+    #[test]
+    fn test_classify_window_row_definitely_inside_and_outside() {
+        let analysis = analyze_query("SELECT * FROM posts ORDER BY created_at DESC LIMIT 20");
+        let boundary = vec![FilterValue::Int(100)];
+        assert_eq!(
+            analysis.classify_window_row(&serde_json::json!({"created_at": 150}), &boundary),
+            EvalResult::Match
+        );
+        assert_eq!(
+            analysis.classify_window_row(&serde_json::json!({"created_at": 50}), &boundary),
+            EvalResult::NoMatch
+        );
+        assert_eq!(
+            analysis.classify_window_row(&serde_json::json!({"created_at": 100}), &boundary),
+            EvalResult::Unknown
+        );
+    }
+
+    #[test]
+    fn test_classify_window_row_ascending_order() {
+        let analysis = analyze_query("SELECT * FROM posts ORDER BY rank ASC LIMIT 5");
+        let boundary = vec![FilterValue::Int(10)];
+        assert_eq!(
+            analysis.classify_window_row(&serde_json::json!({"rank": 3}), &boundary),
+            EvalResult::Match
+        );
+        assert_eq!(
+            analysis.classify_window_row(&serde_json::json!({"rank": 20}), &boundary),
+            EvalResult::NoMatch
+        );
+    }
+
+    #[test]
+    fn test_classify_window_row_unknown_without_bounded_limit() {
+        let analysis = analyze_query("SELECT * FROM posts ORDER BY created_at DESC");
+        let boundary = vec![FilterValue::Int(100)];
+        assert_eq!(
+            analysis.classify_window_row(&serde_json::json!({"created_at": 150}), &boundary),
+            EvalResult::Unknown
+        );
+    }
+
+    #[test]
+    fn test_filter_extraction_and_eval() {
+        let analysis = analyze_query("SELECT * FROM users WHERE status = 'active' AND age >= 18");
+        let active_adult = serde_json::json!({"status": "active", "age": 21});
+        let inactive = serde_json::json!({"status": "inactive", "age": 21});
+        let minor = serde_json::json!({"status": "active", "age": 12});
+        assert_eq!(analysis.filter.eval(&active_adult), EvalResult::Match);
+        assert_eq!(analysis.filter.eval(&inactive), EvalResult::NoMatch);
+        assert_eq!(analysis.filter.eval(&minor), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_filter_unknown_on_missing_column() {
+        let analysis = analyze_query("SELECT * FROM users WHERE status = 'active'");
+        let row = serde_json::json!({"id": 1});
+        assert_eq!(analysis.filter.eval(&row), EvalResult::Unknown);
+    }
+
+    #[test]
+    fn test_null_column_is_unknown_under_eq_and_ne() {
+        let eq = analyze_query("SELECT * FROM users WHERE status = 'active'");
+        let ne = analyze_query("SELECT * FROM users WHERE status != 'active'");
+        let row = serde_json::json!({"status": null});
+        assert_eq!(eq.filter.eval(&row), EvalResult::Unknown);
+        assert_eq!(ne.filter.eval(&row), EvalResult::Unknown);
+    }
+
+    #[test]
+    fn test_null_column_is_unknown_under_in_and_not_in() {
+        let in_filter = analyze_query("SELECT * FROM users WHERE status IN ('a', 'b')");
+        let not_in_filter = analyze_query("SELECT * FROM users WHERE status NOT IN ('a', 'b')");
+        let row = serde_json::json!({"status": null});
+        assert_eq!(in_filter.filter.eval(&row), EvalResult::Unknown);
+        assert_eq!(not_in_filter.filter.eval(&row), EvalResult::Unknown);
+    }
+
+    #[test]
+    fn test_is_null_stays_two_valued_unlike_eq() {
+        let analysis = analyze_query("SELECT * FROM users WHERE status IS NULL");
+        let row = serde_json::json!({"status": null});
+        assert_eq!(analysis.filter.eval(&row), EvalResult::Match);
+    }
+
+    #[test]
+    fn test_and_with_null_operand_follows_kleene_table() {
+        let match_and_unknown = analyze_query("SELECT * FROM users WHERE active = true AND status = 'x'");
+        assert_eq!(
+            match_and_unknown.filter.eval(&serde_json::json!({"active": true, "status": null})),
+            EvalResult::Unknown
+        );
+
+        let nomatch_and_unknown = analyze_query("SELECT * FROM users WHERE active = true AND status = 'x'");
+        assert_eq!(
+            nomatch_and_unknown.filter.eval(&serde_json::json!({"active": false, "status": null})),
+            EvalResult::NoMatch
+        );
+    }
+
+    #[test]
+    fn test_or_with_null_operand_follows_kleene_table() {
+        let nomatch_or_unknown = analyze_query("SELECT * FROM users WHERE active = true OR status = 'x'");
+        assert_eq!(
+            nomatch_or_unknown.filter.eval(&serde_json::json!({"active": false, "status": null})),
+            EvalResult::Unknown
+        );
+
+        let match_or_unknown = analyze_query("SELECT * FROM users WHERE active = true OR status = 'x'");
+        assert_eq!(
+            match_or_unknown.filter.eval(&serde_json::json!({"active": true, "status": null})),
+            EvalResult::Match
+        );
+    }
+
+    #[test]
+    fn test_no_where_clause_always_matches() {
+        let analysis = analyze_query("SELECT * FROM users");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({})), EvalResult::Match);
+    }
+
+    #[test]
+    fn test_arithmetic_expression_on_column_side_evaluates() {
+        let analysis = analyze_query("SELECT * FROM orders WHERE price * quantity > 100");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"price": 10, "quantity": 11})), EvalResult::Match);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"price": 10, "quantity": 5})), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_scalar_function_on_column_side_evaluates() {
+        let analysis = analyze_query("SELECT * FROM users WHERE lower(name) = 'bob'");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"name": "Bob"})), EvalResult::Match);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"name": "Alice"})), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_column_expression_unknown_on_missing_operand() {
+        let analysis = analyze_query("SELECT * FROM orders WHERE price * quantity > 100");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"price": 10})), EvalResult::Unknown);
+    }
+
+    #[test]
+    fn test_division_by_zero_is_unknown_not_complex() {
+        let analysis = analyze_query("SELECT * FROM orders WHERE total / count > 10");
+        assert_ne!(analysis.filter.eval(&serde_json::json!({"total": 100, "count": 0})), EvalResult::Match);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"total": 100, "count": 0})), EvalResult::Unknown);
+    }
+
+    #[test]
+    fn test_bare_column_comparison_still_fast_path() {
+        let analysis = analyze_query("SELECT * FROM users WHERE age >= 18");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"age": 21})), EvalResult::Match);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"age": 12})), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_json_arrow_path_resolves_nested_object_field() {
+        let analysis = analyze_query("SELECT * FROM users WHERE address->'city' = 'NYC'");
+        let row = serde_json::json!({"address": {"city": "NYC", "zip": "10001"}});
+        assert_eq!(analysis.filter.eval(&row), EvalResult::Match);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"address": {"city": "LA"}})), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_json_arrow_path_resolves_array_index() {
+        let analysis = analyze_query("SELECT * FROM orders WHERE items->0->>'sku' = 'X'");
+        let row = serde_json::json!({"items": [{"sku": "X"}, {"sku": "Y"}]});
+        assert_eq!(analysis.filter.eval(&row), EvalResult::Match);
+    }
+
+    #[test]
+    fn test_json_path_missing_key_is_unknown() {
+        let analysis = analyze_query("SELECT * FROM users WHERE address->'city' = 'NYC'");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"address": {}})), EvalResult::Unknown);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({})), EvalResult::Unknown);
+    }
+
+    #[test]
+    fn test_json_path_through_null_is_unknown() {
+        let analysis = analyze_query("SELECT * FROM users WHERE address->'city' = 'NYC'");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"address": null})), EvalResult::Unknown);
+    }
+
+    #[test]
+    fn test_json_path_index_out_of_range_is_unknown() {
+        let analysis = analyze_query("SELECT * FROM orders WHERE items->5->>'sku' = 'X'");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"items": [{"sku": "X"}]})), EvalResult::Unknown);
+    }
+
+    #[test]
+    fn test_with_security_injects_user_id_predicate() {
+        let analysis = analyze_query("SELECT * FROM orders WHERE status = 'open'");
+        let ctx = SecurityContext { user_id: Some(FilterValue::Int(7)), ..Default::default() };
+        let secured = analysis.with_security(&ctx);
+
+        let owned_and_open = serde_json::json!({"status": "open", "user_id": 7});
+        let others_open = serde_json::json!({"status": "open", "user_id": 8});
+        assert_eq!(secured.filter.eval(&owned_and_open), EvalResult::Match);
+        assert_eq!(secured.filter.eval(&others_open), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_with_security_injects_claim_in_predicate() {
+        let analysis = analyze_query("SELECT * FROM orders WHERE status = 'open'");
+        let ctx = SecurityContext {
+            claims: vec![("tenant_id".into(), ClaimPredicate::In(vec![FilterValue::Int(1), FilterValue::Int(2)]))],
+            ..Default::default()
+        };
+        let secured = analysis.with_security(&ctx);
+
+        assert_eq!(secured.filter.eval(&serde_json::json!({"status": "open", "tenant_id": 2})), EvalResult::Match);
+        assert_eq!(secured.filter.eval(&serde_json::json!({"status": "open", "tenant_id": 3})), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_with_security_bypass_role_leaves_filter_unchanged() {
+        let analysis = analyze_query("SELECT * FROM orders WHERE status = 'open'");
+        let ctx = SecurityContext {
+            user_id: Some(FilterValue::Int(7)),
+            roles: vec![SecurityContext::BYPASS_ROLE.into()],
+            ..Default::default()
+        };
+        let secured = analysis.with_security(&ctx);
+
+        assert_eq!(secured.filter.eval(&serde_json::json!({"status": "open", "user_id": 999})), EvalResult::Match);
+    }
+
+    #[test]
+    fn test_not_in_compiles_to_not_in_variant() {
+        let analysis = analyze_query("SELECT * FROM users WHERE status NOT IN ('a', 'b')");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"status": "a"})), EvalResult::NoMatch);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"status": "c"})), EvalResult::Match);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({})), EvalResult::Unknown);
+    }
+
+    #[test]
+    fn test_between_desugars_to_and_of_gte_lte() {
+        let analysis = analyze_query("SELECT * FROM users WHERE age BETWEEN 18 AND 65");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"age": 30})), EvalResult::Match);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"age": 17})), EvalResult::NoMatch);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"age": 65})), EvalResult::Match);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"age": 66})), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_not_between_negates_via_de_morgan() {
+        let analysis = analyze_query("SELECT * FROM users WHERE age NOT BETWEEN 18 AND 65");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"age": 30})), EvalResult::NoMatch);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"age": 70})), EvalResult::Match);
+    }
+
+    #[test]
+    fn test_between_simplifies_to_single_range_node() {
+        let analysis = analyze_query("SELECT * FROM users WHERE age BETWEEN 18 AND 65");
+        assert!(matches!(analysis.filter, WhereFilter::Range { .. }));
+    }
+
+    #[test]
+    fn test_separate_inequalities_on_same_column_merge_into_range() {
+        let analysis = analyze_query("SELECT * FROM users WHERE age >= 18 AND age <= 65");
+        assert!(matches!(analysis.filter, WhereFilter::Range { .. }));
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"age": 30})), EvalResult::Match);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"age": 17})), EvalResult::NoMatch);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"age": 66})), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_tighter_bound_among_redundant_inequalities_wins() {
+        let analysis = analyze_query("SELECT * FROM users WHERE age > 18 AND age > 30");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"age": 25})), EvalResult::NoMatch);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"age": 31})), EvalResult::Match);
+    }
+
+    #[test]
+    fn test_contradictory_range_collapses_to_never() {
+        let analysis = analyze_query("SELECT * FROM users WHERE age > 100 AND age < 10");
+        assert!(matches!(analysis.filter, WhereFilter::Never));
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"age": 50})), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_range_and_other_conjunct_both_preserved() {
+        let analysis = analyze_query("SELECT * FROM orders WHERE total >= 10 AND total <= 50 AND status = 'open'");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"total": 20, "status": "open"})), EvalResult::Match);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"total": 20, "status": "closed"})), EvalResult::NoMatch);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"total": 5, "status": "open"})), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_like_with_wildcards_and_escape() {
+        let analysis = analyze_query(r"SELECT * FROM users WHERE name LIKE 'a%\_b'");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"name": "anything_b"})), EvalResult::Match);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"name": "anythingxb"})), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_ilike_is_case_insensitive() {
+        let analysis = analyze_query("SELECT * FROM users WHERE name ILIKE 'bob%'");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"name": "BOBBY"})), EvalResult::Match);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"name": "alice"})), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_not_like_negates() {
+        let analysis = analyze_query("SELECT * FROM users WHERE name NOT LIKE 'a%'");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"name": "alice"})), EvalResult::NoMatch);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"name": "bob"})), EvalResult::Match);
+    }
+
+    #[test]
+    fn test_like_unknown_on_non_string_column() {
+        let analysis = analyze_query("SELECT * FROM users WHERE name LIKE 'a%'");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"name": 5})), EvalResult::Unknown);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({})), EvalResult::Unknown);
+    }
+
+    #[test]
+    fn test_not_eq_pushed_down_to_ne() {
+        let analysis = analyze_query("SELECT * FROM users WHERE NOT (deleted = true)");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"deleted": true})), EvalResult::NoMatch);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"deleted": false})), EvalResult::Match);
+    }
+
+    #[test]
+    fn test_not_gt_pushed_down_to_lte() {
+        let analysis = analyze_query("SELECT * FROM users WHERE NOT (age > 18)");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"age": 18})), EvalResult::Match);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"age": 19})), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_not_is_null_pushed_down_to_is_not_null() {
+        let analysis = analyze_query("SELECT * FROM users WHERE NOT (deleted_at IS NULL)");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"deleted_at": "2020-01-01"})), EvalResult::Match);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"deleted_at": null})), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_not_and_pushes_down_via_de_morgan_to_or() {
+        let analysis = analyze_query("SELECT * FROM users WHERE NOT (status = 'active' AND age > 18)");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"status": "inactive", "age": 5})), EvalResult::Match);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"status": "active", "age": 21})), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_not_or_pushes_down_via_de_morgan_to_and() {
+        let analysis = analyze_query("SELECT * FROM users WHERE NOT (status = 'active' OR status = 'pending')");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"status": "closed"})), EvalResult::Match);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"status": "active"})), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_not_of_unrepresentable_subtree_stays_complex() {
+        let analysis = analyze_query("SELECT * FROM users WHERE NOT (status || 'x' = 'activex')");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"status": "active"})), EvalResult::Unknown);
+    }
+
+    #[test]
+    fn test_not_of_function_comparison_pushed_down_via_negate() {
+        let analysis = analyze_query("SELECT * FROM users WHERE NOT (lower(status) = 'active')");
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"status": "active"})), EvalResult::NoMatch);
+        assert_eq!(analysis.filter.eval(&serde_json::json!({"status": "inactive"})), EvalResult::Match);
+    }
+
+    #[test]
+    fn test_literal_comparison_folds_to_constant() {
+        let always = analyze_query("SELECT * FROM users WHERE 1 = 1 AND status = 'active'");
+        assert_eq!(always.filter.eval(&serde_json::json!({"status": "active"})), EvalResult::Match);
+
+        let never = analyze_query("SELECT * FROM users WHERE 1 = 2 AND status = 'active'");
+        assert!(matches!(never.filter, WhereFilter::Never));
+        assert_eq!(never.filter.eval(&serde_json::json!({"status": "active"})), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_and_drops_constant_match_children() {
+        let analysis = analyze_query("SELECT * FROM users WHERE 1 = 1 AND status = 'active'");
+        assert!(matches!(analysis.filter, WhereFilter::Eq { .. }));
+    }
+
+    #[test]
+    fn test_and_collapses_to_never_on_constant_nomatch_child() {
+        let analysis = analyze_query("SELECT * FROM users WHERE status = 'active' AND 1 = 2");
+        assert!(matches!(analysis.filter, WhereFilter::Never));
+    }
+
+    #[test]
+    fn test_or_drops_constant_nomatch_children() {
+        let analysis = analyze_query("SELECT * FROM users WHERE status = 'active' OR 1 = 2");
+        assert!(matches!(analysis.filter, WhereFilter::Eq { .. }));
+    }
+
+    #[test]
+    fn test_or_collapses_to_none_on_constant_match_child() {
+        let analysis = analyze_query("SELECT * FROM users WHERE status = 'active' OR 1 = 1");
+        assert!(matches!(analysis.filter, WhereFilter::None));
+    }
+
+    #[test]
+    fn test_nested_and_and_or_or_flatten() {
+        let analysis = analyze_query(
+            "SELECT * FROM users WHERE (a = 1 AND b = 2) AND c = 3",
+        );
+        match &analysis.filter {
+            WhereFilter::And(children) => assert_eq!(children.len(), 3),
+            other => panic!("expected flattened And, got {:?}", other),
+        }
+
+        let analysis = analyze_query(
+            "SELECT * FROM users WHERE (a = 1 OR b = 2) OR c = 3",
+        );
+        match &analysis.filter {
+            WhereFilter::Or(children) => assert_eq!(children.len(), 3),
+            other => panic!("expected flattened Or, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_canonicalize_case_and_spacing() {
+        let a = canonicalize_query("select * from users where status = 'active'").unwrap();
+        let b = canonicalize_query("SELECT * FROM users WHERE status = 'active'").unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_canonicalize_count_star_spacing() {
+        let a = canonicalize_query("SELECT count( * ) FROM orders").unwrap();
+        let b = canonicalize_query("SELECT COUNT(*) FROM orders").unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_canonicalize_redundant_parens() {
+        let a = canonicalize_query("SELECT * FROM users WHERE (status = 'active')").unwrap();
+        let b = canonicalize_query("SELECT * FROM users WHERE status = 'active'").unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_canonicalize_qualifies_unambiguous_column() {
+        let a = canonicalize_query("SELECT name FROM users WHERE id = 1").unwrap();
+        let b = canonicalize_query("SELECT users.name FROM users WHERE users.id = 1").unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_canonicalize_drops_cosmetic_alias() {
+        let a = canonicalize_query("SELECT name AS name FROM users").unwrap();
+        let b = canonicalize_query("SELECT name FROM users").unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_canonicalize_none_on_parse_error() {
+        assert!(canonicalize_query("not valid sql (((").is_none());
+    }
+
+    #[test]
+    fn test_normalize_whitespace_collapses_and_trims() {
+        assert_eq!(normalize_whitespace("  SELECT   *   FROM   users  "), "SELECT * FROM users");
+    }
+
+    #[test]
+    fn test_normalize_whitespace_skips_comments() {
+        assert_eq!(
+            normalize_whitespace("SELECT * -- trailing comment\nFROM users /* block */ WHERE 1=1"),
+            "SELECT * FROM users WHERE 1=1"
+        );
+    }
+
+    #[test]
+    fn test_normalize_whitespace_preserves_string_literal_contents() {
+        assert_eq!(
+            normalize_whitespace("SELECT * FROM t WHERE s = 'a   b'"),
+            "SELECT * FROM t WHERE s = 'a   b'"
+        );
+    }
+
+    #[test]
+    fn test_normalize_whitespace_preserves_escaped_quotes() {
+        assert_eq!(normalize_whitespace("SELECT 'it''s  fine'"), "SELECT 'it''s  fine'");
+    }
+
+    #[test]
+    fn test_normalize_whitespace_long_run_uses_chunked_scan() {
+        let long = "a".repeat(100);
+        let query = format!("SELECT {} FROM  t", long);
+        assert_eq!(normalize_whitespace(&query), format!("SELECT {} FROM t", long));
+    }
+
+    #[test]
+    fn test_normalize_whitespace_preserves_bare_minus_and_slash() {
+        // A bare `-`/`/` used as an arithmetic operator, not the start of a
+        // `--`/`/* */` comment, must be copied through rather than panicking.
+        assert_eq!(
+            normalize_whitespace("SELECT price - 10 FROM t"),
+            "SELECT price - 10 FROM t"
+        );
+        assert_eq!(normalize_whitespace("SELECT a/b FROM t"), "SELECT a/b FROM t");
+        assert_eq!(
+            normalize_whitespace("SELECT qty / 2 - 1 FROM t"),
+            "SELECT qty / 2 - 1 FROM t"
+        );
+    }
+
+    #[test]
+    fn test_apply_row_filters_no_predicates_is_noop() {
+        let q = "SELECT * FROM orders";
+        assert_eq!(apply_row_filters(q, &[], &[]).unwrap(), q);
+    }
+
+    #[test]
+    fn test_apply_row_filters_restrictive_only() {
+        let out = apply_row_filters("SELECT * FROM orders", &["tenant_id = 1".to_string()], &[]).unwrap();
+        let analysis = analyze_query(&out);
+        let row_match = serde_json::json!({"tenant_id": 1});
+        let row_no_match = serde_json::json!({"tenant_id": 2});
+        assert_eq!(analysis.filter.eval(&row_match), EvalResult::Match);
+        assert_eq!(analysis.filter.eval(&row_no_match), EvalResult::NoMatch);
+    }
+
+    #[test]
+    fn test_apply_row_filters_permissive_ored_restrictive_anded() {
+        let out = apply_row_filters(
+            "SELECT * FROM orders WHERE status = 'open'",
+            &["tenant_id = 1".to_string()],
+            &["owner_id = 5".to_string(), "is_admin = true".to_string()],
+        ).unwrap();
+        // (owner_id = 5 OR is_admin = true) AND tenant_id = 1 AND status = 'open'
+        assert!(out.contains("owner_id = 5 OR"));
+        assert!(out.contains("AND tenant_id = 1"));
+        assert!(out.contains("status = 'open'"));
+    }
+
+    #[test]
+    fn test_apply_row_filters_rejects_bad_restrictive_predicate() {
+        assert!(apply_row_filters("SELECT * FROM orders", &["not valid (((".to_string()], &[]).is_none());
+    }
+
+    #[test]
+    fn test_apply_row_filters_skips_bad_permissive_predicate() {
+        let out = apply_row_filters(
+            "SELECT * FROM orders",
+            &[],
+            &["owner_id = 5".to_string(), "not valid (((".to_string()],
+        ).unwrap();
+        assert!(out.contains("owner_id = 5"));
     }
 }
@@ -1,5 +1,8 @@
 //! Query Deduplication - multiple clients share 1 subscription for identical queries.
-//! Uses PostgreSQL md5() for hashing, regexp_replace() for normalization.
+//! Normalization and hashing run in-process (see `query_analyzer`) rather than
+//! through the executor, since this registry is looked up under `QUERY_REGISTRY`.
+
+use std::hash::Hasher;
 
 use pgrx::prelude::*;
 use pgrx::lwlock::PgLwLock;
@@ -73,30 +76,45 @@ pub fn init_query_registry() {
     pgrx::pg_shmem_init!(QUERY_REGISTRY);
 }
 
-/// Normalize query using PostgreSQL regexp_replace + btrim
+/// Inject per-client row-security predicates into `query`'s `WHERE` before
+/// it's registered or looked up (see `query_analyzer::apply_row_filters`),
+/// so clients with different `restrictive`/`permissive` policies land in
+/// distinct registry entries instead of sharing a subscription whose rows
+/// they're not all entitled to see. `None` means a restrictive predicate
+/// failed to parse - callers must fail closed and refuse the subscription.
+pub fn effective_query(query: &str, restrictive: &[String], permissive: &[String]) -> Option<String> {
+    let result = crate::query_analyzer::apply_row_filters(query, restrictive, permissive);
+    if result.is_none() {
+        pgrx::warning!("pg_subscribe: a restrictive row-filter predicate failed to parse, refusing subscription");
+    }
+    result
+}
+
+/// Normalize query whitespace in-process (see `query_analyzer::normalize_whitespace`).
+/// Used to hand off the old `btrim(regexp_replace($1, E'\s+', ' ', 'g'))` SPI
+/// round-trip to a byte-scanning normalizer so lookups don't serialize on
+/// the executor while holding `QUERY_REGISTRY`.
 #[inline]
 pub fn normalize_query(query: &str) -> String {
-    Spi::get_one_with_args::<String>(
-        "SELECT btrim(regexp_replace($1, E'\\\\s+', ' ', 'g'))",
-        vec![(PgBuiltInOids::TEXTOID.oid(), query.into_datum())],
-    )
-    .ok()
-    .flatten()
-    .unwrap_or_else(|| query.to_string())
+    crate::query_analyzer::normalize_whitespace(query)
 }
 
-/// Compute hash using PostgreSQL md5(), returns first 16 hex chars as u64
+/// Canonical dedup key for `query`: AST-based fingerprint when the query
+/// parses (case/spacing/alias/paren-insensitive, see `query_analyzer`),
+/// falling back to plain whitespace normalization otherwise.
+pub fn canonical_form(query: &str) -> String {
+    crate::query_analyzer::canonicalize_query(query).unwrap_or_else(|| normalize_query(query))
+}
+
+/// Hash using a fast non-cryptographic hasher instead of a `md5()` SPI
+/// round-trip - dedup only needs collision resistance against accidental
+/// duplicates, not cryptographic guarantees.
 #[inline]
 pub fn compute_query_hash(query: &str) -> u64 {
-    let normalized = normalize_query(query);
-    Spi::get_one_with_args::<String>(
-        "SELECT md5($1)",
-        vec![(PgBuiltInOids::TEXTOID.oid(), normalized.into_datum())],
-    )
-    .ok()
-    .flatten()
-    .and_then(|s| u64::from_str_radix(&s[..16.min(s.len())], 16).ok())
-    .unwrap_or(0)
+    let normalized = canonical_form(query);
+    let mut hasher = ahash::AHasher::default();
+    hasher.write(normalized.as_bytes());
+    hasher.finish()
 }
 
 pub struct SubscriptionLookup {
@@ -106,7 +124,7 @@ pub struct SubscriptionLookup {
 
 /// Find existing subscription by query hash, increment client count if found
 pub fn find_existing_subscription(query: &str) -> Option<SubscriptionLookup> {
-    let normalized = normalize_query(query);
+    let normalized = canonical_form(query);
     let query_hash = compute_query_hash(&normalized);
     
     // First check with shared lock
@@ -130,7 +148,7 @@ pub fn find_existing_subscription(query: &str) -> Option<SubscriptionLookup> {
 
 /// Register new subscription in dedup registry
 pub fn register_subscription(query: &str, slot_index: usize) {
-    let normalized = normalize_query(query);
+    let normalized = canonical_form(query);
     let query_hash = compute_query_hash(&normalized);
     
     let mut registry = QUERY_REGISTRY.exclusive();
@@ -195,4 +213,23 @@ mod tests {
         assert_eq!(h1, h2);
         assert_ne!(h1, compute_query_hash("SELECT * FROM orders"));
     }
+
+    #[pg_test]
+    fn test_query_hash_ast_canonicalization() {
+        let h1 = compute_query_hash("select name from users where id = 1");
+        let h2 = compute_query_hash("SELECT users.name FROM users WHERE (users.id = 1)");
+        assert_eq!(h1, h2);
+    }
+
+    #[pg_test]
+    fn test_effective_query_distinct_policies_distinct_hashes() {
+        let a = effective_query("SELECT * FROM orders", &["tenant_id = 1".to_string()], &[]).unwrap();
+        let b = effective_query("SELECT * FROM orders", &["tenant_id = 2".to_string()], &[]).unwrap();
+        assert_ne!(compute_query_hash(&a), compute_query_hash(&b));
+    }
+
+    #[pg_test]
+    fn test_effective_query_rejects_bad_restrictive_predicate() {
+        assert!(effective_query("SELECT * FROM orders", &["not valid (((".to_string()], &[]).is_none());
+    }
 }
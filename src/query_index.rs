@@ -0,0 +1,268 @@
+//! Inverted-index router for matching a changed row against many live
+//! queries without re-running `WhereFilter::eval` on every one of them.
+//!
+//! Naive fan-out is O(#queries) per change. `FilterIndex` drives this down
+//! to O(#actually-matching-queries) the way an index semi-join would: each
+//! query's *necessary* conjunctive equality/`IN` predicates (the ones that
+//! must hold for `eval` to return `Match`, i.e. reachable through `And`
+//! without crossing an `Or`) are indexed by `(column, value)`. A query with
+//! no such predicate can't be ruled out by any row, so it lands in
+//! `fallback` and is always returned as a candidate.
+
+use std::collections::HashMap;
+
+use crate::query_analyzer::{ColExpr, FilterValue, PathSegment, WhereFilter};
+
+/// Identifies a registered subscription/live query within a `FilterIndex`.
+pub type SubId = usize;
+
+/// Minimal growable bitset over `SubId`, backed by `u64` words. Candidate
+/// sets overlap heavily (many subscriptions share a literal value), so a
+/// bitset keeps `remove`/union cheap instead of repeatedly scanning and
+/// deduping a `Vec<SubId>` per entry.
+#[derive(Debug, Clone, Default)]
+struct Bitset {
+    words: Vec<u64>,
+}
+
+impl Bitset {
+    fn insert(&mut self, id: SubId) {
+        let (word, bit) = (id / 64, id % 64);
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        self.words[word] |= 1 << bit;
+    }
+
+    fn remove(&mut self, id: SubId) {
+        if let Some(w) = self.words.get_mut(id / 64) {
+            *w &= !(1 << (id % 64));
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.words.iter().all(|w| *w == 0)
+    }
+
+    /// OR this bitset's bits into `out`, growing `out` if needed.
+    fn union_into(&self, out: &mut Bitset) {
+        if out.words.len() < self.words.len() {
+            out.words.resize(self.words.len(), 0);
+        }
+        for (o, w) in out.words.iter_mut().zip(&self.words) {
+            *o |= w;
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = SubId> + '_ {
+        self.words.iter().enumerate().flat_map(|(word_idx, &word)| {
+            (0..64).filter(move |bit| word & (1 << bit) != 0).map(move |bit| word_idx * 64 + bit)
+        })
+    }
+}
+
+/// Maps a changed row to the subset of registered queries whose filter could
+/// possibly match it. Callers still run the real `WhereFilter::eval` on each
+/// candidate - this only narrows which queries are worth checking.
+///
+/// Conservative by construction: a filter whose equality structure can't be
+/// proven necessary (pure `Or`, bare comparisons, `Complex`) always lands in
+/// `fallback` rather than being silently dropped, so `candidates` may
+/// over-include but never under-includes a subscription that would match.
+#[derive(Default)]
+pub struct FilterIndex {
+    by_value: HashMap<(Box<str>, FilterValue), Bitset>,
+    fallback: Bitset,
+}
+
+impl FilterIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index `filter` under `id`.
+    pub fn insert(&mut self, id: SubId, filter: &WhereFilter) {
+        let mut keys = Vec::new();
+        collect_necessary_keys(filter, &mut keys);
+        if keys.is_empty() {
+            self.fallback.insert(id);
+            return;
+        }
+        for (col, val) in keys {
+            self.by_value.entry((col, val)).or_default().insert(id);
+        }
+    }
+
+    /// Drop every index entry for `id` (e.g. on unsubscribe).
+    pub fn remove(&mut self, id: SubId) {
+        self.by_value.retain(|_, bits| {
+            bits.remove(id);
+            !bits.is_empty()
+        });
+        self.fallback.remove(id);
+    }
+
+    /// Subscription ids whose filter could match `row`: the always-checked
+    /// fallback set, plus any subscription indexed under a `(column, value)`
+    /// pair the row actually has. Deduplicated, since a subscription can be
+    /// indexed under more than one of its necessary predicates.
+    pub fn candidates(&self, row: &serde_json::Value) -> impl Iterator<Item = SubId> {
+        let mut out = self.fallback.clone();
+        if let Some(obj) = row.as_object() {
+            for (col, v) in obj {
+                // `In` compares across types (see `FilterValue::matches`), so
+                // also look up the other literals this value could match.
+                let mut keys: Vec<FilterValue> = FilterValue::from_json(v).into_iter().collect();
+                match v {
+                    serde_json::Value::Number(n) => {
+                        keys.push(FilterValue::Str(n.to_string()));
+                        if let (Some(_), Some(f)) = (n.as_i64(), n.as_f64()) {
+                            keys.push(FilterValue::Float(f));
+                        }
+                    }
+                    serde_json::Value::String(s) => keys.extend(s.parse::<i64>().ok().map(FilterValue::Int)),
+                    _ => {}
+                }
+                for val in keys {
+                    if let Some(bits) = self.by_value.get(&(col.as_str().into(), val)) {
+                        bits.union_into(&mut out);
+                    }
+                }
+            }
+        }
+        out.iter().collect::<Vec<_>>().into_iter()
+    }
+}
+
+/// Collect `(column, value)` pairs that MUST hold for `filter` to evaluate to
+/// `Match` - i.e. equality/`IN` predicates reachable through `And` alone.
+/// Stops descending at `Or`/`Complex`/range predicates, since none of those
+/// alone can rule a query out. Only single-segment (bare column) paths are
+/// indexable this way - a nested `a->'b'` predicate falls through to the
+/// `fallback` set instead, same as any other predicate we can't prove.
+fn collect_necessary_keys(filter: &WhereFilter, out: &mut Vec<(Box<str>, FilterValue)>) {
+    match filter {
+        WhereFilter::Eq { expr: ColExpr::Col(path), val } => {
+            if let [PathSegment::Key(col)] = path.as_slice() {
+                out.push((col.as_str().into(), val.clone()));
+            }
+        }
+        WhereFilter::In { path, vals } => {
+            if let [PathSegment::Key(col)] = path.as_slice() {
+                out.extend(vals.iter().map(|v| (col.as_str().into(), v.clone())));
+            }
+        }
+        WhereFilter::And(filters) => filters.iter().for_each(|f| collect_necessary_keys(f, out)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query_analyzer::analyze_query;
+
+    fn filter_for(sql: &str) -> WhereFilter {
+        analyze_query(sql).filter
+    }
+
+    #[test]
+    fn test_eq_predicate_routes_only_matching_rows() {
+        let mut idx = FilterIndex::new();
+        idx.insert(1, &filter_for("SELECT * FROM orders WHERE status = 'open'"));
+
+        let matching = serde_json::json!({"status": "open"});
+        let non_matching = serde_json::json!({"status": "closed"});
+        assert_eq!(idx.candidates(&matching).collect::<Vec<_>>(), vec![1]);
+        assert!(idx.candidates(&non_matching).collect::<Vec<_>>().is_empty());
+    }
+
+    #[test]
+    fn test_and_of_equalities_indexes_each_conjunct() {
+        let mut idx = FilterIndex::new();
+        idx.insert(1, &filter_for("SELECT * FROM orders WHERE status = 'open' AND tenant_id = 1"));
+
+        let row = serde_json::json!({"status": "open", "tenant_id": 2});
+        assert_eq!(idx.candidates(&row).collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn test_in_predicate_indexed_per_value() {
+        let mut idx = FilterIndex::new();
+        idx.insert(1, &filter_for("SELECT * FROM orders WHERE status IN ('open', 'pending')"));
+
+        assert_eq!(idx.candidates(&serde_json::json!({"status": "pending"})).collect::<Vec<_>>(), vec![1]);
+        assert!(idx.candidates(&serde_json::json!({"status": "closed"})).collect::<Vec<_>>().is_empty());
+    }
+
+    #[test]
+    fn test_or_filter_falls_back_always_checked() {
+        let mut idx = FilterIndex::new();
+        idx.insert(1, &filter_for("SELECT * FROM orders WHERE status = 'open' OR status = 'closed'"));
+
+        assert_eq!(idx.candidates(&serde_json::json!({"status": "anything"})).collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn test_range_only_filter_falls_back() {
+        let mut idx = FilterIndex::new();
+        idx.insert(1, &filter_for("SELECT * FROM orders WHERE total > 100"));
+
+        assert_eq!(idx.candidates(&serde_json::json!({"total": 5})).collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn test_and_with_or_still_indexes_other_conjunct() {
+        let mut idx = FilterIndex::new();
+        idx.insert(
+            1,
+            &filter_for("SELECT * FROM orders WHERE tenant_id = 1 AND (status = 'open' OR status = 'closed')"),
+        );
+
+        assert_eq!(idx.candidates(&serde_json::json!({"tenant_id": 1})).collect::<Vec<_>>(), vec![1]);
+        assert!(idx.candidates(&serde_json::json!({"tenant_id": 2})).collect::<Vec<_>>().is_empty());
+    }
+
+    #[test]
+    fn test_remove_drops_subscription_from_index_and_fallback() {
+        let mut idx = FilterIndex::new();
+        idx.insert(1, &filter_for("SELECT * FROM orders WHERE status = 'open'"));
+        idx.insert(2, &filter_for("SELECT * FROM orders WHERE total > 100"));
+
+        idx.remove(1);
+        idx.remove(2);
+
+        assert!(idx.candidates(&serde_json::json!({"status": "open"})).collect::<Vec<_>>().is_empty());
+        assert!(idx.candidates(&serde_json::json!({"total": 5})).collect::<Vec<_>>().is_empty());
+    }
+
+    #[test]
+    fn test_multiple_queries_on_same_value_both_returned() {
+        let mut idx = FilterIndex::new();
+        idx.insert(1, &filter_for("SELECT * FROM orders WHERE status = 'open'"));
+        idx.insert(2, &filter_for("SELECT * FROM orders WHERE status = 'open'"));
+
+        assert_eq!(idx.candidates(&serde_json::json!({"status": "open"})).collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_bitset_spans_multiple_words_past_64_ids() {
+        let mut idx = FilterIndex::new();
+        idx.insert(5, &filter_for("SELECT * FROM orders WHERE status = 'open'"));
+        idx.insert(130, &filter_for("SELECT * FROM orders WHERE status = 'open'"));
+
+        assert_eq!(idx.candidates(&serde_json::json!({"status": "open"})).collect::<Vec<_>>(), vec![5, 130]);
+    }
+
+    #[test]
+    fn test_no_predicate_falls_back_always_checked() {
+        // `WhereFilter::None` (no WHERE clause) must behave like any other
+        // unindexable predicate - always a candidate - since
+        // `trigger::candidate_slots` indexes a slot with no cached predicate
+        // under `WhereFilter::None` rather than skipping it.
+        let mut idx = FilterIndex::new();
+        idx.insert(1, &WhereFilter::None);
+
+        assert_eq!(idx.candidates(&serde_json::json!({"status": "anything"})).collect::<Vec<_>>(), vec![1]);
+    }
+}
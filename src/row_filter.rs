@@ -0,0 +1,121 @@
+//! Per-subscription row predicates, evaluated against each row's JSON
+//! representation inside `crate::unified_subscribe::Snapshot::execute_and_diff`.
+//!
+//! Modeled on nostr-rs-relay's `ReqFilter`: a flat list of `(column, operator,
+//! value)` predicates that are ANDed together, letting many clients share one
+//! underlying query slot (see `crate::query_dedup`) while each only sees its
+//! own relevant subset of rows.
+
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterOp {
+    Eq,
+    Neq,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    In,
+    Contains,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RowFilter {
+    pub column: String,
+    pub op: FilterOp,
+    pub value: Value,
+}
+
+impl RowFilter {
+    /// Whether `row` satisfies this predicate. A missing column never matches.
+    pub fn matches(&self, row: &Value) -> bool {
+        let Some(actual) = row.get(&self.column) else {
+            return false;
+        };
+        match self.op {
+            FilterOp::Eq => actual == &self.value,
+            FilterOp::Neq => actual != &self.value,
+            FilterOp::Lt => compare(actual, &self.value).is_some_and(|o| o.is_lt()),
+            FilterOp::Lte => compare(actual, &self.value).is_some_and(|o| o.is_le()),
+            FilterOp::Gt => compare(actual, &self.value).is_some_and(|o| o.is_gt()),
+            FilterOp::Gte => compare(actual, &self.value).is_some_and(|o| o.is_ge()),
+            FilterOp::In => self.value.as_array().is_some_and(|a| a.contains(actual)),
+            FilterOp::Contains => match actual {
+                Value::Array(items) => items.contains(&self.value),
+                Value::String(s) => self.value.as_str().is_some_and(|sub| s.contains(sub)),
+                _ => false,
+            },
+        }
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64()?.partial_cmp(&y.as_f64()?),
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        _ => None,
+    }
+}
+
+/// Parse a JSON array of `{"column", "op", "value"}` predicates.
+pub fn parse_filters(value: &Value) -> Result<Vec<RowFilter>, String> {
+    serde_json::from_value(value.clone()).map_err(|e| format!("Invalid filters: {}", e))
+}
+
+/// Whether `row` satisfies every filter (ANDed). An empty filter list always matches.
+pub fn all_match(filters: &[RowFilter], row: &Value) -> bool {
+    filters.iter().all(|f| f.matches(row))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(column: &str, op: FilterOp, value: Value) -> RowFilter {
+        RowFilter { column: column.into(), op, value }
+    }
+
+    #[test]
+    fn test_eq_and_neq() {
+        let row = serde_json::json!({"status": "active"});
+        assert!(filter("status", FilterOp::Eq, serde_json::json!("active")).matches(&row));
+        assert!(filter("status", FilterOp::Neq, serde_json::json!("closed")).matches(&row));
+        assert!(!filter("status", FilterOp::Eq, serde_json::json!("closed")).matches(&row));
+    }
+
+    #[test]
+    fn test_numeric_comparisons() {
+        let row = serde_json::json!({"score": 5});
+        assert!(filter("score", FilterOp::Gt, serde_json::json!(3)).matches(&row));
+        assert!(filter("score", FilterOp::Gte, serde_json::json!(5)).matches(&row));
+        assert!(!filter("score", FilterOp::Lt, serde_json::json!(3)).matches(&row));
+    }
+
+    #[test]
+    fn test_in_and_contains() {
+        let row = serde_json::json!({"tag": "b", "tags": ["a", "b"]});
+        assert!(filter("tag", FilterOp::In, serde_json::json!(["a", "b", "c"])).matches(&row));
+        assert!(filter("tags", FilterOp::Contains, serde_json::json!("b")).matches(&row));
+        assert!(!filter("tags", FilterOp::Contains, serde_json::json!("z")).matches(&row));
+    }
+
+    #[test]
+    fn test_missing_column_never_matches() {
+        let row = serde_json::json!({"status": "active"});
+        assert!(!filter("missing", FilterOp::Eq, serde_json::json!("active")).matches(&row));
+    }
+
+    #[test]
+    fn test_all_match_ands_filters() {
+        let row = serde_json::json!({"status": "active", "score": 5});
+        let filters = vec![
+            filter("status", FilterOp::Eq, serde_json::json!("active")),
+            filter("score", FilterOp::Gte, serde_json::json!(10)),
+        ];
+        assert!(!all_match(&filters, &row));
+        assert!(all_match(&[], &row));
+    }
+}
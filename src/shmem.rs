@@ -13,9 +13,11 @@ use pgrx::pg_shmem_init;
 use pgrx::lwlock::PgLwLock;
 use pgrx::atomics::PgAtomic;
 use pgrx::shmem::{PGRXSharedMemory, PgSharedMemoryInitialization};
+use pgrx::GucSetting;
 use std::sync::atomic::{AtomicU64, Ordering};
 
 use crate::event::SubscribeEvent;
+use crate::query_analyzer::WhereFilter;
 
 // ============================================================================
 // Constants
@@ -37,6 +39,22 @@ pub const MAX_TRACKED_TABLES: usize = 32;
 /// Maximum table name length
 pub const MAX_TABLE_NAME_LEN: usize = 128;
 
+/// Maximum serialized size of a slot's compiled WHERE predicate
+pub const MAX_FILTER_LEN: usize = 512;
+
+/// GUC: how long (in seconds) a subscription slot or table registry entry
+/// must sit idle before `allocate_slot`/`register_table_interest` are
+/// allowed to reclaim it under capacity pressure instead of refusing the new
+/// registration. See `SlotInfo::last_active_at`/`TableRegistry::last_active_at`.
+pub static LRU_IDLE_THRESHOLD_SECS: GucSetting<i32> = GucSetting::<i32>::new(300);
+
+/// Seconds since the Unix epoch, used to stamp `created_at`/`last_active_at`
+/// fields consulted by the LRU eviction policy.
+#[inline]
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+}
+
 // ============================================================================
 // Shared Memory Structures
 // ============================================================================
@@ -45,6 +63,17 @@ pub const MAX_TABLE_NAME_LEN: usize = 128;
 #[derive(Clone, Copy)]
 #[repr(C)]
 pub struct ShmemEvent {
+    /// Monotonically increasing per-slot sequence number, assigned by
+    /// `push_event`. Lets a wake-signal NOTIFY carry just `{slot, seq}` and
+    /// have the client pull the authoritative payload back out by seq (see
+    /// `get_event_by_seq`) instead of inlining it in the NOTIFY itself.
+    pub seq: u64,
+    /// Global cross-backend monotonic counter from `WRITE_VERSION_COUNTER`,
+    /// stamped by `push_event` - unlike `seq` (per-slot) or `timestamp`
+    /// (not a strict order across concurrent backends), comparing two
+    /// consecutive values a client has seen tells it exactly how many
+    /// events it missed.
+    pub write_version: u64,
     /// Timestamp
     pub timestamp: i64,
     /// Diff (+1 insert, -1 delete, 0 progress)
@@ -55,16 +84,25 @@ pub struct ShmemEvent {
     pub payload: [u8; MAX_EVENT_PAYLOAD],
     /// Is this slot occupied
     pub occupied: bool,
+    /// CRC32 over `timestamp`/`diff`/`payload_len`/the used payload bytes,
+    /// computed by `from_subscribe_event`. `shmem` is writable by every
+    /// backend, so a crash or bug mid-write can leave a torn event behind;
+    /// checking this in `to_subscribe_event`/`is_corrupted` turns that from
+    /// silent corruption into a countable `GlobalStats::corrupted_events`.
+    pub checksum: u32,
 }
 
 impl Default for ShmemEvent {
     fn default() -> Self {
         Self {
+            seq: 0,
+            write_version: 0,
             timestamp: 0,
             diff: 0,
             payload_len: 0,
             payload: [0u8; MAX_EVENT_PAYLOAD],
             occupied: false,
+            checksum: 0,
         }
     }
 }
@@ -72,35 +110,60 @@ impl Default for ShmemEvent {
 unsafe impl PGRXSharedMemory for ShmemEvent {}
 
 impl ShmemEvent {
+    fn compute_checksum(timestamp: i64, diff: i32, payload_len: u32, payload: &[u8]) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&timestamp.to_le_bytes());
+        hasher.update(&diff.to_le_bytes());
+        hasher.update(&payload_len.to_le_bytes());
+        hasher.update(payload);
+        hasher.finalize()
+    }
+
     pub fn from_subscribe_event(event: &SubscribeEvent) -> Self {
         let json = serde_json::to_string(&event.data).unwrap_or_default();
         let json_bytes = json.as_bytes();
         let len = std::cmp::min(json_bytes.len(), MAX_EVENT_PAYLOAD);
-        
+
         let mut payload = [0u8; MAX_EVENT_PAYLOAD];
         payload[..len].copy_from_slice(&json_bytes[..len]);
-        
+        let checksum = Self::compute_checksum(event.mz_timestamp, event.mz_diff, len as u32, &payload[..len]);
+
         Self {
             timestamp: event.mz_timestamp,
             diff: event.mz_diff,
             payload_len: len as u32,
             payload,
             occupied: true,
+            checksum,
+            ..Default::default()
         }
     }
-    
-    pub fn to_subscribe_event(&self) -> Option<SubscribeEvent> {
+
+    /// True when occupied but the stored checksum doesn't match its payload
+    /// - a torn write, not just "nothing here". Clamps `payload_len` in case
+    /// *that* field is itself part of the torn write.
+    pub fn is_corrupted(&self) -> bool {
         if !self.occupied {
+            return false;
+        }
+        let len = (self.payload_len as usize).min(MAX_EVENT_PAYLOAD);
+        self.checksum != Self::compute_checksum(self.timestamp, self.diff, self.payload_len, &self.payload[..len])
+    }
+
+    pub fn to_subscribe_event(&self) -> Option<SubscribeEvent> {
+        if !self.occupied || self.is_corrupted() {
             return None;
         }
-        
+
         let json_str = std::str::from_utf8(&self.payload[..self.payload_len as usize]).ok()?;
         let data: Option<serde_json::Value> = serde_json::from_str(json_str).ok();
-        
+
         Some(SubscribeEvent {
             mz_timestamp: self.timestamp,
             mz_diff: self.diff,
             mz_progressed: self.diff == 0,
+            mz_errcode: None,
+            mz_write_version: self.write_version,
             data,
         })
     }
@@ -118,14 +181,47 @@ pub struct SlotInfo {
     pub backend_pid: u32,
     /// Created timestamp
     pub created_at: u64,
-    /// Events sent count
-    pub events_sent: u64,
+    /// Last time this slot was touched (allocated or pushed to). Consulted
+    /// by `allocate_slot`'s LRU eviction policy when every slot is occupied.
+    /// Mirrored (less precisely) in `SlotAllocation::last_active_at` so that
+    /// policy can scan without taking this slot's own lock.
+    pub last_active_at: u64,
     /// Ring buffer head (write position)
     pub head: usize,
-    /// Ring buffer tail (read position)  
+    /// Ring buffer tail (read position)
     pub tail: usize,
     /// Number of events in buffer
     pub count: usize,
+    /// Serialized length of `predicate`, or 0 if none is stored. Used by
+    /// `broadcast_event` to skip fan-out for rows that don't match this
+    /// subscription's WHERE clause.
+    pub predicate_len: u32,
+    /// Compiled `WhereFilter`, JSON-serialized into this fixed buffer (see
+    /// `set_slot_predicate`/`get_slot_predicate`).
+    pub predicate: [u8; MAX_FILTER_LEN],
+    /// When set, `broadcast_event` inlines the full event JSON in the NOTIFY
+    /// payload for events small enough to fit, instead of just the
+    /// `{slot, seq}` wake signal - trading NOTIFY's size bound for one fewer
+    /// round-trip on small rows. See `set_slot_inline_notify`.
+    pub inline_notify: bool,
+    /// Set once the ring buffer has evicted at least one event to the disk
+    /// overflow log (`crate::overflow`); until then `pop_event` never looks
+    /// past shmem.
+    pub overflow_active: bool,
+    /// (segment, offset) of the oldest overflow record not yet returned by
+    /// `pop_event`.
+    pub overflow_read_segment: u64,
+    pub overflow_read_offset: u64,
+    /// (segment, offset) the next overflow append lands at.
+    pub overflow_write_segment: u64,
+    pub overflow_write_offset: u64,
+    /// Events that were irrecoverably lost for this slot - i.e. the disk
+    /// overflow log (`crate::overflow`) rejected a spill, not just "evicted
+    /// from the shmem ring" (that case is now recoverable, see
+    /// `push_event`). A client comparing consecutive `mz_write_version`s can
+    /// already detect *that* it missed events; this is the server's own
+    /// count of events that are truly gone.
+    pub dropped_events: u64,
 }
 
 impl Default for SlotInfo {
@@ -135,10 +231,19 @@ impl Default for SlotInfo {
             subscription_id: [0u8; 36],
             backend_pid: 0,
             created_at: 0,
-            events_sent: 0,
+            last_active_at: 0,
             head: 0,
             tail: 0,
             count: 0,
+            predicate_len: 0,
+            predicate: [0u8; MAX_FILTER_LEN],
+            inline_notify: false,
+            overflow_active: false,
+            overflow_read_segment: 0,
+            overflow_read_offset: 0,
+            overflow_write_segment: 0,
+            overflow_write_offset: 0,
+            dropped_events: 0,
         }
     }
 }
@@ -166,6 +271,17 @@ pub struct GlobalStats {
     pub total_subscriptions: u64,
     pub total_events: u64,
     pub active_slots: u32,
+    /// Count of `ShmemEvent`s found with a mismatched checksum - a torn
+    /// write, not ordinary loss - across every slot. See `ShmemEvent::checksum`.
+    pub corrupted_events: u64,
+    /// Number of times `allocate_slot` reclaimed an idle slot under capacity
+    /// pressure instead of refusing a new subscription. A rising count means
+    /// `MAX_SLOTS` is undersized for the workload.
+    pub slot_evictions: u64,
+    /// Number of times `register_table_interest` reclaimed an idle table
+    /// registry entry under capacity pressure instead of refusing a new
+    /// table. A rising count means `MAX_TRACKED_TABLES` is undersized.
+    pub table_evictions: u64,
 }
 
 unsafe impl PGRXSharedMemory for GlobalStats {}
@@ -183,6 +299,9 @@ pub struct TableRegistry {
     pub interested_slots: u64,
     /// Trigger installed flag
     pub trigger_installed: bool,
+    /// Last time a slot registered interest in this table. Consulted by
+    /// `register_table_interest`'s LRU eviction policy when the registry is full.
+    pub last_active_at: u64,
 }
 
 impl Default for TableRegistry {
@@ -192,6 +311,7 @@ impl Default for TableRegistry {
             active: false,
             interested_slots: 0,
             trigger_installed: false,
+            last_active_at: 0,
         }
     }
 }
@@ -280,32 +400,66 @@ impl Default for SubscriptionSlotData {
 
 unsafe impl PGRXSharedMemory for SubscriptionSlotData {}
 
-/// All subscription slots
+/// Minimal per-slot bookkeeping for `allocate_slot`: just enough to scan for
+/// a free (or LRU-evictable) slot under one lock, without touching any
+/// slot's own `SLOT_DATA` entry (and its much larger ring buffer) to do so.
 #[derive(Clone, Copy)]
 #[repr(C)]
-pub struct AllSlots {
-    pub slots: [SubscriptionSlotData; MAX_SLOTS],
+pub struct SlotAllocation {
+    pub active: bool,
+    pub last_active_at: u64,
+}
+
+impl Default for SlotAllocation {
+    fn default() -> Self {
+        Self { active: false, last_active_at: 0 }
+    }
+}
+
+unsafe impl PGRXSharedMemory for SlotAllocation {}
+
+/// The only state still behind one global lock: which slots are in use, and
+/// the aggregate stats. Per-slot event data lives in `SLOT_DATA`, each entry
+/// independently locked, so unrelated subscriptions' `push_event`/`pop_event`
+/// calls never contend with each other; `push_event` takes this lock only to
+/// refresh its slot's `SlotAllocation::last_active_at` (at most once a second)
+/// or to count a corrupted event.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct SlotTable {
+    pub slots: [SlotAllocation; MAX_SLOTS],
     pub stats: GlobalStats,
 }
 
-impl Default for AllSlots {
+impl Default for SlotTable {
     fn default() -> Self {
         Self {
-            slots: [SubscriptionSlotData::default(); MAX_SLOTS],
+            slots: [SlotAllocation::default(); MAX_SLOTS],
             stats: GlobalStats::default(),
         }
     }
 }
 
-unsafe impl PGRXSharedMemory for AllSlots {}
+unsafe impl PGRXSharedMemory for SlotTable {}
 
 
 // ============================================================================
 // Static Shared Memory Declarations
 // ============================================================================
 
-/// Main shared memory structure protected by LwLock
-static SHMEM_SLOTS: PgLwLock<AllSlots> = PgLwLock::new();
+/// Slot allocation bitmap + aggregate stats, behind one lock (see `SlotTable`).
+static SLOT_TABLE: PgLwLock<SlotTable> = PgLwLock::new();
+
+/// One independent lock per subscription slot, guarding that slot's
+/// `SlotInfo` and ring buffer. `push_event`/`pop_event` only ever take the
+/// lock for their own `slot_index`, so throughput scales with the number of
+/// concurrently active subscriptions instead of flat-lining on a single
+/// shared lock.
+static SLOT_DATA: [PgLwLock<SubscriptionSlotData>; MAX_SLOTS] = [const { PgLwLock::new() }; MAX_SLOTS];
+
+/// Per-slot count of events sent, atomic so stats/seq-numbering never need
+/// to take that slot's `SLOT_DATA` lock just to read or bump a counter.
+static EVENTS_SENT: [PgAtomic<AtomicU64>; MAX_SLOTS] = [const { PgAtomic::new() }; MAX_SLOTS];
 
 /// Table registry for shared triggers
 static TABLE_REGISTRY: PgLwLock<AllTableRegistries> = PgLwLock::new();
@@ -313,6 +467,42 @@ static TABLE_REGISTRY: PgLwLock<AllTableRegistries> = PgLwLock::new();
 /// Atomic counter for fast stats (no lock needed)
 static EVENTS_COUNTER: PgAtomic<AtomicU64> = PgAtomic::new();
 
+/// Global, cross-backend monotonic counter bumped once per `push_event`,
+/// stamped onto `ShmemEvent::write_version`/`SubscribeEvent::mz_write_version`.
+/// Unlike `mz_timestamp` this is a strict total order, so a client can
+/// detect a gap just by comparing two values it has seen.
+static WRITE_VERSION_COUNTER: PgAtomic<AtomicU64> = PgAtomic::new();
+
+/// Global, cross-backend monotonic counter bumped by anything that can
+/// change which slots `trigger::broadcast_event`'s backend-local
+/// `query_index::FilterIndex` cache should return as candidates for a
+/// table: a slot's predicate (`set_slot_predicate`), or table-interest
+/// membership (`allocate_slot`/`release_slot`/`register_table_interest`/
+/// `unregister_slot_from_all_tables`). Each backend process builds its own
+/// `FilterIndex` from `get_slot_predicate`, which can't live in shared
+/// memory itself (its `HashMap`/`Vec` are heap pointers, meaningless across
+/// process address spaces) - comparing against this counter is how that
+/// backend-local cache notices a change another backend made and rebuilds.
+static FILTER_INDEX_GENERATION: PgAtomic<AtomicU64> = PgAtomic::new();
+
+/// Sub-buckets per power-of-two range in the latency histograms below - e.g.
+/// with 4 sub-buckets, microsecond values in `[2^5, 2^6)` are split into 4
+/// equal-width buckets instead of one, trading memory for percentile
+/// resolution within that range.
+const HIST_SUBBUCKETS: usize = 4;
+
+/// One major bucket per leading-bit position of a `u64` microsecond value
+/// (enough range for any latency this extension could plausibly see),
+/// subdivided by `HIST_SUBBUCKETS`.
+const HIST_BUCKETS: usize = 64 * HIST_SUBBUCKETS;
+
+/// Time (in microseconds) to compute one `Snapshot::execute_and_diff` requery.
+static DIFF_LATENCY_HIST: [PgAtomic<AtomicU64>; HIST_BUCKETS] = [const { PgAtomic::new() }; HIST_BUCKETS];
+
+/// End-to-end time (in microseconds) from a row change landing in an event's
+/// `mz_timestamp` to a client pulling it via `pg_subscribe_pull_event`.
+static DELIVERY_LATENCY_HIST: [PgAtomic<AtomicU64>; HIST_BUCKETS] = [const { PgAtomic::new() }; HIST_BUCKETS];
+
 use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
 static SHMEM_INITIALIZED: AtomicBool = AtomicBool::new(false);
 
@@ -332,15 +522,29 @@ pub fn init_shmem() {
         );
         return;
     }
-    
+
     // Register shared memory
-    pg_shmem_init!(SHMEM_SLOTS);
+    pg_shmem_init!(SLOT_TABLE);
+    for lock in SLOT_DATA.iter() {
+        pg_shmem_init!(*lock);
+    }
+    for counter in EVENTS_SENT.iter() {
+        pg_shmem_init!(*counter);
+    }
     pg_shmem_init!(TABLE_REGISTRY);
     pg_shmem_init!(EVENTS_COUNTER);
-    
+    pg_shmem_init!(WRITE_VERSION_COUNTER);
+    pg_shmem_init!(FILTER_INDEX_GENERATION);
+    for bucket in DIFF_LATENCY_HIST.iter() {
+        pg_shmem_init!(*bucket);
+    }
+    for bucket in DELIVERY_LATENCY_HIST.iter() {
+        pg_shmem_init!(*bucket);
+    }
+
     SHMEM_INITIALIZED.store(true, AtomicOrdering::SeqCst);
-    
-    pgrx::info!("pg_subscribe: Shared memory initialized ({} slots, {} events/slot)", 
+
+    pgrx::info!("pg_subscribe: Shared memory initialized ({} slots, {} events/slot)",
                 MAX_SLOTS, MAX_EVENTS_PER_SLOT);
 }
 
@@ -350,35 +554,81 @@ pub fn is_shmem_available() -> bool {
     SHMEM_INITIALIZED.load(AtomicOrdering::SeqCst)
 }
 
+/// Current value of `FILTER_INDEX_GENERATION` - see its doc comment.
+#[inline]
+pub fn filter_index_generation() -> u64 {
+    if !is_shmem_available() {
+        return 0;
+    }
+    FILTER_INDEX_GENERATION.get().load(Ordering::Relaxed)
+}
+
+#[inline]
+fn bump_filter_index_generation() {
+    FILTER_INDEX_GENERATION.get().fetch_add(1, Ordering::Relaxed);
+}
+
 // ============================================================================
 // Public API
 // ============================================================================
 
-/// Allocate a subscription slot
+/// Allocate a subscription slot. If every slot is occupied, reclaims the
+/// least-recently-used one that's been idle past
+/// `pg_subscribe.lru_idle_threshold_secs` (see `SlotInfo::last_active_at`)
+/// rather than refusing the new subscription outright.
 pub fn allocate_slot(subscription_id: &str) -> Option<usize> {
     if !is_shmem_available() {
         pgrx::warning!("pg_subscribe: Shared memory not available");
         return None;
     }
-    
+
     let backend_pid = unsafe { pg_sys::MyProcPid as u32 };
-    let mut slots = SHMEM_SLOTS.exclusive();
-    
-    let idx = slots.slots.iter().position(|s| !s.info.active)?;
-    
-    let slot = &mut slots.slots[idx];
+    let now = now_secs();
+    let mut table = SLOT_TABLE.exclusive();
+
+    let idx = if let Some(idx) = table.slots.iter().position(|s| !s.active) {
+        idx
+    } else {
+        let idle_threshold = LRU_IDLE_THRESHOLD_SECS.get().max(0) as u64;
+        let Some(lru_idx) = table.slots.iter().enumerate()
+            .filter(|(_, s)| now.saturating_sub(s.last_active_at) >= idle_threshold)
+            .min_by_key(|(_, s)| s.last_active_at)
+            .map(|(i, _)| i)
+        else {
+            pgrx::warning!("pg_subscribe: No subscription slots available (all {} active within the idle threshold)", MAX_SLOTS);
+            return None;
+        };
+
+        table.slots[lru_idx].active = false;
+        table.stats.active_slots = table.stats.active_slots.saturating_sub(1);
+        table.stats.slot_evictions += 1;
+        drop(table);
+        unregister_slot_from_all_tables(lru_idx);
+        table = SLOT_TABLE.exclusive();
+        lru_idx
+    };
+
+    table.slots[idx] = SlotAllocation { active: true, last_active_at: now };
+    table.stats.total_subscriptions += 1;
+    table.stats.active_slots += 1;
+    drop(table);
+
+    // Reset this slot's own data under its own lock - never blocks, or is
+    // blocked by, any other slot's push_event/pop_event.
+    let mut slot = SLOT_DATA[idx].exclusive();
     slot.info = SlotInfo {
         active: true,
         backend_pid,
-        created_at: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs(),
+        created_at: now,
+        last_active_at: now,
         ..Default::default()
     };
     slot.info.set_subscription_id(subscription_id);
     slot.events.iter_mut().for_each(|e| e.occupied = false);
-    
-    slots.stats.total_subscriptions += 1;
-    slots.stats.active_slots += 1;
-    
+    drop(slot);
+    EVENTS_SENT[idx].get().store(0, Ordering::Relaxed);
+    bump_filter_index_generation();
+
     Some(idx)
 }
 
@@ -387,66 +637,191 @@ pub fn release_slot(slot_index: usize) {
     if !is_shmem_available() || slot_index >= MAX_SLOTS {
         return;
     }
-    
-    let mut slots = SHMEM_SLOTS.exclusive();
-    if slots.slots[slot_index].info.active {
-        slots.slots[slot_index].info.active = false;
-        slots.stats.active_slots = slots.stats.active_slots.saturating_sub(1);
+
+    let mut table = SLOT_TABLE.exclusive();
+    if !table.slots[slot_index].active {
+        return;
     }
+    table.slots[slot_index].active = false;
+    table.stats.active_slots = table.stats.active_slots.saturating_sub(1);
+    drop(table);
+
+    let mut slot = SLOT_DATA[slot_index].exclusive();
+    slot.info.active = false;
+    let subscription_id = slot.info.get_subscription_id();
+    drop(slot);
+    crate::overflow::remove_slot_dir(&subscription_id);
+    bump_filter_index_generation();
 }
 
-/// Push an event to a slot's buffer
-pub fn push_event(slot_index: usize, event: &SubscribeEvent) -> bool {
+/// Push an event to a slot's buffer. Returns the sequence number it was
+/// assigned (usable with `get_event_by_seq` for the NOTIFY wake-signal pull
+/// protocol), or `None` if the slot isn't active. Once the ring is full, the
+/// oldest event is spilled to the slot's disk overflow log (`crate::overflow`)
+/// instead of being dropped, so a lagging consumer never silently loses data.
+pub fn push_event(slot_index: usize, event: &SubscribeEvent) -> Option<u64> {
     if !is_shmem_available() || slot_index >= MAX_SLOTS {
-        return false;
+        return None;
     }
-    
-    let mut slots = SHMEM_SLOTS.exclusive();
-    let slot = &mut slots.slots[slot_index];
-    
+
+    let mut slot = SLOT_DATA[slot_index].exclusive();
+
     if !slot.info.active {
-        return false;
+        return None;
     }
-    
-    // Check if buffer is full
+
+    // If the ring is full, evict the oldest event to the overflow log
+    // instead of overwriting it.
+    let mut spill = None;
+    let mut corrupted = false;
     if slot.info.count >= MAX_EVENTS_PER_SLOT {
-        // Overwrite oldest (move tail)
+        let evicted_event = slot.events[slot.info.tail];
+        if evicted_event.is_corrupted() {
+            corrupted = true;
+        } else if let Some(evicted) = evicted_event.to_subscribe_event() {
+            spill = Some((
+                slot.info.get_subscription_id(),
+                evicted,
+                slot.info.overflow_write_segment,
+                slot.info.overflow_write_offset,
+                slot.info.overflow_active,
+            ));
+        }
         slot.info.tail = (slot.info.tail + 1) % MAX_EVENTS_PER_SLOT;
         slot.info.count -= 1;
     }
-    
+
     // Write event at head
-    slot.events[slot.info.head] = ShmemEvent::from_subscribe_event(event);
+    let now = now_secs();
+    let mirror_stale = slot.info.last_active_at != now;
+    slot.info.last_active_at = now;
+    let seq = EVENTS_SENT[slot_index].get().fetch_add(1, Ordering::Relaxed) + 1;
+    let write_version = WRITE_VERSION_COUNTER.get().fetch_add(1, Ordering::Relaxed) + 1;
+    let mut shmem_event = ShmemEvent::from_subscribe_event(event);
+    shmem_event.seq = seq;
+    shmem_event.write_version = write_version;
+    slot.events[slot.info.head] = shmem_event;
     slot.info.head = (slot.info.head + 1) % MAX_EVENTS_PER_SLOT;
     slot.info.count += 1;
-    slot.info.events_sent += 1;
-    
-    // Update global counter (atomic, no lock needed after release)
-    drop(slots);
+
+    // Update global counters (atomic, no lock needed after release)
+    drop(slot);
     EVENTS_COUNTER.get().fetch_add(1, Ordering::Relaxed);
-    
-    true
+    if corrupted {
+        SLOT_TABLE.exclusive().stats.corrupted_events += 1;
+    }
+    if mirror_stale {
+        // Refresh the copy `allocate_slot`'s LRU scan reads, at most once a
+        // second per slot.
+        SLOT_TABLE.exclusive().slots[slot_index].last_active_at = now;
+    }
+
+    if let Some((sub_id, evicted, write_segment, write_offset, was_active)) = spill {
+        let evicted_write_version = evicted.mz_write_version;
+        match crate::overflow::append(&sub_id, evicted_write_version, write_segment, write_offset, &evicted) {
+            Ok((new_segment, new_offset)) => {
+                let mut slot = SLOT_DATA[slot_index].exclusive();
+                if slot.info.active {
+                    if !was_active {
+                        // First spill for this slot: the read cursor starts
+                        // right where this record landed.
+                        slot.info.overflow_active = true;
+                        slot.info.overflow_read_segment = write_segment;
+                        slot.info.overflow_read_offset = write_offset;
+                    }
+                    slot.info.overflow_write_segment = new_segment;
+                    slot.info.overflow_write_offset = new_offset;
+                }
+            }
+            Err(e) => {
+                // The disk spill itself failed - this event is now
+                // irrecoverably gone, unlike a plain ring eviction.
+                pgrx::warning!("pg_subscribe: failed to spill evicted event to disk: {e}");
+                let mut slot = SLOT_DATA[slot_index].exclusive();
+                if slot.info.active {
+                    slot.info.dropped_events += 1;
+                }
+            }
+        }
+    }
+
+    Some(seq)
 }
 
-/// Pop an event from a slot's buffer
+/// Pop an event from a slot's buffer, draining shmem first and falling back
+/// to the disk overflow log (`crate::overflow`) once the ring is empty.
 pub fn pop_event(slot_index: usize) -> Option<SubscribeEvent> {
     if !is_shmem_available() || slot_index >= MAX_SLOTS {
         return None;
     }
-    
-    let mut slots = SHMEM_SLOTS.exclusive();
-    let slot = &mut slots.slots[slot_index];
-    
-    if !slot.info.active || slot.info.count == 0 {
+
+    let mut slot = SLOT_DATA[slot_index].exclusive();
+
+    if !slot.info.active {
         return None;
     }
-    
-    // Read event at tail
-    let event = slot.events[slot.info.tail].to_subscribe_event();
-    slot.events[slot.info.tail].occupied = false;
-    slot.info.tail = (slot.info.tail + 1) % MAX_EVENTS_PER_SLOT;
-    slot.info.count -= 1;
-    
+
+    if slot.info.count > 0 {
+        let shmem_event = slot.events[slot.info.tail];
+        let corrupted = shmem_event.is_corrupted();
+        let event = shmem_event.to_subscribe_event();
+        slot.events[slot.info.tail].occupied = false;
+        slot.info.tail = (slot.info.tail + 1) % MAX_EVENTS_PER_SLOT;
+        slot.info.count -= 1;
+        drop(slot);
+        if corrupted {
+            SLOT_TABLE.exclusive().stats.corrupted_events += 1;
+        }
+        return event;
+    }
+
+    if !slot.info.overflow_active {
+        return None;
+    }
+
+    let subscription_id = slot.info.get_subscription_id();
+    let write_segment = slot.info.overflow_write_segment;
+    let mut segment = slot.info.overflow_read_segment;
+    let mut offset = slot.info.overflow_read_offset;
+    drop(slot);
+
+    // Scan forward without the shmem lock, following the writer across a
+    // segment boundary if it has since rolled over past `segment`.
+    let event = loop {
+        match crate::overflow::read_at(&subscription_id, segment, offset) {
+            Ok(Some((event, next_offset))) => {
+                offset = next_offset;
+                break Some(event);
+            }
+            Ok(None) if segment < write_segment => {
+                segment += 1;
+                offset = 0;
+            }
+            Ok(None) => break None, // caught up to the writer
+            Err(e) => {
+                pgrx::warning!("pg_subscribe: failed to read overflow log: {e}");
+                break None;
+            }
+        }
+    };
+
+    let mut slot = SLOT_DATA[slot_index].exclusive();
+    let mut gc_through = None;
+    if slot.info.active {
+        slot.info.overflow_read_segment = segment;
+        slot.info.overflow_read_offset = offset;
+        slot.info.overflow_active = event.is_some()
+            && !(segment == slot.info.overflow_write_segment && offset >= slot.info.overflow_write_offset);
+        if event.is_some() && segment > 0 {
+            gc_through = Some(segment);
+        }
+    }
+    drop(slot);
+
+    if let Some(seg) = gc_through {
+        crate::overflow::gc_segments(&subscription_id, seg);
+    }
+
     event
 }
 
@@ -455,35 +830,160 @@ pub fn get_slot_info(slot_index: usize) -> Option<SlotInfo> {
     if !is_shmem_available() || slot_index >= MAX_SLOTS {
         return None;
     }
-    
-    let slots = SHMEM_SLOTS.share();
-    if slots.slots[slot_index].info.active {
-        Some(slots.slots[slot_index].info)
+
+    let slot = SLOT_DATA[slot_index].share();
+    if slot.info.active {
+        Some(slot.info)
     } else {
         None
     }
 }
 
+/// Reverse lookup of `get_slot_info`'s `subscription_id`: find the slot
+/// a client-facing `subscription_id` (e.g. from `pg_subscribe_open`) was
+/// assigned to. Used by `pg_subscribe_close`, which only has the id the
+/// caller was handed back, not the slot index.
+pub fn find_slot_by_subscription_id(subscription_id: &str) -> Option<usize> {
+    if !is_shmem_available() {
+        return None;
+    }
+    (0..MAX_SLOTS).find(|&i| {
+        let slot = SLOT_DATA[i].share();
+        slot.info.active && slot.info.get_subscription_id() == subscription_id
+    })
+}
+
+/// Store a slot's compiled WHERE predicate, JSON-serialized into a fixed
+/// buffer. If it doesn't fit, logs a warning, returns `false` and leaves the
+/// slot's stored predicate as it was (none for a freshly allocated slot, so
+/// `broadcast_event` falls back to unfiltered fan-out).
+pub fn set_slot_predicate(slot_index: usize, filter: &WhereFilter) -> bool {
+    if !is_shmem_available() || slot_index >= MAX_SLOTS {
+        return false;
+    }
+
+    let json = serde_json::to_string(filter).unwrap_or_default();
+    let json_bytes = json.as_bytes();
+    if json_bytes.len() > MAX_FILTER_LEN {
+        pgrx::warning!("pg_subscribe: WHERE predicate too large to cache ({} bytes)", json_bytes.len());
+        return false;
+    }
+
+    let mut slot = SLOT_DATA[slot_index].exclusive();
+    if !slot.info.active {
+        return false;
+    }
+
+    slot.info.predicate = [0u8; MAX_FILTER_LEN];
+    slot.info.predicate[..json_bytes.len()].copy_from_slice(json_bytes);
+    slot.info.predicate_len = json_bytes.len() as u32;
+    drop(slot);
+    bump_filter_index_generation();
+
+    true
+}
+
+/// Fetch and deserialize a slot's compiled WHERE predicate, if one is stored.
+pub fn get_slot_predicate(slot_index: usize) -> Option<WhereFilter> {
+    if !is_shmem_available() || slot_index >= MAX_SLOTS {
+        return None;
+    }
+
+    let slot = SLOT_DATA[slot_index].share();
+    let info = &slot.info;
+    if !info.active || info.predicate_len == 0 {
+        return None;
+    }
+
+    let json_str = std::str::from_utf8(&info.predicate[..info.predicate_len as usize]).ok()?;
+    serde_json::from_str(json_str).ok()
+}
+
+/// Fetch a previously pushed event by its `push_event`-assigned sequence
+/// number, without consuming it from the ring. Backs the client-side pull
+/// half of the wake-signal protocol: a NOTIFY carries `{slot, seq}`, and the
+/// client calls this to retrieve the authoritative payload. Returns `None`
+/// if the event has already been overwritten (client fell more than
+/// `MAX_EVENTS_PER_SLOT` events behind).
+pub fn get_event_by_seq(slot_index: usize, seq: u64) -> Option<SubscribeEvent> {
+    if !is_shmem_available() || slot_index >= MAX_SLOTS {
+        return None;
+    }
+
+    let slot = SLOT_DATA[slot_index].share();
+    if !slot.info.active {
+        return None;
+    }
+
+    let found = slot.events.iter().find(|e| e.occupied && e.seq == seq).copied();
+    drop(slot);
+
+    let shmem_event = found?;
+    if shmem_event.is_corrupted() {
+        SLOT_TABLE.exclusive().stats.corrupted_events += 1;
+        return None;
+    }
+    shmem_event.to_subscribe_event()
+}
+
+/// Opt in (or out) of inlining small event payloads directly in the NOTIFY
+/// sent to a slot's channel, instead of just the `{slot, seq}` wake signal.
+pub fn set_slot_inline_notify(slot_index: usize, enabled: bool) -> bool {
+    if !is_shmem_available() || slot_index >= MAX_SLOTS {
+        return false;
+    }
+
+    let mut slot = SLOT_DATA[slot_index].exclusive();
+    if !slot.info.active {
+        return false;
+    }
+
+    slot.info.inline_notify = enabled;
+    true
+}
+
 /// Get global statistics
 pub fn get_stats() -> (u64, u64, u32) {
     if !is_shmem_available() {
         return (0, 0, 0);
     }
-    
-    let slots = SHMEM_SLOTS.share();
+
+    let table = SLOT_TABLE.share();
     let total_events = EVENTS_COUNTER.get().load(Ordering::Relaxed);
-    
-    (slots.stats.total_subscriptions, total_events, slots.stats.active_slots)
+
+    (table.stats.total_subscriptions, total_events, table.stats.active_slots)
+}
+
+/// Sum of `SlotInfo::dropped_events` across every slot - events that were
+/// evicted from the shmem ring *and* failed to spill to the disk overflow
+/// log, so they're truly gone rather than just recoverable from disk.
+pub fn get_total_dropped_events() -> u64 {
+    if !is_shmem_available() {
+        return 0;
+    }
+    (0..MAX_SLOTS).map(|i| SLOT_DATA[i].share().info.dropped_events).sum()
+}
+
+/// (slot_evictions, table_evictions): how many times capacity pressure has
+/// forced `allocate_slot`/`register_table_interest` to reclaim an idle entry
+/// instead of refusing a new registration.
+pub fn get_eviction_counts() -> (u64, u64) {
+    if !is_shmem_available() {
+        return (0, 0);
+    }
+    let stats = SLOT_TABLE.share().stats;
+    (stats.slot_evictions, stats.table_evictions)
 }
 
 /// Get statistics as table iterator (for pg_subscribe_stats())
 pub fn get_statistics() -> TableIterator<'static, (name!(stat_name, String), name!(stat_value, i64))> {
     let (total_subs, total_events, active_subs) = get_stats();
     let shmem_available = is_shmem_available();
-    
+
     // Get dedup stats
     let (total_deduped, active_queries, total_clients) = crate::query_dedup::get_dedup_stats();
-    
+    let (slot_evictions, table_evictions) = get_eviction_counts();
+
     let stats = vec![
         ("shmem_available".to_string(), shmem_available as i64),
         ("total_subscriptions_created".to_string(), total_subs as i64),
@@ -491,42 +991,193 @@ pub fn get_statistics() -> TableIterator<'static, (name!(stat_name, String), nam
         ("active_subscriptions".to_string(), active_subs as i64),
         ("max_slots".to_string(), MAX_SLOTS as i64),
         ("max_events_per_slot".to_string(), MAX_EVENTS_PER_SLOT as i64),
+        ("dropped_events_total".to_string(), get_total_dropped_events() as i64),
+        ("slot_evictions_total".to_string(), slot_evictions as i64),
+        ("table_evictions_total".to_string(), table_evictions as i64),
         ("dedup_total_reused".to_string(), total_deduped as i64),
         ("dedup_unique_queries".to_string(), active_queries as i64),
         ("dedup_total_clients".to_string(), total_clients as i64),
     ];
-    
+
     TableIterator::new(stats)
 }
 
+// ============================================================================
+// Latency Histograms
+// ============================================================================
+
+/// p50/p95/p99 of a latency histogram, in microseconds - see
+/// `get_latency_percentiles`.
+pub struct LatencyPercentiles {
+    pub p50: u64,
+    pub p95: u64,
+    pub p99: u64,
+}
+
+/// Bucket index for a microsecond value: the position of its highest set
+/// bit (0 for the `[0, 1)` bucket otherwise `floor(log2(value))`),
+/// subdivided into `HIST_SUBBUCKETS` equal-width sub-buckets covering that
+/// power-of-two's range. Clamped to `HIST_BUCKETS - 1` so a value doesn't
+/// need to be bounds-checked by callers.
+fn hist_bucket_index(value_micros: u64) -> usize {
+    if value_micros == 0 {
+        return 0;
+    }
+    let major = 63 - value_micros.leading_zeros() as usize;
+    let range_start = 1u64 << major;
+    let offset_in_range = value_micros - range_start;
+    let sub = (offset_in_range as u128 * HIST_SUBBUCKETS as u128 / range_start as u128) as usize;
+    (major * HIST_SUBBUCKETS + sub.min(HIST_SUBBUCKETS - 1)).min(HIST_BUCKETS - 1)
+}
+
+/// Lower bound (inclusive, in microseconds) of `hist_bucket_index`'s bucket
+/// `idx` - used by `percentiles_of` to report a percentile's value as the
+/// bucket it fell into rather than just its index.
+fn hist_bucket_lower_bound(idx: usize) -> u64 {
+    let major = idx / HIST_SUBBUCKETS;
+    let sub = idx % HIST_SUBBUCKETS;
+    if major == 0 {
+        return 0;
+    }
+    let range_start = 1u64 << major;
+    range_start + (range_start * sub as u64 / HIST_SUBBUCKETS as u64)
+}
+
+#[inline]
+fn record_latency(hist: &[PgAtomic<AtomicU64>; HIST_BUCKETS], value_micros: u64) {
+    if !is_shmem_available() {
+        return;
+    }
+    hist[hist_bucket_index(value_micros)].get().fetch_add(1, Ordering::Relaxed);
+}
+
+/// Record one `Snapshot::execute_and_diff` requery's wall-clock time.
+pub fn record_diff_latency(value_micros: u64) {
+    record_latency(&DIFF_LATENCY_HIST, value_micros);
+}
+
+/// Record one event's end-to-end delivery lag (see `DELIVERY_LATENCY_HIST`).
+pub fn record_delivery_latency(value_micros: u64) {
+    record_latency(&DELIVERY_LATENCY_HIST, value_micros);
+}
+
+/// Scan cumulative bucket counts once, reporting the bucket at which each of
+/// the p50/p95/p99 fractions of all recorded samples have been seen.
+fn percentiles_of(hist: &[PgAtomic<AtomicU64>; HIST_BUCKETS]) -> LatencyPercentiles {
+    let counts: Vec<u64> = hist.iter().map(|b| b.get().load(Ordering::Relaxed)).collect();
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return LatencyPercentiles { p50: 0, p95: 0, p99: 0 };
+    }
+
+    let targets = [0.50, 0.95, 0.99];
+    let mut results = [0u64; 3];
+    let mut cumulative = 0u64;
+    let mut next_target = 0usize;
+
+    for (idx, count) in counts.iter().enumerate() {
+        cumulative += count;
+        while next_target < targets.len()
+            && cumulative as f64 >= targets[next_target] * total as f64
+        {
+            results[next_target] = hist_bucket_lower_bound(idx);
+            next_target += 1;
+        }
+        if next_target == targets.len() {
+            break;
+        }
+    }
+
+    LatencyPercentiles { p50: results[0], p95: results[1], p99: results[2] }
+}
+
+/// p50/p95/p99 microseconds for `metric`, or `None` if `metric` isn't
+/// `"diff"` or `"delivery"`. See `pg_subscribe_latency`.
+pub fn get_latency_percentiles(metric: &str) -> Option<LatencyPercentiles> {
+    if metric != "diff" && metric != "delivery" {
+        return None;
+    }
+    if !is_shmem_available() {
+        return Some(LatencyPercentiles { p50: 0, p95: 0, p99: 0 });
+    }
+    match metric {
+        "diff" => Some(percentiles_of(&DIFF_LATENCY_HIST)),
+        _ => Some(percentiles_of(&DELIVERY_LATENCY_HIST)),
+    }
+}
+
 // ============================================================================
 // Table Registry API (for Shared Triggers)
 // ============================================================================
 
-/// Register a subscription slot's interest in a table
-/// Returns true if this is a NEW table (trigger needs to be installed)
-pub fn register_table_interest(table_name: &str, slot_index: usize) -> bool {
-    if !is_shmem_available() || slot_index >= MAX_SLOTS { return false; }
-    
+/// Outcome of `register_table_interest`.
+pub struct TableInterestResult {
+    /// True if this is a newly (re)claimed table - the caller must install
+    /// (or reinstall) the shared trigger.
+    pub needs_trigger: bool,
+    /// Set when capacity pressure forced reclaiming another table's
+    /// registry entry to make room for this one. The caller should drop
+    /// that table's shared trigger, since it no longer has any interested slots.
+    pub evicted_table: Option<String>,
+}
+
+/// Register a subscription slot's interest in a table. If the registry is
+/// full, reclaims the least-recently-used entry that's been idle past
+/// `pg_subscribe.lru_idle_threshold_secs` (see `TableRegistry::last_active_at`)
+/// rather than refusing the new table outright.
+pub fn register_table_interest(table_name: &str, slot_index: usize) -> TableInterestResult {
+    let refused = TableInterestResult { needs_trigger: false, evicted_table: None };
+    if !is_shmem_available() || slot_index >= MAX_SLOTS { return refused; }
+
+    let now = now_secs();
     let mut registry = TABLE_REGISTRY.exclusive();
-    
+
     // Check if table already exists
     if let Some(t) = registry.tables.iter_mut().find(|t| t.active && t.get_table_name() == table_name) {
         t.add_slot(slot_index);
-        return !t.trigger_installed;
+        t.last_active_at = now;
+        let needs_trigger = !t.trigger_installed;
+        drop(registry);
+        bump_filter_index_generation();
+        return TableInterestResult { needs_trigger, evicted_table: None };
     }
-    
+
     // Create new entry
     if let Some(t) = registry.tables.iter_mut().find(|t| !t.active) {
         t.active = true;
         t.set_table_name(table_name);
         t.add_slot(slot_index);
         t.trigger_installed = false;
-        return true;
+        t.last_active_at = now;
+        drop(registry);
+        bump_filter_index_generation();
+        return TableInterestResult { needs_trigger: true, evicted_table: None };
     }
-    
-    pgrx::warning!("pg_subscribe: Table registry full");
-    false
+
+    // Registry full - reclaim the least-recently-used idle entry instead of
+    // refusing this table outright.
+    let idle_threshold = LRU_IDLE_THRESHOLD_SECS.get().max(0) as u64;
+    let lru = registry.tables.iter_mut()
+        .filter(|t| t.active && now.saturating_sub(t.last_active_at) >= idle_threshold)
+        .min_by_key(|t| t.last_active_at);
+
+    let Some(t) = lru else {
+        pgrx::warning!("pg_subscribe: Table registry full");
+        return refused;
+    };
+
+    let evicted_name = t.get_table_name();
+    t.set_table_name(table_name);
+    t.interested_slots = 0;
+    t.add_slot(slot_index);
+    t.trigger_installed = false;
+    t.last_active_at = now;
+    drop(registry);
+
+    SLOT_TABLE.exclusive().stats.table_evictions += 1;
+    bump_filter_index_generation();
+
+    TableInterestResult { needs_trigger: true, evicted_table: Some(evicted_name) }
 }
 
 pub fn mark_trigger_installed(table_name: &str) {
@@ -548,9 +1199,9 @@ pub fn is_trigger_installed(table_name: &str) -> bool {
 
 pub fn unregister_slot_from_all_tables(slot_index: usize) -> Vec<String> {
     if !is_shmem_available() || slot_index >= MAX_SLOTS { return Vec::new(); }
-    
+
     let mut registry = TABLE_REGISTRY.exclusive();
-    registry.tables.iter_mut()
+    let removed: Vec<String> = registry.tables.iter_mut()
         .filter(|t| t.active && t.is_slot_interested(slot_index))
         .filter_map(|t| {
             t.remove_slot(slot_index);
@@ -561,7 +1212,10 @@ pub fn unregister_slot_from_all_tables(slot_index: usize) -> Vec<String> {
                 Some(name)
             } else { None }
         })
-        .collect()
+        .collect();
+    drop(registry);
+    bump_filter_index_generation();
+    removed
 }
 
 pub fn get_interested_slots_for_table(table_name: &str) -> Vec<usize> {
@@ -595,6 +1249,8 @@ mod tests {
             mz_timestamp: 12345,
             mz_diff: 1,
             mz_progressed: false,
+            mz_errcode: None,
+            mz_write_version: 0,
             data: Some(serde_json::json!({"id": 1, "name": "test"})),
         };
         
@@ -605,6 +1261,34 @@ mod tests {
         assert_eq!(restored.mz_diff, event.mz_diff);
     }
     
+    #[test]
+    fn test_shmem_event_default_seq_is_zero() {
+        assert_eq!(ShmemEvent::default().seq, 0);
+    }
+
+    #[test]
+    fn test_slot_predicate_roundtrip() {
+        let mut info = SlotInfo::default();
+        assert_eq!(info.predicate_len, 0);
+
+        let filter = crate::query_analyzer::WhereFilter::Eq {
+            expr: crate::query_analyzer::ColExpr::Col(vec![crate::query_analyzer::PathSegment::Key("status".into())]),
+            val: crate::query_analyzer::FilterValue::Str("active".into()),
+        };
+        let json = serde_json::to_string(&filter).unwrap();
+        info.predicate[..json.len()].copy_from_slice(json.as_bytes());
+        info.predicate_len = json.len() as u32;
+
+        let restored: crate::query_analyzer::WhereFilter = serde_json::from_str(
+            std::str::from_utf8(&info.predicate[..info.predicate_len as usize]).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            restored.eval(&serde_json::json!({"status": "active"})),
+            crate::query_analyzer::EvalResult::Match
+        );
+    }
+
     #[test]
     fn test_slot_info_subscription_id() {
         let mut info = SlotInfo::default();
@@ -663,4 +1347,24 @@ mod tests {
         registry.set_table_name(&long_name);
         assert!(registry.get_table_name().len() < 200);
     }
+
+    #[test]
+    fn test_hist_bucket_index_monotonic_and_bounded() {
+        assert_eq!(hist_bucket_index(0), 0);
+        let mut prev = hist_bucket_index(1);
+        for v in [2u64, 10, 100, 1_000, 1_000_000, u64::MAX / 2, u64::MAX] {
+            let idx = hist_bucket_index(v);
+            assert!(idx >= prev, "bucket index should be non-decreasing as value grows");
+            assert!(idx < HIST_BUCKETS);
+            prev = idx;
+        }
+    }
+
+    #[test]
+    fn test_hist_bucket_lower_bound_matches_index() {
+        for v in [0u64, 1, 7, 63, 64, 4095, 70_000] {
+            let idx = hist_bucket_index(v);
+            assert!(hist_bucket_lower_bound(idx) <= v);
+        }
+    }
 }
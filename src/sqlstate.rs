@@ -0,0 +1,101 @@
+//! SQLSTATE classification for SPI query failures - see
+//! `crate::event::SubscribeEvent::error`.
+
+use std::fmt;
+
+/// A subset of PostgreSQL SQLSTATE codes relevant to deciding whether a
+/// subscriber should retry a failed query, modeled after the phf-backed
+/// lookup table rust-postgres generates from `errcodes.txt`. Codes we don't
+/// recognize fall back to `Other`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SqlState {
+    SyntaxError,
+    UndefinedColumn,
+    UndefinedTable,
+    InsufficientPrivilege,
+    SerializationFailure,
+    DeadlockDetected,
+    QueryCanceled,
+    Other(String),
+}
+
+static CODES: phf::Map<&'static str, SqlState> = phf::phf_map! {
+    "42601" => SqlState::SyntaxError,
+    "42703" => SqlState::UndefinedColumn,
+    "42P01" => SqlState::UndefinedTable,
+    "42501" => SqlState::InsufficientPrivilege,
+    "40001" => SqlState::SerializationFailure,
+    "40P01" => SqlState::DeadlockDetected,
+    "57014" => SqlState::QueryCanceled,
+};
+
+impl SqlState {
+    /// Parse a five-character SQLSTATE code (see the Postgres errcodes
+    /// appendix), falling back to `Other` for anything not in `CODES`.
+    pub fn from_code(code: &str) -> Self {
+        CODES.get(code).cloned().unwrap_or_else(|| Self::Other(code.to_string()))
+    }
+
+    /// The five-character SQLSTATE code this variant was built from.
+    pub fn code(&self) -> &str {
+        match self {
+            Self::SyntaxError => "42601",
+            Self::UndefinedColumn => "42703",
+            Self::UndefinedTable => "42P01",
+            Self::InsufficientPrivilege => "42501",
+            Self::SerializationFailure => "40001",
+            Self::DeadlockDetected => "40P01",
+            Self::QueryCanceled => "57014",
+            Self::Other(code) => code,
+        }
+    }
+
+    /// The code's two-character class, e.g. `"40"` (transaction rollback) or
+    /// `"42"` (syntax error or access rule violation). Transient classes like
+    /// `"40"` are generally worth a subscriber retry; most others aren't.
+    pub fn class(&self) -> &str {
+        let code = self.code();
+        &code[..2.min(code.len())]
+    }
+}
+
+impl fmt::Display for SqlState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// Best-effort extraction of the SQLSTATE from a failed `client.select`.
+/// pgrx doesn't expose a typed accessor for the originating `ErrorData`, so
+/// this scans the error's rendered text for the `SQLSTATE xxxxx` suffix
+/// Postgres appends to elog/ereport messages, falling back to the generic
+/// `XX000` (internal_error) code when it can't find one.
+pub fn extract_sqlstate(e: &pgrx::spi::Error) -> SqlState {
+    const MARKER: &str = "SQLSTATE ";
+    let text = e.to_string();
+    text.rfind(MARKER)
+        .and_then(|i| text.get(i + MARKER.len()..))
+        .map(|rest| rest.trim_end_matches(|c: char| !c.is_ascii_alphanumeric()))
+        .filter(|code| code.len() == 5)
+        .map(SqlState::from_code)
+        .unwrap_or_else(|| SqlState::from_code("XX000"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_code_round_trips() {
+        assert_eq!(SqlState::from_code("42601"), SqlState::SyntaxError);
+        assert_eq!(SqlState::SyntaxError.code(), "42601");
+        assert_eq!(SqlState::SyntaxError.class(), "42");
+    }
+
+    #[test]
+    fn test_unknown_code_falls_back_to_other() {
+        let state = SqlState::from_code("99999");
+        assert_eq!(state, SqlState::Other("99999".into()));
+        assert_eq!(state.class(), "99");
+    }
+}
@@ -1,4 +1,8 @@
 use anyhow::Result;
+use opentelemetry::metrics::{Counter, Histogram, UpDownCounter};
+use opentelemetry::{global, KeyValue};
+use std::sync::LazyLock;
+use std::time::Duration;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
 
@@ -23,3 +27,82 @@ pub fn init(cfg: &Config) -> Result<()> {
     }
     Ok(())
 }
+
+// === Metrics ===
+//
+// All instruments come from `opentelemetry::global::meter`, which is a no-op
+// until the binary installs a `MeterProvider` (it doesn't, by default) - so
+// every call below costs an uncontended atomic load and nothing else unless
+// an operator opts in. Kept here, alongside tracing init, rather than as a
+// separate `infra::telemetry` module: this crate already treats `telemetry`
+// as the one place cross-cutting observability setup lives, and the
+// instruments below are read by both `core` and `infra` call sites.
+static METER: LazyLock<opentelemetry::metrics::Meter> =
+    LazyLock::new(|| global::meter("livequery_server"));
+
+static DIFF_LATENCY_MS: LazyLock<Histogram<f64>> = LazyLock::new(|| {
+    METER
+        .f64_histogram("livequery.diff_rows.latency_ms")
+        .with_description("Snapshot::diff_rows wall-clock time")
+        .build()
+});
+
+static DIFF_EVENTS: LazyLock<Histogram<u64>> = LazyLock::new(|| {
+    METER
+        .u64_histogram("livequery.diff_rows.events")
+        .with_description("Events emitted per Snapshot::diff_rows call")
+        .build()
+});
+
+static PGOUTPUT_DECODES: LazyLock<Counter<u64>> = LazyLock::new(|| {
+    METER
+        .u64_counter("livequery.pgoutput.decoded")
+        .with_description("PgOutputDecoder::decode calls, labeled by message kind")
+        .build()
+});
+
+static ACTIVE_SUBSCRIPTIONS: LazyLock<UpDownCounter<i64>> = LazyLock::new(|| {
+    METER
+        .i64_up_down_counter("livequery.subscriptions.active")
+        .with_description("Currently live subscriptions across all queries")
+        .build()
+});
+
+static QUERY_ANALYZE_CACHE_HIT: LazyLock<Histogram<u64>> = LazyLock::new(|| {
+    METER
+        .u64_histogram("livequery.query_analyze.cache_hit")
+        .with_description("1 on a query::analyze cache hit, 0 on a miss - average gives hit ratio")
+        .build()
+});
+
+/// Record one `Snapshot::diff_rows` call's latency and emitted event count.
+#[inline]
+pub fn record_diff_rows(latency: Duration, event_count: usize) {
+    DIFF_LATENCY_MS.record(latency.as_secs_f64() * 1000.0, &[]);
+    DIFF_EVENTS.record(event_count as u64, &[]);
+}
+
+/// Record one `PgOutputDecoder::decode` call, labeled by the pgoutput
+/// message kind it decoded (`"relation"`, `"insert"`, ...).
+#[inline]
+pub fn record_pgoutput_decode(kind: &'static str) {
+    PGOUTPUT_DECODES.add(1, &[KeyValue::new("kind", kind)]);
+}
+
+/// A subscription was added to a `SubscriptionManager`.
+#[inline]
+pub fn subscription_opened() {
+    ACTIVE_SUBSCRIPTIONS.add(1, &[]);
+}
+
+/// A subscription was removed from a `SubscriptionManager`.
+#[inline]
+pub fn subscription_closed() {
+    ACTIVE_SUBSCRIPTIONS.add(-1, &[]);
+}
+
+/// Record a `query::analyze` cache probe - `hit = true` for a cache hit.
+#[inline]
+pub fn record_query_analyze_cache(hit: bool) {
+    QUERY_ANALYZE_CACHE_HIT.record(if hit { 1 } else { 0 }, &[]);
+}
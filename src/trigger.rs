@@ -1,33 +1,85 @@
 //! Shared trigger implementation - captures table changes and fans out to subscribers.
 
+use std::cell::RefCell;
+use std::collections::HashMap;
+
 use pgrx::prelude::*;
 use pgrx::heap_tuple::PgHeapTuple;
 use pgrx::trigger_support::{PgTrigger, PgTriggerError, PgTriggerOperation};
 use pgrx::WhoAllocated;
 
 use crate::event::SubscribeEvent;
+use crate::query_analyzer::{EvalResult, WhereFilter};
+use crate::query_index::FilterIndex;
 use crate::shmem;
 use crate::streaming::get_current_timestamp;
 use crate::types::tuple_attr_to_json;
 
 const SHARED_TRIGGER_PREFIX: &str = "_pgsub_shared_";
 
+thread_local! {
+    /// Per-backend cache of one `FilterIndex` per table, keyed by table name
+    /// and tagged with the `shmem::filter_index_generation()` it was built
+    /// at. Rebuilding a `FilterIndex` means reading every interested slot's
+    /// predicate out of shmem, which is exactly the O(#queries) cost this
+    /// index exists to avoid paying on *every* trigger firing - a backend
+    /// only pays it again once `filter_index_generation()` has moved past
+    /// what this entry was built at (another backend changed a predicate or
+    /// a slot's table interest since).
+    static INDEX_CACHE: RefCell<HashMap<String, (u64, FilterIndex)>> = RefCell::new(HashMap::new());
+}
+
+/// Subscription ids among `interested_slots` whose cached predicate can't
+/// already rule out `row` - the candidate set `broadcast_event` actually
+/// needs to run `WhereFilter::eval` against, instead of every interested
+/// slot. Rebuilds this backend's cached `FilterIndex` for `table_name` when
+/// `shmem::filter_index_generation()` shows it's gone stale; a slot with no
+/// cached predicate is indexed under `WhereFilter::None`, which always lands
+/// in the index's always-checked fallback set, so it's never wrongly excluded.
+fn candidate_slots(table_name: &str, interested_slots: &[usize], row: &serde_json::Value) -> Vec<usize> {
+    let current_gen = shmem::filter_index_generation();
+    INDEX_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        let needs_rebuild = match cache.get(table_name) {
+            Some((gen, _)) => *gen != current_gen,
+            None => true,
+        };
+        if needs_rebuild {
+            let mut index = FilterIndex::new();
+            for &slot_index in interested_slots {
+                let filter = shmem::get_slot_predicate(slot_index).unwrap_or(WhereFilter::None);
+                index.insert(slot_index, &filter);
+            }
+            cache.insert(table_name.to_string(), (current_gen, index));
+        }
+        cache.get(table_name).unwrap().1.candidates(row).collect()
+    })
+}
+
+/// NOTIFY payloads above this size are rejected by Postgres (~8000 bytes);
+/// inline mode only ever sends a payload under this, falling back to the
+/// wake-signal envelope otherwise.
+const INLINE_NOTIFY_LIMIT: usize = 7900;
+
 #[inline]
 pub fn get_shared_trigger_name(table_name: &str) -> String {
-    format!("{}{}", SHARED_TRIGGER_PREFIX, 
+    format!("{}{}", SHARED_TRIGGER_PREFIX,
         table_name.replace(".", "_").replace("\"", "").replace(" ", "_"))
 }
 
 #[inline]
-fn send_notify(channel: &str, event: &SubscribeEvent) {
-    let payload = serde_json::to_string(event).unwrap_or_default();
-    let payload = if payload.len() > 7900 {
-        format!("{{\"truncated\":true,\"mz_timestamp\":{},\"mz_diff\":{}}}", 
-                event.mz_timestamp, event.mz_diff)
-    } else { payload };
+fn notify(channel: &str, payload: &str) {
     let _ = Spi::run(&format!("SELECT pg_notify('{}', '{}')", channel, payload.replace("'", "''")));
 }
 
+/// Tiny fixed-size wake signal: the client pulls the authoritative event out
+/// of shmem by `(slot, seq)` (see `shmem::get_event_by_seq`) rather than
+/// relying on NOTIFY to carry the payload, so this never truncates.
+#[inline]
+fn send_wake_signal(channel: &str, slot_index: usize, seq: u64) {
+    notify(channel, &format!(r#"{{"slot":{},"seq":{}}}"#, slot_index, seq));
+}
+
 
 #[inline]
 fn heap_tuple_to_json<'a, A: WhoAllocated>(tuple: &PgHeapTuple<'a, A>, relation: &pgrx::PgRelation) -> serde_json::Value {
@@ -43,14 +95,54 @@ fn heap_tuple_to_json<'a, A: WhoAllocated>(tuple: &PgHeapTuple<'a, A>, relation:
     serde_json::Value::Object(map)
 }
 
-/// Broadcast event to all interested slots
+/// Broadcast event to all interested slots, skipping any slot whose compiled
+/// WHERE predicate proves this row can't match (`EvalResult::NoMatch`). A
+/// slot with no cached predicate, or a predicate we can't decide on
+/// (`Unknown`), is always notified - this is a fan-out optimization only,
+/// never a source of missed updates, since the client always requeries.
+///
+/// `table_name`'s backend-local `FilterIndex` (see `candidate_slots`) first
+/// narrows `interested_slots` down to the subset whose predicate's necessary
+/// equality/`IN` structure doesn't already rule the row out, so the O(n)
+/// `get_slot_predicate`+`eval` loop below only ever runs over candidates
+/// instead of every interested slot.
+///
+/// The event's full payload always lands in shmem via `push_event`; NOTIFY
+/// itself only ever carries the tiny `{slot, seq}` wake signal, unless the
+/// slot opted into `inline_notify` and this particular event is small enough
+/// to fit under Postgres' NOTIFY size limit.
 #[inline]
-fn broadcast_event(event: &SubscribeEvent, interested_slots: &[usize]) {
-    for &slot_index in interested_slots {
-        let _ = shmem::push_event(slot_index, event);
-        if let Some(info) = shmem::get_slot_info(slot_index) {
-            send_notify(&format!("pgsub_{}", info.get_subscription_id().replace("-", "")), event);
+fn broadcast_event(table_name: &str, event: &SubscribeEvent, interested_slots: &[usize]) {
+    let candidates;
+    let slots_to_check: &[usize] = match &event.data {
+        Some(row) => {
+            candidates = candidate_slots(table_name, interested_slots, row);
+            &candidates
+        }
+        None => interested_slots,
+    };
+
+    for &slot_index in slots_to_check {
+        if let Some(row) = &event.data {
+            if let Some(filter) = shmem::get_slot_predicate(slot_index) {
+                if filter.eval(row) == EvalResult::NoMatch {
+                    continue;
+                }
+            }
         }
+
+        let Some(seq) = shmem::push_event(slot_index, event) else { continue };
+        let Some(info) = shmem::get_slot_info(slot_index) else { continue };
+        let channel = format!("pgsub_{}", info.get_subscription_id().replace("-", ""));
+
+        if info.inline_notify {
+            let payload = serde_json::to_string(event).unwrap_or_default();
+            if payload.len() <= INLINE_NOTIFY_LIMIT {
+                notify(&channel, &payload);
+                continue;
+            }
+        }
+        send_wake_signal(&channel, slot_index, seq);
     }
 }
 
@@ -118,13 +210,21 @@ pub fn register_subscription_for_table(
     slot_index: usize,
 ) -> Result<(), String> {
     // Register interest in shared memory
-    let needs_trigger = shmem::register_table_interest(table_name, slot_index);
-    
-    // Install shared trigger if this is a new table
-    if needs_trigger {
+    let result = shmem::register_table_interest(table_name, slot_index);
+
+    // If that claimed someone else's idle registry entry, their trigger has
+    // no more interested slots behind it - drop it.
+    if let Some(evicted) = result.evicted_table {
+        if let Err(e) = remove_shared_trigger(&evicted) {
+            pgrx::warning!("Failed to remove shared trigger from evicted table {}: {}", evicted, e);
+        }
+    }
+
+    // Install shared trigger if this is a new (or reclaimed) table
+    if result.needs_trigger {
         install_shared_trigger(table_name)?;
     }
-    
+
     Ok(())
 }
 
@@ -172,20 +272,20 @@ fn pg_subscribe_shared_trigger<'a>(
     match trigger.op()? {
         PgTriggerOperation::Insert => {
             if let Some(t) = trigger.new() {
-                broadcast_event(&SubscribeEvent::insert(ts, heap_tuple_to_json(&t, &rel)), &slots);
+                broadcast_event(&table_name, &SubscribeEvent::insert(ts, heap_tuple_to_json(&t, &rel)), &slots);
             }
         }
         PgTriggerOperation::Delete => {
             if let Some(t) = trigger.old() {
-                broadcast_event(&SubscribeEvent::delete(ts, heap_tuple_to_json(&t, &rel)), &slots);
+                broadcast_event(&table_name, &SubscribeEvent::delete(ts, heap_tuple_to_json(&t, &rel)), &slots);
             }
         }
         PgTriggerOperation::Update => {
             if let Some(old) = trigger.old() {
-                broadcast_event(&SubscribeEvent::delete(ts, heap_tuple_to_json(&old, &rel)), &slots);
+                broadcast_event(&table_name, &SubscribeEvent::delete(ts, heap_tuple_to_json(&old, &rel)), &slots);
             }
             if let Some(new) = trigger.new() {
-                broadcast_event(&SubscribeEvent::insert(ts, heap_tuple_to_json(&new, &rel)), &slots);
+                broadcast_event(&table_name, &SubscribeEvent::insert(ts, heap_tuple_to_json(&new, &rel)), &slots);
             }
         }
         PgTriggerOperation::Truncate => {}
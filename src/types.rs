@@ -1,19 +1,19 @@
 //! Shared type conversion utilities for PostgreSQL → JSON
 
 use pgrx::pg_sys;
-use pgrx::datum::{Date, Time, Timestamp, TimestampWithTimeZone, Interval};
-use pgrx::AnyNumeric;
+use pgrx::datum::{AnyElement, Date, Time, Timestamp, TimestampWithTimeZone, Interval};
+use pgrx::{AnyNumeric, IntoDatum, PgBuiltInOids, Spi};
 
 /// Convert PostgreSQL datum to JSON by type OID (for SPI rows)
 #[inline]
 pub fn datum_to_json(row: &pgrx::spi::SpiHeapTupleData, ordinal: usize, type_oid: pg_sys::Oid) -> serde_json::Value {
     use serde_json::Value::{Bool, Null, Number, String as JString};
-    
+
     macro_rules! get {
         ($t:ty) => { row.get::<$t>(ordinal).ok().flatten() };
         ($t:ty, $map:expr) => { get!($t).map($map).unwrap_or(Null) };
     }
-    
+
     match type_oid {
         pg_sys::BOOLOID => get!(bool, Bool),
         pg_sys::INT2OID => get!(i16, |v| Number(v.into())),
@@ -31,22 +31,54 @@ pub fn datum_to_json(row: &pgrx::spi::SpiHeapTupleData, ordinal: usize, type_oid
         pg_sys::INTERVALOID => get!(Interval, |v| JString(v.to_string())),
         pg_sys::JSONOID => get!(pgrx::Json).map(|v| v.0).unwrap_or(Null),
         pg_sys::JSONBOID => get!(pgrx::JsonB).map(|v| v.0).unwrap_or(Null),
-        pg_sys::INT4ARRAYOID => get!(Vec<i32>).map(|v| serde_json::json!(v)).unwrap_or(Null),
-        pg_sys::INT8ARRAYOID => get!(Vec<i64>).map(|v| serde_json::json!(v)).unwrap_or(Null),
-        pg_sys::TEXTARRAYOID => get!(Vec<String>).map(|v| serde_json::json!(v)).unwrap_or(Null),
-        pg_sys::BOOLARRAYOID => get!(Vec<bool>).map(|v| serde_json::json!(v)).unwrap_or(Null),
-        _ => fallback_spi(row, ordinal),
+        _ => get!(AnyElement).map(generic_pg_value_to_json).unwrap_or(Null),
     }
 }
 
-#[inline]
-fn fallback_spi(row: &pgrx::spi::SpiHeapTupleData, ordinal: usize) -> serde_json::Value {
-    row.get::<String>(ordinal).ok().flatten().map(serde_json::Value::String)
-        .or_else(|| row.get::<i64>(ordinal).ok().flatten().map(|v| serde_json::Value::Number(v.into())))
-        .or_else(|| row.get::<f64>(ordinal).ok().flatten().map(|v| serde_json::json!(v)))
-        .or_else(|| row.get::<bool>(ordinal).ok().flatten().map(serde_json::Value::Bool))
-        .or_else(|| row.get::<pgrx::JsonB>(ordinal).ok().flatten().map(|v| v.0))
-        .or_else(|| row.get::<AnyNumeric>(ordinal).ok().flatten().map(|v| serde_json::Value::String(v.to_string())))
+/// Convert anything not special-cased above (enums, composites, ranges,
+/// bytea, and arrays of arbitrary element type/dimension) by asking
+/// PostgreSQL's own catalog and output functions via SPI instead of
+/// reimplementing them. `to_jsonb` already recurses through enum labels,
+/// composite attributes and nested arrays correctly; ranges get their own
+/// query since `to_jsonb` would otherwise just emit their text form.
+fn generic_pg_value_to_json(value: AnyElement) -> serde_json::Value {
+    let oid = value.oid();
+    let datum = value.datum();
+    let arg = || vec![(oid, Some(datum))];
+
+    if oid == pg_sys::BYTEAOID {
+        return Spi::get_one_with_args::<String>("SELECT encode($1, 'base64')", arg())
+            .ok()
+            .flatten()
+            .map(serde_json::Value::String)
+            .unwrap_or(serde_json::Value::Null);
+    }
+
+    let is_range = Spi::get_one_with_args::<bool>(
+        "SELECT typtype = 'r' FROM pg_catalog.pg_type WHERE oid = $1",
+        vec![(PgBuiltInOids::OIDOID.oid(), oid.into_datum())],
+    )
+    .ok()
+    .flatten()
+    .unwrap_or(false);
+
+    if is_range {
+        return Spi::get_one_with_args::<pgrx::JsonB>(
+            "SELECT CASE WHEN isempty($1) THEN jsonb_build_object('empty', true) ELSE \
+             jsonb_build_object('lower', to_jsonb(lower($1)), 'upper', to_jsonb(upper($1)), \
+             'lower_inc', lower_inc($1), 'upper_inc', upper_inc($1)) END",
+            arg(),
+        )
+        .ok()
+        .flatten()
+        .map(|v| v.0)
+        .unwrap_or(serde_json::Value::Null);
+    }
+
+    Spi::get_one_with_args::<pgrx::JsonB>("SELECT to_jsonb($1)", arg())
+        .ok()
+        .flatten()
+        .map(|v| v.0)
         .unwrap_or(serde_json::Value::Null)
 }
 
@@ -59,17 +91,17 @@ pub fn tuple_attr_to_json<'a, A: pgrx::WhoAllocated>(
 ) -> serde_json::Value {
     use serde_json::Value::{Bool, Null, Number, String as JString};
     use std::num::NonZeroUsize;
-    
+
     let idx = match NonZeroUsize::new(attnum) {
         Some(i) => i,
         None => return Null,
     };
-    
+
     macro_rules! get {
         ($t:ty) => { tuple.get_by_index::<$t>(idx).ok().flatten() };
         ($t:ty, $map:expr) => { get!($t).map($map).unwrap_or(Null) };
     }
-    
+
     match type_oid {
         pg_sys::BOOLOID => get!(bool, Bool),
         pg_sys::INT2OID => get!(i16, |v| Number(v.into())),
@@ -85,6 +117,6 @@ pub fn tuple_attr_to_json<'a, A: pgrx::WhoAllocated>(
         pg_sys::DATEOID => get!(Date, |v| JString(v.to_string())),
         pg_sys::UUIDOID => get!(pgrx::Uuid, |v| JString(uuid::Uuid::from_bytes(*v.as_bytes()).to_string())),
         pg_sys::NUMERICOID => get!(AnyNumeric, |v| JString(v.to_string())),
-        _ => get!(String, JString),
+        _ => get!(AnyElement).map(generic_pg_value_to_json).unwrap_or(Null),
     }
 }
@@ -8,9 +8,12 @@ use std::hash::{Hash, Hasher};
 use std::collections::hash_map::DefaultHasher;
 
 use crate::event::SubscribeEvent;
+use crate::json_patch;
 use crate::query_analyzer::analyze_query;
 use crate::query_dedup;
+use crate::row_filter::{self, RowFilter};
 use crate::shmem;
+use crate::sqlstate;
 use crate::streaming::{get_current_timestamp, HEARTBEAT_INTERVAL_MS};
 use crate::types::datum_to_json;
 
@@ -40,18 +43,86 @@ fn get_column_name(ordinal: usize) -> Option<String> {
     }
 }
 
+/// `true` when `predicate` is `None`, or it evaluates to true for `row` via
+/// `filter_spec::compile_visibility_check` - fails closed (not visible) on
+/// any parse/type/SQL error, the same way a restrictive filter that fails
+/// to parse refuses the whole subscription in `query_dedup::effective_query`.
+/// Shared by `Snapshot::row_visible` (the `subscribe` event path) and
+/// `pg_subscribe_pull_event` (the raw `pg_subscribe_prepare` path), which
+/// has no `Snapshot` to hang a per-subscription predicate off of.
+pub fn row_visible(predicate: Option<&str>, row: &serde_json::Value) -> bool {
+    let Some(predicate) = predicate else { return true };
+    let sql = match crate::filter_spec::compile_visibility_check(predicate, row) {
+        Ok(sql) => sql,
+        Err(_) => return false,
+    };
+    Spi::get_one::<bool>(&sql).ok().flatten().unwrap_or(false)
+}
+
 /// Snapshot state - stores current query results keyed by row identity
 pub struct Snapshot {
     rows: HashMap<String, serde_json::Value>,
     identity_columns: Option<Vec<String>>,
+    /// Predicates ANDed together and evaluated against each row before it's
+    /// considered for the diff - see `row_filter::all_match`. A row that no
+    /// longer matches falls out of `new_rows` just like a deleted row would,
+    /// so it naturally surfaces as a DELETE; a row that starts matching
+    /// surfaces as an INSERT.
+    filters: Vec<RowFilter>,
+    /// When set, a row that changes at the same identity is sent as a single
+    /// `SubscribeEvent::update` JSON Patch instead of a delete+insert pair -
+    /// see `Snapshot::diff_row`.
+    patch_updates: bool,
+    /// When set, a row that changes at the same identity is sent as a single
+    /// `SubscribeEvent::update_full` carrying both the old and new row,
+    /// instead of a delete+insert pair. Takes priority over `patch_updates`
+    /// if both are set - see `Snapshot::diff_row`.
+    full_updates: bool,
+    /// SQL boolean expression re-checked against every row before it's
+    /// allowed into `new_rows`/applied from a trigger event - see
+    /// `row_visible`. Unlike `restrictive_filters`/`permissive_filters`,
+    /// this isn't baked into the shared query text, so clients with
+    /// different visibility can still share one dedup'd subscription slot.
+    visibility_predicate: Option<String>,
 }
 
 impl Snapshot {
-    pub fn new(identity_columns: Option<Vec<String>>) -> Self {
+    pub fn new(
+        identity_columns: Option<Vec<String>>,
+        filters: Vec<RowFilter>,
+        patch_updates: bool,
+        full_updates: bool,
+        visibility_predicate: Option<String>,
+    ) -> Self {
         Self {
             rows: HashMap::new(),
             identity_columns,
+            filters,
+            patch_updates,
+            full_updates,
+            visibility_predicate,
+        }
+    }
+
+    /// Events for a row changing from `old` to `new` at the same identity:
+    /// a full-row `update_full` when `full_updates` is enabled, else a JSON
+    /// Patch `update` when `patch_updates` is enabled and both sides are
+    /// JSON objects, else the default delete+insert pair.
+    fn diff_row(&self, timestamp: i64, id: &str, old: &serde_json::Value, new: &serde_json::Value) -> Vec<SubscribeEvent> {
+        if self.full_updates {
+            return vec![SubscribeEvent::update_full(timestamp, id, old.clone(), new.clone())];
+        }
+        if self.patch_updates {
+            if let (Some(o), Some(n)) = (old.as_object(), new.as_object()) {
+                return vec![SubscribeEvent::update(timestamp, id, json_patch::diff(o, n))];
+            }
         }
+        vec![SubscribeEvent::delete(timestamp, old.clone()), SubscribeEvent::insert(timestamp, new.clone())]
+    }
+
+    #[inline]
+    fn row_visible(&self, row: &serde_json::Value) -> bool {
+        row_visible(self.visibility_predicate.as_deref(), row)
     }
 
     #[inline]
@@ -70,8 +141,10 @@ impl Snapshot {
     }
 
     pub fn execute_and_diff(&mut self, query: &str) -> Result<Vec<SubscribeEvent>, String> {
+        let started = std::time::Instant::now();
         let timestamp = get_current_timestamp();
         let mut new_rows: HashMap<String, serde_json::Value> = HashMap::new();
+        let mut query_error = None;
 
         // Execute query
         Spi::connect(|client| {
@@ -79,15 +152,26 @@ impl Snapshot {
                 Ok(table) => {
                     for row in table {
                         if let Some(json) = row_to_json(&row) {
-                            let id = self.compute_identity(&json);
-                            new_rows.insert(id, json);
+                            if row_filter::all_match(&self.filters, &json) && self.row_visible(&json) {
+                                let id = self.compute_identity(&json);
+                                new_rows.insert(id, json);
+                            }
                         }
                     }
-                    Ok(())
                 }
-                Err(e) => Err(format!("Query failed: {:?}", e)),
+                Err(e) => query_error = Some((sqlstate::extract_sqlstate(&e), e.to_string())),
             }
-        })?;
+        });
+
+        // Surface a failed requery as an error row instead of aborting the
+        // subscription - the client can inspect `mz_errcode` and decide
+        // whether a transient failure (e.g. SerializationFailure) is worth
+        // retrying. The snapshot is left untouched so the next successful
+        // requery still diffs against the last known-good state.
+        if let Some((state, message)) = query_error {
+            shmem::record_diff_latency(started.elapsed().as_micros() as u64);
+            return Ok(vec![SubscribeEvent::error(timestamp, state, message)]);
+        }
 
         let mut events = Vec::new();
 
@@ -100,17 +184,41 @@ impl Snapshot {
         for (id, new_row) in &new_rows {
             match self.rows.get(id) {
                 None => events.push(SubscribeEvent::insert(timestamp, new_row.clone())),
-                Some(old) if old != new_row => {
-                    events.push(SubscribeEvent::delete(timestamp, old.clone()));
-                    events.push(SubscribeEvent::insert(timestamp, new_row.clone()));
-                }
+                Some(old) if old != new_row => events.extend(self.diff_row(timestamp, id, old, new_row)),
                 _ => {}
             }
         }
 
         self.rows = new_rows;
+        shmem::record_diff_latency(started.elapsed().as_micros() as u64);
         Ok(events)
     }
+
+    /// Apply one shared-trigger row event (see `crate::trigger::broadcast_event`)
+    /// directly to `self.rows`, without touching SPI. Only valid for queries
+    /// `UnifiedSubscription::new` marked `incremental` - the trigger already
+    /// skipped this event for slots its compiled WHERE predicate proves can't
+    /// match, so a delete for a row we never inserted (or an insert for one
+    /// already filtered out by `self.filters` or `self.visibility_predicate`)
+    /// is simply a no-op here.
+    fn apply_trigger_event(&mut self, event: SubscribeEvent) -> Vec<SubscribeEvent> {
+        let Some(row) = event.data else { return Vec::new() };
+        let timestamp = event.mz_timestamp;
+        let id = self.compute_identity(&row);
+
+        if event.mz_diff < 0 || !row_filter::all_match(&self.filters, &row) || !self.row_visible(&row) {
+            return match self.rows.remove(&id) {
+                Some(old) => vec![SubscribeEvent::delete(timestamp, old)],
+                None => Vec::new(),
+            };
+        }
+
+        match self.rows.insert(id.clone(), row.clone()) {
+            Some(old) if old == row => Vec::new(),
+            Some(old) => self.diff_row(timestamp, &id, &old, &row),
+            None => vec![SubscribeEvent::insert(timestamp, row)],
+        }
+    }
 }
 
 pub struct UnifiedSubscription {
@@ -118,11 +226,32 @@ pub struct UnifiedSubscription {
     tables: Vec<String>,
     snapshot: Snapshot,
     slot_index: usize,
+    /// Single-table `SELECT *` queries with no join/aggregation/grouping can
+    /// be kept up to date from shared-trigger row events (already filtered
+    /// per-slot by the compiled WHERE predicate - see `shmem::set_slot_predicate`)
+    /// instead of re-running `query` on every change. Anything else falls
+    /// back to the full `execute_and_diff` requery.
+    incremental: bool,
 }
 
 impl UnifiedSubscription {
-    pub fn new(query: &str, identity_columns: Option<Vec<String>>) -> Result<Self, String> {
-        let analysis = analyze_query(query);
+    pub fn new(
+        query: &str,
+        identity_columns: Option<Vec<String>>,
+        filters: Vec<RowFilter>,
+        patch_updates: bool,
+        full_updates: bool,
+        restrictive: &[String],
+        permissive: &[String],
+        visibility_predicate: Option<String>,
+    ) -> Result<Self, String> {
+        // Row-security predicates become part of the query text before
+        // anything else touches it, so dedup, the cached trigger predicate,
+        // and the actual snapshot query all agree on what this client may see.
+        let query = query_dedup::effective_query(query, restrictive, permissive)
+            .ok_or("Row-filter predicate failed to parse")?;
+
+        let analysis = analyze_query(&query);
         if !analysis.is_valid {
             return Err(analysis.incompatibility_reason.unwrap_or("Invalid SQL".into()));
         }
@@ -131,26 +260,38 @@ impl UnifiedSubscription {
         }
 
         // Try reuse existing subscription
-        if let Some(existing) = query_dedup::find_existing_subscription(query) {
+        let slot_index = if let Some(existing) = query_dedup::find_existing_subscription(&query) {
             pgrx::info!("pg_subscribe: Reusing slot {}, {} clients", existing.slot_index, existing.client_count);
-            return Ok(Self {
-                query: query.into(),
-                tables: Self::extract_table_names(&analysis),
-                snapshot: Snapshot::new(identity_columns),
-                slot_index: existing.slot_index,
-            });
-        }
-
-        // New subscription
-        let slot_index = shmem::allocate_slot(&uuid::Uuid::new_v4().to_string())
-            .ok_or("No available subscription slots")?;
-        query_dedup::register_subscription(query, slot_index);
+            existing.slot_index
+        } else {
+            // New subscription
+            let slot_index = shmem::allocate_slot(&uuid::Uuid::new_v4().to_string())
+                .ok_or("No available subscription slots")?;
+            query_dedup::register_subscription(&query, slot_index);
+            slot_index
+        };
+
+        // Cache the compiled WHERE predicate so the shared trigger can skip
+        // fan-out for rows it can prove don't match this subscription.
+        shmem::set_slot_predicate(slot_index, &analysis.filter);
+
+        let incremental = analysis.referenced_tables.len() == 1
+            && !analysis.has_join
+            && !analysis.has_aggregation
+            && !analysis.has_group_by
+            && !analysis.has_window_functions
+            && !analysis.has_subqueries
+            && !analysis.has_cte
+            && !analysis.has_distinct
+            && analysis.select_columns.len() == 1
+            && analysis.select_columns[0] == "*";
 
         Ok(Self {
-            query: query.into(),
+            query,
             tables: Self::extract_table_names(&analysis),
-            snapshot: Snapshot::new(identity_columns),
+            snapshot: Snapshot::new(identity_columns, filters, patch_updates, full_updates, visibility_predicate),
             slot_index,
+            incremental,
         })
     }
 
@@ -170,9 +311,21 @@ impl UnifiedSubscription {
         self.snapshot.execute_and_diff(&self.query)
     }
 
-    #[inline]
-    pub fn check_changes(&mut self) -> Result<Vec<SubscribeEvent>, String> {
-        self.snapshot.execute_and_diff(&self.query)
+    /// Apply the latest change(s) for this slot. `first` is the raw event
+    /// the iterator already popped off shmem to detect there was something
+    /// to do; incremental subscriptions drain the rest of the slot's ring
+    /// buffer too, applying each one directly to the snapshot instead of
+    /// requerying `query`.
+    pub fn check_changes(&mut self, first: SubscribeEvent) -> Result<Vec<SubscribeEvent>, String> {
+        if !self.incremental {
+            return self.snapshot.execute_and_diff(&self.query);
+        }
+
+        let mut events = self.snapshot.apply_trigger_event(first);
+        while let Some(raw) = shmem::pop_event(self.slot_index) {
+            events.extend(self.snapshot.apply_trigger_event(raw));
+        }
+        Ok(events)
     }
 }
 
@@ -196,11 +349,28 @@ pub struct UnifiedEventIterator {
     subscription: UnifiedSubscription,
     pending: Vec<SubscribeEvent>,
     initialized: bool,
+    /// When set, a trailing `SubscribeEvent::progress` is appended after
+    /// every non-empty batch, and a standalone one is emitted whenever
+    /// `WaitLatch` times out with nothing pending - see
+    /// `SubscribeEvent::progress`'s doc comment for the invariant clients
+    /// get out of this.
+    progress: bool,
 }
 
 impl UnifiedEventIterator {
-    pub fn new(subscription: UnifiedSubscription) -> Self {
-        Self { subscription, pending: Vec::new(), initialized: false }
+    pub fn new(subscription: UnifiedSubscription, progress: bool) -> Self {
+        Self { subscription, pending: Vec::new(), initialized: false, progress }
+    }
+
+    /// Queue `events` for delivery, oldest first, appending a trailing
+    /// progress marker when enabled. Returns the first event to hand back.
+    fn queue(&mut self, mut events: Vec<SubscribeEvent>) -> Option<SubscribeEvent> {
+        if self.progress {
+            events.push(SubscribeEvent::progress(get_current_timestamp()));
+        }
+        events.reverse();
+        self.pending = events;
+        self.pending.pop()
     }
 }
 
@@ -214,10 +384,10 @@ impl Iterator for UnifiedEventIterator {
 
         if !self.initialized {
             self.initialized = true;
-            if let Ok(mut events) = self.subscription.initialize() {
-                events.reverse();
-                self.pending = events;
-                return self.pending.pop();
+            if let Ok(events) = self.subscription.initialize() {
+                if !events.is_empty() {
+                    return self.queue(events);
+                }
             }
         }
 
@@ -229,12 +399,10 @@ impl Iterator for UnifiedEventIterator {
                 }
             }
 
-            if shmem::pop_event(self.subscription.slot_index).is_some() {
-                if let Ok(mut events) = self.subscription.check_changes() {
+            if let Some(raw) = shmem::pop_event(self.subscription.slot_index) {
+                if let Ok(events) = self.subscription.check_changes(raw) {
                     if !events.is_empty() {
-                        events.reverse();
-                        self.pending = events;
-                        return self.pending.pop();
+                        return self.queue(events);
                     }
                 }
             }
@@ -252,6 +420,9 @@ impl Iterator for UnifiedEventIterator {
                 if (result & pg_sys::WL_POSTMASTER_DEATH as i32) != 0 {
                     return None;
                 }
+                if self.progress && (result & pg_sys::WL_TIMEOUT as i32) != 0 {
+                    return Some(SubscribeEvent::progress(get_current_timestamp()));
+                }
             }
         }
     }
@@ -260,23 +431,39 @@ impl Iterator for UnifiedEventIterator {
 pub fn create_unified_subscription(
     query: &str,
     identity_columns: Option<Vec<String>>,
+    filters: Option<serde_json::Value>,
+    progress: bool,
+    patch_updates: bool,
+    full_updates: bool,
+    restrictive_filters: Option<Vec<String>>,
+    permissive_filters: Option<Vec<String>>,
+    visibility_predicate: Option<String>,
 ) -> TableIterator<
     'static,
     (
         name!(mz_timestamp, i64),
         name!(mz_diff, i32),
         name!(mz_progressed, bool),
+        name!(mz_errcode, Option<String>),
         name!(data, pgrx::JsonB),
     ),
 > {
-    match UnifiedSubscription::new(query, identity_columns) {
+    let filters = match filters.map(|f| row_filter::parse_filters(&f)) {
+        Some(Ok(filters)) => filters,
+        Some(Err(e)) => pgrx::error!("Invalid filters: {}", e),
+        None => Vec::new(),
+    };
+    let restrictive = restrictive_filters.unwrap_or_default();
+    let permissive = permissive_filters.unwrap_or_default();
+    match UnifiedSubscription::new(query, identity_columns, filters, patch_updates, full_updates, &restrictive, &permissive, visibility_predicate) {
         Ok(sub) => {
-            let iter = UnifiedEventIterator::new(sub);
+            let iter = UnifiedEventIterator::new(sub, progress);
             TableIterator::new(iter.map(|event| {
                 (
                     event.mz_timestamp,
                     event.mz_diff,
                     event.mz_progressed,
+                    event.mz_errcode.clone(),
                     event.data_as_jsonb(),
                 )
             }))
@@ -290,17 +477,24 @@ pub fn create_unified_subscription(
 pub fn create_snapshot_subscription(
     query: &str,
     identity_columns: Option<Vec<String>>,
+    filters: Option<serde_json::Value>,
 ) -> TableIterator<
     'static,
     (
         name!(mz_timestamp, i64),
         name!(mz_diff, i32),
         name!(mz_progressed, bool),
+        name!(mz_errcode, Option<String>),
         name!(data, pgrx::JsonB),
     ),
 > {
-    let mut snapshot = Snapshot::new(identity_columns);
-    
+    let filters = match filters.map(|f| row_filter::parse_filters(&f)) {
+        Some(Ok(filters)) => filters,
+        Some(Err(e)) => pgrx::error!("Invalid filters: {}", e),
+        None => Vec::new(),
+    };
+    let mut snapshot = Snapshot::new(identity_columns, filters, false, false, None);
+
     match snapshot.execute_and_diff(query) {
         Ok(events) => {
             TableIterator::new(events.into_iter().map(|event| {
@@ -308,6 +502,7 @@ pub fn create_snapshot_subscription(
                     event.mz_timestamp,
                     event.mz_diff,
                     event.mz_progressed,
+                    event.mz_errcode.clone(),
                     event.data_as_jsonb(),
                 )
             }))
@@ -318,13 +513,55 @@ pub fn create_snapshot_subscription(
     }
 }
 
+/// One keyset-paginated page of `query`'s current result set, ordered by
+/// `identity_columns`, for `subscribe_snapshot_cursor`. Unlike
+/// `create_snapshot_subscription`, this never buffers the whole result set
+/// - each call only executes and materializes one `page_size`-row page,
+/// resuming after `after` (the previous page's last row's identity tuple,
+/// in `identity_columns` order) via `filter_spec::compile_snapshot_page`.
+///
+/// Returns `(mz_timestamp, rows)`; the caller derives `next_after` from the
+/// last row once it has the full page (see `subscribe_snapshot_cursor`) -
+/// `mz_timestamp` is the point a client should switch to the live stream
+/// from once it has paged through the whole snapshot.
+pub fn execute_snapshot_page(
+    query: &str,
+    identity_columns: &[String],
+    after: Option<&[serde_json::Value]>,
+    page_size: i64,
+) -> Result<(i64, Vec<serde_json::Value>), String> {
+    let timestamp = get_current_timestamp();
+    let sql = crate::filter_spec::compile_snapshot_page(query, identity_columns, after, page_size)?;
+
+    let mut rows = Vec::new();
+    let mut query_error = None;
+    Spi::connect(|client| {
+        match client.select(&sql, None, None) {
+            Ok(table) => {
+                for row in table {
+                    if let Some(json) = row_to_json(&row) {
+                        rows.push(json);
+                    }
+                }
+            }
+            Err(e) => query_error = Some(e.to_string()),
+        }
+    });
+
+    if let Some(message) = query_error {
+        return Err(message);
+    }
+
+    Ok((timestamp, rows))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_identity_computation_with_columns() {
-        let snapshot = Snapshot::new(Some(vec!["id".to_string(), "user_id".to_string()]));
+        let snapshot = Snapshot::new(Some(vec!["id".to_string(), "user_id".to_string()]), Vec::new(), false, false, None);
         let row = serde_json::json!({"id": 1, "user_id": 5, "name": "test"});
         let id = snapshot.compute_identity(&row);
         assert_eq!(id, "1|5");
@@ -332,7 +569,7 @@ mod tests {
 
     #[test]
     fn test_identity_computation_hash() {
-        let snapshot = Snapshot::new(None);
+        let snapshot = Snapshot::new(None, Vec::new(), false, false, None);
         let row1 = serde_json::json!({"id": 1, "name": "test"});
         let row2 = serde_json::json!({"id": 1, "name": "test"});
         let row3 = serde_json::json!({"id": 2, "name": "other"});
@@ -340,4 +577,49 @@ mod tests {
         assert_eq!(snapshot.compute_identity(&row1), snapshot.compute_identity(&row2));
         assert_ne!(snapshot.compute_identity(&row1), snapshot.compute_identity(&row3));
     }
+
+    #[test]
+    fn test_diff_row_emits_delete_insert_pair_by_default() {
+        let snapshot = Snapshot::new(None, Vec::new(), false, false, None);
+        let old = serde_json::json!({"id": 1, "status": "open"});
+        let new = serde_json::json!({"id": 1, "status": "closed"});
+        let events = snapshot.diff_row(0, "1", &old, &new);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].mz_diff, -1);
+        assert_eq!(events[1].mz_diff, 1);
+    }
+
+    #[test]
+    fn test_diff_row_emits_patch_when_enabled() {
+        let snapshot = Snapshot::new(None, Vec::new(), true, false, None);
+        let old = serde_json::json!({"id": 1, "status": "open"});
+        let new = serde_json::json!({"id": 1, "status": "closed"});
+        let events = snapshot.diff_row(0, "1", &old, &new);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].mz_diff, 2);
+        assert_eq!(events[0].data.as_ref().unwrap()["identity"], "1");
+    }
+
+    #[test]
+    fn test_diff_row_emits_full_update_when_enabled() {
+        let snapshot = Snapshot::new(None, Vec::new(), false, true, None);
+        let old = serde_json::json!({"id": 1, "status": "open"});
+        let new = serde_json::json!({"id": 1, "status": "closed"});
+        let events = snapshot.diff_row(0, "1", &old, &new);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].mz_diff, 2);
+        let data = events[0].data.as_ref().unwrap();
+        assert_eq!(data["old"], old);
+        assert_eq!(data["new"], new);
+    }
+
+    #[test]
+    fn test_full_updates_takes_priority_over_patch_updates() {
+        let snapshot = Snapshot::new(None, Vec::new(), true, true, None);
+        let old = serde_json::json!({"id": 1, "status": "open"});
+        let new = serde_json::json!({"id": 1, "status": "closed"});
+        let events = snapshot.diff_row(0, "1", &old, &new);
+        assert_eq!(events.len(), 1);
+        assert!(events[0].data.as_ref().unwrap().get("patch").is_none());
+    }
 }
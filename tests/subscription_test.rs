@@ -1,7 +1,7 @@
 //! Tests for subscription management - New Architecture
 //! Uses client-provided subscription_id with shared query optimization
 
-use livequery_server::core::event::{self, SubscriptionMode};
+use livequery_server::core::event::{self, SubscriptionMode, WireFormat};
 use livequery_server::core::query;
 use livequery_server::core::row::RowData;
 use livequery_server::core::subscription::{Snapshot, SubscriptionManager};
@@ -27,7 +27,7 @@ fn test_snapshot_init() {
     let mut s = Snapshot::new();
     let c = cols(&["id"]);
     let rows = vec![row(json!({"id": 1, "name": "Alice"})), row(json!({"id": 2, "name": "Bob"}))];
-    let ev = s.init_rows(rows, &c);
+    let ev = s.init_rows(rows, &c, &event::SystemClock);
     assert_eq!(ev.len(), 2);
     assert!(ev.iter().all(|e| e.mz_diff == 1));
 }
@@ -36,8 +36,8 @@ fn test_snapshot_init() {
 fn test_snapshot_diff_insert() {
     let mut s = Snapshot::new();
     let c = cols(&["id"]);
-    s.init_rows(vec![row(json!({"id": 1, "name": "Alice"}))], &c);
-    let ev = s.diff_rows(vec![row(json!({"id": 1, "name": "Alice"})), row(json!({"id": 2, "name": "Bob"}))], &c);
+    s.init_rows(vec![row(json!({"id": 1, "name": "Alice"}))], &c, &event::SystemClock);
+    let ev = s.diff_rows(vec![row(json!({"id": 1, "name": "Alice"})), row(json!({"id": 2, "name": "Bob"}))], &c, &event::SystemClock);
     assert_eq!(ev.len(), 1);
     assert_eq!(ev[0].mz_diff, 1);
 }
@@ -46,8 +46,8 @@ fn test_snapshot_diff_insert() {
 fn test_snapshot_diff_delete() {
     let mut s = Snapshot::new();
     let c = cols(&["id"]);
-    s.init_rows(vec![row(json!({"id": 1, "name": "Alice"})), row(json!({"id": 2, "name": "Bob"}))], &c);
-    let ev = s.diff_rows(vec![row(json!({"id": 1, "name": "Alice"}))], &c);
+    s.init_rows(vec![row(json!({"id": 1, "name": "Alice"})), row(json!({"id": 2, "name": "Bob"}))], &c, &event::SystemClock);
+    let ev = s.diff_rows(vec![row(json!({"id": 1, "name": "Alice"}))], &c, &event::SystemClock);
     assert_eq!(ev.len(), 1);
     assert_eq!(ev[0].mz_diff, -1);
 }
@@ -56,8 +56,8 @@ fn test_snapshot_diff_delete() {
 fn test_snapshot_diff_update() {
     let mut s = Snapshot::new();
     let c = cols(&["id"]);
-    s.init_rows(vec![row(json!({"id": 1, "name": "Alice"}))], &c);
-    let ev = s.diff_rows(vec![row(json!({"id": 1, "name": "Alice Updated"}))], &c);
+    s.init_rows(vec![row(json!({"id": 1, "name": "Alice"}))], &c, &event::SystemClock);
+    let ev = s.diff_rows(vec![row(json!({"id": 1, "name": "Alice Updated"}))], &c, &event::SystemClock);
     assert_eq!(ev.len(), 2);
     assert!(ev.iter().any(|e| e.mz_diff == -1));
     assert!(ev.iter().any(|e| e.mz_diff == 1));
@@ -67,8 +67,8 @@ fn test_snapshot_diff_update() {
 fn test_snapshot_no_changes() {
     let mut s = Snapshot::new();
     let c = cols(&["id"]);
-    s.init_rows(vec![row(json!({"id": 1, "name": "Alice"}))], &c);
-    let ev = s.diff_rows(vec![row(json!({"id": 1, "name": "Alice"}))], &c);
+    s.init_rows(vec![row(json!({"id": 1, "name": "Alice"}))], &c, &event::SystemClock);
+    let ev = s.diff_rows(vec![row(json!({"id": 1, "name": "Alice"}))], &c, &event::SystemClock);
     assert_eq!(ev.len(), 0);
 }
 
@@ -78,7 +78,7 @@ fn test_snapshot_no_changes() {
 fn test_subscribe_new() {
     let m = SubscriptionManager::new(1000);
     // New architecture: subscription_id is first param
-    let r = m.subscribe("sub-1", "SELECT * FROM users", Some(vec!["id".into()]), SubscriptionMode::Events).unwrap();
+    let r = m.subscribe("sub-1", "SELECT * FROM users", Some(vec!["id".into()]), SubscriptionMode::Events, WireFormat::Json).unwrap();
     assert!(r.is_new_query);
     assert_eq!(r.subscription_id.as_ref(), "sub-1");
     assert!(m.get_sub("sub-1").is_some());
@@ -87,8 +87,8 @@ fn test_subscribe_new() {
 #[test]
 fn test_shared_query() {
     let m = SubscriptionManager::new(1000);
-    let r1 = m.subscribe("sub-1", "SELECT * FROM users", None, SubscriptionMode::Events).unwrap();
-    let r2 = m.subscribe("sub-2", "SELECT * FROM users", None, SubscriptionMode::Events).unwrap();
+    let r1 = m.subscribe("sub-1", "SELECT * FROM users", None, SubscriptionMode::Events, WireFormat::Json).unwrap();
+    let r2 = m.subscribe("sub-2", "SELECT * FROM users", None, SubscriptionMode::Events, WireFormat::Json).unwrap();
     assert!(r1.is_new_query);
     assert!(!r2.is_new_query); // Same query, not new
     assert_eq!(r1.query_id, r2.query_id); // Same query_id
@@ -99,8 +99,8 @@ fn test_shared_query() {
 #[test]
 fn test_unsubscribe_new() {
     let m = SubscriptionManager::new(1000);
-    m.subscribe("sub-1", "SELECT * FROM users", None, SubscriptionMode::Events).unwrap();
-    m.subscribe("sub-2", "SELECT * FROM users", None, SubscriptionMode::Events).unwrap();
+    m.subscribe("sub-1", "SELECT * FROM users", None, SubscriptionMode::Events, WireFormat::Json).unwrap();
+    m.subscribe("sub-2", "SELECT * FROM users", None, SubscriptionMode::Events, WireFormat::Json).unwrap();
     assert_eq!(m.stats(), (2, 1));
     
     assert!(m.unsubscribe("sub-1"));
@@ -113,7 +113,7 @@ fn test_unsubscribe_new() {
 #[test]
 fn test_heartbeat_new() {
     let m = SubscriptionManager::new(1000);
-    m.subscribe("sub-1", "SELECT * FROM users", None, SubscriptionMode::Events).unwrap();
+    m.subscribe("sub-1", "SELECT * FROM users", None, SubscriptionMode::Events, WireFormat::Json).unwrap();
     assert!(m.heartbeat("sub-1"));
     assert!(!m.heartbeat("nope")); // Non-existent sub
 }
@@ -121,21 +121,21 @@ fn test_heartbeat_new() {
 #[test]
 fn test_reject_non_select() {
     let m = SubscriptionManager::new(1000);
-    assert!(m.subscribe("sub-1", "INSERT INTO users (name) VALUES ('x')", None, SubscriptionMode::Events).is_err());
+    assert!(m.subscribe("sub-1", "INSERT INTO users (name) VALUES ('x')", None, SubscriptionMode::Events, WireFormat::Json).is_err());
 }
 
 #[test]
 fn test_duplicate_subscription_id() {
     let m = SubscriptionManager::new(1000);
-    m.subscribe("sub-1", "SELECT * FROM users", None, SubscriptionMode::Events).unwrap();
+    m.subscribe("sub-1", "SELECT * FROM users", None, SubscriptionMode::Events, WireFormat::Json).unwrap();
     // Same subscription_id should fail
-    assert!(m.subscribe("sub-1", "SELECT * FROM orders", None, SubscriptionMode::Events).is_err());
+    assert!(m.subscribe("sub-1", "SELECT * FROM orders", None, SubscriptionMode::Events, WireFormat::Json).is_err());
 }
 
 #[test]
 fn test_cleanup_new() {
     let m = SubscriptionManager::new(1000);
-    m.subscribe("sub-1", "SELECT * FROM users", None, SubscriptionMode::Events).unwrap();
+    m.subscribe("sub-1", "SELECT * FROM users", None, SubscriptionMode::Events, WireFormat::Json).unwrap();
     let rm = m.cleanup(std::time::Duration::ZERO);
     assert_eq!(rm.len(), 1);
     assert_eq!(rm[0].as_ref(), "sub-1");
@@ -146,7 +146,7 @@ fn test_cleanup_new() {
 #[test]
 fn test_make_batch() {
     let m = SubscriptionManager::new(1000);
-    let r = m.subscribe("sub-1", "SELECT * FROM users", None, SubscriptionMode::Events).unwrap();
+    let r = m.subscribe("sub-1", "SELECT * FROM users", None, SubscriptionMode::Events, WireFormat::Json).unwrap();
     let q = m.get_query(&r.query_id).unwrap();
     
     let batch1 = q.make_batch(vec![event::SubscribeEvent::insert(1, json!({"id": 1}))]);
@@ -161,6 +161,32 @@ fn test_make_batch() {
     assert!(batch3.is_none());
 }
 
+#[test]
+fn test_make_batch_uses_injected_clock() {
+    let m = SubscriptionManager::with_clock(1000, Arc::new(event::TestClock::new(1_000)));
+    let r = m
+        .subscribe(
+            "sub-1",
+            "SELECT * FROM users",
+            None,
+            SubscriptionMode::Events,
+            WireFormat::Json,
+        )
+        .unwrap();
+    let q = m.get_query(&r.query_id).unwrap();
+
+    let batch1 = q
+        .make_batch(vec![event::SubscribeEvent::insert(1, json!({"id": 1}))])
+        .unwrap();
+    let batch2 = q
+        .make_batch(vec![event::SubscribeEvent::insert(2, json!({"id": 2}))])
+        .unwrap();
+
+    // Deterministic and strictly increasing, independent of wall-clock time.
+    assert_eq!(batch1.ts, 1_000);
+    assert_eq!(batch2.ts, 1_001);
+}
+
 // === Performance Tests ===
 
 #[test]
@@ -169,14 +195,14 @@ fn perf_snapshot_diff_100_rows() {
     let c = cols(&["id"]);
     
     let rows: Vec<_> = (0..100).map(|i| row(json!({"id": i, "name": format!("User {}", i)}))).collect();
-    s.init_rows(rows.clone(), &c);
+    s.init_rows(rows.clone(), &c, &event::SystemClock);
     
     let mut new_rows: Vec<_> = (0..100).map(|i| row(json!({"id": i, "name": format!("User {}", i)}))).collect();
     for i in 0..10 { new_rows[i] = row(json!({"id": i, "name": format!("Updated {}", i)})); }
     
     let start = Instant::now();
     let iterations = 1000;
-    for _ in 0..iterations { let _ = s.diff_rows(new_rows.clone(), &c); }
+    for _ in 0..iterations { let _ = s.diff_rows(new_rows.clone(), &c, &event::SystemClock); }
     let elapsed = start.elapsed();
     let per_op = elapsed.as_nanos() / iterations;
     
@@ -190,14 +216,14 @@ fn perf_snapshot_diff_1000_rows() {
     let c = cols(&["id"]);
     
     let rows: Vec<_> = (0..1000).map(|i| row(json!({"id": i, "name": format!("User {}", i), "email": format!("user{}@test.com", i)}))).collect();
-    s.init_rows(rows.clone(), &c);
+    s.init_rows(rows.clone(), &c, &event::SystemClock);
     
     let mut new_rows: Vec<_> = (0..1000).map(|i| row(json!({"id": i, "name": format!("User {}", i), "email": format!("user{}@test.com", i)}))).collect();
     for i in 0..50 { new_rows[i] = row(json!({"id": i, "name": format!("Updated {}", i), "email": format!("updated{}@test.com", i)})); }
     
     let start = Instant::now();
     let iterations = 100;
-    for _ in 0..iterations { let _ = s.diff_rows(new_rows.clone(), &c); }
+    for _ in 0..iterations { let _ = s.diff_rows(new_rows.clone(), &c, &event::SystemClock); }
     let elapsed = start.elapsed();
     let per_op = elapsed.as_nanos() / iterations as u128;
     
@@ -209,12 +235,12 @@ fn perf_snapshot_diff_1000_rows() {
 fn perf_subscribe_shared_query() {
     let m = SubscriptionManager::new(100000);
     let q = "SELECT id, name, email FROM users WHERE status = 'active'";
-    let _ = m.subscribe("sub-0", q, None, SubscriptionMode::Events).unwrap();
+    let _ = m.subscribe("sub-0", q, None, SubscriptionMode::Events, WireFormat::Json).unwrap();
     
     let start = Instant::now();
     let iterations = 10000u64;
     for i in 1..=iterations { 
-        let _ = m.subscribe(&format!("sub-{}", i), q, None, SubscriptionMode::Events); 
+        let _ = m.subscribe(&format!("sub-{}", i), q, None, SubscriptionMode::Events, WireFormat::Json); 
     }
     let elapsed = start.elapsed();
     let per_op = elapsed.as_nanos() / iterations as u128;
@@ -275,7 +301,7 @@ fn perf_hrow_identity() {
 #[test]
 fn perf_event_batch_serialize() {
     let events: Vec<_> = (0..100).map(|i| event::SubscribeEvent::insert(i, json!({"id": i, "name": format!("User {}", i)}))).collect();
-    let batch = event::EventBatch::new(1, events);
+    let batch = event::EventBatch::new(1, events, &event::SystemClock);
     
     let start = Instant::now();
     let iterations = 10000u128;